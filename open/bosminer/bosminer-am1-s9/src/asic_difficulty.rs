@@ -0,0 +1,206 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Automatic adjustment of ASIC difficulty (ticket mask) of hash chains
+//!
+//! The difficulty is chosen to get a stable rate of nonces returned by a hash chain. The rate has
+//! to be high enough for accurate hashrate and hardware error statistics but it must not flood
+//! the solution processing. The difficulty also never exceeds the pool difficulty otherwise some
+//! shares would be filtered out by the chips.
+
+use std::time::{Duration, Instant};
+
+/// Average number of hashes required for finding a nonce with difficulty 1
+const DIFFICULTY_1_HASHES: f64 = 4_294_967_296.0;
+
+/// Desired number of nonces returned by one hash chain per second
+pub const TARGET_SOLUTIONS_PER_SEC: f64 = 10.0;
+/// Lowest difficulty supported by the chips
+pub const MIN_DIFFICULTY: usize = 1;
+/// Highest difficulty which still provides enough nonces for statistics of individual cores
+pub const MAX_DIFFICULTY: usize = 4096;
+/// Interval of difficulty re-evaluation
+pub const ADJUST_INTERVAL: Duration = Duration::from_secs(10);
+/// Time after difficulty change when nonces computed with the previous difficulty can still be
+/// received from the chips
+pub const TRANSITION_TIME: Duration = Duration::from_secs(2);
+
+/// Return the highest power of two which is less than or equal to `value`
+fn prev_power_of_two(value: usize) -> usize {
+    if value <= 1 {
+        1
+    } else {
+        let shift = 8 * std::mem::size_of::<usize>() as u32 - 1 - value.leading_zeros();
+        1 << shift
+    }
+}
+
+/// Convert pool target to difficulty which is suitable for comparison with ASIC difficulty. The
+/// division is done in 256 bits and the result is clamped to the range of `u32` because
+/// `Target::get_difficulty` truncates the value on platforms with 32-bit `usize`. Targets easier
+/// than difficulty 1 are reported as difficulty 1 (zero means unknown difficulty).
+pub fn pool_difficulty(target: &ii_bitcoin::Target) -> usize {
+    const MAX_POOL_DIFFICULTY: u64 = u32::max_value() as u64;
+
+    let target = target.into_inner();
+    if target.is_zero() {
+        return MAX_POOL_DIFFICULTY as usize;
+    }
+    let difficulty = ii_bitcoin::Target::default().into_inner() / target;
+    let difficulty = if difficulty.bits() > 64 {
+        MAX_POOL_DIFFICULTY
+    } else {
+        difficulty.low_u64().min(MAX_POOL_DIFFICULTY)
+    };
+    (difficulty as usize).max(MIN_DIFFICULTY)
+}
+
+/// Calculate ASIC difficulty for a hash chain with `hashrate` (in hashes per second) which mines
+/// jobs with `pool_difficulty`. The result is a power of two nearest to the difficulty providing
+/// `TARGET_SOLUTIONS_PER_SEC`. Zero `pool_difficulty` means that it is not known yet.
+pub fn calculate(hashrate: u128, pool_difficulty: usize) -> usize {
+    let ideal_difficulty = hashrate as f64 / (TARGET_SOLUTIONS_PER_SEC * DIFFICULTY_1_HASHES);
+    // Round the difficulty to the nearest power of two in logarithmic scale
+    let exponent = ideal_difficulty
+        .max(1.0)
+        .log2()
+        .round()
+        .min(MAX_DIFFICULTY.trailing_zeros() as f64) as u32;
+    let difficulty = (1 << exponent).max(MIN_DIFFICULTY);
+
+    if pool_difficulty > 0 {
+        difficulty.min(prev_power_of_two(pool_difficulty))
+    } else {
+        difficulty
+    }
+}
+
+/// Current ASIC difficulty of a hash chain which remembers the recent change so that nonces
+/// computed with the previous difficulty are not considered to be hardware errors
+#[derive(Debug, Clone, Copy)]
+pub struct State {
+    current: usize,
+    previous: usize,
+    changed: Instant,
+}
+
+impl State {
+    pub fn new(difficulty: usize) -> Self {
+        Self {
+            current: difficulty,
+            previous: difficulty,
+            changed: Instant::now(),
+        }
+    }
+
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn set(&mut self, difficulty: usize) {
+        self.previous = self.current;
+        self.current = difficulty;
+        self.changed = Instant::now();
+    }
+
+    /// Return difficulty which has to be met by a nonce received right now
+    pub fn solution_difficulty(&self) -> usize {
+        if self.changed.elapsed() < TRANSITION_TIME {
+            self.current.min(self.previous)
+        } else {
+            self.current
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Hashrate of one S9 hash chain running at 650 MHz
+    const CHAIN_HASHRATE: u128 = 650_000_000 * 114 * 63;
+
+    #[test]
+    fn test_prev_power_of_two() {
+        assert_eq!(prev_power_of_two(0), 1);
+        assert_eq!(prev_power_of_two(1), 1);
+        assert_eq!(prev_power_of_two(2), 2);
+        assert_eq!(prev_power_of_two(3), 2);
+        assert_eq!(prev_power_of_two(1023), 512);
+        assert_eq!(prev_power_of_two(1024), 1024);
+    }
+
+    #[test]
+    fn test_pool_difficulty() {
+        for difficulty in &[1, 64, 8192, 65536] {
+            let target = ii_bitcoin::Target::from_pool_difficulty(*difficulty);
+            assert_eq!(pool_difficulty(&target), *difficulty);
+        }
+        // Target easier than difficulty 1
+        let target = ii_bitcoin::Target::from_hex(
+            "0000ffff00000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        assert_eq!(pool_difficulty(&target), MIN_DIFFICULTY);
+        // Difficulty 2^40 doesn't fit into 32 bits
+        let target = ii_bitcoin::Target::from_hex(
+            "000000000000000000ffff000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        assert_eq!(pool_difficulty(&target), u32::max_value() as usize);
+        assert_eq!(
+            calculate(CHAIN_HASHRATE, pool_difficulty(&target)),
+            calculate(CHAIN_HASHRATE, 0)
+        );
+    }
+
+    #[test]
+    fn test_calculate() {
+        // Nominal chain gets ~10 nonces per second
+        assert_eq!(calculate(CHAIN_HASHRATE, 0), 128);
+        // Slower chains use lower difficulty
+        assert_eq!(calculate(CHAIN_HASHRATE / 4, 0), 32);
+        assert_eq!(calculate(0, 0), MIN_DIFFICULTY);
+        // Difficulty is limited by hardware
+        assert_eq!(calculate(CHAIN_HASHRATE * 1000, 0), MAX_DIFFICULTY);
+        // Pool difficulty cannot be exceeded
+        assert_eq!(calculate(CHAIN_HASHRATE, 100), 64);
+        assert_eq!(calculate(CHAIN_HASHRATE, 1), 1);
+        assert_eq!(calculate(CHAIN_HASHRATE, 65536), 128);
+    }
+
+    #[test]
+    fn test_state_transition() {
+        let mut state = State::new(64);
+        assert_eq!(state.solution_difficulty(), 64);
+
+        // Nonces computed with previous lower difficulty are still accepted
+        state.set(128);
+        assert_eq!(state.current(), 128);
+        assert_eq!(state.solution_difficulty(), 64);
+
+        // Lower difficulty is applied immediately
+        state.set(32);
+        assert_eq!(state.solution_difficulty(), 32);
+    }
+}
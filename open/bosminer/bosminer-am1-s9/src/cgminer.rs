@@ -20,13 +20,14 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-use ii_cgminer_api::command::{DEVDETAILS, FANS, TEMPCTRL, TEMPS};
+use ii_cgminer_api::command::{DEVDETAILS, FANS, RELOAD_CONFIG, TEMPCTRL, TEMPS};
 use ii_cgminer_api::{command, commands, response};
 
 use serde::Serialize;
 
 use std::sync::Arc;
 
+use crate::config::reload;
 use crate::monitor;
 use crate::sensor;
 
@@ -34,6 +35,8 @@ use crate::sensor;
 #[repr(u32)]
 pub enum StatusCode {
     NotReady = 1,
+    ReloadFailed = 2,
+    NotConfiguredFromFile = 3,
 }
 
 impl From<StatusCode> for u32 {
@@ -44,12 +47,22 @@ impl From<StatusCode> for u32 {
 
 pub enum ErrorCode {
     NotReady,
+    ReloadFailed(String),
+    NotConfiguredFromFile,
 }
 
 impl From<ErrorCode> for response::Error {
     fn from(code: ErrorCode) -> Self {
         let (code, msg) = match code {
             ErrorCode::NotReady => (StatusCode::NotReady, "Not ready".to_string()),
+            ErrorCode::ReloadFailed(reason) => (
+                StatusCode::ReloadFailed,
+                format!("Configuration reload failed: {}", reason),
+            ),
+            ErrorCode::NotConfiguredFromFile => (
+                StatusCode::NotConfiguredFromFile,
+                "Pools are not configured from file".to_string(),
+            ),
         };
 
         Self::from_custom_error(code, msg)
@@ -80,6 +93,7 @@ pub struct Handler {
     model: String,
     managers: Vec<Arc<crate::Manager>>,
     monitor: Arc<monitor::Monitor>,
+    reloader: Option<Arc<reload::Reloader>>,
 }
 
 impl Handler {
@@ -87,11 +101,13 @@ impl Handler {
         model: String,
         managers: Vec<Arc<crate::Manager>>,
         monitor: Arc<monitor::Monitor>,
+        reloader: Option<Arc<reload::Reloader>>,
     ) -> Self {
         Self {
             model,
             managers,
             monitor,
+            reloader,
         }
     }
 
@@ -202,20 +218,40 @@ impl Handler {
                 .collect(),
         })
     }
+
+    async fn handle_reload_config(&self) -> command::Result<response::ext::ReloadConfig> {
+        // Reloading is not available when pools were overridden from command line
+        let reloader = self
+            .reloader
+            .as_ref()
+            .ok_or(ErrorCode::NotConfiguredFromFile)?;
+        reloader
+            .reload()
+            .await
+            .map_err(|e| ErrorCode::ReloadFailed(e))?;
+        Ok(response::ext::ReloadConfig)
+    }
 }
 
 pub fn create_custom_commands(
     backend: Arc<crate::Backend>,
     managers: Vec<Arc<crate::Manager>>,
     monitor: Arc<monitor::Monitor>,
+    reloader: Option<Arc<reload::Reloader>>,
 ) -> Option<command::Map> {
-    let handler = Arc::new(Handler::new(backend.to_string(), managers, monitor));
+    let handler = Arc::new(Handler::new(
+        backend.to_string(),
+        managers,
+        monitor,
+        reloader,
+    ));
 
     let custom_commands = commands![
         (DEVDETAILS: ParameterLess -> handler.handle_dev_details),
         (TEMPCTRL: ParameterLess -> handler.handle_temp_ctrl),
         (TEMPS: ParameterLess -> handler.handle_temps),
-        (FANS: ParameterLess -> handler.handle_fans)
+        (FANS: ParameterLess -> handler.handle_fans),
+        (RELOAD_CONFIG: ParameterLess -> handler.handle_reload_config)
     ];
 
     Some(custom_commands)
@@ -31,6 +31,7 @@ use ii_logging::macros::*;
 
 pub mod api;
 mod metadata;
+pub mod reload;
 pub mod support;
 
 use crate::bm1387::MidstateCount;
@@ -52,6 +53,7 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::fs;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -133,7 +135,7 @@ pub const FAN_SPEED_MAX: usize = 100;
 pub const FANS_MIN: usize = 0;
 pub const FANS_MAX: usize = 4;
 
-/// Default ASIC difficulty
+/// Initial ASIC difficulty used until it is adjusted according to the hash chain hashrate
 pub const DEFAULT_ASIC_DIFFICULTY: usize = 64;
 
 /// Default hashrate interval used for statistics in seconds
@@ -142,6 +144,9 @@ pub const DEFAULT_HASHRATE_INTERVAL: Duration = Duration::from_secs(60);
 /// Maximum time it takes to compute one job under normal circumstances
 pub const JOB_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Maximum time the clients wait for acknowledgements of submitted shares on exit
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct ResolvedChainConfig {
     pub midstate_count: MidstateCount,
     pub frequency: FrequencySettings,
@@ -241,10 +246,24 @@ pub struct Backend {
     #[serde(rename = "group")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub groups: Option<Vec<bosminer_config::GroupConfig>>,
+    /// Work accounted to groups when balancing hashrate between them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_accounting: Option<bosminer_config::ShareAccounting>,
+    /// Journal with all shares submitted to pools
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_journal: Option<bosminer_config::ShareJournalConfig>,
+    /// Recording of jobs, work and solutions for reproducing problems
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recording: Option<bosminer_config::RecordingConfig>,
     #[serde(skip)]
     pub hooks: Option<Arc<dyn hooks::Hooks>>,
     #[serde(skip)]
     pub fans_on_while_warming_up: Option<bool>,
+    #[serde(skip)]
+    pub metrics_listen_addr: Option<SocketAddr>,
+    /// Path of configuration file used for reloading pools at run-time
+    #[serde(skip)]
+    pub config_path: Option<String>,
 }
 
 pub trait ConfigBody
@@ -600,4 +619,16 @@ impl hal::BackendConfig for Backend {
     fn info(&self) -> Option<hal::BackendInfo> {
         Some(self.info.clone())
     }
+
+    fn metrics_listen_addr(&self) -> Option<SocketAddr> {
+        self.metrics_listen_addr
+    }
+
+    fn share_journal(&self) -> Option<bosminer_config::ShareJournalConfig> {
+        self.share_journal.clone()
+    }
+
+    fn recording(&self) -> Option<bosminer_config::RecordingConfig> {
+        self.recording.clone()
+    }
 }
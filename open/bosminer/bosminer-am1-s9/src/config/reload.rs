@@ -0,0 +1,115 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Reloading of pool configuration at run-time without restarting hash chains
+
+use ii_logging::macros::*;
+
+use super::{Backend, FormatWrapper, FormatWrapperError, DEFAULT_POOL_ENABLED};
+
+use bosminer::client;
+use bosminer::hal;
+
+use std::sync::Arc;
+
+use ii_async_compat::prelude::*;
+use tokio::signal::unix::{signal, SignalKind};
+
+pub struct Reloader {
+    config_path: String,
+    client_manager: client::Manager,
+    backend_info: Option<hal::BackendInfo>,
+}
+
+impl Reloader {
+    pub fn new(
+        config_path: String,
+        client_manager: client::Manager,
+        backend_info: Option<hal::BackendInfo>,
+    ) -> Self {
+        Self {
+            config_path,
+            client_manager,
+            backend_info,
+        }
+    }
+
+    /// Parse configuration file again and apply its group/pool settings to running client
+    /// manager. Clients with unchanged settings keep their connections.
+    pub async fn reload(&self) -> Result<(), String> {
+        let backend_config = match FormatWrapper::<Backend>::parse(&self.config_path) {
+            Err(FormatWrapperError::IncompatibleVersion(version, Some(v))) => {
+                warn!(
+                    "Incompatible format version '{}', but continuing anyway",
+                    version
+                );
+                v.body
+            }
+            Err(e) => {
+                return Err(format!(
+                    "cannot load configuration file \"{}\": {}",
+                    self.config_path, e
+                ))
+            }
+            Ok(v) => v.body,
+        };
+        if !backend_config.has_pools() {
+            return Err(format!(
+                "no pools specified in configuration file \"{}\"",
+                self.config_path
+            ));
+        }
+
+        // Share accounting is applied only when the new groups pass the validation
+        self.client_manager
+            .reload_config(
+                backend_config.groups,
+                backend_config.share_accounting.unwrap_or_default(),
+                self.backend_info.as_ref(),
+                DEFAULT_POOL_ENABLED,
+            )
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Reload configuration each time `SIGHUP` is received. The signal is always hooked to prevent
+/// the default action which terminates the process without the halt sequence.
+pub fn hook_reload_signal(reloader: Option<Arc<Reloader>>) {
+    tokio::spawn(async move {
+        let mut hangup = signal(SignalKind::hangup()).expect("BUG: failed hooking signal");
+        while let Some(_) = hangup.next().await {
+            match reloader.as_ref() {
+                Some(reloader) => {
+                    info!("Reloading configuration from '{}'", reloader.config_path);
+                    match reloader.reload().await {
+                        Ok(_) => info!("Configuration reloaded"),
+                        Err(e) => error!("Configuration reload failed: {}", e),
+                    }
+                }
+                None => {
+                    warn!("Configuration reload not available: pools are not configured from file")
+                }
+            }
+        }
+    });
+}
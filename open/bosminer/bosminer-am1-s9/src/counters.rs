@@ -120,15 +120,17 @@ impl HashChain {
             .duration_since(self.started)
     }
 
-    pub fn add_valid(&mut self, addr: bm1387::CoreAddress) {
+    /// Account valid nonce found with `difficulty` which may differ from the current ASIC
+    /// difficulty shortly after its change
+    pub fn add_valid(&mut self, addr: bm1387::CoreAddress, difficulty: usize) {
         if addr.chip >= self.chip.len() {
             // nonce from non-existent chip
             // TODO: what to do?
             return;
         }
-        self.valid += self.asic_difficulty;
-        self.chip[addr.chip].valid += self.asic_difficulty;
-        self.chip[addr.chip].core[addr.core].valid += self.asic_difficulty;
+        self.valid += difficulty;
+        self.chip[addr.chip].valid += difficulty;
+        self.chip[addr.chip].core[addr.core].valid += difficulty;
     }
 
     pub fn add_error(&mut self, addr: bm1387::CoreAddress) {
@@ -243,12 +243,8 @@ impl Sender {
     /// This is a hack around `halt_sender` having to be run from tokio context, because it spawns
     /// additional threads.
     pub fn hook_termination_signals(self: Arc<Self>) {
-        // Hook `SIGINT`, `SIGHUP` and `SIGTERM`
-        for signal_type in vec![
-            SignalKind::interrupt(),
-            SignalKind::hangup(),
-            SignalKind::terminate(),
-        ] {
+        // Hook `SIGINT` and `SIGTERM` (`SIGHUP` is hooked by `config::reload::hook_reload_signal`)
+        for signal_type in vec![SignalKind::interrupt(), SignalKind::terminate()] {
             let halt_sender = self.clone();
             tokio::spawn(async move {
                 if let Some(_) = signal(signal_type)
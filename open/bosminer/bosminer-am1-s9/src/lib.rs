@@ -21,6 +21,7 @@
 // contact us at opensource@braiins.com.
 #![recursion_limit = "256"]
 
+pub mod asic_difficulty;
 mod async_i2c;
 pub mod bm1387;
 mod cgminer;
@@ -47,6 +48,7 @@ pub mod test;
 use ii_logging::macros::*;
 
 use bosminer::async_trait;
+use bosminer::client;
 use bosminer::hal::{self, BackendConfig as _};
 use bosminer::node;
 use bosminer::stats;
@@ -55,6 +57,7 @@ use bosminer::work;
 use bosminer_macros::WorkSolverNode;
 
 use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{Duration, Instant};
 
@@ -181,10 +184,10 @@ pub struct HashChain {
     chip_count: usize,
     /// Eliminates the need to query the IP core about the current number of configured midstates
     midstate_count: MidstateCount,
-    /// ASIC difficulty
-    asic_difficulty: usize,
-    /// ASIC target (matches difficulty)
-    asic_target: ii_bitcoin::Target,
+    /// ASIC difficulty which is adjusted automatically according to hashrate and pool target
+    asic_difficulty: StdMutex<asic_difficulty::State>,
+    /// Difficulty of the last job sent to the hash chain (zero when no job has been sent yet)
+    pool_difficulty: AtomicUsize,
     /// Voltage controller on this hashboard
     voltage_ctrl: Arc<power::Control>,
     /// Pin for resetting the hashboard
@@ -219,7 +222,7 @@ impl HashChain {
     /// * `voltage_ctrl_backend` - communication backend for the voltage controller
     /// * `hashboard_idx` - index of this hashboard determines which FPGA IP core is to be mapped
     /// * `midstate_count` - see Self
-    /// * `asic_difficulty` - to what difficulty set the hardware target filter
+    /// * `asic_difficulty` - initial difficulty of the hardware target filter
     pub fn new(
         reset_pin: ResetPin,
         plug_pin: PlugPin,
@@ -250,8 +253,8 @@ impl HashChain {
         Ok(Self {
             chip_count: 0,
             midstate_count,
-            asic_difficulty,
-            asic_target: ii_bitcoin::Target::from_pool_difficulty(asic_difficulty),
+            asic_difficulty: StdMutex::new(asic_difficulty::State::new(asic_difficulty)),
+            pool_difficulty: AtomicUsize::new(0),
             voltage_ctrl: Arc::new(power::Control::new(voltage_ctrl_backend, hashboard_idx)),
             reset_pin,
             hashboard_idx,
@@ -338,17 +341,39 @@ impl HashChain {
         Ok(())
     }
 
+    /// Current ASIC difficulty of all chips within the hashchain
+    pub fn asic_difficulty(&self) -> usize {
+        self.asic_difficulty
+            .lock()
+            .expect("BUG: lock failed")
+            .current()
+    }
+
     /// Configures difficulty globally on all chips within the hashchain
-    async fn set_asic_diff(&mut self, difficulty: usize) -> error::Result<()> {
+    async fn set_asic_diff(&self, difficulty: usize) -> error::Result<()> {
         let tm_reg = bm1387::TicketMaskReg::new(difficulty as u32)?;
         trace!(
             "Setting ticket mask register for difficulty {}, value {:#010x?}",
             difficulty,
             tm_reg
         );
-        self.command_context
+        // Change the state in advance because the chips may start returning nonces with the new
+        // difficulty before the register write is confirmed
+        let previous_state = {
+            let mut state = self.asic_difficulty.lock().expect("BUG: lock failed");
+            let previous_state = *state;
+            state.set(difficulty);
+            previous_state
+        };
+        if let Err(e) = self
+            .command_context
             .write_register_readback(ChipAddress::All, &tm_reg)
-            .await?;
+            .await
+        {
+            *self.asic_difficulty.lock().expect("BUG: lock failed") = previous_state;
+            return Err(e);
+        }
+        self.counter.lock().await.asic_difficulty = difficulty;
         Ok(())
     }
 
@@ -397,7 +422,7 @@ impl HashChain {
             .await?;
         self.set_ip_core_baud_rate(TARGET_CHIP_BAUD_RATE)?;
 
-        self.set_asic_diff(self.asic_difficulty).await?;
+        self.set_asic_diff(self.asic_difficulty()).await?;
 
         Ok(())
     }
@@ -421,7 +446,8 @@ impl HashChain {
 
         info!(
             "Initializing hash chain {}, (difficulty {})",
-            self.hashboard_idx, self.asic_difficulty
+            self.hashboard_idx,
+            self.asic_difficulty()
         );
         self.ip_core_init().await?;
 
@@ -655,6 +681,7 @@ impl HashChain {
     /// generator.
     /// It exits when generator returns `None`.
     async fn work_tx_task(
+        self: Arc<Self>,
         work_registry: Arc<Mutex<registry::WorkRegistry>>,
         mut tx_fifo: io::WorkTx,
         mut work_generator: work::Generator,
@@ -665,6 +692,11 @@ impl HashChain {
             match work {
                 None => return,
                 Some(work) => {
+                    // remember pool target for ASIC difficulty adjustment
+                    self.pool_difficulty.store(
+                        asic_difficulty::pool_difficulty(&work.job_target()),
+                        Ordering::Relaxed,
+                    );
                     // assign `work_id` to `work`
                     let work_id = work_registry.lock().await.store_work(work.clone(), false);
                     // send work is synchronous
@@ -694,7 +726,15 @@ impl HashChain {
                 rx_fifo.recv_solution().await.expect("recv solution failed");
             rx_fifo = rx_fifo_out;
             let work_id = hw_solution.hardware_id;
-            let solution = Solution::from_hw_solution(&hw_solution, self.asic_target);
+            let difficulty = self
+                .asic_difficulty
+                .lock()
+                .expect("BUG: lock failed")
+                .solution_difficulty();
+            let solution = Solution::from_hw_solution(
+                &hw_solution,
+                ii_bitcoin::Target::from_pool_difficulty(difficulty),
+            );
             let mut work_registry = work_registry.lock().await;
 
             let work = work_registry.find_work(work_id as usize);
@@ -715,7 +755,7 @@ impl HashChain {
                                 info!("Solution from hashchain not hitting ASIC target; {}", hash);
                                 counter.lock().await.add_error(core_addr);
                             } else {
-                                counter.lock().await.add_valid(core_addr);
+                                counter.lock().await.add_valid(core_addr, difficulty);
                             }
                             solution_sender.send(unique_solution);
                         }
@@ -872,6 +912,34 @@ impl HashChain {
         }
     }
 
+    /// ASIC difficulty task
+    /// Periodically adjusts ASIC difficulty according to the hash chain frequency and the target
+    /// of jobs being solved
+    async fn asic_difficulty_task(self: Arc<Self>) {
+        loop {
+            delay_for(asic_difficulty::ADJUST_INTERVAL).await;
+
+            let hashrate =
+                self.frequency.lock().await.total() as u128 * bm1387::NUM_CORES_ON_CHIP as u128;
+            let pool_difficulty = self.pool_difficulty.load(Ordering::Relaxed);
+            let difficulty = asic_difficulty::calculate(hashrate, pool_difficulty);
+
+            let current_difficulty = self.asic_difficulty();
+            if difficulty != current_difficulty {
+                info!(
+                    "Hash chain {}: changing ASIC difficulty {} -> {} (pool difficulty {})",
+                    self.hashboard_idx, current_difficulty, difficulty, pool_difficulty
+                );
+                if let Err(e) = self.set_asic_diff(difficulty).await {
+                    error!(
+                        "Hash chain {}: failed to set ASIC difficulty: {}",
+                        self.hashboard_idx, e
+                    );
+                }
+            }
+        }
+    }
+
     async fn start(
         self: Arc<Self>,
         work_generator: work::Generator,
@@ -884,6 +952,7 @@ impl HashChain {
             .register_client("work-tx".into())
             .await
             .spawn(Self::work_tx_task(
+                self.clone(),
                 work_registry.clone(),
                 tx_fifo,
                 work_generator,
@@ -902,6 +971,12 @@ impl HashChain {
                 self.counter.clone(),
             ));
 
+        // spawn ASIC difficulty adjustment
+        self.halt_receiver
+            .register_client("asic difficulty".into())
+            .await
+            .spawn(Self::asic_difficulty_task(self.clone()));
+
         // spawn hashrate monitor
         // Disabled until we found a use for this
         /*
@@ -1118,7 +1193,7 @@ impl RunningChain {
             .expect("BUG: hashchain is not running");
         RunningChain {
             manager: manager.clone(),
-            asic_difficulty: hash_chain.asic_difficulty,
+            asic_difficulty: hash_chain.asic_difficulty(),
             start_id: inner.start_count,
         }
     }
@@ -1308,8 +1383,12 @@ pub struct Manager {
     /// TODO: wrap this type in a structure (in Monitor)
     pub status_receiver: watch::Receiver<Option<monitor::Status>>,
     owned_by: StdMutex<Option<&'static str>>,
+    /// Hashchain has been taken offline on request and it is not expected to be running
+    disabled: AtomicBool,
     pub inner: Mutex<ManagerInner>,
     pub chain_config: config::ResolvedChainConfig,
+    /// Clients have to be notified whenever the hashchain is started or stopped
+    client_manager: client::Manager,
 }
 
 impl Manager {
@@ -1402,6 +1481,7 @@ impl Manager {
 
         // remember we started
         inner.hash_chain.replace(hash_chain);
+        self.notify_nominal_hashrate_changed();
 
         Ok(())
     }
@@ -1425,11 +1505,34 @@ impl Manager {
         self.monitor_tx
             .unbounded_send(monitor::Message::Off)
             .expect("BUG: send failed");
+        self.notify_nominal_hashrate_changed();
+    }
+
+    /// Let all clients announce the new nominal hashrate to their remote servers.
+    /// NOTE: the notification is done in a separate task because calculation of the nominal
+    /// hashrate requires locking of `inner` which is usually held by the caller
+    fn notify_nominal_hashrate_changed(&self) {
+        let client_manager = self.client_manager.clone();
+        tokio::spawn(async move {
+            client_manager.notify_nominal_hashrate_changed().await;
+        });
     }
 
     async fn termination_handler(self: Arc<Self>) {
         self.stop_chain(true).await;
     }
+
+    /// Acquire hashchain for run-time control requested by an operator
+    async fn acquire_control(self: Arc<Self>) -> bosminer::Result<ChainStatus> {
+        let hashboard_idx = self.hashboard_idx;
+        self.acquire("control").await.map_err(|owner| {
+            bosminer::error::ErrorKind::Backend(format!(
+                "chain {} is controlled by '{}'",
+                hashboard_idx, owner
+            ))
+            .into()
+        })
+    }
 }
 
 #[async_trait]
@@ -1448,6 +1551,48 @@ impl node::WorkSolver for Manager {
             None => None,
         }
     }
+
+    async fn get_state(&self) -> node::WorkSolverState {
+        if self.inner.lock().await.hash_chain.is_some() {
+            node::WorkSolverState::Running
+        } else if self.disabled.load(Ordering::Relaxed) {
+            node::WorkSolverState::Disabled
+        } else {
+            node::WorkSolverState::Stopped
+        }
+    }
+
+    async fn enable(self: Arc<Self>) -> bosminer::Result<()> {
+        let chain = self.clone().acquire_control().await?;
+        self.disabled.store(false, Ordering::Relaxed);
+        if let ChainStatus::Stopped(chain) = chain {
+            info!("Enabling chain {}", self.hashboard_idx);
+            // Chain initialization takes a long time so do not block the caller
+            tokio::spawn(async move {
+                if let Err((_, e)) = chain
+                    .start(
+                        &self.chain_config.frequency,
+                        self.chain_config.voltage,
+                        config::DEFAULT_ASIC_DIFFICULTY,
+                    )
+                    .await
+                {
+                    error!("Chain {} cannot be enabled: {}", self.hashboard_idx, e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn disable(self: Arc<Self>) -> bosminer::Result<()> {
+        let chain = self.clone().acquire_control().await?;
+        self.disabled.store(true, Ordering::Relaxed);
+        if let ChainStatus::Running(chain) = chain {
+            info!("Disabling chain {}", self.hashboard_idx);
+            chain.stop().await;
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Debug for Manager {
@@ -1547,6 +1692,7 @@ impl Backend {
         enabled_chains: Vec<usize>,
         work_hub: work::SolverBuilder<Backend>,
         backend_config: config::Backend,
+        client_manager: client::Manager,
         app_halt_receiver: halt::Receiver,
         app_halt_sender: Arc<halt::Sender>,
     ) -> (Vec<Arc<Manager>>, Arc<monitor::Monitor>) {
@@ -1619,11 +1765,13 @@ impl Backend {
                         monitor_tx,
                         status_receiver,
                         owned_by: StdMutex::new(None),
+                        disabled: AtomicBool::new(false),
                         inner: Mutex::new(ManagerInner {
                             hash_chain: None,
                             start_count: 0,
                         }),
                         chain_config,
+                        client_manager: client_manager.clone(),
                     }
                 })
                 .await;
@@ -1692,7 +1840,15 @@ impl hal::Backend for Backend {
             .take()
             .expect("BUG: missing client manager");
         let group_configs = backend_config.groups.take();
+        let share_accounting = backend_config.share_accounting.unwrap_or_default();
         let backend_info = backend_config.info();
+        let reloader = backend_config.config_path.take().map(|config_path| {
+            Arc::new(config::reload::Reloader::new(
+                config_path,
+                client_manager.clone(),
+                backend_info.clone(),
+            ))
+        });
 
         let backend = work_hub.to_node().clone();
         let gpio_mgr = gpio::ControlPinManager::new();
@@ -1702,11 +1858,23 @@ impl hal::Backend for Backend {
             Self::detect_hashboards(&gpio_mgr).expect("failed detecting hashboards"),
             work_hub,
             backend_config,
+            client_manager.clone(),
             app_halt_receiver,
             app_halt_sender.clone(),
         )
         .await;
 
+        // On miner exit, submit solutions found by the halted hash chains and wait for their
+        // acknowledgements before the connections to the pools are closed
+        let shutdown_client_manager = client_manager.clone();
+        app_halt_sender
+            .add_exit_hook(async move {
+                shutdown_client_manager
+                    .shutdown(config::SHUTDOWN_TIMEOUT)
+                    .await;
+            })
+            .await;
+
         // On miner exit, halt the whole program
         app_halt_sender
             .add_exit_hook(async {
@@ -1718,6 +1886,7 @@ impl hal::Backend for Backend {
         app_halt_sender.hook_termination_signals();
 
         // Load initial pool configuration
+        client_manager.set_share_accounting(share_accounting).await;
         client_manager
             .load_config(
                 group_configs,
@@ -1725,13 +1894,17 @@ impl hal::Backend for Backend {
                 config::DEFAULT_POOL_ENABLED,
             )
             .await?;
+        // Reload pool configuration on `SIGHUP`
+        config::reload::hook_reload_signal(reloader.clone());
         if let Some(hooks) = hooks {
             // Pass the client manager to hook for further processing
             hooks.clients_loaded(client_manager).await;
         }
 
         Ok(hal::FrontendConfig {
-            cgminer_custom_commands: cgminer::create_custom_commands(backend, managers, monitor),
+            cgminer_custom_commands: cgminer::create_custom_commands(
+                backend, managers, monitor, reloader,
+            ),
         })
     }
 
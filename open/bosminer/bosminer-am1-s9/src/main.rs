@@ -80,6 +80,14 @@ async fn main() {
                 .required(false)
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("metrics")
+                .long("metrics")
+                .value_name("HOSTNAME:PORT")
+                .help("Export Prometheus metrics over HTTP on given address")
+                .required(false)
+                .takes_value(true),
+        )
         .subcommand(
             clap::SubCommand::with_name("config")
                 .about("Configuration backend API")
@@ -170,6 +178,8 @@ async fn main() {
                 url: url.to_string(),
                 user: user_info.user.to_string(),
                 password: user_info.password.map(|v| v.to_string()),
+                stale_policy: None,
+                allow_cross_host_reconnect: None,
             }]),
         };
 
@@ -178,6 +188,9 @@ async fn main() {
         }
 
         backend_config.groups = Some(vec![group_config]);
+    } else {
+        // Pools are taken from configuration file so allow reloading them later
+        backend_config.config_path = Some(config_path.to_string());
     }
 
     // Check if there's enough pools
@@ -242,6 +255,20 @@ async fn main() {
             .replace(voltage);
     }
 
+    if let Some(value) = matches.value_of("metrics") {
+        match value.parse() {
+            Ok(addr) => backend_config.metrics_listen_addr = Some(addr),
+            Err(e) => {
+                error!(
+                    "Cannot use metrics address '{}' from command line: {}",
+                    value,
+                    e.to_string()
+                );
+                return;
+            }
+        }
+    }
+
     if let Err(e) = backend_config.fill_info::<config::Backend>() {
         error!("Cannot get backend information: {}", e.to_string());
         return;
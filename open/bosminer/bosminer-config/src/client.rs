@@ -24,6 +24,7 @@ use crate::error;
 
 use ii_stratum::v2;
 
+use serde::{Deserialize, Serialize};
 use url::Url;
 
 use std::convert::TryFrom;
@@ -32,7 +33,7 @@ use std::fmt;
 use failure::ResultExt;
 
 pub const URL_JAVA_SCRIPT_REGEX: &'static str =
-    "(?:drain|(?:stratum2?\\+tcp(?:\\+insecure)?)):\\/\\/[\\w\\.-]+(?::\\d+)?(?:\\/[\\dA-HJ-NP-Za-km-z]+)?";
+    "(?:drain|gbt\\+http|(?:stratum2?\\+tcp(?:\\+insecure)?)):\\/\\/[\\w\\.-]+(?::\\d+)?(?:\\/[\\dA-HJ-NP-Za-km-z]+)?(?:#\\w+)?";
 
 #[derive(Clone, Debug)]
 pub enum Protocol {
@@ -40,6 +41,8 @@ pub enum Protocol {
     StratumV1,
     StratumV2(v2::noise::auth::EncodedEd25519PublicKey),
     StratumV2Insecure,
+    /// Solo mining with `getblocktemplate` of a local Bitcoin node
+    Gbt,
 }
 
 impl Protocol {
@@ -47,11 +50,13 @@ impl Protocol {
     pub const SCHEME_STRATUM_V1: &'static str = "stratum+tcp";
     pub const SCHEME_STRATUM_V2: &'static str = "stratum2+tcp";
     pub const SCHEME_STRATUM_V2_INSECURE: &'static str = "stratum2+tcp+insecure";
+    pub const SCHEME_GBT: &'static str = "gbt+http";
 
     pub const DEFAULT_PORT_DRAIN: u16 = 0;
     pub const DEFAULT_PORT_STRATUM_V1: u16 = 3333;
     pub const DEFAULT_PORT_STRATUM_V2: u16 = 3336;
     pub const DEFAULT_PORT_STRATUM_V2_INSECURE: u16 = 3336;
+    pub const DEFAULT_PORT_GBT: u16 = 8332;
 
     pub fn default_port(&self) -> u16 {
         match self {
@@ -59,6 +64,7 @@ impl Protocol {
             Self::StratumV1 => Self::DEFAULT_PORT_STRATUM_V1,
             Self::StratumV2(_) => Self::DEFAULT_PORT_STRATUM_V2,
             Self::StratumV2Insecure => Self::DEFAULT_PORT_STRATUM_V2_INSECURE,
+            Self::Gbt => Self::DEFAULT_PORT_GBT,
         }
     }
 
@@ -89,6 +95,7 @@ impl Protocol {
                 Self::StratumV2(upstream_authority_public_key)
             }
             Self::SCHEME_STRATUM_V2_INSECURE => Self::StratumV2Insecure,
+            Self::SCHEME_GBT => Self::Gbt,
             _ => Err(error::ErrorKind::Client(format!(
                 "unknown protocol '{}'",
                 scheme
@@ -102,6 +109,7 @@ impl Protocol {
             Self::StratumV1 => Self::SCHEME_STRATUM_V1,
             Self::StratumV2(_) => Self::SCHEME_STRATUM_V2,
             Self::StratumV2Insecure => Self::SCHEME_STRATUM_V2_INSECURE,
+            Self::Gbt => Self::SCHEME_GBT,
         }
     }
 }
@@ -115,6 +123,7 @@ impl fmt::Display for Protocol {
                 write!(f, "Stratum V2 (authority key: {})", public_key)
             }
             Protocol::StratumV2Insecure => write!(f, "Stratum V2 Insecure"),
+            Protocol::Gbt => write!(f, "GetBlockTemplate"),
         }
     }
 }
@@ -143,6 +152,23 @@ impl<'a> UserInfo<'a> {
     }
 }
 
+/// Handling of solutions found for jobs that have been invalidated by the remote server
+/// (e.g. after a new block has been announced)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StalePolicy {
+    /// Stale solutions are accounted and never sent to the remote server
+    Drop,
+    /// Stale solutions are submitted and the remote server decides about them
+    Submit,
+}
+
+impl Default for StalePolicy {
+    fn default() -> Self {
+        Self::Drop
+    }
+}
+
 /// Contains basic information about client used for obtaining jobs for solving.
 #[derive(Clone, Debug)]
 pub struct Descriptor {
@@ -154,6 +180,9 @@ pub struct Descriptor {
     pub port: Option<u16>,
     // Currently used only for `#xnsub`: `stratum+tcp://equihash.eu.nicehash.com:3357#xnsub`
     pub fragment: Option<String>,
+    pub stale_policy: StalePolicy,
+    /// Upstream may redirect the client to a different host, otherwise only the port can change
+    pub allow_cross_host_reconnect: bool,
 }
 
 impl Descriptor {
@@ -209,6 +238,8 @@ impl Descriptor {
             host,
             port,
             fragment,
+            stale_policy: Default::default(),
+            allow_cross_host_reconnect: false,
         })
     }
 }
@@ -22,7 +22,111 @@
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+/// Daily time window in local time written in format `HH:MM-HH:MM`. The start is inclusive and
+/// the end is exclusive. Window with the end lower than the start spans midnight and empty window
+/// with the same start and end is not allowed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeWindow {
+    /// Minutes since midnight
+    start: u16,
+    end: u16,
+}
+
+impl TimeWindow {
+    pub const MINUTES_PER_DAY: u16 = 24 * 60;
+
+    pub fn new(start: u16, end: u16) -> Result<Self, String> {
+        if start >= Self::MINUTES_PER_DAY || end >= Self::MINUTES_PER_DAY {
+            Err(format!(
+                "time window {}-{} out of range (minutes since midnight)",
+                start, end
+            ))?;
+        }
+        let window = Self { start, end };
+        if start == end {
+            Err(format!(
+                "empty time window '{}' (start has to differ from end)",
+                window
+            ))?;
+        }
+        Ok(window)
+    }
+
+    fn parse_time(value: &str) -> Result<u16, String> {
+        let invalid_time = || format!("invalid time '{}' (expected HH:MM)", value);
+
+        let mut parts = value.trim().splitn(2, ':');
+        let hours: u16 = parts
+            .next()
+            .and_then(|hours| hours.parse().ok())
+            .ok_or_else(invalid_time)?;
+        let minutes: u16 = parts
+            .next()
+            .and_then(|minutes| minutes.parse().ok())
+            .ok_or_else(invalid_time)?;
+        if hours >= 24 || minutes >= 60 {
+            Err(invalid_time())?;
+        }
+        Ok(hours * 60 + minutes)
+    }
+
+    /// Check if the time given in minutes since midnight falls into the window
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        if self.start <= self.end {
+            self.start <= minute_of_day && minute_of_day < self.end
+        } else {
+            self.start <= minute_of_day || minute_of_day < self.end
+        }
+    }
+}
+
+impl TryFrom<&str> for TimeWindow {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut times = value.splitn(2, '-');
+        let start = Self::parse_time(times.next().unwrap_or_default())?;
+        let end =
+            Self::parse_time(times.next().ok_or_else(|| {
+                format!("invalid time window '{}' (expected HH:MM-HH:MM)", value)
+            })?)?;
+        Self::new(start, end)
+    }
+}
+
+impl TryFrom<String> for TimeWindow {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+impl From<TimeWindow> for String {
+    fn from(window: TimeWindow) -> Self {
+        window.to_string()
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub enum LoadBalanceStrategy {
     #[serde(rename = "quota")]
@@ -31,6 +135,10 @@ pub enum LoadBalanceStrategy {
     /// generated from the group
     #[serde(rename = "fixed_share_ratio")]
     FixedShareRatio(f64),
+    /// Group is active only within listed daily time windows and it behaves like a group with
+    /// default quota then. When no group is active, the scheduler falls back to the default group.
+    #[serde(rename = "schedule")]
+    Schedule(Vec<TimeWindow>),
 }
 
 impl LoadBalanceStrategy {
@@ -47,10 +155,81 @@ impl LoadBalanceStrategy {
             _ => None,
         }
     }
+
+    pub fn get_schedule(&self) -> Option<&[TimeWindow]> {
+        match self {
+            Self::Schedule(windows) => Some(windows.as_slice()),
+            _ => None,
+        }
+    }
+}
+
+/// Determines what work is accounted to groups when the hashrate is balanced between them
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareAccounting {
+    /// Work generated from jobs of group clients
+    GeneratedWork,
+    /// Difficulty of shares accepted by remote servers. Rejected and stale shares are not
+    /// accounted so the share ratio of groups corresponds to the valid work only.
+    AcceptedShares,
+}
+
+impl Default for ShareAccounting {
+    fn default() -> Self {
+        Self::GeneratedWork
+    }
+}
+
+/// Failover settings of clients within a group. Clients are ordered by priority and the first
+/// one has the highest priority.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Failover {
+    /// Keep backup clients connected even when the primary one is running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hot_standby: Option<bool>,
+    /// Time in seconds after which the scheduler switches to a backup client when the currently
+    /// selected client is down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub switch_timeout: Option<u64>,
+    /// Time in seconds for which a client with higher priority has to be running without any
+    /// interruption before the scheduler fails back to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failback_delay: Option<u64>,
+    /// Minimal time in seconds between two attempts to connect a client which is down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check_interval: Option<u64>,
+}
+
+impl Failover {
+    pub const DEFAULT_HOT_STANDBY: bool = false;
+    pub const DEFAULT_SWITCH_TIMEOUT: u64 = 10;
+    pub const DEFAULT_FAILBACK_DELAY: u64 = 300;
+    pub const DEFAULT_HEALTH_CHECK_INTERVAL: u64 = 5;
+
+    pub fn hot_standby(&self) -> bool {
+        self.hot_standby.unwrap_or(Self::DEFAULT_HOT_STANDBY)
+    }
+
+    pub fn switch_timeout(&self) -> Duration {
+        Duration::from_secs(self.switch_timeout.unwrap_or(Self::DEFAULT_SWITCH_TIMEOUT))
+    }
+
+    pub fn failback_delay(&self) -> Duration {
+        Duration::from_secs(self.failback_delay.unwrap_or(Self::DEFAULT_FAILBACK_DELAY))
+    }
+
+    pub fn health_check_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.health_check_interval
+                .unwrap_or(Self::DEFAULT_HEALTH_CHECK_INTERVAL),
+        )
+    }
 }
 
 /// Contains basic information about group
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Descriptor {
     pub name: String,
@@ -59,6 +238,8 @@ pub struct Descriptor {
     #[serde(flatten)]
     #[serde(skip_serializing_if = "Option::is_none")]
     strategy: Option<LoadBalanceStrategy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failover: Option<Failover>,
 }
 
 impl Descriptor {
@@ -74,6 +255,7 @@ impl Descriptor {
             name,
             private,
             strategy: strategy.into(),
+            failover: None,
         }
     }
 
@@ -86,6 +268,7 @@ impl Descriptor {
     pub fn get_quota(&self) -> Option<usize> {
         match self.strategy() {
             LoadBalanceStrategy::Quota(value) => Some(value),
+            LoadBalanceStrategy::Schedule(_) => Some(Self::DEFAULT_QUOTA),
             _ => None,
         }
     }
@@ -95,6 +278,24 @@ impl Descriptor {
             .as_ref()
             .and_then(|strategy| strategy.get_fixed_share_ratio())
     }
+
+    pub fn get_schedule(&self) -> Option<&[TimeWindow]> {
+        self.strategy
+            .as_ref()
+            .and_then(|strategy| strategy.get_schedule())
+    }
+
+    /// Check if the group is scheduled to be active at given time in minutes since midnight.
+    /// Groups without schedule are always active.
+    pub fn is_scheduled_at(&self, minute_of_day: u16) -> bool {
+        self.get_schedule().map_or(true, |windows| {
+            windows.iter().any(|window| window.contains(minute_of_day))
+        })
+    }
+
+    pub fn failover(&self) -> Failover {
+        self.failover.clone().unwrap_or_default()
+    }
 }
 
 impl Default for Descriptor {
@@ -103,6 +304,7 @@ impl Default for Descriptor {
             name: Self::DEFAULT_NAME.to_string(),
             private: false,
             strategy: None,
+            failover: None,
         }
     }
 }
@@ -27,11 +27,15 @@ mod group;
 // Reexport inner structures
 pub use client::Descriptor as ClientDescriptor;
 pub use client::Protocol as ClientProtocol;
+pub use client::StalePolicy;
 pub use client::UserInfo as ClientUserInfo;
 pub use client::URL_JAVA_SCRIPT_REGEX as CLIENT_URL_JAVA_SCRIPT_REGEX;
 
 pub use group::Descriptor as GroupDescriptor;
+pub use group::Failover as GroupFailover;
 pub use group::LoadBalanceStrategy;
+pub use group::ShareAccounting;
+pub use group::TimeWindow as GroupTimeWindow;
 
 // reexport common crates
 pub use clap;
@@ -48,6 +52,34 @@ pub struct PoolConfig {
     pub user: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_policy: Option<StalePolicy>,
+    /// Follow `client.reconnect` of a Stratum V1 pool to a different host (disabled by default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_cross_host_reconnect: Option<bool>,
+}
+
+/// Settings of the journal with submitted shares
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ShareJournalConfig {
+    pub path: String,
+    /// Size of one journal file in bytes before it is rotated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// Number of rotated journal files kept besides the current one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+}
+
+/// Settings of the recording of jobs, work and solutions used for later replay
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RecordingConfig {
+    pub path: String,
+    /// Size of the recording in bytes after which no more events are recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
 }
 
 // NOTE: `#[serde(deny_unknown_fields)]` cannot be used due to flatten descriptor but the error is
@@ -0,0 +1,104 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use bosminer::client;
+use bosminer::hal;
+
+use bosminer_config::ClientDescriptor;
+
+use std::time::Duration;
+
+/// Override the default drain channel size as miner tends to burst messages into the logger
+pub const ASYNC_LOGGER_DRAIN_CHANNEL_SIZE: usize = 128;
+
+/// Number of midstates
+pub const DEFAULT_MIDSTATE_COUNT: usize = 1;
+
+/// Default difficulty of the backend target
+pub const DEFAULT_BACKEND_DIFFICULTY: usize = 1;
+
+/// Default hashrate interval used for statistics in seconds
+pub const DEFAULT_HASHRATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum time it takes to compute one job under normal circumstances
+/// NOTE: the CPU is not able to sweep the whole nonce space in any reasonable time
+pub const JOB_TIMEOUT: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub struct Backend {
+    client_manager: Option<client::Manager>,
+    client_descriptor: Option<ClientDescriptor>,
+    /// Number of worker threads searching nonces
+    pub thread_count: usize,
+    /// Target which has to be met by all reported solutions
+    pub target: ii_bitcoin::Target,
+}
+
+impl Backend {
+    pub fn new(client_descriptor: ClientDescriptor) -> Self {
+        Self {
+            client_descriptor: Some(client_descriptor),
+            ..Default::default()
+        }
+    }
+
+    /// Client manager used by workers for announcing changes of their nominal hashrate
+    pub fn client_manager(&self) -> Option<client::Manager> {
+        self.client_manager.clone()
+    }
+
+    pub async fn init_client(self) {
+        if let Some(client_descriptor) = self.client_descriptor {
+            let group = self
+                .client_manager
+                .expect("BUG: missing client manager")
+                .create_or_get_default_group()
+                .await;
+
+            group
+                .push_client(client::Handle::new(client_descriptor, None, None))
+                .await;
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self {
+            client_manager: None,
+            client_descriptor: None,
+            thread_count: num_cpus::get(),
+            target: ii_bitcoin::Target::from_pool_difficulty(DEFAULT_BACKEND_DIFFICULTY),
+        }
+    }
+}
+
+impl hal::BackendConfig for Backend {
+    #[inline]
+    fn midstate_count(&self) -> usize {
+        DEFAULT_MIDSTATE_COUNT
+    }
+
+    fn set_client_manager(&mut self, client_manager: client::Manager) {
+        self.client_manager.replace(client_manager);
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use ii_logging::macros::*;
+
+pub mod config;
+pub mod worker;
+
+use bosminer::async_trait;
+use bosminer::hal;
+use bosminer::node::{self, WorkSolver as _};
+use bosminer::stats;
+use bosminer::work;
+use bosminer_macros::WorkSolverNode;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Represents solution found by the CPU worker
+#[derive(Debug)]
+pub struct Solution {
+    /// Actual nonce
+    nonce: u32,
+    /// Index of a midstate that corresponds to the found nonce
+    midstate_idx: usize,
+    /// Index of a solution (if multiple were found)
+    solution_idx: usize,
+    /// Target to which was this solution solved
+    target: ii_bitcoin::Target,
+}
+
+impl hal::BackendSolution for Solution {
+    #[inline]
+    fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    #[inline]
+    fn midstate_idx(&self) -> usize {
+        self.midstate_idx
+    }
+
+    #[inline]
+    fn solution_idx(&self) -> usize {
+        self.solution_idx
+    }
+
+    #[inline]
+    fn target(&self) -> &ii_bitcoin::Target {
+        &self.target
+    }
+}
+
+#[derive(Debug, WorkSolverNode)]
+pub struct Backend {
+    #[member_work_solver_stats]
+    work_solver_stats: stats::BasicWorkSolver,
+}
+
+impl Backend {
+    pub fn new() -> Self {
+        Self {
+            work_solver_stats: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl hal::Backend for Backend {
+    type Type = Self;
+    type Config = config::Backend;
+
+    const DEFAULT_HASHRATE_INTERVAL: Duration = config::DEFAULT_HASHRATE_INTERVAL;
+    const JOB_TIMEOUT: Duration = config::JOB_TIMEOUT;
+
+    fn create(_backend_config: &mut config::Backend) -> hal::WorkNode<Self> {
+        node::WorkSolverType::WorkHub(Box::new(Self::new))
+    }
+
+    async fn init_work_hub(
+        backend_config: config::Backend,
+        work_hub: work::SolverBuilder<Self>,
+    ) -> bosminer::Result<hal::FrontendConfig> {
+        info!(
+            "CPU: initializing {} workers, target={:x}",
+            backend_config.thread_count, backend_config.target
+        );
+        let mut workers = Vec::with_capacity(backend_config.thread_count);
+        for worker_idx in 1..=backend_config.thread_count {
+            let worker = work_hub
+                .create_work_solver(|work_generator, solution_sender| {
+                    worker::Worker::new(
+                        worker_idx,
+                        backend_config.target,
+                        work_generator,
+                        solution_sender,
+                        backend_config.client_manager(),
+                    )
+                })
+                .await;
+            workers.push(worker);
+        }
+        for worker in workers {
+            worker.enable().await?;
+        }
+
+        // Create initial client configuration
+        backend_config.init_client().await;
+
+        Ok(hal::FrontendConfig {
+            cgminer_custom_commands: None,
+        })
+    }
+
+    async fn init_work_solver(
+        _backend_config: config::Backend,
+        _work_solver: Arc<Self>,
+    ) -> bosminer::Result<hal::FrontendConfig> {
+        panic!("BUG: called `init_work_solver`");
+    }
+}
+
+#[async_trait]
+impl node::WorkSolver for Backend {
+    async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
+        None
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CPU")
+    }
+}
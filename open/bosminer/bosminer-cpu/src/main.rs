@@ -0,0 +1,131 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use ii_logging::macros::*;
+
+use bosminer_cpu::config;
+
+use bosminer_config::clap;
+use bosminer_config::{ClientDescriptor, ClientUserInfo};
+
+use ii_async_compat::tokio;
+
+fn set_worker_params(
+    matches: &clap::ArgMatches,
+    backend_config: &mut config::Backend,
+) -> Result<(), String> {
+    if let Some(value) = matches.value_of("threads") {
+        backend_config.thread_count = match value.parse::<usize>() {
+            Ok(thread_count) if thread_count > 0 => thread_count,
+            _ => Err(format!("invalid number of threads '{}'", value))?,
+        };
+    }
+    if let Some(value) = matches.value_of("difficulty") {
+        backend_config.target = match value.parse::<usize>() {
+            Ok(difficulty) if difficulty > 0 => {
+                ii_bitcoin::Target::from_pool_difficulty(difficulty)
+            }
+            _ => Err(format!("invalid difficulty '{}'", value))?,
+        };
+    }
+    if let Some(value) = matches.value_of("target") {
+        backend_config.target = ii_bitcoin::Target::from_hex(value)
+            .map_err(|_| format!("invalid target '{}'", value))?;
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let app = clap::App::new(bosminer::SIGNATURE)
+        .version(bosminer::version::STRING.as_str())
+        .arg(
+            clap::Arg::with_name("pool")
+                .short("p")
+                .long("pool")
+                .value_name("HOSTNAME:PORT")
+                .help("Address the stratum V2 server")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("user")
+                .short("u")
+                .long("user")
+                .value_name("USERNAME.WORKERNAME[:PASSWORD]")
+                .help("Specify user and worker name")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .value_name("COUNT")
+                .help("Number of worker threads (defaults to number of CPUs)")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("difficulty")
+                .short("d")
+                .long("difficulty")
+                .value_name("DIFFICULTY")
+                .help("Difficulty of the backend target")
+                .conflicts_with("target")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("target")
+                .long("target")
+                .value_name("HEX")
+                .help("Backend target (may be easier than difficulty 1)")
+                .takes_value(true),
+        );
+
+    let matches = app.get_matches();
+    let _log_guard =
+        ii_logging::setup_for_app(bosminer_cpu::config::ASYNC_LOGGER_DRAIN_CHANNEL_SIZE);
+
+    let url = matches
+        .value_of("pool")
+        .expect("BUG: missing 'pool' attribute");
+    let user_info = matches
+        .value_of("user")
+        .expect("BUG: missing 'user' attribute");
+    let user_info = ClientUserInfo::parse(user_info);
+
+    let mut backend_config =
+        config::Backend::new(match ClientDescriptor::create(url, &user_info, true) {
+            Err(e) => {
+                error!("Cannot set pool from command line: {}", e.to_string());
+                return;
+            }
+            Ok(v) => v,
+        });
+    if let Err(e) = set_worker_params(&matches, &mut backend_config) {
+        error!("Cannot set worker parameters from command line: {}", e);
+        return;
+    }
+
+    ii_async_compat::setup_panic_handling();
+    bosminer::main::<bosminer_cpu::Backend>(backend_config, bosminer::SIGNATURE.to_string()).await;
+}
@@ -0,0 +1,265 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Worker searching nonce space of work assignments on the CPU
+
+use ii_logging::macros::*;
+
+use crate::Solution;
+
+use bosminer::async_trait;
+use bosminer::client;
+use bosminer::node;
+use bosminer::stats;
+use bosminer::work;
+use bosminer_macros::WorkSolverNode;
+
+use ii_async_compat::tokio;
+use tokio::task;
+
+use ii_bitcoin::{HashTrait as _, MeetsTarget as _};
+
+use std::cmp;
+use std::fmt;
+use std::mem::size_of;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Offset of the nonce in the binary representation of block header
+const NONCE_OFFSET: usize = ii_bitcoin::BLOCK_HEADER_SIZE - size_of::<u32>();
+
+/// Number of nonces searched before the validity of the job is checked again
+const NONCE_BATCH_SIZE: u32 = 0x10000;
+
+/// Iterates over the `nonces` of the block `header` and calls `found` for every nonce whose double
+/// hash meets the `target`
+pub fn search_nonces<F>(
+    header: ii_bitcoin::BlockHeader,
+    nonces: RangeInclusive<u32>,
+    target: &ii_bitcoin::Target,
+    mut found: F,
+) where
+    F: FnMut(u32),
+{
+    let mut header_bytes = header.into_bytes();
+    for nonce in nonces {
+        header_bytes[NONCE_OFFSET..].copy_from_slice(&nonce.to_le_bytes());
+        if ii_bitcoin::DHash::hash(&header_bytes).meets(target) {
+            found(nonce);
+        }
+    }
+}
+
+#[derive(Debug, WorkSolverNode)]
+pub struct Worker {
+    #[member_work_solver_stats]
+    work_solver_stats: stats::BasicWorkSolver,
+    /// Index of the worker starting from 1
+    worker_idx: usize,
+    /// Target which has to be met by all reported solutions
+    target: ii_bitcoin::Target,
+    work_generator: work::Generator,
+    solution_sender: work::SolutionSender,
+    /// Client manager notified about changes of the nominal hashrate
+    client_manager: Option<client::Manager>,
+    /// Hashrate measured on the last batch of nonces in hashes per second. Zero means that the
+    /// worker is not running.
+    measured_hashrate: AtomicU64,
+    /// Worker task has been spawned and it hasn't terminated yet
+    running: AtomicBool,
+}
+
+impl Worker {
+    pub fn new(
+        worker_idx: usize,
+        target: ii_bitcoin::Target,
+        work_generator: work::Generator,
+        solution_sender: work::SolutionSender,
+        client_manager: Option<client::Manager>,
+    ) -> Self {
+        Self {
+            work_solver_stats: Default::default(),
+            worker_idx,
+            target,
+            work_generator,
+            solution_sender,
+            client_manager,
+            measured_hashrate: AtomicU64::new(0),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    async fn run(self: Arc<Self>) {
+        // The hashrate has to be known before the remote servers are notified about it
+        let worker = self.clone();
+        if let Err(e) = task::spawn_blocking(move || worker.benchmark()).await {
+            error!("CPU: worker {} benchmark failed: {}", self.worker_idx, e);
+            self.running.store(false, Ordering::Relaxed);
+            return;
+        }
+        self.notify_nominal_hashrate_changed();
+
+        let mut work_generator = self.work_generator.clone();
+        while let Some(work) = work_generator.generate().await {
+            let worker = self.clone();
+            // Hashing would block the executor so it is moved to the blocking thread pool
+            if let Err(e) = task::spawn_blocking(move || worker.solve(work)).await {
+                error!("CPU: worker {} failed: {}", self.worker_idx, e);
+                break;
+            }
+        }
+        self.measured_hashrate.store(0, Ordering::Relaxed);
+        self.running.store(false, Ordering::Relaxed);
+        self.notify_nominal_hashrate_changed();
+        trace!("CPU: worker {} terminated", self.worker_idx);
+    }
+
+    /// Let all clients announce the new nominal hashrate to their remote servers
+    fn notify_nominal_hashrate_changed(&self) {
+        if let Some(client_manager) = self.client_manager.clone() {
+            tokio::spawn(async move {
+                client_manager.notify_nominal_hashrate_changed().await;
+            });
+        }
+    }
+
+    /// Stores the hashrate measured on `hashes` searched in `elapsed` time
+    fn update_hashrate(&self, hashes: u32, elapsed: Duration) {
+        let hashrate = hashes as f64 / elapsed.as_secs_f64();
+        // Keep the hashrate non-zero even for imprecise measurement of a running worker
+        self.measured_hashrate
+            .store(cmp::max(hashrate as u64, 1), Ordering::Relaxed);
+    }
+
+    /// Measures the hashrate on one batch of nonces of an empty block header
+    fn benchmark(&self) {
+        let start = Instant::now();
+        search_nonces(
+            Default::default(),
+            0..=NONCE_BATCH_SIZE - 1,
+            &self.target,
+            |_| {},
+        );
+        self.update_hashrate(NONCE_BATCH_SIZE, start.elapsed());
+    }
+
+    /// Searches the whole nonce space of all midstates of the `work` until its job is invalidated
+    fn solve(&self, work: work::Assignment) {
+        let mut solution_idx = 0;
+        for midstate_idx in 0..work.midstates.len() {
+            let header = work.get_block_header(midstate_idx, 0);
+            let mut first_nonce = 0;
+            loop {
+                if !work.has_valid_job() {
+                    return;
+                }
+                let last_nonce = first_nonce.saturating_add(NONCE_BATCH_SIZE - 1);
+                let start = Instant::now();
+                search_nonces(header, first_nonce..=last_nonce, &self.target, |nonce| {
+                    let solution = Solution {
+                        nonce,
+                        midstate_idx,
+                        solution_idx,
+                        target: self.target,
+                    };
+                    self.solution_sender
+                        .send(work::Solution::new(work.clone(), solution, None));
+                    solution_idx += 1;
+                });
+                self.update_hashrate(last_nonce - first_nonce + 1, start.elapsed());
+                if last_nonce == std::u32::MAX {
+                    break;
+                }
+                first_nonce = last_nonce + 1;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl node::WorkSolver for Worker {
+    fn get_id(&self) -> Option<usize> {
+        Some(self.worker_idx)
+    }
+
+    async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
+        match self.measured_hashrate.load(Ordering::Relaxed) {
+            0 => None,
+            hashrate => Some(ii_bitcoin::HashesUnit::Hashes(hashrate as u128)),
+        }
+    }
+
+    async fn get_state(&self) -> node::WorkSolverState {
+        if self.running.load(Ordering::Relaxed) {
+            node::WorkSolverState::Running
+        } else {
+            node::WorkSolverState::Stopped
+        }
+    }
+
+    async fn enable(self: Arc<Self>) -> bosminer::Result<()> {
+        // Enabling running worker has no effect
+        if !self.running.swap(true, Ordering::Relaxed) {
+            tokio::spawn(self.run());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Worker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CPU Worker {}", self.worker_idx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ii_bitcoin::HashTrait;
+
+    /// Search nonces around the known solution of test blocks
+    #[test]
+    fn test_search_nonces() {
+        let target = ii_bitcoin::Target::default();
+
+        for test_block in ii_bitcoin::TEST_BLOCKS.iter() {
+            let header = ii_bitcoin::BlockHeader {
+                version: test_block.version,
+                previous_hash: test_block.previous_hash.into_inner(),
+                merkle_root: test_block.merkle_root.into_inner(),
+                time: test_block.time,
+                bits: test_block.bits,
+                nonce: 0,
+            };
+            let first_nonce = test_block.nonce.saturating_sub(1000);
+            let last_nonce = test_block.nonce.saturating_add(1000);
+
+            let mut nonces = Vec::new();
+            search_nonces(header, first_nonce..=last_nonce, &target, |nonce| {
+                nonces.push(nonce)
+            });
+            assert_eq!(nonces, vec![test_block.nonce]);
+        }
+    }
+}
@@ -215,6 +215,8 @@ fn impl_derive_mining_stats(ast: &DeriveInput, derive_name: &str) -> proc_macro2
         member_accepted,
         member_rejected,
         member_stale,
+        member_duplicate,
+        member_discarded,
         member_valid_network_diff,
         member_valid_job_diff,
         member_valid_backend_diff,
@@ -243,6 +245,8 @@ fn impl_derive_client_stats(
     let accepted = find_member(&fields, "member_accepted");
     let rejected = find_member(&fields, "member_rejected");
     let stale = find_member(&fields, "member_stale");
+    let duplicate = find_member(&fields, "member_duplicate");
+    let discarded = find_member(&fields, "member_discarded");
 
     stream.extend(quote! {
         impl#generics stats::Client for #name#generics {
@@ -275,6 +279,16 @@ fn impl_derive_client_stats(
             fn stale(&self) -> &stats::Meter {
                 &self.#stale
             }
+
+            #[inline]
+            fn duplicate(&self) -> &stats::Meter {
+                &self.#duplicate
+            }
+
+            #[inline]
+            fn discarded(&self) -> &stats::Meter {
+                &self.#discarded
+            }
         }
     });
     stream
@@ -0,0 +1,373 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Simulated hash chain consisting of multiple chips which pull work from the frontend and emit
+//! solutions with real nonces ground on the CPU against an easy ASIC target
+
+use ii_logging::macros::*;
+
+use crate::config;
+use crate::Solution;
+
+use bosminer::async_trait;
+use bosminer::client;
+use bosminer::node;
+use bosminer::stats;
+use bosminer::work;
+use bosminer_macros::WorkSolverNode;
+
+use ii_async_compat::tokio;
+use tokio::task;
+use tokio::time::{delay_until, Instant};
+
+use ii_bitcoin::{HashTrait as _, MeetsTarget as _};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use std::fmt;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Number of hashes needed for sweeping the whole nonce space of one midstate
+const NONCE_SPACE_SIZE: f64 = (1u64 << 32) as f64;
+
+/// Offset of the nonce in the binary representation of block header
+const NONCE_OFFSET: usize = ii_bitcoin::BLOCK_HEADER_SIZE - size_of::<u32>();
+
+/// Average number of hashes needed for finding a solution which meets the `target`. The result
+/// is a floating point number because the simulated target may be easier than difficulty 1.
+pub fn expected_hashes(target: &ii_bitcoin::Target) -> f64 {
+    let target = target
+        .into_inner()
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |value, &limb| {
+            value * (1u128 << 64) as f64 + limb as f64
+        });
+    2f64.powi(256) / (target + 1.0)
+}
+
+/// Searches the nonce space of the block `header` starting at `first_nonce` and returns the first
+/// nonce whose double hash meets the `target` or misses it when `hw_error` is to be simulated.
+/// `None` is returned when no such nonce exists in the whole nonce space.
+pub fn grind_nonce(
+    header: ii_bitcoin::BlockHeader,
+    first_nonce: u32,
+    target: &ii_bitcoin::Target,
+    hw_error: bool,
+) -> Option<u32> {
+    let mut header_bytes = header.into_bytes();
+    let mut nonce = first_nonce;
+    loop {
+        header_bytes[NONCE_OFFSET..].copy_from_slice(&nonce.to_le_bytes());
+        if ii_bitcoin::DHash::hash(&header_bytes).meets(target) != hw_error {
+            return Some(nonce);
+        }
+        nonce = nonce.wrapping_add(1);
+        if nonce == first_nonce {
+            return None;
+        }
+    }
+}
+
+#[derive(Debug, WorkSolverNode)]
+pub struct HashChain {
+    #[member_work_solver_stats]
+    work_solver_stats: stats::BasicWorkSolver,
+    /// Index of the hash chain starting from 1
+    chain_idx: usize,
+    chip_count: usize,
+    /// Hashrate of one chip in hashes per second
+    chip_hashrate: f64,
+    /// Target of solutions found by simulated chips
+    asic_target: ii_bitcoin::Target,
+    hw_error_rate: f64,
+    stale_rate: f64,
+    work_generator: work::Generator,
+    solution_sender: work::SolutionSender,
+    /// Client manager notified about changes of the nominal hashrate
+    client_manager: Option<client::Manager>,
+    /// Number of chips which are currently solving work
+    running_chips: AtomicUsize,
+}
+
+impl HashChain {
+    pub fn new(
+        chain_idx: usize,
+        backend_config: &config::Backend,
+        work_generator: work::Generator,
+        solution_sender: work::SolutionSender,
+    ) -> Self {
+        Self {
+            work_solver_stats: Default::default(),
+            chain_idx,
+            chip_count: backend_config.chip_count,
+            chip_hashrate: backend_config.chip_hashrate(),
+            asic_target: backend_config.asic_target,
+            hw_error_rate: backend_config.hw_error_rate,
+            stale_rate: backend_config.stale_rate,
+            work_generator,
+            solution_sender,
+            client_manager: backend_config.client_manager(),
+            running_chips: AtomicUsize::new(0),
+        }
+    }
+
+    /// Spawn separate task for each simulated chip, the caller is responsible for accounting the
+    /// chips as running
+    fn start(self: Arc<Self>) {
+        info!(
+            "Simulator: starting hash chain {} with {} chips",
+            self.chain_idx, self.chip_count
+        );
+        for chip_idx in 0..self.chip_count {
+            tokio::spawn(self.clone().run_chip(chip_idx));
+        }
+        self.notify_nominal_hashrate_changed();
+    }
+
+    /// Let all clients announce the new nominal hashrate to their remote servers
+    fn notify_nominal_hashrate_changed(&self) {
+        if let Some(client_manager) = self.client_manager.clone() {
+            tokio::spawn(async move {
+                client_manager.notify_nominal_hashrate_changed().await;
+            });
+        }
+    }
+
+    async fn run_chip(self: Arc<Self>, chip_idx: usize) {
+        let mut rng = StdRng::from_entropy();
+        let mut work_generator = self.work_generator.clone();
+        // The chip keeps its own pace so that the simulated hashrate is stable even when some
+        // deadlines are missed
+        let mut deadline = Instant::now();
+        let mut previous_work: Option<work::Assignment> = None;
+        // The most recent work with invalidated job used for injection of stale solutions
+        let mut stale_work: Option<work::Assignment> = None;
+
+        while let Some(work) = work_generator.generate().await {
+            if let Some(previous_work) = previous_work.take() {
+                if !previous_work.has_valid_job() {
+                    stale_work.replace(previous_work);
+                }
+            }
+            self.solve(&mut rng, &mut deadline, &work, stale_work.as_ref())
+                .await;
+            previous_work.replace(work);
+        }
+        if self.running_chips.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.notify_nominal_hashrate_changed();
+        }
+        trace!(
+            "Simulator: chip {} on hash chain {} terminated",
+            chip_idx,
+            self.chain_idx
+        );
+    }
+
+    /// Simulates sweeping of the whole nonce space of all midstates in the `work`. Solutions are
+    /// emitted as a Poisson process with the rate given by chip hashrate and ASIC target.
+    /// The sweep is interrupted when the job is invalidated because with hashrate feasible for
+    /// CPU grinding it would take very long time.
+    async fn solve(
+        &self,
+        rng: &mut StdRng,
+        deadline: &mut Instant,
+        work: &work::Assignment,
+        stale_work: Option<&work::Assignment>,
+    ) {
+        let sweep_time = work.midstates.len() as f64 * NONCE_SPACE_SIZE / self.chip_hashrate;
+        let solution_rate = self.chip_hashrate / expected_hashes(&self.asic_target);
+        let start = *deadline;
+        let mut elapsed = 0.0;
+        let mut solution_idx = 0;
+
+        loop {
+            // Exponentially distributed time to the next solution
+            let interval = -(1.0 - rng.gen::<f64>()).ln() / solution_rate;
+            if elapsed + interval >= sweep_time {
+                break;
+            }
+            elapsed += interval;
+            delay_until(start + Duration::from_secs_f64(elapsed)).await;
+            if !work.has_valid_job() {
+                *deadline = Instant::now();
+                return;
+            }
+
+            let solution_work = match stale_work {
+                Some(stale_work) if rng.gen_bool(self.stale_rate) => stale_work,
+                _ => work,
+            };
+            if let Some(solution) = self.make_solution(rng, solution_work, solution_idx).await {
+                self.solution_sender.send(solution);
+                solution_idx += 1;
+            }
+        }
+        *deadline = start + Duration::from_secs_f64(sweep_time);
+        delay_until(*deadline).await;
+    }
+
+    /// Grinds a real nonce of randomly chosen midstate so that the solution passes (or fails in
+    /// case of injected hardware error) the verification in bOSminer and upstream
+    async fn make_solution(
+        &self,
+        rng: &mut StdRng,
+        work: &work::Assignment,
+        solution_idx: usize,
+    ) -> Option<work::Solution> {
+        let hw_error = rng.gen_bool(self.hw_error_rate);
+        let midstate_idx = rng.gen_range(0, work.midstates.len());
+        let header = work.get_block_header(midstate_idx, 0);
+        let first_nonce = rng.gen();
+        let target = self.asic_target;
+
+        // Hashing would block the executor so it is moved to the blocking thread pool
+        let nonce =
+            match task::spawn_blocking(move || grind_nonce(header, first_nonce, &target, hw_error))
+                .await
+            {
+                Ok(Some(nonce)) => nonce,
+                Ok(None) => {
+                    warn!(
+                        "Simulator: no nonce found on hash chain {} for ASIC target {}",
+                        self.chain_idx, self.asic_target
+                    );
+                    return None;
+                }
+                Err(e) => {
+                    error!(
+                        "Simulator: nonce grinding on hash chain {} failed: {}",
+                        self.chain_idx, e
+                    );
+                    return None;
+                }
+            };
+        let solution = Solution {
+            nonce,
+            midstate_idx,
+            solution_idx,
+            target: self.asic_target,
+        };
+        Some(work::Solution::new(work.clone(), solution, None))
+    }
+}
+
+#[async_trait]
+impl node::WorkSolver for HashChain {
+    fn get_id(&self) -> Option<usize> {
+        Some(self.chain_idx)
+    }
+
+    async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
+        match self.running_chips.load(Ordering::Relaxed) {
+            0 => None,
+            running_chips => Some(ii_bitcoin::HashesUnit::Hashes(
+                (self.chip_hashrate * running_chips as f64) as u128,
+            )),
+        }
+    }
+
+    async fn get_state(&self) -> node::WorkSolverState {
+        match self.running_chips.load(Ordering::Relaxed) {
+            0 => node::WorkSolverState::Stopped,
+            _ => node::WorkSolverState::Running,
+        }
+    }
+
+    async fn enable(self: Arc<Self>) -> bosminer::Result<()> {
+        // The chips are started only when none of them is running
+        if self
+            .running_chips
+            .compare_exchange(0, self.chip_count, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.start();
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for HashChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash Chain {}", self.chain_idx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ii_bitcoin::MeetsTarget;
+
+    fn test_header() -> ii_bitcoin::BlockHeader {
+        ii_bitcoin::BlockHeader {
+            version: 0x2000_0000,
+            previous_hash: [0x11; ii_bitcoin::SHA256_DIGEST_SIZE],
+            merkle_root: [0x22; ii_bitcoin::SHA256_DIGEST_SIZE],
+            time: 0x5e5d_5a00,
+            bits: 0x1d00_ffff,
+            nonce: 0,
+        }
+    }
+
+    #[test]
+    fn test_grind_nonce() {
+        // Target with difficulty 2^-16 is met once in 2^16 hashes on average
+        let target = ii_bitcoin::Target::from_hex(config::DEFAULT_ASIC_TARGET)
+            .expect("BUG: invalid default ASIC target");
+
+        for first_nonce in (0..4).map(|i| i * 0x4000_0000) {
+            let nonce = grind_nonce(test_header(), first_nonce, &target, false)
+                .expect("BUG: no nonce found");
+            let header = ii_bitcoin::BlockHeader {
+                nonce,
+                ..test_header()
+            };
+            assert!(header.hash().meets(&target));
+
+            let nonce = grind_nonce(test_header(), first_nonce, &target, true)
+                .expect("BUG: no nonce found");
+            let header = ii_bitcoin::BlockHeader {
+                nonce,
+                ..test_header()
+            };
+            assert!(!header.hash().meets(&target));
+        }
+    }
+
+    #[test]
+    fn test_expected_hashes() {
+        let expected = expected_hashes(&ii_bitcoin::Target::from_pool_difficulty(1));
+        assert!((expected / NONCE_SPACE_SIZE - 1.0).abs() < 1e-4);
+
+        let expected = expected_hashes(&ii_bitcoin::Target::from_pool_difficulty(64));
+        assert!((expected / NONCE_SPACE_SIZE - 64.0).abs() < 1e-2);
+
+        let target = ii_bitcoin::Target::from_hex(config::DEFAULT_ASIC_TARGET)
+            .expect("BUG: invalid default ASIC target");
+        assert!((expected_hashes(&target) / 65536.0 - 1.0).abs() < 1e-4);
+    }
+}
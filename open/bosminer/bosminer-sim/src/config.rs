@@ -0,0 +1,140 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use bosminer::client;
+use bosminer::hal;
+
+use bosminer_config::ClientDescriptor;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Override the default drain channel size as miner tends to burst messages into the logger
+pub const ASYNC_LOGGER_DRAIN_CHANNEL_SIZE: usize = 128;
+
+/// Number of midstates
+pub const DEFAULT_MIDSTATE_COUNT: usize = 1;
+
+/// Default number of simulated hash chains
+pub const DEFAULT_CHAIN_COUNT: usize = 3;
+
+/// Default number of simulated chips on each hash chain
+pub const DEFAULT_CHIP_COUNT: usize = 63;
+
+/// Default hashrate of the whole simulated device in GH/s
+/// All solutions are ground on the CPU so the hashrate has to be kept low
+pub const DEFAULT_HASHRATE_GHS: f64 = 0.001;
+
+/// Default target of solutions found by simulated chips (difficulty 2^-16)
+pub const DEFAULT_ASIC_TARGET: &str =
+    "0000ffff00000000000000000000000000000000000000000000000000000000";
+
+/// Default hashrate interval used for statistics in seconds
+pub const DEFAULT_HASHRATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum time it takes to compute one job under normal circumstances
+pub const JOB_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Backend {
+    client_manager: Option<client::Manager>,
+    client_descriptor: Option<ClientDescriptor>,
+    /// Number of simulated hash chains
+    pub chain_count: usize,
+    /// Number of simulated chips on each hash chain
+    pub chip_count: usize,
+    /// Hashrate of the whole simulated device
+    pub hashrate: ii_bitcoin::HashesUnit,
+    /// Target of solutions found by simulated chips (may be easier than difficulty 1)
+    pub asic_target: ii_bitcoin::Target,
+    /// Probability that a solution is a hardware error
+    pub hw_error_rate: f64,
+    /// Probability that a solution belongs to an already invalidated job
+    pub stale_rate: f64,
+    /// Address of HTTP server exporting Prometheus metrics
+    pub metrics_listen_addr: Option<SocketAddr>,
+}
+
+impl Backend {
+    pub fn new(client_descriptor: ClientDescriptor) -> Self {
+        Self {
+            client_descriptor: Some(client_descriptor),
+            ..Default::default()
+        }
+    }
+
+    /// Hashrate of one simulated chip in hashes per second
+    pub fn chip_hashrate(&self) -> f64 {
+        self.hashrate.into_hashes().into_f64() / (self.chain_count * self.chip_count) as f64
+    }
+
+    /// Client manager used by hash chains for announcing changes of their nominal hashrate
+    pub fn client_manager(&self) -> Option<client::Manager> {
+        self.client_manager.clone()
+    }
+
+    pub async fn init_client(self) {
+        if let Some(client_descriptor) = self.client_descriptor {
+            let group = self
+                .client_manager
+                .expect("BUG: missing client manager")
+                .create_or_get_default_group()
+                .await;
+
+            group
+                .push_client(client::Handle::new(client_descriptor, None, None))
+                .await;
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self {
+            client_manager: None,
+            client_descriptor: None,
+            chain_count: DEFAULT_CHAIN_COUNT,
+            chip_count: DEFAULT_CHIP_COUNT,
+            hashrate: ii_bitcoin::HashesUnit::GigaHashes(DEFAULT_HASHRATE_GHS),
+            asic_target: ii_bitcoin::Target::from_hex(DEFAULT_ASIC_TARGET)
+                .expect("BUG: invalid default ASIC target"),
+            hw_error_rate: 0.0,
+            stale_rate: 0.0,
+            metrics_listen_addr: None,
+        }
+    }
+}
+
+impl hal::BackendConfig for Backend {
+    #[inline]
+    fn midstate_count(&self) -> usize {
+        DEFAULT_MIDSTATE_COUNT
+    }
+
+    fn set_client_manager(&mut self, client_manager: client::Manager) {
+        self.client_manager.replace(client_manager);
+    }
+
+    fn metrics_listen_addr(&self) -> Option<SocketAddr> {
+        self.metrics_listen_addr
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use ii_logging::macros::*;
+
+use bosminer_sim::config;
+
+use bosminer_config::clap;
+use bosminer_config::{ClientDescriptor, ClientUserInfo};
+
+use ii_async_compat::tokio;
+
+use std::str::FromStr;
+
+/// Parse optional command line argument and check that its value is in the specified range
+fn parse_arg<T>(
+    matches: &clap::ArgMatches,
+    name: &str,
+    range: (T, T),
+    default: T,
+) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + std::fmt::Display,
+{
+    let value = match matches.value_of(name) {
+        Some(value) => value
+            .parse::<T>()
+            .map_err(|_| format!("invalid value '{}' of '{}'", value, name))?,
+        None => return Ok(default),
+    };
+    if value < range.0 || value > range.1 {
+        Err(format!(
+            "value of '{}' out of range [{}, {}]",
+            name, range.0, range.1
+        ))?;
+    }
+    Ok(value)
+}
+
+fn set_simulation_params(
+    matches: &clap::ArgMatches,
+    backend_config: &mut config::Backend,
+) -> Result<(), String> {
+    backend_config.chain_count = parse_arg(
+        matches,
+        "chains",
+        (1, usize::max_value()),
+        config::DEFAULT_CHAIN_COUNT,
+    )?;
+    backend_config.chip_count = parse_arg(
+        matches,
+        "chips",
+        (1, usize::max_value()),
+        config::DEFAULT_CHIP_COUNT,
+    )?;
+    backend_config.hashrate = ii_bitcoin::HashesUnit::GigaHashes(parse_arg(
+        matches,
+        "hashrate",
+        (std::f64::MIN_POSITIVE, std::f64::MAX),
+        config::DEFAULT_HASHRATE_GHS,
+    )?);
+    if let Some(value) = matches.value_of("difficulty") {
+        backend_config.asic_target = match value.parse::<usize>() {
+            Ok(difficulty) if difficulty > 0 => {
+                ii_bitcoin::Target::from_pool_difficulty(difficulty)
+            }
+            _ => Err(format!("invalid difficulty '{}'", value))?,
+        };
+    }
+    if let Some(value) = matches.value_of("target") {
+        backend_config.asic_target = ii_bitcoin::Target::from_hex(value)
+            .map_err(|_| format!("invalid target '{}'", value))?;
+    }
+    backend_config.hw_error_rate = parse_arg(matches, "hw-error-rate", (0.0, 1.0), 0.0)?;
+    backend_config.stale_rate = parse_arg(matches, "stale-rate", (0.0, 1.0), 0.0)?;
+    backend_config.metrics_listen_addr = match matches.value_of("metrics") {
+        Some(value) => Some(
+            value
+                .parse()
+                .map_err(|_| format!("invalid value '{}' of 'metrics'", value))?,
+        ),
+        None => None,
+    };
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let app = clap::App::new(bosminer::SIGNATURE)
+        .version(bosminer::version::STRING.as_str())
+        .arg(
+            clap::Arg::with_name("pool")
+                .short("p")
+                .long("pool")
+                .value_name("HOSTNAME:PORT")
+                .help("Address the stratum V2 server")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("user")
+                .short("u")
+                .long("user")
+                .value_name("USERNAME.WORKERNAME[:PASSWORD]")
+                .help("Specify user and worker name")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("chains")
+                .long("chains")
+                .value_name("COUNT")
+                .help("Number of simulated hash chains")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("chips")
+                .long("chips")
+                .value_name("COUNT")
+                .help("Number of simulated chips on each hash chain")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("hashrate")
+                .long("hashrate")
+                .value_name("GH/s")
+                .help("Hashrate of the whole simulated device")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("difficulty")
+                .long("difficulty")
+                .value_name("DIFFICULTY")
+                .help("Difficulty of solutions found by simulated chips")
+                .conflicts_with("target")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("target")
+                .long("target")
+                .value_name("HEX")
+                .help("Target of solutions found by simulated chips")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("hw-error-rate")
+                .long("hw-error-rate")
+                .value_name("PROBABILITY")
+                .help("Probability that a solution is a hardware error")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("stale-rate")
+                .long("stale-rate")
+                .value_name("PROBABILITY")
+                .help("Probability that a solution belongs to an invalidated job")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("metrics")
+                .long("metrics")
+                .value_name("HOSTNAME:PORT")
+                .help("Export Prometheus metrics over HTTP on given address")
+                .takes_value(true),
+        );
+
+    let matches = app.get_matches();
+    let _log_guard =
+        ii_logging::setup_for_app(bosminer_sim::config::ASYNC_LOGGER_DRAIN_CHANNEL_SIZE);
+
+    let url = matches
+        .value_of("pool")
+        .expect("BUG: missing 'pool' attribute");
+    let user_info = matches
+        .value_of("user")
+        .expect("BUG: missing 'user' attribute");
+    let user_info = ClientUserInfo::parse(user_info);
+
+    let mut backend_config =
+        config::Backend::new(match ClientDescriptor::create(url, &user_info, true) {
+            Err(e) => {
+                error!("Cannot set pool from command line: {}", e.to_string());
+                return;
+            }
+            Ok(v) => v,
+        });
+
+    if let Err(e) = set_simulation_params(&matches, &mut backend_config) {
+        error!("Cannot set simulation parameters from command line: {}", e);
+        return;
+    }
+
+    ii_async_compat::setup_panic_handling();
+    bosminer::main::<bosminer_sim::Backend>(backend_config, bosminer::SIGNATURE.to_string()).await;
+}
@@ -21,13 +21,25 @@
 // contact us at opensource@braiins.com.
 
 mod cgminer;
+mod prometheus;
 
 use crate::hal;
 use crate::hub;
 
+use ii_async_compat::tokio;
+
+use std::net::SocketAddr;
 use std::sync::Arc;
 
-pub async fn run(core: Arc<hub::Core>, config: hal::FrontendConfig, signature: String) {
+pub async fn run(
+    core: Arc<hub::Core>,
+    config: hal::FrontendConfig,
+    signature: String,
+    metrics_listen_addr: Option<SocketAddr>,
+) {
+    if let Some(metrics_listen_addr) = metrics_listen_addr {
+        tokio::spawn(prometheus::run(core.clone(), metrics_listen_addr));
+    }
     let addr = "0.0.0.0:4028".parse().unwrap();
     cgminer::run(core, addr, config.cgminer_custom_commands, signature).await;
 }
@@ -84,6 +84,7 @@ impl Handler {
         let accepted = client_stats.accepted().take_snapshot().await;
         let rejected = client_stats.rejected().take_snapshot().await;
         let stale = client_stats.stale().take_snapshot().await;
+        let discarded = client_stats.discarded().take_snapshot().await;
         let last_share = client_stats.last_share().take_snapshot().await;
         let valid_backend_diff = client_stats.valid_backend_diff().take_snapshot().await;
         let best_share = client_stats.best_share().take_snapshot();
@@ -145,8 +146,7 @@ impl Handler {
             accepted: accepted.solutions,
             rejected: rejected.solutions,
             works: *generated_work as i32,
-            // TODO: BOSminer does not account this information
-            discarded: 0,
+            discarded: discarded.solutions as u32,
             stale: stale.solutions as u32,
             // TODO: account failures
             get_failures: 0,
@@ -196,6 +196,7 @@ impl Handler {
         let valid_job_diff = mining_stats.valid_job_diff().take_snapshot().await;
         let valid_backend_diff = mining_stats.valid_backend_diff().take_snapshot().await;
         let error_backend_diff = mining_stats.error_backend_diff().take_snapshot().await;
+        let state = work_solver.get_state().await;
 
         let now = time::Instant::now();
         let elapsed = now.duration_since(*mining_stats.start_time());
@@ -222,10 +223,15 @@ impl Handler {
             // TODO: get actual ASIC name from work solver
             name: "".to_string(),
             id: work_solver.get_id().unwrap_or(idx) as i32,
-            // TODO: get actual state from work solver
-            enabled: response::Bool::Y,
-            // TODO: get actual status from work solver
-            status: response::AscStatus::Alive,
+            enabled: match state {
+                node::WorkSolverState::Disabled => response::Bool::N,
+                _ => response::Bool::Y,
+            },
+            status: match state {
+                node::WorkSolverState::Running => response::AscStatus::Alive,
+                node::WorkSolverState::Disabled => response::AscStatus::Dead,
+                node::WorkSolverState::Stopped => response::AscStatus::NoStart,
+            },
             // TODO: get actual temperature from work solver?
             temperature: 0.0,
             mhs_av: total_mega_hashes / elapsed.as_secs_f64(),
@@ -370,6 +376,17 @@ impl Handler {
             .map(|client| (client, clients))
     }
 
+    async fn get_work_solver(
+        &self,
+        idx: i32,
+    ) -> Result<Arc<dyn node::WorkSolver>, response::ErrorCode> {
+        let work_solvers = self.core.get_work_solvers().await;
+        work_solvers
+            .get(idx as usize)
+            .cloned()
+            .ok_or_else(|| response::ErrorCode::InvalidAscId(idx, work_solvers.len() as i32 - 1))
+    }
+
     fn get_client_descriptor(&self, parameter: &str) -> Result<ClientDescriptor, ()> {
         let parameters: Vec<_> = parameter
             .split(ii_cgminer_api::PARAMETER_DELIMITER)
@@ -455,6 +472,7 @@ impl command::Handler for Handler {
         let mut pools_rejected_shares = 0.0;
         let mut pools_stale = 0;
         let mut pools_stale_shares = 0.0;
+        let mut pools_discarded = 0;
 
         for client in self.get_clients().await {
             let client_stats = client.stats();
@@ -463,6 +481,7 @@ impl command::Handler for Handler {
             let accepted = client_stats.accepted().take_snapshot().await;
             let rejected = client_stats.rejected().take_snapshot().await;
             let stale = client_stats.stale().take_snapshot().await;
+            let discarded = client_stats.discarded().take_snapshot().await;
 
             pools_valid_jobs += *valid_jobs as u64;
             pools_accepted += accepted.solutions;
@@ -471,6 +490,7 @@ impl command::Handler for Handler {
             pools_rejected_shares += rejected.shares.as_f64();
             pools_stale += stale.solutions;
             pools_stale_shares += stale.shares.as_f64();
+            pools_discarded += discarded.solutions;
         }
 
         let pools_all_solutions = pools_accepted + pools_rejected + pools_stale;
@@ -520,8 +540,7 @@ impl command::Handler for Handler {
             rejected: pools_rejected,
             hardware_errors: backend_error_solutions as i32,
             utility: pools_utility,
-            // TODO: BOSminer does not account this information
-            discarded: 0,
+            discarded: pools_discarded as i64,
             stale: pools_stale,
             // TODO: BOSminer does not account this information
             get_failures: 0,
@@ -747,16 +766,54 @@ impl command::Handler for Handler {
             .expect("BUG: missing ASC parameter")
             .to_i32()
             .expect("BUG: invalid ASC parameter type");
+        let work_solver = self.get_work_solver(idx).await?;
 
-        let work_solvers = self.core.get_work_solvers().await;
-        let work_solver = work_solvers.get(idx as usize).cloned();
+        Ok(Self::get_asc_status(idx as usize, work_solver).await)
+    }
 
-        match work_solver {
-            Some(work_solver) => Ok(Self::get_asc_status(idx as usize, work_solver).await),
-            None => {
-                Err(response::ErrorCode::InvalidAscId(idx, work_solvers.len() as i32 - 1).into())
-            }
+    async fn handle_asc_enable(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<response::AscEnable> {
+        let idx = parameter
+            .expect("BUG: missing ASCENABLE parameter")
+            .to_i32()
+            .expect("BUG: invalid ASCENABLE parameter type");
+        let work_solver = self.get_work_solver(idx).await?;
+
+        // Stopped work solver can be enabled to retry its start
+        if work_solver.get_state().await == node::WorkSolverState::Running {
+            return Err(response::InfoCode::AscAlreadyEnabled(idx).into());
+        }
+        let path: node::Path = vec![Arc::new(work_solver) as node::DynInfo];
+        self.core
+            .enable_work_solver(&path)
+            .await
+            .map_err(|e| response::ErrorCode::AscSetError(idx, e.to_string()))?;
+
+        Ok(response::AscEnable { idx })
+    }
+
+    async fn handle_asc_disable(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<response::AscDisable> {
+        let idx = parameter
+            .expect("BUG: missing ASCDISABLE parameter")
+            .to_i32()
+            .expect("BUG: invalid ASCDISABLE parameter type");
+        let work_solver = self.get_work_solver(idx).await?;
+
+        if work_solver.get_state().await == node::WorkSolverState::Disabled {
+            return Err(response::InfoCode::AscAlreadyDisabled(idx).into());
         }
+        let path: node::Path = vec![Arc::new(work_solver) as node::DynInfo];
+        self.core
+            .disable_work_solver(&path)
+            .await
+            .map_err(|e| response::ErrorCode::AscSetError(idx, e.to_string()))?;
+
+        Ok(response::AscDisable { idx })
     }
 
     async fn handle_lcd(&self) -> command::Result<response::Lcd> {
@@ -0,0 +1,511 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! This module implements HTTP server exporting statistics of the whole BOSminer hierarchy in
+//! Prometheus text format on `/metrics` path.
+
+use ii_logging::macros::*;
+
+use crate::client;
+use crate::hub;
+use crate::node::{self, Stats as _};
+use crate::stats::{self, UnixTime as _};
+
+use futures::stream::StreamExt;
+use ii_async_compat::{futures, tokio, FutureExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time;
+
+use stats::TIME_MEAN_INTERVAL_15M as INTERVAL_15M;
+use stats::TIME_MEAN_INTERVAL_1M as INTERVAL_1M;
+use stats::TIME_MEAN_INTERVAL_24H as INTERVAL_24H;
+use stats::TIME_MEAN_INTERVAL_5M as INTERVAL_5M;
+use stats::TIME_MEAN_INTERVAL_5S as INTERVAL_5S;
+
+/// Path of the only resource provided by the server
+const METRICS_PATH: &str = "/metrics";
+/// Content type of Prometheus text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// Maximal size of accepted HTTP request header
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Time given to a client to send its request
+const REQUEST_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+const PREFIX: &str = "bosminer_";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+        }
+    }
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+/// All samples of one metric with the same name
+#[derive(Debug)]
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    metric_type: MetricType,
+    samples: Vec<(Labels, f64)>,
+}
+
+/// Collection of metric families rendered to Prometheus text format. Families are kept in the
+/// order of their first occurrence because the format requires all samples of one family to be
+/// grouped together.
+#[derive(Debug, Default)]
+struct Exposition {
+    families: Vec<Family>,
+}
+
+impl Exposition {
+    fn add(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        metric_type: MetricType,
+        labels: Labels,
+        value: f64,
+    ) {
+        let family = match self
+            .families
+            .iter_mut()
+            .position(|family| family.name == name)
+        {
+            Some(idx) => &mut self.families[idx],
+            None => {
+                self.families.push(Family {
+                    name,
+                    help,
+                    metric_type,
+                    samples: vec![],
+                });
+                self.families.last_mut().expect("BUG: missing family")
+            }
+        };
+        assert_eq!(
+            family.metric_type, metric_type,
+            "BUG: inconsistent type of metric '{}'",
+            name
+        );
+        family.samples.push((labels, value));
+    }
+
+    #[inline]
+    fn counter(&mut self, name: &'static str, help: &'static str, labels: &Labels, value: f64) {
+        self.add(name, help, MetricType::Counter, labels.clone(), value)
+    }
+
+    #[inline]
+    fn gauge(&mut self, name: &'static str, help: &'static str, labels: &Labels, value: f64) {
+        self.add(name, help, MetricType::Gauge, labels.clone(), value)
+    }
+
+    fn escape_label_value(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        for family in &self.families {
+            let _ = writeln!(output, "# HELP {}{} {}", PREFIX, family.name, family.help);
+            let _ = writeln!(
+                output,
+                "# TYPE {}{} {}",
+                PREFIX,
+                family.name,
+                family.metric_type.as_str()
+            );
+            for (labels, value) in &family.samples {
+                let _ = write!(output, "{}{}", PREFIX, family.name);
+                if !labels.is_empty() {
+                    let labels: Vec<_> = labels
+                        .iter()
+                        .map(|(name, value)| {
+                            format!("{}=\"{}\"", name, Self::escape_label_value(value))
+                        })
+                        .collect();
+                    let _ = write!(output, "{{{}}}", labels.join(","));
+                }
+                let _ = writeln!(output, " {}", value);
+            }
+        }
+        output
+    }
+}
+
+/// Extends `labels` with one more label
+fn with_label(labels: &Labels, name: &'static str, value: String) -> Labels {
+    let mut labels = labels.clone();
+    labels.push((name, value));
+    labels
+}
+
+async fn collect_meter(
+    exposition: &mut Exposition,
+    labels: &Labels,
+    meter: &stats::Meter,
+    target: &'static str,
+    now: time::Instant,
+) {
+    let snapshot = meter.take_snapshot().await;
+    let labels = with_label(labels, "target", target.to_string());
+
+    exposition.counter(
+        "valid_solutions_total",
+        "Number of valid solutions meeting the target",
+        &labels,
+        snapshot.solutions as f64,
+    );
+    exposition.counter(
+        "valid_shares_total",
+        "Difficulty of valid solutions meeting the target",
+        &labels,
+        snapshot.shares.as_f64(),
+    );
+    for (window, interval) in &[
+        ("5s", *INTERVAL_5S),
+        ("1m", *INTERVAL_1M),
+        ("5m", *INTERVAL_5M),
+        ("15m", *INTERVAL_15M),
+        ("24h", *INTERVAL_24H),
+    ] {
+        exposition.gauge(
+            "hashrate_hashes_per_second",
+            "Hashrate computed from valid solutions in given time window",
+            &with_label(&labels, "window", window.to_string()),
+            snapshot
+                .to_kilo_hashes(*interval, now)
+                .into_hashes()
+                .into_f64(),
+        );
+    }
+}
+
+/// Collect statistics common for all nodes in the hierarchy
+async fn collect_mining_stats<T>(
+    exposition: &mut Exposition,
+    labels: &Labels,
+    mining_stats: &T,
+    now: time::Instant,
+) where
+    T: stats::Mining + ?Sized,
+{
+    collect_meter(
+        exposition,
+        labels,
+        mining_stats.valid_backend_diff(),
+        "backend",
+        now,
+    )
+    .await;
+    collect_meter(
+        exposition,
+        labels,
+        mining_stats.valid_job_diff(),
+        "job",
+        now,
+    )
+    .await;
+    collect_meter(
+        exposition,
+        labels,
+        mining_stats.valid_network_diff(),
+        "network",
+        now,
+    )
+    .await;
+
+    let error_backend_diff = mining_stats.error_backend_diff().take_snapshot().await;
+    exposition.counter(
+        "hardware_errors_total",
+        "Number of invalid solutions (hardware errors)",
+        labels,
+        error_backend_diff.solutions as f64,
+    );
+    exposition.counter(
+        "hardware_error_shares_total",
+        "Difficulty of invalid solutions (hardware errors)",
+        labels,
+        error_backend_diff.shares.as_f64(),
+    );
+
+    if let Some(last_share) = mining_stats.last_share().take_snapshot().await {
+        exposition.gauge(
+            "last_share_timestamp_seconds",
+            "Unix time of the last valid share",
+            labels,
+            last_share.time.get_unix_time().unwrap_or_default() as f64,
+        );
+        exposition.gauge(
+            "last_share_difficulty",
+            "Difficulty of the last valid share",
+            labels,
+            last_share.difficulty as f64,
+        );
+    }
+    if let Some(best_share) = mining_stats.best_share().take_snapshot() {
+        exposition.gauge(
+            "best_share_difficulty",
+            "Difficulty of the best share",
+            labels,
+            *best_share as f64,
+        );
+    }
+}
+
+fn node_labels(kind: &'static str, node: &Arc<dyn node::WorkSolver>) -> Labels {
+    let mut labels = vec![("node", kind.to_string()), ("name", node.to_string())];
+    if let Some(id) = node.get_id() {
+        labels.push(("id", id.to_string()));
+    }
+    labels
+}
+
+async fn collect_client(
+    exposition: &mut Exposition,
+    group: &client::Group,
+    idx: usize,
+    client: Arc<client::Handle>,
+    now: time::Instant,
+) {
+    let descriptor = client.descriptor().await;
+    let labels = vec![
+        ("node", "pool".to_string()),
+        ("group", group.descriptor.name.clone()),
+        ("pool", idx.to_string()),
+        ("url", descriptor.get_url(true, true, false)),
+        ("user", descriptor.user.clone()),
+    ];
+
+    exposition.gauge(
+        "pool_enabled",
+        "Pool is enabled",
+        &labels,
+        client.is_enabled() as u8 as f64,
+    );
+    exposition.gauge(
+        "pool_up",
+        "Pool is connected and running",
+        &labels,
+        client.is_running() as u8 as f64,
+    );
+
+    let client_stats = client.stats();
+    exposition.counter(
+        "pool_valid_jobs_total",
+        "Number of valid jobs received from the pool",
+        &labels,
+        *client_stats.valid_jobs().take_snapshot() as f64,
+    );
+    exposition.counter(
+        "pool_invalid_jobs_total",
+        "Number of invalid jobs received from the pool",
+        &labels,
+        *client_stats.invalid_jobs().take_snapshot() as f64,
+    );
+    exposition.counter(
+        "pool_generated_work_total",
+        "Amount of work generated from jobs of the pool",
+        &labels,
+        *client_stats.generated_work().take_snapshot() as f64,
+    );
+    for (result, meter) in &[
+        ("accepted", client_stats.accepted()),
+        ("rejected", client_stats.rejected()),
+        ("stale", client_stats.stale()),
+        ("duplicate", client_stats.duplicate()),
+        ("discarded", client_stats.discarded()),
+    ] {
+        let snapshot = meter.take_snapshot().await;
+        let labels = with_label(&labels, "result", result.to_string());
+        exposition.counter(
+            "pool_shares_total",
+            "Number of shares submitted to the pool by result",
+            &labels,
+            snapshot.solutions as f64,
+        );
+        exposition.counter(
+            "pool_shares_difficulty_total",
+            "Difficulty of shares submitted to the pool by result",
+            &labels,
+            snapshot.shares.as_f64(),
+        );
+    }
+
+    collect_mining_stats(exposition, &labels, client.stats(), now).await;
+}
+
+/// Walk the frontend, all client groups and the backend hierarchy and collect their statistics
+async fn collect(core: &hub::Core) -> Exposition {
+    let mut exposition = Exposition::default();
+    let now = time::Instant::now();
+
+    let frontend: Arc<dyn node::WorkSolver> = core.frontend.clone();
+    collect_mining_stats(
+        &mut exposition,
+        &node_labels("frontend", &frontend),
+        frontend.mining_stats(),
+        now,
+    )
+    .await;
+
+    for group in core.get_client_manager().get_groups().await {
+        for (idx, client) in group.get_clients().await.drain(..).enumerate() {
+            collect_client(&mut exposition, &group, idx, client, now).await;
+        }
+    }
+
+    for work_hub in core.get_work_hubs().await {
+        collect_mining_stats(
+            &mut exposition,
+            &node_labels("hub", &work_hub),
+            work_hub.mining_stats(),
+            now,
+        )
+        .await;
+    }
+    for work_solver in core.get_work_solvers().await {
+        collect_mining_stats(
+            &mut exposition,
+            &node_labels("solver", &work_solver),
+            work_solver.mining_stats(),
+            now,
+        )
+        .await;
+    }
+
+    exposition
+}
+
+/// Read HTTP request header and return requested path of GET method
+async fn read_request_path(stream: &mut tokio::net::TcpStream) -> Option<String> {
+    let mut request = Vec::with_capacity(512);
+    let mut buffer = [0u8; 512];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let len = match stream.read(&mut buffer).timeout(REQUEST_TIMEOUT).await {
+            Ok(Ok(len)) if len > 0 => len,
+            _ => return None,
+        };
+        request.extend_from_slice(&buffer[..len]);
+        if request.len() > MAX_REQUEST_SIZE {
+            return None;
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next()?.split_whitespace();
+    match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(path)) => Some(path.to_string()),
+        _ => None,
+    }
+}
+
+async fn handle_connection(mut stream: tokio::net::TcpStream, core: Arc<hub::Core>) {
+    let (status, body) = match read_request_path(&mut stream).await {
+        // Query string is not supported and it is ignored
+        Some(path) if path.split('?').next() == Some(METRICS_PATH) => {
+            ("200 OK", collect(&core).await.render())
+        }
+        Some(_) => ("404 Not Found", "Not Found\n".to_string()),
+        None => ("400 Bad Request", "Bad Request\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        CONTENT_TYPE,
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        warn!("Prometheus exporter: cannot send response ({})", e);
+    }
+}
+
+pub async fn run(core: Arc<hub::Core>, listen_addr: SocketAddr) {
+    let mut server = match ii_wire::Server::bind(&listen_addr) {
+        Ok(server) => server,
+        Err(e) => {
+            error!(
+                "Prometheus exporter: cannot listen on '{}' ({})",
+                listen_addr, e
+            );
+            return;
+        }
+    };
+    info!(
+        "Prometheus exporter: listening on 'http://{}{}'",
+        listen_addr, METRICS_PATH
+    );
+
+    while let Some(stream) = server.next().await {
+        if let Ok(stream) = stream {
+            tokio::spawn(handle_connection(stream, core.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exposition_render() {
+        let mut exposition = Exposition::default();
+        let labels = vec![("pool", "0".to_string())];
+        exposition.counter("shares_total", "Shares", &labels, 1.0);
+        exposition.gauge("up", "Pool is up", &vec![], 1.0);
+        exposition.counter(
+            "shares_total",
+            "Shares",
+            &vec![("url", "a\"b\\c\nd".to_string())],
+            2.5,
+        );
+
+        assert_eq!(
+            exposition.render(),
+            "# HELP bosminer_shares_total Shares\n\
+             # TYPE bosminer_shares_total counter\n\
+             bosminer_shares_total{pool=\"0\"} 1\n\
+             bosminer_shares_total{url=\"a\\\"b\\\\c\\nd\"} 2.5\n\
+             # HELP bosminer_up Pool is up\n\
+             # TYPE bosminer_up gauge\n\
+             bosminer_up 1\n"
+        );
+    }
+}
@@ -69,6 +69,7 @@ impl HierarchyBuilder for IgnoreHierarchy {}
 /// This structure contains list of backend nodes and is also the default hierarchy builder for the
 /// BOSminer. It collects all work solvers and work hubs (special case of solver which only routes
 /// work to its child nodes and is useful for statistics aggregation and group control)
+#[derive(Debug)]
 pub struct Registry {
     /// Special work hub which represents the whole backend
     root_hub: Mutex<Option<Arc<dyn node::WorkSolver>>>,
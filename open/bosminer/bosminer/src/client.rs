@@ -23,19 +23,24 @@
 //! This module contains common functionality related to mining protocol client and allows
 //! executing a specific type of mining protocol client instance.
 
+pub mod journal;
 mod scheduler;
 
 // Sub-modules with client implementation
 pub mod drain;
+pub mod gbt;
 pub mod stratum_v2;
 pub mod stratum_v2_channels;
 
+use ii_logging::macros::*;
+
+use crate::backend;
 use crate::error;
 use crate::hal;
 use crate::job;
 use crate::node;
 use crate::stats;
-use crate::sync::event;
+use crate::sync::{self, event};
 use crate::work;
 
 // Scheduler re-exports
@@ -43,16 +48,17 @@ pub use scheduler::JobExecutor;
 
 use bosminer_config::{
     ClientDescriptor, ClientProtocol, ClientUserInfo, GroupConfig, GroupDescriptor,
-    LoadBalanceStrategy,
+    LoadBalanceStrategy, PoolConfig, ShareAccounting,
 };
 
-use futures::channel::mpsc;
 use futures::lock::Mutex;
-use ii_async_compat::futures;
+use ii_async_compat::{futures, tokio};
+use tokio::time::delay_for;
 
 use std::slice;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time;
 
 #[derive(Debug)]
 pub struct Handle {
@@ -61,7 +67,7 @@ pub struct Handle {
     node: Arc<dyn node::Client>,
     enabled: AtomicBool,
     engine_sender: Arc<work::EngineSender>,
-    solution_sender: mpsc::UnboundedSender<work::Solution>,
+    solution_sender: job::SolutionSender,
 }
 
 impl Handle {
@@ -75,7 +81,7 @@ impl Handle {
             stratum_v2::ExtensionChannelFromStratumSender,
         )>,
     ) -> Self {
-        let (solution_sender, solution_receiver) = mpsc::unbounded();
+        let (solution_sender, solution_receiver) = job::solution_channel();
         // Initially register new client without ability to send work
         let engine_sender = Arc::new(work::EngineSender::new(None));
 
@@ -88,6 +94,16 @@ impl Handle {
                 );
                 Arc::new(drain::Client::new(descriptor.get_full_url(), job_solver))
             }
+            ClientProtocol::Gbt => {
+                assert!(
+                    channel.is_none(),
+                    "BUG: protocol 'GetBlockTemplate' does not support channel"
+                );
+                Arc::new(gbt::Client::new(
+                    gbt::ConnectionDetails::from_descriptor(&descriptor),
+                    job_solver,
+                ))
+            }
             ClientProtocol::StratumV1 => {
                 assert!(
                     channel.is_none(),
@@ -134,6 +150,11 @@ impl Handle {
         *current_descriptor = descriptor;
     }
 
+    #[inline]
+    pub fn update_nominal_hashrate(&self, nominal_hashrate: ii_bitcoin::HashesUnit) {
+        self.node.update_nominal_hashrate(nominal_hashrate);
+    }
+
     pub fn replace_engine_generator(
         &self,
         engine_generator: work::EngineGenerator,
@@ -179,6 +200,15 @@ impl Handle {
         self.node.status().status()
     }
 
+    /// Check if the client is neither running nor shutting down
+    #[inline]
+    fn is_finished(&self) -> bool {
+        let status = self.status();
+        status == sync::Status::Created
+            || status == sync::Status::Stopped
+            || status == sync::Status::Failed
+    }
+
     #[inline]
     fn start(&self) {
         if self.node.status().initiate_starting() {
@@ -313,13 +343,13 @@ impl Group {
     pub async fn push_client(&self, client_handle: Handle) -> Arc<Handle> {
         let midstate_count = self.midstate_count;
         let _ = client_handle.replace_engine_generator(Box::new(move |job| {
-            Arc::new(work::engine::VersionRolling::new(job, midstate_count))
+            work::engine::create(job, midstate_count)
         }));
         let _ = client_handle.try_disable();
         client_handle.set_event_sender(self.event_sender.clone());
 
         let client_handle = Arc::new(client_handle);
-        let scheduler_client_handle = scheduler::ClientHandle::new(client_handle.clone());
+        let scheduler_client_handle = scheduler::ClientHandle::new(client_handle.clone()).await;
         self.scheduler_client_handles
             .lock()
             .await
@@ -394,6 +424,135 @@ impl Group {
         Ok(client_handle)
     }
 
+    async fn position(&self, client_handle: &Arc<Handle>) -> Option<usize> {
+        self.scheduler_client_handles
+            .lock()
+            .await
+            .iter()
+            .position(|scheduler_client_handle| {
+                Arc::ptr_eq(&scheduler_client_handle.client_handle, client_handle)
+            })
+    }
+
+    /// Create a new group with a different descriptor which takes over all clients of this group
+    async fn take_over(&self, descriptor: GroupDescriptor) -> Self {
+        let scheduler_client_handles =
+            std::mem::replace(&mut *self.scheduler_client_handles.lock().await, vec![]);
+        Self {
+            descriptor,
+            scheduler_client_handles: Mutex::new(scheduler_client_handles),
+            event_sender: self.event_sender.clone(),
+            midstate_count: self.midstate_count,
+        }
+    }
+
+    /// Remove all clients from the group
+    async fn clear(&self) {
+        while !self.is_empty().await {
+            let _ = self.remove_client_at(0).await;
+        }
+    }
+
+    /// Apply changed descriptor to the running client. The connection is restarted only when
+    /// the connection details have been changed.
+    async fn update_client(client_handle: &Handle, descriptor: ClientDescriptor) {
+        let current_descriptor = client_handle.descriptor().await;
+        let connection_changed = current_descriptor.protocol.to_string()
+            != descriptor.protocol.to_string()
+            || current_descriptor.get_full_url() != descriptor.get_full_url()
+            || current_descriptor.password != descriptor.password
+            || current_descriptor.fragment != descriptor.fragment;
+        let enabled = descriptor.enabled;
+        if !connection_changed
+            && current_descriptor.enabled == enabled
+            && current_descriptor.stale_policy == descriptor.stale_policy
+            && current_descriptor.allow_cross_host_reconnect
+                == descriptor.allow_cross_host_reconnect
+        {
+            return;
+        }
+
+        client_handle.change_descriptor(descriptor).await;
+        if !enabled {
+            let _ = client_handle.try_disable();
+        } else if connection_changed {
+            let _ = client_handle.try_restart(false);
+        } else {
+            let _ = client_handle.try_enable();
+        }
+    }
+
+    /// Synchronize clients of the group with given `descriptors` which are ordered by priority.
+    /// Clients connected to the same pool (URL with user) are kept and only changed settings are
+    /// applied to them. A client at the same position using the same protocol is reconfigured to
+    /// a new pool. All other clients are removed and new clients are created for remaining pools.
+    pub async fn reconcile_clients(
+        &self,
+        descriptors: Vec<ClientDescriptor>,
+        backend_info: Option<&hal::BackendInfo>,
+    ) {
+        let clients = self.get_clients().await;
+        let mut client_urls = Vec::with_capacity(clients.len());
+        let mut client_schemes = Vec::with_capacity(clients.len());
+        for client_handle in clients.iter() {
+            let descriptor = client_handle.descriptor().await;
+            client_urls.push(descriptor.get_full_url());
+            client_schemes.push(descriptor.protocol.scheme());
+        }
+
+        let mut used = vec![false; clients.len()];
+        let mut assigned: Vec<Option<usize>> = vec![None; descriptors.len()];
+        for (idx, descriptor) in descriptors.iter().enumerate() {
+            let url = descriptor.get_full_url();
+            if let Some(client_idx) =
+                (0..clients.len()).find(|i| !used[*i] && client_urls[*i] == url)
+            {
+                used[client_idx] = true;
+                assigned[idx] = Some(client_idx);
+            }
+        }
+        for (idx, descriptor) in descriptors.iter().enumerate() {
+            if assigned[idx].is_none()
+                && idx < clients.len()
+                && !used[idx]
+                && client_schemes[idx] == descriptor.protocol.scheme()
+            {
+                used[idx] = true;
+                assigned[idx] = Some(idx);
+            }
+        }
+
+        for (client_handle, _) in clients.iter().zip(used.iter()).filter(|(_, used)| !**used) {
+            if let Some(idx) = self.position(client_handle).await {
+                let _ = self.remove_client_at(idx).await;
+            }
+        }
+
+        let mut next_clients = Vec::with_capacity(descriptors.len());
+        for (descriptor, client_idx) in descriptors.into_iter().zip(assigned) {
+            next_clients.push(match client_idx {
+                Some(client_idx) => {
+                    let client_handle = clients[client_idx].clone();
+                    Self::update_client(&client_handle, descriptor).await;
+                    client_handle
+                }
+                None => {
+                    self.push_client(Handle::new(descriptor, backend_info.cloned(), None))
+                        .await
+                }
+            });
+        }
+
+        // Order clients by their priority
+        for (idx, client_handle) in next_clients.iter().enumerate() {
+            if let Some(current_idx) = self.position(client_handle).await {
+                if current_idx != idx {
+                    let _ = self.move_client_to(current_idx, idx).await;
+                }
+            }
+        }
+    }
+
     async fn find_client(&self, solution: &work::Solution) -> Option<Arc<Handle>> {
         self.scheduler_client_handles
             .lock()
@@ -412,9 +571,10 @@ impl Group {
 pub struct GroupRegistry {
     list: Vec<scheduler::GroupHandle>,
     event_monitor: event::Monitor,
-    total_quota: usize,
     fixed_share_ratio_count: usize,
     total_fixed_share_ratio: f64,
+    /// Work accounted to groups when balancing hashrate between them
+    share_accounting: ShareAccounting,
 }
 
 impl GroupRegistry {
@@ -422,9 +582,9 @@ impl GroupRegistry {
         Self {
             list: vec![],
             event_monitor,
-            total_quota: 0,
             fixed_share_ratio_count: 0,
             total_fixed_share_ratio: 0.0,
+            share_accounting: Default::default(),
         }
     }
 
@@ -458,9 +618,7 @@ impl GroupRegistry {
         midstate_count: usize,
     ) -> Result<Arc<Group>, error::Client> {
         match descriptor.strategy() {
-            LoadBalanceStrategy::Quota(quota) => {
-                self.total_quota += quota;
-            }
+            LoadBalanceStrategy::Quota(_) | LoadBalanceStrategy::Schedule(_) => {}
             LoadBalanceStrategy::FixedShareRatio(fixed_share_ratio) => {
                 if self.is_empty() {
                     Err(error::Client::OnlyFixedShareRatio)?;
@@ -492,6 +650,20 @@ impl GroupRegistry {
             .collect()
     }
 
+    /// Public groups along with the ratio of hashrate they are currently allocated
+    fn get_groups_with_share_ratio(&self) -> Vec<(Arc<Group>, f64)> {
+        self.list
+            .iter()
+            .filter(|scheduler_group_handle| !scheduler_group_handle.is_private())
+            .map(|scheduler_group_handle| {
+                (
+                    scheduler_group_handle.group_handle.clone(),
+                    scheduler_group_handle.share_ratio,
+                )
+            })
+            .collect()
+    }
+
     pub fn get_group(&self, index: usize) -> Option<Arc<Group>> {
         self.list
             .get(index)
@@ -513,6 +685,140 @@ impl GroupRegistry {
         None
     }
 
+    /// Replace all public groups with groups given by `descriptors` while the order of groups
+    /// follows the descriptors. Groups are matched by their names and clients of matching groups
+    /// are kept even if the group descriptor has been changed. Clients of groups missing in
+    /// `descriptors` are removed. Private groups are kept untouched.
+    pub async fn reconcile_groups(
+        &mut self,
+        descriptors: Vec<GroupDescriptor>,
+        midstate_count: usize,
+    ) -> Result<Vec<Arc<Group>>, error::Client> {
+        // Check the group constraints before the registry is modified
+        if descriptors.is_empty() {
+            Err(error::Client::NoGroup)?;
+        }
+        let mut fixed_share_ratio_count = 0;
+        let mut total_fixed_share_ratio = 0.0;
+        let private_fixed_share_ratios = self
+            .list
+            .iter()
+            .filter(|scheduler_group_handle| scheduler_group_handle.is_private())
+            .filter_map(|scheduler_group_handle| {
+                scheduler_group_handle
+                    .group_handle
+                    .descriptor
+                    .get_fixed_share_ratio()
+            });
+        for (idx, fixed_share_ratio) in descriptors
+            .iter()
+            .map(|descriptor| descriptor.get_fixed_share_ratio())
+            .enumerate()
+            .filter_map(|(idx, ratio)| ratio.map(|ratio| (idx, ratio)))
+            .chain(private_fixed_share_ratios.map(|ratio| (usize::max_value(), ratio)))
+        {
+            if idx == 0 {
+                Err(error::Client::OnlyFixedShareRatio)?;
+            } else if total_fixed_share_ratio + fixed_share_ratio >= 1.0 {
+                Err(error::Client::FixedShareRatioOverflow)?;
+            }
+            fixed_share_ratio_count += 1;
+            total_fixed_share_ratio += fixed_share_ratio;
+        }
+
+        let mut prev_list = std::mem::replace(&mut self.list, vec![]);
+        let mut groups = Vec::with_capacity(descriptors.len());
+        for descriptor in descriptors {
+            let prev_idx = prev_list.iter().position(|scheduler_group_handle| {
+                !scheduler_group_handle.is_private()
+                    && scheduler_group_handle.group_handle.descriptor.name == descriptor.name
+            });
+            let scheduler_group_handle = match prev_idx.map(|idx| prev_list.remove(idx)) {
+                Some(scheduler_group_handle)
+                    if scheduler_group_handle.group_handle.descriptor == descriptor =>
+                {
+                    scheduler_group_handle
+                }
+                Some(scheduler_group_handle) => scheduler::GroupHandle::new(Arc::new(
+                    scheduler_group_handle
+                        .group_handle
+                        .take_over(descriptor)
+                        .await,
+                )),
+                None => scheduler::GroupHandle::new(Arc::new(Group::new(
+                    descriptor,
+                    self.event_monitor.publish(),
+                    midstate_count,
+                ))),
+            };
+            groups.push(scheduler_group_handle.group_handle.clone());
+            self.list.push(scheduler_group_handle);
+        }
+        for scheduler_group_handle in prev_list {
+            if scheduler_group_handle.is_private() {
+                self.list.push(scheduler_group_handle);
+            } else {
+                scheduler_group_handle.group_handle.clear().await;
+            }
+        }
+
+        self.fixed_share_ratio_count = fixed_share_ratio_count;
+        self.total_fixed_share_ratio = total_fixed_share_ratio;
+        self.recalculate_quotas(true);
+
+        Ok(groups)
+    }
+
+    pub fn set_share_accounting(&mut self, share_accounting: ShareAccounting) {
+        if self.share_accounting != share_accounting {
+            self.share_accounting = share_accounting;
+            // Previously accounted work is not comparable in the new mode
+            self.recalculate_quotas(true);
+        }
+    }
+
+    /// Activate and deactivate groups according to their schedule at given time in minutes since
+    /// midnight. The default group is activated when there is no other active group sharing the
+    /// ratio left by groups with fixed share ratio.
+    /// Returns true when any group has changed its state.
+    pub(crate) fn update_schedule(&mut self, minute_of_day: u16) -> bool {
+        // The desired state has to be resolved for all groups first to prevent the default group
+        // from being deactivated and activated again by the fallback
+        let mut scheduled: Vec<_> = self
+            .list
+            .iter()
+            .map(|scheduler_group_handle| {
+                scheduler_group_handle
+                    .group_handle
+                    .descriptor
+                    .is_scheduled_at(minute_of_day)
+            })
+            .collect();
+        // Groups with fixed share ratio are always active, the remaining ratio would be lost
+        // without any active quota or schedule group
+        let quota_group_scheduled =
+            self.list
+                .iter()
+                .zip(scheduled.iter())
+                .any(|(scheduler_group_handle, &scheduled)| {
+                    scheduled && !scheduler_group_handle.has_fixed_share_ratio()
+                });
+        if !quota_group_scheduled {
+            if let Some(scheduled) = scheduled.get_mut(GroupDescriptor::DEFAULT_INDEX) {
+                *scheduled = true;
+            }
+        }
+
+        let mut changed = false;
+        for (scheduler_group_handle, scheduled) in self.list.iter_mut().zip(scheduled) {
+            changed |= scheduler_group_handle.set_scheduled(scheduled);
+        }
+        if changed {
+            self.recalculate_quotas(true);
+        }
+        changed
+    }
+
     fn recalculate_quotas(&mut self, reset_generated_work: bool) {
         assert!(
             self.total_fixed_share_ratio < 1.0 && self.fixed_share_ratio_count < self.count(),
@@ -522,9 +828,26 @@ impl GroupRegistry {
         if self.is_empty() {
             return;
         }
+        // Only groups active according to their schedule share the remaining ratio
+        let total_quota: usize = self
+            .list
+            .iter()
+            .filter(|scheduler_group_handle| {
+                scheduler_group_handle.is_scheduled()
+                    && !scheduler_group_handle.has_fixed_share_ratio()
+            })
+            .map(|scheduler_group_handle| {
+                scheduler_group_handle
+                    .get_quota()
+                    .expect("BUG: missing group quota")
+            })
+            .sum();
         // Precalculate remaining share ratio normalized per 1 quota unit
-        let share_ratio_per_quota_unit =
-            (1.0 - self.total_fixed_share_ratio) / self.total_quota as f64;
+        let share_ratio_per_quota_unit = if total_quota > 0 {
+            (1.0 - self.total_fixed_share_ratio) / total_quota as f64
+        } else {
+            0.0
+        };
 
         // Update all groups with newly calculated share ratio.
         // Also reset generated work to prevent switching all future work to new group because
@@ -533,7 +856,9 @@ impl GroupRegistry {
             if reset_generated_work {
                 scheduler_group_handle.reset_generated_work();
             }
-            if !scheduler_group_handle.has_fixed_share_ratio() {
+            if !scheduler_group_handle.is_scheduled() {
+                scheduler_group_handle.share_ratio = 0.0;
+            } else if !scheduler_group_handle.has_fixed_share_ratio() {
                 scheduler_group_handle.share_ratio = share_ratio_per_quota_unit
                     * scheduler_group_handle
                         .get_quota()
@@ -548,15 +873,62 @@ pub struct Manager {
     group_registry: Arc<Mutex<GroupRegistry>>,
     event_monitor: event::Monitor,
     midstate_count: usize,
+    /// Backend nodes used for determining nominal hashrate announced to the remote servers
+    backend_registry: Weak<backend::Registry>,
+    /// Serializes configuration reloads (e.g. SIGHUP and API) so that groups and their clients
+    /// are never reconciled with two configurations at once
+    reload_lock: Arc<Mutex<()>>,
 }
 
 impl Manager {
-    pub fn new(midstate_count: usize) -> Self {
+    /// Interval in which progress of the graceful shutdown is checked
+    const SHUTDOWN_POLL_INTERVAL: time::Duration = time::Duration::from_millis(100);
+    /// Maximal time given to clients for closing their connections on shutdown
+    const SHUTDOWN_CLOSE_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+    pub fn new(midstate_count: usize, backend_registry: Weak<backend::Registry>) -> Self {
         let event_monitor = event::Monitor::new();
         Self {
             group_registry: Arc::new(Mutex::new(GroupRegistry::new(event_monitor.clone()))),
             event_monitor,
             midstate_count,
+            backend_registry,
+            reload_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Sum of nominal hashrates of all work solvers that are currently mining
+    pub async fn get_nominal_hashrate(&self) -> ii_bitcoin::HashesUnit {
+        let work_solvers = match self.backend_registry.upgrade() {
+            Some(backend_registry) => backend_registry.lock_work_solvers().await.clone(),
+            None => vec![],
+        };
+        let mut total_hashes = 0;
+        for work_solver in work_solvers {
+            if let Some(nominal_hashrate) = work_solver.get_nominal_hashrate().await {
+                total_hashes += nominal_hashrate.into_hashes().into_u128();
+            }
+        }
+        total_hashes.into()
+    }
+
+    /// Backend is expected to call this method whenever its nominal hashrate changes (e.g. when
+    /// a hash chain is started or stopped). All clients are then notified so that they can update
+    /// their channels on the remote servers. Clients of each group announce only the part of the
+    /// hashrate given by the share ratio of the group.
+    pub async fn notify_nominal_hashrate_changed(&self) {
+        let nominal_hashrate = self.get_nominal_hashrate().await.into_hashes().into_f64();
+        let groups = self
+            .group_registry
+            .lock()
+            .await
+            .get_groups_with_share_ratio();
+        for (group, share_ratio) in groups {
+            let group_hashrate =
+                ii_bitcoin::HashesUnit::Hashes((nominal_hashrate * share_ratio) as u128);
+            for client_handle in group.get_clients().await {
+                client_handle.update_nominal_hashrate(group_hashrate);
+            }
         }
     }
 
@@ -574,15 +946,8 @@ impl Manager {
                 let group = self.create_group(group_config.descriptor).await?;
                 if let Some(pool_configs) = group_config.pools {
                     for pool_config in pool_configs {
-                        let descriptor = ClientDescriptor::create(
-                            pool_config.url.as_str(),
-                            &ClientUserInfo::new(
-                                pool_config.user.as_str(),
-                                pool_config.password.as_deref(),
-                            ),
-                            pool_config.enabled.unwrap_or(default_pool_enabled),
-                        )
-                        .map_err(|e| e.to_string())?;
+                        let descriptor =
+                            Self::create_client_descriptor(&pool_config, default_pool_enabled)?;
                         let client_handle = Handle::new(descriptor, backend_info.cloned(), None);
                         group.push_client(client_handle).await;
                     }
@@ -592,11 +957,80 @@ impl Manager {
         Ok(())
     }
 
+    fn create_client_descriptor(
+        pool_config: &PoolConfig,
+        default_pool_enabled: bool,
+    ) -> error::Result<ClientDescriptor> {
+        let mut descriptor = ClientDescriptor::create(
+            pool_config.url.as_str(),
+            &ClientUserInfo::new(pool_config.user.as_str(), pool_config.password.as_deref()),
+            pool_config.enabled.unwrap_or(default_pool_enabled),
+        )
+        .map_err(|e| e.to_string())?;
+        descriptor.stale_policy = pool_config.stale_policy.unwrap_or_default();
+        descriptor.allow_cross_host_reconnect =
+            pool_config.allow_cross_host_reconnect.unwrap_or(false);
+        Ok(descriptor)
+    }
+
+    /// Apply new configuration of groups and pools to the running miner. Unlike `load_config`
+    /// the current groups and clients are diffed with the configuration and only changes are
+    /// applied so that unchanged clients keep their connections. Whole configuration is validated
+    /// before any change is made and share accounting is changed together with the groups.
+    pub async fn reload_config<T>(
+        &self,
+        group_configs: T,
+        share_accounting: ShareAccounting,
+        backend_info: Option<&hal::BackendInfo>,
+        default_pool_enabled: bool,
+    ) -> error::Result<()>
+    where
+        T: Into<Option<Vec<GroupConfig>>>,
+    {
+        let _reload_guard = self.reload_lock.lock().await;
+
+        let group_configs = group_configs.into().unwrap_or_default();
+        let mut group_descriptors = Vec::with_capacity(group_configs.len());
+        let mut client_descriptors = Vec::with_capacity(group_configs.len());
+        for group_config in group_configs {
+            let mut descriptors = vec![];
+            for pool_config in group_config.pools.iter().flatten() {
+                descriptors.push(Self::create_client_descriptor(
+                    pool_config,
+                    default_pool_enabled,
+                )?);
+            }
+            group_descriptors.push(group_config.descriptor);
+            client_descriptors.push(descriptors);
+        }
+
+        let groups = {
+            let mut group_registry = self.group_registry.lock().await;
+            let groups = group_registry
+                .reconcile_groups(group_descriptors, self.midstate_count)
+                .await?;
+            group_registry.set_share_accounting(share_accounting);
+            groups
+        };
+        for (group, descriptors) in groups.iter().zip(client_descriptors) {
+            group.reconcile_clients(descriptors, backend_info).await;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn subscribe_to_clients_status_changes(&self) -> event::Receiver {
         self.event_monitor.subscribe()
     }
 
+    /// Select work accounted to groups when balancing hashrate between them
+    pub async fn set_share_accounting(&self, share_accounting: ShareAccounting) {
+        self.group_registry
+            .lock()
+            .await
+            .set_share_accounting(share_accounting)
+    }
+
     #[inline]
     pub async fn create_group(
         &self,
@@ -630,4 +1064,323 @@ impl Manager {
     pub async fn get_groups(&self) -> Vec<Arc<Group>> {
         self.group_registry.lock().await.get_groups()
     }
+
+    async fn get_all_clients(&self) -> Vec<Arc<Handle>> {
+        let mut client_handles = vec![];
+        for group in self.get_groups().await {
+            client_handles.extend(group.get_clients().await);
+        }
+        client_handles
+    }
+
+    /// Gracefully shut down all clients after the backend has stopped solving work. Solutions
+    /// which have already been found are submitted and the clients wait at most `timeout` for
+    /// their acknowledgements. Then the clients are disabled which closes their connections and
+    /// solutions without known result are accounted as discarded. Final statistics of all
+    /// clients are reported at the end.
+    pub async fn shutdown(&self, timeout: time::Duration) {
+        let client_handles = self.get_all_clients().await;
+
+        let deadline = time::Instant::now() + timeout;
+        loop {
+            // Give the clients a chance to submit solutions which are still queued
+            delay_for(Self::SHUTDOWN_POLL_INTERVAL).await;
+            let mut pending_solutions = 0;
+            for client_handle in client_handles.iter() {
+                pending_solutions += client_handle.node.pending_solutions().await;
+            }
+            if pending_solutions == 0 {
+                break;
+            }
+            if time::Instant::now() >= deadline {
+                warn!(
+                    "Shutdown: {} solution(s) have not been acknowledged in time",
+                    pending_solutions
+                );
+                break;
+            }
+        }
+
+        for client_handle in client_handles.iter() {
+            let _ = client_handle.try_disable();
+        }
+        // Wait for the clients to close their connections and to account discarded solutions
+        let deadline = time::Instant::now() + Self::SHUTDOWN_CLOSE_TIMEOUT;
+        while client_handles
+            .iter()
+            .any(|client_handle| !client_handle.is_finished())
+            && time::Instant::now() < deadline
+        {
+            delay_for(Self::SHUTDOWN_POLL_INTERVAL).await;
+        }
+
+        for client_handle in client_handles.iter() {
+            let client_stats = client_handle.stats();
+            info!(
+                "Shutdown: client '{}' accepted={} rejected={} stale={} duplicate={} \
+                 discarded={}",
+                client_handle.node,
+                client_stats.accepted().take_snapshot().await.solutions,
+                client_stats.rejected().take_snapshot().await.solutions,
+                client_stats.stale().take_snapshot().await.solutions,
+                client_stats.duplicate().take_snapshot().await.solutions,
+                client_stats.discarded().take_snapshot().await.solutions,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use bosminer_config::GroupTimeWindow;
+
+    use ii_async_compat::tokio;
+
+    fn scheduled_group(name: &str, start: u16, end: u16) -> GroupDescriptor {
+        GroupDescriptor::new(
+            name.to_string(),
+            false,
+            LoadBalanceStrategy::Schedule(vec![
+                GroupTimeWindow::new(start, end).expect("cannot create time window")
+            ]),
+        )
+    }
+
+    fn share_ratios(group_registry: &GroupRegistry) -> Vec<f64> {
+        group_registry
+            .iter()
+            .map(|scheduler_group_handle| scheduler_group_handle.share_ratio)
+            .collect()
+    }
+
+    fn drain_descriptor(host: &str, password: Option<&str>) -> ClientDescriptor {
+        ClientDescriptor::create(
+            format!("drain://{}", host).as_str(),
+            &ClientUserInfo::new("user", password),
+            true,
+        )
+        .expect("cannot create client descriptor")
+    }
+
+    async fn client_hosts(group: &Group) -> Vec<String> {
+        let mut hosts = vec![];
+        for client_handle in group.get_clients().await {
+            hosts.push(client_handle.descriptor().await.host);
+        }
+        hosts
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_clients() {
+        let mut group_registry = GroupRegistry::new(event::Monitor::new());
+        let group = group_registry
+            .create_group(Default::default(), 1)
+            .expect("cannot create group");
+        for host in &["a", "b", "c"] {
+            group
+                .push_client(Handle::new(drain_descriptor(host, None), None, None))
+                .await;
+        }
+        let clients = group.get_clients().await;
+
+        group
+            .reconcile_clients(
+                vec![
+                    drain_descriptor("c", Some("secret")),
+                    drain_descriptor("d", None),
+                    drain_descriptor("a", None),
+                ],
+                None,
+            )
+            .await;
+        assert_eq!(client_hosts(&group).await, vec!["c", "d", "a"]);
+
+        let next_clients = group.get_clients().await;
+        // Pools with the same URL keep their clients
+        assert!(Arc::ptr_eq(&next_clients[0], &clients[2]));
+        assert!(Arc::ptr_eq(&next_clients[2], &clients[0]));
+        // Client at the same position with the same protocol is reconfigured to a new pool
+        assert!(Arc::ptr_eq(&next_clients[1], &clients[1]));
+        assert_eq!(
+            next_clients[0].descriptor().await.password,
+            Some("secret".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let client_manager = Manager::new(1, Weak::new());
+        let group = client_manager.create_or_get_default_group().await;
+        let client_handle = group
+            .push_client(Handle::new(drain_descriptor("a", None), None, None))
+            .await;
+        assert!(client_handle.is_enabled());
+
+        client_manager
+            .shutdown(time::Duration::from_millis(100))
+            .await;
+        // All clients are disabled to prevent the scheduler from starting them again
+        assert!(!client_handle.is_enabled());
+        assert!(client_handle.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_config() {
+        let client_manager = Manager::new(1, Weak::new());
+        // Configuration without any group is rejected and share accounting is left untouched
+        assert!(client_manager
+            .reload_config(
+                Vec::<GroupConfig>::new(),
+                ShareAccounting::AcceptedShares,
+                None,
+                true
+            )
+            .await
+            .is_err());
+        assert_eq!(
+            client_manager.group_registry.lock().await.share_accounting,
+            ShareAccounting::GeneratedWork
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_groups() {
+        let mut group_registry = GroupRegistry::new(event::Monitor::new());
+        let group_a = group_registry
+            .create_group(
+                GroupDescriptor::new("A".to_string(), false, LoadBalanceStrategy::Quota(1)),
+                1,
+            )
+            .expect("cannot create group");
+        let group_b = group_registry
+            .create_group(
+                GroupDescriptor::new("B".to_string(), false, LoadBalanceStrategy::Quota(1)),
+                1,
+            )
+            .expect("cannot create group");
+        group_b
+            .push_client(Handle::new(drain_descriptor("b", None), None, None))
+            .await;
+
+        // Only fixed share ratio groups are not allowed and the registry is left untouched
+        assert!(group_registry
+            .reconcile_groups(
+                vec![GroupDescriptor::new(
+                    "B".to_string(),
+                    false,
+                    LoadBalanceStrategy::FixedShareRatio(0.5),
+                )],
+                1,
+            )
+            .await
+            .is_err());
+        assert_eq!(group_registry.count(), 2);
+
+        let groups = group_registry
+            .reconcile_groups(
+                vec![
+                    GroupDescriptor::new("C".to_string(), false, LoadBalanceStrategy::Quota(1)),
+                    GroupDescriptor::new(
+                        "B".to_string(),
+                        false,
+                        LoadBalanceStrategy::FixedShareRatio(0.5),
+                    ),
+                ],
+                1,
+            )
+            .await
+            .expect("cannot reconcile groups");
+        assert_eq!(group_registry.count(), 2);
+        assert_eq!(groups[0].descriptor.name, "C");
+        assert!(groups.iter().all(|group| !Arc::ptr_eq(group, &group_a)));
+        // Group with changed descriptor takes over the clients
+        assert_eq!(groups[1].descriptor.get_fixed_share_ratio(), Some(0.5));
+        assert_eq!(client_hosts(&groups[1]).await, vec!["b"]);
+        assert!(group_b.is_empty().await);
+        assert_eq!(share_ratios(&group_registry), vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_time_window() {
+        use std::convert::TryFrom;
+
+        let window = GroupTimeWindow::try_from("22:00-06:00").expect("cannot parse time window");
+        assert_eq!(window, GroupTimeWindow::new(22 * 60, 6 * 60).unwrap());
+        assert!(window.contains(23 * 60) && !window.contains(12 * 60));
+        // Invalid configuration is reported as an error instead of panic
+        assert!(GroupTimeWindow::new(GroupTimeWindow::MINUTES_PER_DAY, 0).is_err());
+        assert!(GroupTimeWindow::try_from("24:00-06:00").is_err());
+        // Empty window would never match
+        assert!(GroupTimeWindow::new(8 * 60, 8 * 60).is_err());
+        assert!(GroupTimeWindow::try_from("08:00-08:00").is_err());
+    }
+
+    #[test]
+    fn test_group_schedule() {
+        let mut group_registry = GroupRegistry::new(event::Monitor::new());
+        // Off-peak group spans midnight
+        group_registry
+            .create_group(scheduled_group("Off-peak", 22 * 60, 6 * 60), 1)
+            .expect("cannot create group");
+        group_registry
+            .create_group(scheduled_group("Peak", 8 * 60, 20 * 60), 1)
+            .expect("cannot create group");
+
+        group_registry.update_schedule(23 * 60);
+        assert_eq!(share_ratios(&group_registry), vec![1.0, 0.0]);
+        group_registry.update_schedule(5 * 60 + 59);
+        assert_eq!(share_ratios(&group_registry), vec![1.0, 0.0]);
+        group_registry.update_schedule(12 * 60);
+        assert_eq!(share_ratios(&group_registry), vec![0.0, 1.0]);
+        // No window matches so the default group is used
+        assert!(group_registry.update_schedule(7 * 60));
+        assert_eq!(share_ratios(&group_registry), vec![1.0, 0.0]);
+        // The default group stays active without being toggled by the fallback
+        assert!(!group_registry.update_schedule(7 * 60));
+        assert_eq!(share_ratios(&group_registry), vec![1.0, 0.0]);
+
+        // Group without schedule is always active
+        group_registry
+            .create_group(
+                GroupDescriptor::new("Always".to_string(), false, LoadBalanceStrategy::Quota(1)),
+                1,
+            )
+            .expect("cannot create group");
+        group_registry.update_schedule(7 * 60);
+        assert_eq!(share_ratios(&group_registry), vec![0.0, 0.0, 1.0]);
+        group_registry.update_schedule(12 * 60);
+        assert_eq!(share_ratios(&group_registry), vec![0.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_group_schedule_fixed_share_ratio() {
+        let mut group_registry = GroupRegistry::new(event::Monitor::new());
+        group_registry
+            .create_group(scheduled_group("Off-peak", 22 * 60, 6 * 60), 1)
+            .expect("cannot create group");
+        group_registry
+            .create_group(scheduled_group("Peak", 8 * 60, 20 * 60), 1)
+            .expect("cannot create group");
+        group_registry
+            .create_group(
+                GroupDescriptor::new(
+                    "Fixed".to_string(),
+                    false,
+                    LoadBalanceStrategy::FixedShareRatio(0.25),
+                ),
+                1,
+            )
+            .expect("cannot create group");
+
+        group_registry.update_schedule(12 * 60);
+        assert_eq!(share_ratios(&group_registry), vec![0.0, 0.75, 0.25]);
+        // Always active fixed share ratio group must not prevent the fallback to the default
+        // group, otherwise it would get all the work
+        assert!(group_registry.update_schedule(7 * 60));
+        assert_eq!(share_ratios(&group_registry), vec![0.75, 0.0, 0.25]);
+        assert!(!group_registry.update_schedule(7 * 60));
+        assert_eq!(share_ratios(&group_registry), vec![0.75, 0.0, 0.25]);
+    }
 }
@@ -0,0 +1,430 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Append-only journal of shares submitted to remote servers. Each share is stored as one JSON
+//! line together with the server response so that there is evidence on the miner side when
+//! a pool disputes the payouts. The journal is rotated when it exceeds configured size:
+//! `<path>` is renamed to `<path>.1`, `<path>.1` to `<path>.2` etc. and the oldest file is removed.
+//! Clients only queue the records and the file is written by a separate task.
+
+use ii_logging::macros::*;
+
+use crate::work;
+
+use bosminer_config::ShareJournalConfig;
+
+use ii_async_compat::tokio;
+use tokio::sync::mpsc;
+use tokio::task;
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time;
+
+/// Default size of one journal file before it is rotated
+pub const DEFAULT_MAX_SIZE: u64 = 4 * 1024 * 1024;
+/// Default number of rotated files kept besides the current one
+pub const DEFAULT_MAX_FILES: usize = 4;
+/// Maximum number of records waiting for the writer task. Records are dropped when the writer
+/// cannot keep up so that clients are never blocked by the journal.
+const RECORD_QUEUE_SIZE: usize = 256;
+
+/// Journal installed for the whole process together with the queue of its writer task
+struct Installed {
+    journal: Arc<Journal>,
+    record_sender: mpsc::Sender<Record>,
+}
+
+/// Journal installed for the whole process (see `install`)
+static JOURNAL: OnceCell<Installed> = OnceCell::new();
+
+/// Response of the remote server to a submitted share
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareResult {
+    Accepted,
+    Rejected(String),
+}
+
+/// One journal entry describing submitted share
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Time of the server response in milliseconds since UNIX epoch
+    pub timestamp: u64,
+    /// Client which submitted the share
+    pub client: String,
+    pub job_id: u32,
+    pub nonce: u32,
+    pub version: u32,
+    pub ntime: u32,
+    /// Difficulty of the job target the share has been submitted for
+    pub difficulty: u64,
+    pub result: ShareResult,
+    /// Time between submitting the share and receiving the response in milliseconds
+    pub latency: u64,
+}
+
+impl Record {
+    pub fn new(
+        client: &dyn fmt::Display,
+        job_id: u32,
+        solution: &work::Solution,
+        submitted: time::Instant,
+        result: ShareResult,
+    ) -> Self {
+        let timestamp = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            timestamp: timestamp.as_millis() as u64,
+            client: client.to_string(),
+            job_id,
+            nonce: solution.nonce(),
+            version: solution.version(),
+            ntime: solution.time(),
+            difficulty: target_difficulty(&solution.job_target()),
+            result,
+            latency: submitted.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// Pool difficulty of the `target` computed in 256 bits because `Target::get_difficulty` truncates
+/// it to `usize` which has only 32 bits on the mining hardware
+fn target_difficulty(target: &ii_bitcoin::Target) -> u64 {
+    let target = target.into_inner();
+    if target.is_zero() {
+        return u64::max_value();
+    }
+    let difficulty = ii_bitcoin::Target::default().into_inner() / target;
+    if difficulty.bits() > 64 {
+        u64::max_value()
+    } else {
+        difficulty.low_u64()
+    }
+}
+
+/// Currently written journal file
+#[derive(Debug)]
+struct Writer {
+    file: fs::File,
+    size: u64,
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    writer: StdMutex<Writer>,
+}
+
+impl Journal {
+    /// Open existing journal at `path` or create a new one. Records are always appended.
+    pub fn open<P: AsRef<Path>>(path: P, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let writer = Self::open_writer(&path)?;
+
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            writer: StdMutex::new(writer),
+        })
+    }
+
+    pub fn from_config(config: &ShareJournalConfig) -> io::Result<Self> {
+        Self::open(
+            &config.path,
+            config.max_size.unwrap_or(DEFAULT_MAX_SIZE),
+            config.max_files.unwrap_or(DEFAULT_MAX_FILES),
+        )
+    }
+
+    fn open_writer(path: &Path) -> io::Result<Writer> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Writer { file, size })
+    }
+
+    /// Path of rotated journal file with index `idx` (index 0 is the current file)
+    fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+        if idx == 0 {
+            return path.to_path_buf();
+        }
+        let mut file_name = path.as_os_str().to_os_string();
+        file_name.push(format!(".{}", idx));
+        file_name.into()
+    }
+
+    fn rotate(&self, writer: &mut Writer) -> io::Result<()> {
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for idx in (0..self.max_files).rev() {
+                let from = Self::rotated_path(&self.path, idx);
+                if from.exists() {
+                    fs::rename(from, Self::rotated_path(&self.path, idx + 1))?;
+                }
+            }
+        }
+        *writer = Self::open_writer(&self.path)?;
+        Ok(())
+    }
+
+    pub fn append(&self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().expect("BUG: cannot lock journal writer");
+        if writer.size > 0 && writer.size + line.len() as u64 > self.max_size {
+            self.rotate(&mut writer)?;
+        }
+        writer.file.write_all(&line)?;
+        writer.file.flush()?;
+        writer.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Create reader of all records stored in this journal
+    pub fn reader(&self) -> io::Result<Reader> {
+        Reader::open(&self.path, self.max_files)
+    }
+}
+
+/// Reader of journal records in chronological order starting from the oldest rotated file
+pub struct Reader {
+    paths: VecDeque<PathBuf>,
+    lines: Option<io::Lines<io::BufReader<fs::File>>>,
+}
+
+impl Reader {
+    /// `max_files` - number of rotated files which should be searched besides the current one
+    pub fn open<P: AsRef<Path>>(path: P, max_files: usize) -> io::Result<Self> {
+        let path = path.as_ref();
+        let paths = (0..=max_files)
+            .rev()
+            .map(|idx| Journal::rotated_path(path, idx))
+            .filter(|path| path.exists())
+            .collect();
+
+        Ok(Self { paths, lines: None })
+    }
+}
+
+impl Iterator for Reader {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(lines) = self.lines.as_mut() {
+                match lines.next() {
+                    Some(Ok(line)) if line.trim().is_empty() => continue,
+                    Some(Ok(line)) => {
+                        return Some(serde_json::from_str(&line).map_err(|e| e.into()))
+                    }
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => self.lines = None,
+                }
+            }
+            let path = self.paths.pop_front()?;
+            match fs::File::open(path) {
+                Ok(file) => self.lines = Some(io::BufReader::new(file).lines()),
+                // The file may have been rotated in the meantime
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Task appending queued records to the journal. The file is written on the blocking thread pool
+/// to not block the executor.
+async fn run_writer(journal: Arc<Journal>, mut record_receiver: mpsc::Receiver<Record>) {
+    while let Some(record) = record_receiver.recv().await {
+        let journal = journal.clone();
+        match task::spawn_blocking(move || journal.append(&record)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Share journal: cannot append record: {}", e),
+            Err(e) => error!("Share journal: writer failed: {}", e),
+        }
+    }
+}
+
+/// Install journal used by all clients for recording submitted shares and start its writer task
+pub fn install(journal: Journal) -> Result<(), Journal> {
+    let journal = Arc::new(journal);
+    let (record_sender, record_receiver) = mpsc::channel(RECORD_QUEUE_SIZE);
+    if let Err(installed) = JOURNAL.set(Installed {
+        journal: journal.clone(),
+        record_sender,
+    }) {
+        drop(installed);
+        return Err(Arc::try_unwrap(journal).expect("BUG: journal is still shared"));
+    }
+    tokio::spawn(run_writer(journal, record_receiver));
+    Ok(())
+}
+
+/// Return journal installed for this process
+pub fn get() -> Option<&'static Journal> {
+    JOURNAL.get().map(|installed| installed.journal.as_ref())
+}
+
+/// Queue record for the writer task of the installed journal (if any)
+pub fn record(record: Record) {
+    if let Some(installed) = JOURNAL.get() {
+        if let Err(e) = installed.record_sender.clone().try_send(record) {
+            warn!("Share journal: record dropped: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils;
+
+    fn test_record(nonce: u32, result: ShareResult) -> Record {
+        Record {
+            timestamp: 1_580_000_000_000,
+            client: "stratum2+tcp://localhost@user".to_string(),
+            job_id: 1,
+            nonce,
+            version: 0x2000_0000,
+            ntime: 0x5e2c_b2a8,
+            difficulty: 8192,
+            result,
+            latency: 35,
+        }
+    }
+
+    #[test]
+    fn test_journal_append_and_read() {
+        let dir = test_utils::TempDir::new("journal");
+        let path = dir.join("shares.log");
+        let journal = Journal::open(&path, DEFAULT_MAX_SIZE, DEFAULT_MAX_FILES).unwrap();
+        let records = vec![
+            test_record(1, ShareResult::Accepted),
+            test_record(2, ShareResult::Rejected("stale-share".to_string())),
+        ];
+        for record in records.iter() {
+            journal.append(record).unwrap();
+        }
+        drop(journal);
+
+        // Reopened journal has to append records after the existing ones
+        let journal = Journal::open(&path, DEFAULT_MAX_SIZE, DEFAULT_MAX_FILES).unwrap();
+        journal.append(&records[0]).unwrap();
+
+        let read_records: Vec<_> = journal.reader().unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(read_records.len(), 3);
+        assert_eq!(read_records[..2], records[..]);
+        assert_eq!(read_records[2], records[0]);
+    }
+
+    #[tokio::test]
+    async fn test_journal_record() {
+        let dir = test_utils::TempDir::new("journal-record");
+        let path = dir.join("shares.log");
+        let journal = Journal::open(&path, DEFAULT_MAX_SIZE, DEFAULT_MAX_FILES).unwrap();
+        install(journal).expect("BUG: share journal already installed");
+
+        // Other tests running in this process may record shares to the installed journal too
+        let records: Vec<_> = (1..=2)
+            .map(|nonce| Record {
+                client: "journal-record-test".to_string(),
+                ..test_record(nonce, ShareResult::Accepted)
+            })
+            .collect();
+        for record in records.iter().cloned() {
+            super::record(record);
+        }
+        // Records are written asynchronously by the writer task
+        for _ in 0..100 {
+            let read_records: Vec<_> = get()
+                .unwrap()
+                .reader()
+                .unwrap()
+                .map(|r| r.unwrap())
+                .filter(|r| r.client == records[0].client)
+                .collect();
+            if read_records.len() == records.len() {
+                assert_eq!(read_records, records);
+                return;
+            }
+            tokio::time::delay_for(time::Duration::from_millis(10)).await;
+        }
+        panic!("records have not been written to the journal");
+    }
+
+    #[test]
+    fn test_target_difficulty() {
+        for &difficulty in &[1, 64, 8192, 65536] {
+            let target = ii_bitcoin::Target::from_pool_difficulty(difficulty);
+            assert_eq!(target_difficulty(&target), difficulty as u64);
+        }
+        // Difficulty 2^40 doesn't fit into 32-bit `usize`
+        let target = ii_bitcoin::Target::from_hex(
+            "000000000000000000ffff000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        assert_eq!(target_difficulty(&target), 1 << 40);
+    }
+
+    #[test]
+    fn test_journal_rotation() {
+        let dir = test_utils::TempDir::new("journal-rotation");
+        let path = dir.join("shares.log");
+        let record_size = serde_json::to_vec(&test_record(0, ShareResult::Accepted))
+            .unwrap()
+            .len() as u64
+            + 1;
+        // Every file has room for 2 records and only 2 rotated files are kept
+        let journal = Journal::open(&path, 2 * record_size, 2).unwrap();
+        for nonce in 0..10 {
+            journal
+                .append(&test_record(nonce, ShareResult::Accepted))
+                .unwrap();
+        }
+
+        assert!(Journal::rotated_path(&path, 2).exists());
+        assert!(!Journal::rotated_path(&path, 3).exists());
+        let nonces: Vec<_> = journal
+            .reader()
+            .unwrap()
+            .map(|r| r.unwrap().nonce)
+            .collect();
+        assert_eq!(nonces, vec![4, 5, 6, 7, 8, 9]);
+    }
+}
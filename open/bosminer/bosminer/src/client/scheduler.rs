@@ -21,16 +21,115 @@
 // contact us at opensource@braiins.com.
 
 use crate::client;
+use crate::job;
 use crate::sync::event;
 use crate::work;
 
-use futures::channel::mpsc;
+use bosminer_config::{GroupFailover, ShareAccounting};
+use ii_logging::macros::*;
+
+use chrono::{Local, Timelike};
 use futures::lock::{Mutex, MutexGuard};
 use ii_async_compat::{futures, FutureExt};
 
 use std::sync::Arc;
 use std::time;
 
+/// Health of a client observed by the scheduler and used for selecting the client which the group
+/// mines on
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct ClientHealth {
+    enabled: bool,
+    /// Time since the client has been running without any interruption
+    running_since: Option<time::Instant>,
+    /// Time since the client is not running or since it has been selected for mining
+    down_since: Option<time::Instant>,
+}
+
+impl ClientHealth {
+    #[inline]
+    fn is_running(&self) -> bool {
+        self.enabled && self.running_since.is_some()
+    }
+
+    /// Client is considered as stable when it has been running for at least the `delay`
+    fn is_stable(&self, now: time::Instant, delay: time::Duration) -> bool {
+        self.enabled
+            && self
+                .running_since
+                .map_or(false, |since| now.saturating_duration_since(since) >= delay)
+    }
+
+    fn is_down_for(&self, now: time::Instant, timeout: time::Duration) -> bool {
+        self.down_since.map_or(false, |since| {
+            now.saturating_duration_since(since) >= timeout
+        })
+    }
+}
+
+/// Select index of a client which the group should mine on. Clients are ordered by priority and
+/// the scheduler:
+/// - fails back to a client with higher priority once it has been running for `failback_delay`
+/// - holds the current client when it is down for less than `switch_timeout`
+/// - otherwise switches to the stable or at least running client with the highest priority
+/// - and finally tries the next enabled client when there isn't any running one
+fn select_client(
+    health: &[ClientHealth],
+    current: Option<usize>,
+    now: time::Instant,
+    failover: &GroupFailover,
+) -> Option<usize> {
+    if health.is_empty() {
+        return None;
+    }
+    let failback_delay = failover.failback_delay();
+    let current = current.filter(|idx| *idx < health.len());
+
+    if let Some(idx) = current {
+        let current_health = &health[idx];
+        if current_health.is_running() {
+            return health[..idx]
+                .iter()
+                .position(|health| health.is_stable(now, failback_delay))
+                .or(Some(idx));
+        }
+        if current_health.enabled && !current_health.is_down_for(now, failover.switch_timeout()) {
+            // Give the current client chance to recover before switching to a backup
+            return Some(idx);
+        }
+    }
+
+    let start = current.map_or(0, |idx| idx + 1);
+    health
+        .iter()
+        .position(|health| health.is_stable(now, failback_delay))
+        .or_else(|| health.iter().position(ClientHealth::is_running))
+        .or_else(|| {
+            (0..health.len())
+                .map(|i| (start + i) % health.len())
+                .find(|idx| health[*idx].enabled)
+        })
+}
+
+/// Absolute difference between the configured `share_ratio` of a group and its share ratio after
+/// the next interval provided that `work_delta` will be accounted to it. When nothing has been
+/// accounted yet, the group is expected to get all work so the group with the highest share ratio
+/// is preferred.
+fn next_share_ratio_error(
+    share_ratio: f64,
+    group_work: u64,
+    total_work: u64,
+    work_delta: u64,
+) -> f64 {
+    let next_total_work = total_work + work_delta;
+    let next_group_share_ratio = if next_total_work == 0 {
+        1.0
+    } else {
+        (group_work + work_delta) as f64 / next_total_work as f64
+    };
+    (share_ratio - next_group_share_ratio).abs()
+}
+
 /// This struct cannot be shared and it is possible to use mutable references. However, the
 /// client handle is shared object with interior mutability scheduler::ClientHandle. It solves
 /// many synchronization problems.
@@ -38,13 +137,26 @@ use std::time;
 pub struct ClientHandle {
     pub client_handle: Arc<client::Handle>,
     last_generated_work: u64,
+    last_accepted_shares: u64,
+    health: ClientHealth,
+    last_start_attempt: Option<time::Instant>,
+    /// Time since the client has not been needed by the group
+    idle_since: Option<time::Instant>,
 }
 
 impl ClientHandle {
-    pub fn new(client_handle: Arc<client::Handle>) -> Self {
+    /// Time for which an unused client is kept connected before it is stopped
+    const STOP_DELAY: time::Duration = time::Duration::from_secs(60);
+
+    pub async fn new(client_handle: Arc<client::Handle>) -> Self {
         Self {
             last_generated_work: Self::get_generated_work(&client_handle),
+            // Shares accepted before the client has been added to the group are not accounted
+            last_accepted_shares: Self::get_accepted_shares(&client_handle).await,
             client_handle,
+            health: Default::default(),
+            last_start_attempt: None,
+            idle_since: None,
         }
     }
 
@@ -53,25 +165,60 @@ impl ClientHandle {
         self.client_handle.is_running()
     }
 
-    #[inline]
-    fn try_start(&self) -> Result<(), ()> {
-        if self.client_handle.is_enabled() {
-            self.client_handle.start();
-            Ok(())
+    fn update_health(&mut self, now: time::Instant) {
+        self.health.enabled = self.client_handle.is_enabled();
+        if self.is_running() {
+            self.health.running_since.get_or_insert(now);
+            self.health.down_since = None;
         } else {
-            Err(())
+            self.health.running_since = None;
+            self.health.down_since.get_or_insert(now);
         }
     }
 
+    /// Restart measuring of switch timeout when the client is selected for mining
     #[inline]
-    fn try_delayed_stop(&self) -> Result<(), ()> {
-        // TODO: Implement delay before actual stopping
-        if self.client_handle.is_enabled() {
+    fn reset_down_since(&mut self, now: time::Instant) {
+        if self.health.down_since.is_some() {
+            self.health.down_since = Some(now);
+        }
+    }
+
+    /// Start the client when it is not running. Repeated attempts are throttled by the
+    /// `health_check_interval` to avoid flooding unavailable pools.
+    fn try_start(
+        &mut self,
+        now: time::Instant,
+        health_check_interval: time::Duration,
+    ) -> Result<(), ()> {
+        if !self.client_handle.is_enabled() {
+            return Err(());
+        }
+        self.idle_since = None;
+        if self.is_running() {
+            return Ok(());
+        }
+        let throttled = self.last_start_attempt.map_or(false, |last| {
+            now.saturating_duration_since(last) < health_check_interval
+        });
+        if !throttled {
+            self.last_start_attempt = Some(now);
+            self.client_handle.start();
+        }
+        Ok(())
+    }
+
+    /// Stop the client when it has not been needed by the group for `STOP_DELAY`. The delay
+    /// prevents reconnecting to pools when the scheduler switches clients back and forth.
+    fn try_delayed_stop(&mut self, now: time::Instant) -> Result<(), ()> {
+        if !self.client_handle.is_enabled() {
+            return Err(());
+        }
+        let idle_since = *self.idle_since.get_or_insert(now);
+        if now.saturating_duration_since(idle_since) >= Self::STOP_DELAY {
             self.client_handle.stop();
-            Ok(())
-        } else {
-            Err(())
         }
+        Ok(())
     }
 
     fn get_generated_work(client_handle: &Arc<client::Handle>) -> u64 {
@@ -93,6 +240,31 @@ impl ClientHandle {
         self.last_generated_work = next_generated_work;
         delta
     }
+
+    async fn get_accepted_shares(client_handle: &Arc<client::Handle>) -> u64 {
+        client_handle
+            .node
+            .client_stats()
+            .accepted()
+            .take_snapshot()
+            .await
+            .shares
+            .value()
+    }
+
+    /// Difficulty of shares accepted by remote server since the last call. Rejected and stale
+    /// shares are not accounted at all.
+    pub async fn get_delta_and_update_accepted_shares(&mut self) -> u64 {
+        let next_accepted_shares = Self::get_accepted_shares(&self.client_handle).await;
+        assert!(
+            next_accepted_shares >= self.last_accepted_shares,
+            "accepted shares must be monotonic"
+        );
+
+        let delta = next_accepted_shares - self.last_accepted_shares;
+        self.last_accepted_shares = next_accepted_shares;
+        delta
+    }
 }
 
 impl PartialEq for ClientHandle {
@@ -105,8 +277,15 @@ impl PartialEq for ClientHandle {
 #[derive(Debug, Clone)]
 pub struct GroupHandle {
     pub group_handle: Arc<client::Group>,
+    /// Client selected by failover which may be temporarily down
+    selected_client: Option<Arc<client::Handle>>,
+    /// Selected client which is running and can provide work
     active_client: Option<Arc<client::Handle>>,
     generated_work: u64,
+    /// Difficulty of all shares accepted by remote servers
+    accepted_shares: u64,
+    /// Group is active according to its schedule
+    scheduled: bool,
     /// Current ratio of hashrate that this group has been allocated to. This number
     /// changes based on newly added/removed groups.
     pub share_ratio: f64,
@@ -115,8 +294,11 @@ pub struct GroupHandle {
 impl GroupHandle {
     pub fn new(group_handle: Arc<client::Group>) -> Self {
         Self {
+            selected_client: None,
             active_client: None,
             generated_work: 0,
+            accepted_shares: 0,
+            scheduled: true,
             share_ratio: group_handle
                 .descriptor
                 .get_fixed_share_ratio()
@@ -143,33 +325,114 @@ impl GroupHandle {
         self.group_handle.descriptor.get_quota()
     }
 
+    #[inline]
+    pub fn is_scheduled(&self) -> bool {
+        self.scheduled
+    }
+
+    /// Returns true when the state has been changed
+    pub fn set_scheduled(&mut self, scheduled: bool) -> bool {
+        if self.scheduled != scheduled {
+            info!(
+                "Client group '{}' has been {} by schedule",
+                self.group_handle.descriptor.name,
+                if scheduled {
+                    "activated"
+                } else {
+                    "deactivated"
+                }
+            );
+            self.scheduled = scheduled;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Amount of work accounted to the group in given accounting mode
+    #[inline]
+    fn accounted_work(&self, share_accounting: ShareAccounting) -> u64 {
+        match share_accounting {
+            ShareAccounting::GeneratedWork => self.generated_work,
+            ShareAccounting::AcceptedShares => self.accepted_shares,
+        }
+    }
+
     async fn update_status(&mut self) {
+        let now = time::Instant::now();
+        let failover = self.group_handle.descriptor.failover();
         let mut scheduler_client_handles = self.group_handle.scheduler_client_handles.lock().await;
         let mut generated_work_delta = 0;
+        let mut accepted_shares_delta = 0;
 
-        self.active_client = None;
+        let mut health = Vec::with_capacity(scheduler_client_handles.len());
         for scheduler_client_handle in scheduler_client_handles.iter_mut() {
             generated_work_delta += scheduler_client_handle.get_delta_and_update_generated_work();
-            match self.active_client {
-                None => {
-                    if scheduler_client_handle.is_running() {
-                        self.active_client = Some(scheduler_client_handle.client_handle.clone());
-                    } else {
-                        let _ = scheduler_client_handle.try_start();
-                    }
-                }
-                Some(_) => {
-                    let _ = scheduler_client_handle.try_delayed_stop();
+            accepted_shares_delta += scheduler_client_handle
+                .get_delta_and_update_accepted_shares()
+                .await;
+            scheduler_client_handle.update_health(now);
+            health.push(scheduler_client_handle.health);
+        }
+        self.generated_work += generated_work_delta;
+        self.accepted_shares += accepted_shares_delta;
+
+        if !self.scheduled {
+            // Group is inactive and none of its clients is needed
+            for scheduler_client_handle in scheduler_client_handles.iter_mut() {
+                let _ = scheduler_client_handle.try_delayed_stop(now);
+            }
+            self.selected_client = None;
+            self.active_client = None;
+            return;
+        }
+
+        let current_idx = self.selected_client.as_ref().and_then(|selected_client| {
+            scheduler_client_handles
+                .iter()
+                .position(|handle| &handle.client_handle == selected_client)
+        });
+        let selected_idx = select_client(&health, current_idx, now, &failover);
+        if selected_idx != current_idx {
+            if let Some(idx) = selected_idx {
+                info!(
+                    "Client group '{}': switching to client with priority {}",
+                    self.group_handle.descriptor.name, idx
+                );
+                scheduler_client_handles[idx].reset_down_since(now);
+            }
+        }
+
+        // Clients with higher priority are started to check their health for fail-back and
+        // clients with lower priority are kept connected only as hot standby or when the selected
+        // client is down
+        let selected_running = selected_idx.map_or(false, |idx| health[idx].is_running());
+        for (idx, scheduler_client_handle) in scheduler_client_handles.iter_mut().enumerate() {
+            let needed = match selected_idx {
+                Some(selected_idx) => {
+                    idx <= selected_idx || failover.hot_standby() || !selected_running
                 }
+                None => true,
+            };
+            if needed {
+                let _ = scheduler_client_handle.try_start(now, failover.health_check_interval());
+            } else {
+                let _ = scheduler_client_handle.try_delayed_stop(now);
             }
         }
 
-        self.generated_work += generated_work_delta;
+        self.selected_client =
+            selected_idx.map(|idx| scheduler_client_handles[idx].client_handle.clone());
+        self.active_client = selected_idx
+            .filter(|_| selected_running)
+            .map(|idx| scheduler_client_handles[idx].client_handle.clone());
     }
 
+    /// Reset all work accounted to the group in any accounting mode
     #[inline]
     pub fn reset_generated_work(&mut self) {
         self.generated_work = 0;
+        self.accepted_shares = 0;
     }
 }
 
@@ -220,6 +483,12 @@ impl PartialEq<Arc<client::Handle>> for ActiveClient {
     }
 }
 
+/// Current local time in minutes since midnight used for evaluation of group schedules
+fn local_minute_of_day() -> u16 {
+    let now = Local::now();
+    (now.hour() * 60 + now.minute()) as u16
+}
+
 /// Responsible for selecting and switching jobs
 struct JobDispatcher {
     active_client: ActiveClient,
@@ -265,18 +534,30 @@ impl JobDispatcher {
             return None;
         }
 
-        let mut total_generated_work = 0;
+        group_registry.update_schedule(local_minute_of_day());
+        let share_accounting = group_registry.share_accounting;
+        let mut total_accounted_work = 0;
         for scheduler_group_handle in group_registry.iter_mut() {
             scheduler_group_handle.update_status().await;
-            total_generated_work += scheduler_group_handle.generated_work;
+            total_accounted_work += scheduler_group_handle.accounted_work(share_accounting);
         }
+        // Estimate how much work will be accounted to the selected group in the next interval.
+        // One unit of generated work corresponds to one share of difficulty 1 on average so it is
+        // used as an estimate of accepted shares too. Shares are accepted with a delay and their
+        // count in the last interval is mostly zero which would break the balancing.
+        let accounted_work_delta = generated_work_delta;
 
         let mut next_client = None;
-        for scheduler_group_handle in group_registry.iter() {
-            let group_generated_work = scheduler_group_handle.generated_work;
-            let next_group_share_ratio = (group_generated_work + generated_work_delta) as f64
-                / (total_generated_work + generated_work_delta) as f64;
-            let next_error = (scheduler_group_handle.share_ratio - next_group_share_ratio).abs();
+        for scheduler_group_handle in group_registry
+            .iter()
+            .filter(|scheduler_group_handle| scheduler_group_handle.is_scheduled())
+        {
+            let next_error = next_share_ratio_error(
+                scheduler_group_handle.share_ratio,
+                scheduler_group_handle.accounted_work(share_accounting),
+                total_accounted_work,
+                accounted_work_delta,
+            );
             if let Some(active_client) = scheduler_group_handle.active_client.as_ref().cloned() {
                 match next_client {
                     None => next_client = Some((active_client, next_error)),
@@ -350,7 +631,7 @@ impl JobExecutor {
     pub async fn get_solution_sender(
         &self,
         solution: &work::Solution,
-    ) -> Option<mpsc::UnboundedSender<work::Solution>> {
+    ) -> Option<job::SolutionSender> {
         let active_client = self.active_client().await;
 
         // solution receiver is probably active client which is work generated from
@@ -396,3 +677,126 @@ impl JobExecutor {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Longest period in seconds used for client health in tests
+    const MAX_HEALTH_PERIOD: u64 = 1000;
+
+    /// Return base instant which is far enough in the future so that subtracting any health period
+    /// cannot underflow on hosts with short uptime
+    fn test_now() -> time::Instant {
+        time::Instant::now() + time::Duration::from_secs(MAX_HEALTH_PERIOD)
+    }
+
+    fn down(enabled: bool, now: time::Instant, secs: u64) -> ClientHealth {
+        ClientHealth {
+            enabled,
+            running_since: None,
+            down_since: Some(now - time::Duration::from_secs(secs)),
+        }
+    }
+
+    fn running(now: time::Instant, secs: u64) -> ClientHealth {
+        ClientHealth {
+            enabled: true,
+            running_since: Some(now - time::Duration::from_secs(secs)),
+            down_since: None,
+        }
+    }
+
+    fn failover() -> GroupFailover {
+        GroupFailover {
+            hot_standby: None,
+            switch_timeout: Some(10),
+            failback_delay: Some(60),
+            health_check_interval: None,
+        }
+    }
+
+    /// Return index of the group with the lowest share ratio error after the next interval
+    fn next_group(groups: &[(f64, u64)], work_delta: u64) -> usize {
+        let total_work = groups.iter().map(|(_, work)| work).sum();
+        let errors: Vec<_> = groups
+            .iter()
+            .map(|&(share_ratio, work)| {
+                next_share_ratio_error(share_ratio, work, total_work, work_delta)
+            })
+            .collect();
+        assert!(errors.iter().all(|error| error.is_finite()));
+        (0..errors.len())
+            .min_by(|&a, &b| errors[a].partial_cmp(&errors[b]).expect("BUG: NaN error"))
+            .expect("BUG: no group")
+    }
+
+    #[test]
+    fn test_accepted_shares_balancing() {
+        // Nothing has been accepted yet and the group with the highest share ratio is preferred
+        assert_eq!(next_group(&[(0.25, 0), (0.75, 0)], 0), 1);
+        assert_eq!(next_group(&[(0.25, 0), (0.75, 0)], 10), 1);
+        // The group behind its share ratio gets the work estimated from generated work
+        assert_eq!(next_group(&[(0.5, 100), (0.5, 0)], 10), 1);
+        assert_eq!(next_group(&[(0.5, 0), (0.5, 100)], 10), 0);
+        assert_eq!(next_group(&[(0.25, 20), (0.75, 80)], 10), 0);
+        // The group above its share ratio is not selected
+        assert_eq!(next_group(&[(0.25, 40), (0.75, 60)], 10), 1);
+    }
+
+    #[test]
+    fn test_select_initial_client() {
+        let now = test_now();
+        let failover = failover();
+
+        assert_eq!(select_client(&[], None, now, &failover), None);
+        // Nothing is running yet so the first enabled client is selected
+        let health = [down(false, now, 0), down(true, now, 0), down(true, now, 0)];
+        assert_eq!(select_client(&health, None, now, &failover), Some(1));
+        // Running client with the highest priority is preferred
+        let health = [down(true, now, 0), running(now, 0), running(now, 0)];
+        assert_eq!(select_client(&health, None, now, &failover), Some(1));
+        // Stable client is preferred over client which has just been connected
+        let health = [down(true, now, 0), running(now, 0), running(now, 100)];
+        assert_eq!(select_client(&health, None, now, &failover), Some(2));
+    }
+
+    #[test]
+    fn test_switch_after_timeout() {
+        let now = test_now();
+        let failover = failover();
+
+        // Primary client is held until the switch timeout elapses
+        let health = [down(true, now, 5), running(now, 100)];
+        assert_eq!(select_client(&health, Some(0), now, &failover), Some(0));
+        let health = [down(true, now, 10), running(now, 100)];
+        assert_eq!(select_client(&health, Some(0), now, &failover), Some(1));
+        // Disabled client is switched immediately
+        let health = [down(false, now, 0), running(now, 0)];
+        assert_eq!(select_client(&health, Some(0), now, &failover), Some(1));
+        // The next enabled client is tried when no one is running
+        let health = [
+            down(true, now, 10),
+            down(false, now, 10),
+            down(true, now, 10),
+        ];
+        assert_eq!(select_client(&health, Some(0), now, &failover), Some(2));
+        assert_eq!(select_client(&health, Some(2), now, &failover), Some(0));
+    }
+
+    #[test]
+    fn test_failback_to_stable_client() {
+        let now = test_now();
+        let failover = failover();
+
+        // Primary client has recovered but it is not stable yet
+        let health = [running(now, 30), running(now, 1000)];
+        assert_eq!(select_client(&health, Some(1), now, &failover), Some(1));
+        // Primary client is stable again
+        let health = [running(now, 60), running(now, 1000)];
+        assert_eq!(select_client(&health, Some(1), now, &failover), Some(0));
+        // Running client with lower priority never replaces the current one
+        let health = [running(now, 60), running(now, 1000)];
+        assert_eq!(select_client(&health, Some(0), now, &failover), Some(0));
+    }
+}
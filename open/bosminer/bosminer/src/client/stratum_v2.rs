@@ -25,10 +25,12 @@ pub mod telemetry;
 
 use ii_logging::macros::*;
 
+use crate::client::journal;
 use crate::error;
 use crate::hal;
 use crate::job;
 use crate::node;
+use crate::recorder;
 use crate::stats;
 use crate::sync;
 use crate::work;
@@ -37,7 +39,7 @@ use failure::ResultExt;
 
 use ii_bitcoin::HashTrait;
 
-use bosminer_config::{ClientDescriptor, ClientProtocol};
+use bosminer_config::{ClientDescriptor, ClientProtocol, StalePolicy};
 use bosminer_macros::ClientNode;
 
 use async_trait::async_trait;
@@ -49,15 +51,18 @@ use ii_async_compat::select;
 use std::collections::VecDeque;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Mutex as StdMutex;
 use std::sync::{Arc, Weak};
 use std::time;
 
+use ii_stratum::v2::channel_group::ChannelGroups;
 use ii_stratum::v2::messages::{
-    NewMiningJob, OpenStandardMiningChannel, OpenStandardMiningChannelError,
-    OpenStandardMiningChannelSuccess, SetNewPrevHash, SetTarget, SetupConnection,
-    SetupConnectionError, SetupConnectionSuccess, SubmitSharesError, SubmitSharesStandard,
-    SubmitSharesSuccess,
+    ChannelEndpointChanged, CloseChannel, NewMiningJob, OpenStandardMiningChannel,
+    OpenStandardMiningChannelError, OpenStandardMiningChannelSuccess, Reconnect, SetGroupChannel,
+    SetNewPrevHash, SetTarget, SetupConnection, SetupConnectionError, SetupConnectionSuccess,
+    SubmitSharesError, SubmitSharesStandard, SubmitSharesSuccess, UpdateChannel,
+    UpdateChannelError,
 };
 use ii_stratum::v2::types::*;
 use ii_stratum::v2::{
@@ -79,6 +84,7 @@ pub struct ConnectionDetails {
     pub user: String,
     pub host: String,
     pub port: u16,
+    pub stale_policy: StalePolicy,
 }
 
 impl ConnectionDetails {
@@ -88,6 +94,7 @@ impl ConnectionDetails {
             user: descriptor.user.clone(),
             host: descriptor.host.clone(),
             port: descriptor.port(),
+            stale_policy: descriptor.stale_policy,
         }
     }
 
@@ -107,11 +114,16 @@ pub struct StratumJob {
     time: u32,
     bits: u32,
     target: ii_bitcoin::Target,
+    /// Job generation of the client at the time the job has been received
+    generation: usize,
 }
 
 impl StratumJob {
+    /// `channel_id` - the job may have been broadcast to a group channel, therefore the actual
+    /// channel used for submitting solutions has to be specified explicitly
     pub fn new(
         client: Arc<StratumClient>,
+        channel_id: u32,
         job_msg: &NewMiningJob,
         prevhash_msg: &SetNewPrevHash,
         target: ii_bitcoin::Target,
@@ -119,7 +131,7 @@ impl StratumJob {
         Self {
             client: Arc::downgrade(&client),
             id: job_msg.job_id,
-            channel_id: job_msg.channel_id,
+            channel_id,
             version: job_msg.version,
             prev_hash: ii_bitcoin::DHash::from_slice(prevhash_msg.prev_hash.as_ref())
                 .expect("BUG: Stratum: incorrect size of prev hash"),
@@ -128,6 +140,7 @@ impl StratumJob {
             time: prevhash_msg.min_ntime,
             bits: prevhash_msg.nbits,
             target,
+            generation: client.job_generation.load(Ordering::Relaxed),
         }
     }
 }
@@ -166,69 +179,190 @@ impl job::Bitcoin for StratumJob {
     }
 
     fn is_valid(&self) -> bool {
-        // TODO: currently there is no easy way to detect the job is valid -> we have to check
-        //  its presence in the registry. The inequality below was possible in the previous
-        //  iteration of the protocol
-        // self.block_height >= self.current_block_height.load(Ordering::Relaxed)
-        true
+        self.client
+            .upgrade()
+            .map(|client| job::is_generation_current(&client.job_generation, self.generation))
+            .unwrap_or(false)
     }
 }
 
 /// Queue that contains pairs of solution and its assigned sequence number. It is our responsibility
 /// to keep the sequence number monotonic so that we as a stratum V2 client can easily process bulk
 /// acknowledgements. The sequence number type has been selected as u32 to match
-/// up with the protocol.
-type SolutionQueue = Mutex<VecDeque<(work::Solution, u32)>>;
+/// up with the protocol. The submission time is kept for measuring latency of the response.
+pub(super) type SolutionQueue = Mutex<VecDeque<(work::Solution, u32, time::Instant)>>;
+
+/// Drop all solutions which have not been submitted yet or which wait for acknowledgement
+/// and account them as discarded
+pub(super) async fn discard_solutions(
+    solution_receiver: &Mutex<job::SolutionReceiver>,
+    solutions: &SolutionQueue,
+) {
+    let submitted: Vec<_> = solutions
+        .lock()
+        .await
+        .drain(..)
+        .map(|(solution, _, _)| solution)
+        .collect();
+    let discarded = solution_receiver.lock().await.discard(submitted).await;
+    if discarded > 0 {
+        info!(
+            "Stratum: discarded {} unacknowledged solution(s)",
+            discarded
+        );
+    }
+}
 
-/// Helper task for `StratumClient` that implements Stratum V2 visitor which processes incoming
-/// messages from remote server.
-struct StratumEventHandler {
-    client: Arc<StratumClient>,
+/// Mining channel negotiated with the upstream endpoint
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelInfo {
+    channel_id: u32,
+    /// Group channel that the upstream endpoint may use for broadcasting jobs
+    group_channel_id: u32,
+    init_target: ii_bitcoin::Target,
+}
+
+/// Requests for the running mining session that don't originate from the upstream endpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SessionRequest {
+    /// Nominal hashrate has changed and has to be announced to the upstream endpoint
+    UpdateChannel,
+    /// The client is being stopped and the mining channel has to be closed
+    CloseChannel,
+}
+
+/// Jobs and target of a single standard channel used for mining
+#[derive(Debug)]
+struct ChannelState {
     all_jobs: HashMap<u32, NewMiningJob>,
     current_prevhash_msg: Option<SetNewPrevHash>,
     /// Mining target for the next job that is to be solved
     current_target: ii_bitcoin::Target,
 }
 
-impl StratumEventHandler {
-    pub fn new(client: Arc<StratumClient>, current_target: ii_bitcoin::Target) -> Self {
+impl ChannelState {
+    fn new(init_target: ii_bitcoin::Target) -> Self {
         Self {
-            client,
             all_jobs: Default::default(),
             current_prevhash_msg: None,
-            current_target,
+            current_target: init_target,
         }
     }
+}
+
+/// Helper task for `StratumClient` that implements Stratum V2 visitor which processes incoming
+/// messages from remote server.
+struct StratumEventHandler {
+    client: Arc<StratumClient>,
+    /// Channel opened by this client, nominal hashrate updates and channel closing apply to it
+    channel_id: u32,
+    /// Group membership of the mining channels, jobs and prevhashes may be addressed to a group
+    /// channel and then they apply to all its members
+    channel_groups: ChannelGroups,
+    /// Standard channels used for mining
+    channels: HashMap<u32, ChannelState>,
+}
+
+impl StratumEventHandler {
+    fn new(client: Arc<StratumClient>, channel: ChannelInfo) -> Self {
+        let mut event_handler = Self {
+            client,
+            channel_id: channel.channel_id,
+            channel_groups: ChannelGroups::new(),
+            channels: Default::default(),
+        };
+        event_handler.add_channel(channel);
+        event_handler
+    }
+
+    fn add_channel(&mut self, channel: ChannelInfo) {
+        self.channel_groups
+            .add_channel(channel.group_channel_id, channel.channel_id);
+        self.channels
+            .insert(channel.channel_id, ChannelState::new(channel.init_target));
+    }
+
+    fn channel_mut(&mut self, channel_id: u32) -> &mut ChannelState {
+        self.channels
+            .get_mut(&channel_id)
+            .expect("BUG: missing mining channel")
+    }
+
+    /// Resolves mining channels of this client that a channel message addressed to
+    /// `target_channel_id` applies to (directly or via their group channel)
+    fn resolve_channels(&self, target_channel_id: u32) -> Vec<u32> {
+        let channel_ids: Vec<_> = self
+            .channel_groups
+            .resolve(target_channel_id)
+            .into_iter()
+            .filter(|channel_id| self.channels.contains_key(channel_id))
+            .collect();
+        if channel_ids.is_empty() {
+            warn!(
+                "Stratum: ignoring message for unknown channel {}",
+                target_channel_id
+            );
+        }
+        channel_ids
+    }
 
     /// Convert new mining job message into StratumJob and send it down the line for solving.
     ///
+    /// * `channel_id` - mining channel the job is solved on
     /// * `job_msg` - job message used as a base for the StratumJob
-    async fn update_job(&mut self, job_msg: &NewMiningJob) {
+    async fn update_job(&mut self, channel_id: u32, job_msg: &NewMiningJob) {
+        let channel = self
+            .channels
+            .get(&channel_id)
+            .expect("BUG: missing mining channel");
         let job = Arc::new(StratumJob::new(
             self.client.clone(),
+            channel_id,
             job_msg,
-            self.current_prevhash_msg
+            channel
+                .current_prevhash_msg
                 .as_ref()
                 .expect("TODO: no prevhash"),
-            self.current_target,
+            channel.current_target,
         ));
         self.client.update_last_job(job.clone()).await;
         self.client.job_sender.lock().await.send(job);
     }
 
-    fn update_target(&mut self, value: Uint256Bytes) {
+    fn update_target(&mut self, channel_id: u32, value: Uint256Bytes) {
         let new_target: ii_bitcoin::Target = value.into();
         info!(
-            "Stratum: changing target to {} diff={}",
+            "Stratum: changing target of channel {} to {} diff={}",
+            channel_id,
             new_target,
             new_target.get_difficulty()
         );
-        self.current_target = new_target;
+        self.channel_mut(channel_id).current_target = new_target;
+    }
+
+    /// Record the server response to submitted share in the recording and the share journal
+    fn journal_share(
+        &self,
+        solution: &work::Solution,
+        submitted: time::Instant,
+        result: journal::ShareResult,
+    ) {
+        recorder::record_response(solution, &result);
+        let job: &StratumJob = solution.job();
+        journal::record(journal::Record::new(
+            &*self.client,
+            job.id,
+            solution,
+            submitted,
+            result,
+        ));
     }
 
     async fn process_accepted_shares(&self, success_msg: &SubmitSharesSuccess) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submitted)) =
+            self.client.solutions.lock().await.pop_front()
+        {
             info!(
                 "Stratum: accepted solution #{} with nonce={:08x}",
                 seq_num,
@@ -239,6 +373,7 @@ impl StratumEventHandler {
                 .accepted
                 .account_solution(&solution.job_target(), now)
                 .await;
+            self.journal_share(&solution, submitted, journal::ShareResult::Accepted);
             if success_msg.last_seq_num == seq_num {
                 // all accepted solutions have been found
                 return;
@@ -252,18 +387,27 @@ impl StratumEventHandler {
 
     async fn process_rejected_shares(&self, error_msg: &SubmitSharesError) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submitted)) =
+            self.client.solutions.lock().await.pop_front()
+        {
             if error_msg.seq_num == seq_num {
                 info!(
                     "Stratum: rejected solution #{} with nonce={:08x}!",
                     seq_num,
                     solution.nonce()
                 );
-                self.client
-                    .client_stats
-                    .rejected
-                    .account_solution(&solution.job_target(), now)
-                    .await;
+                // Rejected solution of invalidated job is most likely stale
+                let meter = if solution.has_valid_job() {
+                    &self.client.client_stats.rejected
+                } else {
+                    &self.client.client_stats.stale
+                };
+                meter.account_solution(&solution.job_target(), now).await;
+                self.journal_share(
+                    &solution,
+                    submitted,
+                    journal::ShareResult::Rejected(error_msg.code.to_string()),
+                );
                 // the rejected solution has been found
                 return;
             } else {
@@ -279,6 +423,7 @@ impl StratumEventHandler {
                     .accepted
                     .account_solution(&solution.job_target(), now)
                     .await;
+                self.journal_share(&solution, submitted, journal::ShareResult::Accepted);
                 warn!(
                     "Stratum: the solution #{} precedes rejected solution #{}!",
                     seq_num, error_msg.seq_num
@@ -306,47 +451,77 @@ impl Handler for StratumEventHandler {
     //      - replace it
     //      - start mining the job it references (by job id)
     //      - flush all other jobs
+    //  - a message addressed to a group channel applies to all its member channels
 
     async fn visit_new_mining_job(&mut self, _header: &Header, job_msg: &NewMiningJob) {
-        // all jobs since last `prevmsg` have to be stored in job table
-        self.all_jobs.insert(job_msg.job_id, job_msg.clone());
-        // TODO: close connection when maximal capacity of `all_jobs` has been reached
-
-        // When not marked as future job, we can start mining on it right away
-        // TODO see the _channels variant when consolidating this version of the client.
-        //  Transform the documentation from there too. Currently, this workaround with
-        //  .is_some() prevents a problem when a server indicates a new job however it doesn't
-        //  send the new prevhash ahead of this job. This scenario is still yet to be investigated
-        //  as it should prevented typically on the V2->V1->upstream translation proxies. These
-        //  proxies should guarantee that no such case like a job without a prevhash would exist.
-        if !job_msg.future_job && self.current_prevhash_msg.is_some() {
-            self.update_job(job_msg).await;
+        for channel_id in self.resolve_channels(job_msg.channel_id) {
+            let channel = self.channel_mut(channel_id);
+            // all jobs since last `prevmsg` have to be stored in job table
+            channel.all_jobs.insert(job_msg.job_id, job_msg.clone());
+            // TODO: close connection when maximal capacity of `all_jobs` has been reached
+
+            // When not marked as future job, we can start mining on it right away
+            // TODO see the _channels variant when consolidating this version of the client.
+            //  Transform the documentation from there too. Currently, this workaround with
+            //  .is_some() prevents a problem when a server indicates a new job however it doesn't
+            //  send the new prevhash ahead of this job. This scenario is still yet to be
+            //  investigated as it should prevented typically on the V2->V1->upstream translation
+            //  proxies. These proxies should guarantee that no such case like a job without
+            //  a prevhash would exist.
+            if !job_msg.future_job && channel.current_prevhash_msg.is_some() {
+                self.update_job(channel_id, job_msg).await;
+            }
         }
     }
 
     async fn visit_set_new_prev_hash(&mut self, _header: &Header, prevhash_msg: &SetNewPrevHash) {
-        self.current_prevhash_msg.replace(prevhash_msg.clone());
-
-        // find the future job with ID referenced in prevhash_msg
-        let (_, mut future_job_msg) = self
-            .all_jobs
-            .remove_entry(&prevhash_msg.job_id)
-            .expect("TODO: requested job ID not found");
-
-        // remove all other jobs (they are now invalid)
-        self.all_jobs.retain(|_, _| true);
-        // turn the job into an immediate job
-        future_job_msg.future_job = false;
-        // reinsert the job
-        self.all_jobs
-            .insert(future_job_msg.job_id, future_job_msg.clone());
-
-        // and start immediately solving it
-        self.update_job(&future_job_msg).await;
+        let channel_ids = self.resolve_channels(prevhash_msg.channel_id);
+        if channel_ids.is_empty() {
+            return;
+        }
+        // All jobs received so far are stale now
+        self.client.job_generation.fetch_add(1, Ordering::Relaxed);
+
+        for channel_id in channel_ids {
+            let channel = self.channel_mut(channel_id);
+            channel.current_prevhash_msg.replace(prevhash_msg.clone());
+
+            // find the future job with ID referenced in prevhash_msg
+            let (_, mut future_job_msg) = channel
+                .all_jobs
+                .remove_entry(&prevhash_msg.job_id)
+                .expect("TODO: requested job ID not found");
+
+            // remove all other jobs (they are now invalid)
+            channel.all_jobs.retain(|_, _| true);
+            // turn the job into an immediate job
+            future_job_msg.future_job = false;
+            // reinsert the job
+            channel
+                .all_jobs
+                .insert(future_job_msg.job_id, future_job_msg.clone());
+
+            // and start immediately solving it
+            self.update_job(channel_id, &future_job_msg).await;
+        }
     }
 
     async fn visit_set_target(&mut self, _header: &Header, target_msg: &SetTarget) {
-        self.update_target(target_msg.max_target);
+        for channel_id in self.resolve_channels(target_msg.channel_id) {
+            self.update_target(channel_id, target_msg.max_target);
+        }
+    }
+
+    async fn visit_set_group_channel(&mut self, _header: &Header, group_msg: &SetGroupChannel) {
+        self.channel_groups.set_group_channel(group_msg);
+        for channel_id in group_msg.channel_ids.iter() {
+            if self.channels.contains_key(channel_id) {
+                info!(
+                    "Stratum: channel {} is a member of group channel {}",
+                    channel_id, group_msg.group_channel_id
+                );
+            }
+        }
     }
 
     async fn visit_submit_shares_success(
@@ -360,6 +535,40 @@ impl Handler for StratumEventHandler {
     async fn visit_submit_shares_error(&mut self, _header: &Header, error_msg: &SubmitSharesError) {
         self.process_rejected_shares(error_msg).await;
     }
+
+    async fn visit_channel_endpoint_changed(
+        &mut self,
+        _header: &Header,
+        endpoint_msg: &ChannelEndpointChanged,
+    ) {
+        // Upstream endpoint won't acknowledge any of the pending solutions
+        let now = time::Instant::now();
+        let mut solutions = self.client.solutions.lock().await;
+        info!(
+            "Stratum: channel {} endpoint changed, dropping {} unacknowledged solution(s)",
+            endpoint_msg.channel_id,
+            solutions.len()
+        );
+        for (solution, _, _) in solutions.drain(..) {
+            stats::account_discarded_solution(&solution, now).await;
+        }
+    }
+
+    async fn visit_reconnect(&mut self, _header: &Header, reconnect_msg: &Reconnect) {
+        self.client.redirect(reconnect_msg);
+    }
+
+    async fn visit_update_channel_error(
+        &mut self,
+        _header: &Header,
+        error_msg: &UpdateChannelError,
+    ) {
+        warn!(
+            "Stratum: channel {} update has been refused: {}",
+            error_msg.channel_id,
+            error_msg.code.to_string()
+        );
+    }
 }
 
 trait FrameSink:
@@ -442,7 +651,7 @@ where
             .solutions
             .lock()
             .await
-            .push_back((solution, seq_num));
+            .push_back((solution, seq_num, time::Instant::now()));
         // send solutions back to the stratum server
         StratumClient::send_msg(&self.connection_tx, share_msg)
             .await
@@ -454,7 +663,7 @@ where
 
 struct StratumConnectionHandler {
     client: Arc<StratumClient>,
-    init_target: ii_bitcoin::Target,
+    channel: ChannelInfo,
     status: Option<error::Result<()>>,
 }
 
@@ -462,7 +671,7 @@ impl StratumConnectionHandler {
     pub fn new(client: Arc<StratumClient>) -> Self {
         Self {
             client,
-            init_target: Default::default(),
+            channel: Default::default(),
             status: None,
         }
     }
@@ -476,7 +685,7 @@ impl StratumConnectionHandler {
         R: FrameStream,
         S: FrameSink,
     {
-        let connection_details = self.client.connection_details();
+        let connection_details = self.client.endpoint_connection_details();
         let setup_msg = SetupConnection {
             protocol: 0,
             max_version: 2,
@@ -520,7 +729,7 @@ impl StratumConnectionHandler {
                 .clone()
                 .try_into()
                 .expect("BUG: cannot convert 'OpenStandardMiningChannel::user'"),
-            nominal_hashrate: 1e9,
+            nominal_hashrate: self.client.nominal_hashrate(),
             // Maximum bitcoin target is 0xffff << 208 (= difficulty 1 share)
             max_target: ii_bitcoin::Target::default().into(),
         };
@@ -542,7 +751,7 @@ impl StratumConnectionHandler {
     }
 
     async fn connect(&self) -> error::Result<v2::Framed> {
-        let connection_details = self.client.connection_details();
+        let connection_details = self.client.endpoint_connection_details();
         let addr = ii_wire::Address::from_str(connection_details.get_host_and_port().as_str())?;
         let mut client = ii_wire::Client::new(addr);
         // Attempt only once to connect (as the stratum client is being managed externally)
@@ -569,12 +778,13 @@ impl StratumConnectionHandler {
         Ok(client_framed_stream)
     }
 
-    /// Starts mining session and provides the initial target negotiated by the upstream endpoint
+    /// Starts mining session and provides the channel (including the initial target) negotiated
+    /// by the upstream endpoint
     async fn init_mining_session<R, S>(
         mut self,
         connection_rx: &mut R,
         connection_tx: Arc<Mutex<S>>,
-    ) -> error::Result<ii_bitcoin::Target>
+    ) -> error::Result<ChannelInfo>
     where
         R: FrameStream,
         S: FrameSink,
@@ -586,7 +796,7 @@ impl StratumConnectionHandler {
             .await
             .context("Cannot open stratum channel")?;
 
-        Ok(self.init_target)
+        Ok(self.channel)
     }
 }
 
@@ -614,7 +824,11 @@ impl Handler for StratumConnectionHandler {
         _header: &Header,
         success_msg: &OpenStandardMiningChannelSuccess,
     ) {
-        self.init_target = success_msg.target.into();
+        self.channel = ChannelInfo {
+            channel_id: success_msg.channel_id,
+            group_channel_id: success_msg.group_channel_id,
+            init_target: success_msg.target.into(),
+        };
         self.status = Ok(()).into();
     }
 
@@ -665,17 +879,32 @@ pub struct StratumClient {
     solutions: SolutionQueue,
     job_sender: Mutex<job::Sender>,
     solution_receiver: Mutex<job::SolutionReceiver>,
+    solution_queue_length: job::SolutionQueueLength,
     /// Frames received from this channel will be forwarded to the network connection
     extension_channel_receiver: Mutex<ExtensionChannelToStratumReceiver>,
     /// Frames intended for the specified extension will be forwarded into this channel (wrapped
     /// into ExtensionChannelMsg
     extension_channel_sender: Mutex<ExtensionChannelFromStratumSender>,
+    /// Upstream has requested reconnection to a different endpoint (see `Reconnect` message)
+    reconnect_requested: AtomicBool,
+    /// Host and port requested by upstream which override the configured endpoint. The
+    /// configured connection details are kept untouched so that the client falls back to them
+    /// when the redirected session terminates for any other reason than another redirect.
+    redirect_endpoint: StdMutex<Option<(String, u16)>>,
+    /// Incremented each time all received jobs become stale
+    job_generation: AtomicUsize,
+    /// Latest nominal hashrate of the backend announced when opening or updating the channel
+    nominal_hashrate: StdMutex<Option<ii_bitcoin::HashesUnit>>,
+    session_request_sender: mpsc::UnboundedSender<SessionRequest>,
+    session_request_receiver: Mutex<mpsc::UnboundedReceiver<SessionRequest>>,
 }
 
 impl StratumClient {
     const CONNECTION_TIMEOUT: time::Duration = time::Duration::from_secs(5);
     const EVENT_TIMEOUT: time::Duration = time::Duration::from_secs(150);
     const SEND_TIMEOUT: time::Duration = time::Duration::from_secs(2);
+    /// Nominal hashrate used until the backend reports the real one (1 GH/s)
+    const DEFAULT_NOMINAL_HASHRATE: f32 = 1e9;
 
     /// Start a task that plays a dummy role for both communication channels that the stratum
     /// client uses to talk to stratum extension.
@@ -723,6 +952,7 @@ impl StratumClient {
         )>,
     ) -> Self {
         let (stop_sender, stop_receiver) = mpsc::channel(1);
+        let (session_request_sender, session_request_receiver) = mpsc::unbounded();
 
         // Extract the both channel endpoints that connect the client with the stratum extension
         // or populate it with dummy endpoints. That way we can handle the endpoints uniformly
@@ -746,9 +976,16 @@ impl StratumClient {
             last_job: Mutex::new(None),
             solutions: Mutex::new(VecDeque::new()),
             job_sender: Mutex::new(solver.job_sender),
+            solution_queue_length: solver.solution_receiver.queue_length(),
             solution_receiver: Mutex::new(solver.solution_receiver),
             extension_channel_receiver: Mutex::new(extension_channel_receiver),
             extension_channel_sender: Mutex::new(extension_channel_sender),
+            reconnect_requested: AtomicBool::new(false),
+            redirect_endpoint: StdMutex::new(None),
+            job_generation: AtomicUsize::new(0),
+            nominal_hashrate: StdMutex::new(None),
+            session_request_sender,
+            session_request_receiver: Mutex::new(session_request_receiver),
         }
     }
 
@@ -759,6 +996,108 @@ impl StratumClient {
             .clone()
     }
 
+    /// Connection details with the endpoint requested by upstream (if any) which are used for
+    /// connecting to the remote server
+    fn endpoint_connection_details(&self) -> ConnectionDetails {
+        let mut connection_details = self.connection_details();
+        if let Some((host, port)) = self
+            .redirect_endpoint
+            .lock()
+            .expect("BUG: cannot lock redirect endpoint")
+            .clone()
+        {
+            connection_details.host = host;
+            connection_details.port = port;
+        }
+        connection_details
+    }
+
+    /// Applies new host and port requested by upstream and schedules reconnection. The client
+    /// (including its statistics and configured connection details) is preserved, only the
+    /// connection is reestablished.
+    fn redirect(&self, reconnect_msg: &Reconnect) {
+        let connection_details = self.endpoint_connection_details();
+
+        // Empty host or zero port means that the current value is to be kept
+        let host = if reconnect_msg.new_host.is_empty() {
+            connection_details.host.clone()
+        } else {
+            reconnect_msg.new_host.to_string()
+        };
+        let port = if reconnect_msg.new_port == 0 {
+            connection_details.port
+        } else {
+            reconnect_msg.new_port
+        };
+        info!(
+            "Stratum: upstream requested reconnect from {} to {}:{}",
+            connection_details.get_host_and_port(),
+            host,
+            port
+        );
+
+        self.redirect_endpoint
+            .lock()
+            .expect("BUG: cannot lock redirect endpoint")
+            .replace((host, port));
+        self.reconnect_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Nominal hashrate in hashes per second as expected by the stratum messages
+    fn nominal_hashrate(&self) -> f32 {
+        self.nominal_hashrate
+            .lock()
+            .expect("BUG: cannot lock nominal hashrate")
+            .map(|nominal_hashrate| nominal_hashrate.into_hashes().into_f64() as f32)
+            .unwrap_or(Self::DEFAULT_NOMINAL_HASHRATE)
+    }
+
+    /// Pass a request to the mining session. Requests queued while there is no session running
+    /// are dropped when the next session starts.
+    fn request_session(&self, request: SessionRequest) {
+        self.session_request_sender
+            .unbounded_send(request)
+            .expect("BUG: session request channel closed");
+    }
+
+    async fn update_channel<S: FrameSink>(
+        &self,
+        connection_tx: &Arc<Mutex<S>>,
+        channel_id: u32,
+    ) -> error::Result<()> {
+        let nominal_hashrate = self.nominal_hashrate();
+        info!(
+            "Stratum: updating channel {} with nominal hashrate {}",
+            channel_id,
+            ii_bitcoin::HashesUnit::Hashes(nominal_hashrate as u128).into_pretty_hashes()
+        );
+        let update_msg = UpdateChannel {
+            channel_id,
+            nominal_hashrate,
+            max_target: ii_bitcoin::Target::default().into(),
+        };
+        Self::send_msg(connection_tx, update_msg)
+            .await
+            .context("Cannot send stratum update channel")?;
+        Ok(())
+    }
+
+    async fn close_channel<S: FrameSink>(
+        &self,
+        connection_tx: &Arc<Mutex<S>>,
+        channel_id: u32,
+    ) -> error::Result<()> {
+        info!("Stratum: closing channel {}", channel_id);
+        let close_msg = CloseChannel {
+            channel_id,
+            reason_code: Str0_32::from_str("shutdown"),
+        };
+        Self::send_msg(connection_tx, close_msg)
+            .await
+            .context("Cannot send stratum close channel")?;
+        Ok(())
+    }
+
     async fn update_last_job(&self, job: Arc<StratumJob>) {
         self.last_job.lock().await.replace(job);
     }
@@ -836,9 +1175,15 @@ impl StratumClient {
         S: FrameSink,
     {
         let mut solution_receiver = self.solution_receiver.lock().await;
+        solution_receiver.set_stale_policy(self.connection_details().stale_policy);
         let mut extension_channel_rx = self.extension_channel_receiver.lock().await;
+        let mut session_request_rx = self.session_request_receiver.lock().await;
         let mut solution_handler = StratumSolutionHandler::new(self.clone(), connection_tx.clone());
 
+        // Drop all requests from the previous sessions, the channel has been opened with the
+        // current nominal hashrate
+        while let Ok(Some(_)) = session_request_rx.try_next() {}
+
         // Notify the extension user that we are ready to start forwarding its protocol, use a
         // separate block, so that the lock is dropped immediately after the start notification
         // is sent
@@ -852,7 +1197,7 @@ impl StratumClient {
                 })
                 .expect("BUG: stratum extension channel not available for start");
         }
-        while !self.status.is_shutting_down() {
+        while !self.status.is_shutting_down() && !self.reconnect_requested.load(Ordering::Relaxed) {
             select! {
                 frame = connection_rx.next().timeout(Self::EVENT_TIMEOUT).fuse() => {
                     match frame {
@@ -877,8 +1222,22 @@ impl StratumClient {
                         }
                     }
                 }
+                request = session_request_rx.next() => {
+                    match request.expect("BUG: session request channel closed") {
+                        SessionRequest::UpdateChannel => {
+                            self.update_channel(&connection_tx, event_handler.channel_id)
+                                .await?;
+                        }
+                        // The loop condition takes care of the shutdown
+                        SessionRequest::CloseChannel => {}
+                    }
+                }
             }
         }
+        if self.status.is_shutting_down() {
+            self.close_channel(&connection_tx, event_handler.channel_id)
+                .await?;
+        }
         Ok(())
     }
 
@@ -886,12 +1245,12 @@ impl StratumClient {
         self: Arc<Self>,
         connection_rx: R,
         connection_tx: Arc<Mutex<S>>,
-        init_target: ii_bitcoin::Target,
+        channel: ChannelInfo,
     ) where
         R: FrameStream,
         S: FrameSink,
     {
-        let event_handler = StratumEventHandler::new(self.clone(), init_target);
+        let event_handler = StratumEventHandler::new(self.clone(), channel);
         // TODO consider changing main_loop to accept Arc<Self> and build the solution_handler
         //  along with solution handler communication channels inside of the main_loop.
         let client = self.clone();
@@ -905,7 +1264,7 @@ impl StratumClient {
 
     async fn run(self: Arc<Self>) {
         let connection_handler = StratumConnectionHandler::new(self.clone());
-        let connection_details = connection_handler.client.connection_details();
+        let connection_details = connection_handler.client.endpoint_connection_details();
         let host_and_port = connection_details.get_host_and_port();
         let user = connection_details.user.clone();
 
@@ -925,10 +1284,10 @@ impl StratumClient {
                     .map_err(|_| {
                         error::ErrorKind::General("Init mining session timeout".to_string()).into()
                     }) {
-                    Ok(Ok(init_target)) => {
+                    Ok(Ok(channel)) => {
                         if self.status.initiate_running() {
                             self.clone()
-                                .run_job_solver(framed_stream, framed_sink, init_target)
+                                .run_job_solver(framed_stream, framed_sink, channel)
                                 .await;
                         }
                     }
@@ -954,15 +1313,29 @@ impl StratumClient {
     }
 
     async fn main_task(self: Arc<Self>) {
-        // TODO: Count as a discarded solution?
         // Flush all obsolete solutions from previous run
-        self.solution_receiver.lock().await.flush();
+        discard_solutions(&self.solution_receiver, &self.solutions).await;
 
         loop {
             let mut stop_receiver = self.stop_receiver.lock().await;
-            select! {
-                _ = self.clone().run().fuse() => {}
-                _ = stop_receiver.next() => {}
+            let run = self.clone().run().fuse();
+            futures::pin_mut!(run);
+            let stopped = select! {
+                _ = run => false,
+                _ = stop_receiver.next() => true,
+            };
+            if stopped {
+                // Give the mining session a chance to close the channel gracefully
+                self.request_session(SessionRequest::CloseChannel);
+                let _ = run.timeout(Self::SEND_TIMEOUT).await;
+            }
+            let reconnect = self.reconnect_requested.swap(false, Ordering::Relaxed);
+            if !reconnect {
+                // Failed or stopped session falls back to the configured endpoint
+                self.redirect_endpoint
+                    .lock()
+                    .expect("BUG: cannot lock redirect endpoint")
+                    .take();
             }
 
             // Notify the other end that uses the extension channel that it should restart its
@@ -983,12 +1356,16 @@ impl StratumClient {
                 );
             }
             // Invalidate current job to stop working on it
+            self.job_generation.fetch_add(1, Ordering::Relaxed);
             self.job_sender.lock().await.invalidate();
             // Flush all unprocessed solutions to empty buffer
-            // TODO: Count as a discarded solution?
-            self.solution_receiver.lock().await.flush();
-            self.solutions.lock().await.clear();
+            discard_solutions(&self.solution_receiver, &self.solutions).await;
 
+            if reconnect && self.status.status() == sync::Status::Running {
+                // The connection has been torn down on upstream request, connect to the new
+                // endpoint right away
+                continue;
+            }
             if self.status.can_stop() {
                 // NOTE: it is not safe to add here any code!
                 // The reason is that at this point the main task can be executed in parallel again
@@ -1015,6 +1392,11 @@ impl node::Client for StratumClient {
         }
     }
 
+    async fn pending_solutions(&self) -> usize {
+        // Solutions which have not been taken from the queue yet are pending too
+        self.solutions.lock().await.len() + self.solution_queue_length.get()
+    }
+
     async fn get_last_job(&self) -> Option<Arc<dyn job::Bitcoin>> {
         self.last_job
             .lock()
@@ -1023,13 +1405,26 @@ impl node::Client for StratumClient {
             .map(|job| job.clone() as Arc<dyn job::Bitcoin>)
     }
 
-    /// Build new connection details from the specified `descriptor`
+    /// Build new connection details from the specified `descriptor`, the endpoint requested by
+    /// upstream is forgotten
     fn change_connection_details(&self, descriptor: &bosminer_config::ClientDescriptor) {
         *self
             .connection_details
             .lock()
             .expect("BUG: cannot lock connection details") =
             ConnectionDetails::from_descriptor(descriptor);
+        self.redirect_endpoint
+            .lock()
+            .expect("BUG: cannot lock redirect endpoint")
+            .take();
+    }
+
+    fn update_nominal_hashrate(&self, nominal_hashrate: ii_bitcoin::HashesUnit) {
+        self.nominal_hashrate
+            .lock()
+            .expect("BUG: cannot lock nominal hashrate")
+            .replace(nominal_hashrate);
+        self.request_session(SessionRequest::UpdateChannel);
     }
 }
 
@@ -1043,3 +1438,303 @@ impl fmt::Display for StratumClient {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ii_async_compat::tokio;
+
+    fn test_client() -> (Arc<StratumClient>, job::SolutionSender) {
+        let (solution_sender, solution_receiver) = job::solution_channel();
+        let solver = job::Solver::new(Arc::new(work::EngineSender::new(None)), solution_receiver);
+        let client = Arc::new(StratumClient::new(
+            ConnectionDetails {
+                protocol: ClientProtocol::StratumV2Insecure,
+                user: "user".to_string(),
+                host: "127.0.0.1".to_string(),
+                port: 3336,
+                stale_policy: Default::default(),
+            },
+            None,
+            solver,
+            None,
+        ));
+        (client, solution_sender)
+    }
+
+    #[derive(Debug)]
+    struct TestSolution {
+        nonce: u32,
+        target: ii_bitcoin::Target,
+    }
+
+    impl hal::BackendSolution for TestSolution {
+        fn nonce(&self) -> u32 {
+            self.nonce
+        }
+
+        fn midstate_idx(&self) -> usize {
+            0
+        }
+
+        fn solution_idx(&self) -> usize {
+            0
+        }
+
+        fn target(&self) -> &ii_bitcoin::Target {
+            &self.target
+        }
+    }
+
+    /// Passes `message` to `event_handler` as if it has been received from the remote server
+    async fn simulate_incoming_message<M>(event_handler: &mut StratumEventHandler, message: M)
+    where
+        M: TryInto<<Framing as ii_wire::Framing>::Tx, Error = <Framing as ii_wire::Framing>::Error>,
+    {
+        let frame = message.try_into().expect("BUG: cannot serialize message");
+        build_message_from_frame(frame)
+            .expect("BUG: cannot deserialize message")
+            .accept(event_handler)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_group_channel_job() {
+        const GROUP_CHANNEL_ID: u32 = 10;
+        const JOB_ID: u32 = 5;
+
+        let (client, _) = test_client();
+        let mut event_handler = StratumEventHandler::new(
+            client.clone(),
+            ChannelInfo {
+                channel_id: 1,
+                group_channel_id: GROUP_CHANNEL_ID,
+                init_target: Default::default(),
+            },
+        );
+        event_handler.add_channel(ChannelInfo {
+            channel_id: 2,
+            group_channel_id: GROUP_CHANNEL_ID,
+            init_target: Default::default(),
+        });
+        // Additional members must not replace the channel opened by the client
+        assert_eq!(event_handler.channel_id, 1);
+
+        // A single job and prevhash is broadcast to the whole group channel
+        simulate_incoming_message(
+            &mut event_handler,
+            NewMiningJob {
+                channel_id: GROUP_CHANNEL_ID,
+                job_id: JOB_ID,
+                future_job: true,
+                version: 0x20000000,
+                merkle_root: Uint256Bytes([0x11; 32]),
+            },
+        )
+        .await;
+        simulate_incoming_message(
+            &mut event_handler,
+            SetNewPrevHash {
+                channel_id: GROUP_CHANNEL_ID,
+                job_id: JOB_ID,
+                prev_hash: Uint256Bytes([0x22; 32]),
+                min_ntime: 0x5e000000,
+                nbits: 0x1d00ffff,
+            },
+        )
+        .await;
+
+        // The job is solved on each member channel
+        for channel_id in &[1u32, 2] {
+            let channel = &event_handler.channels[channel_id];
+            assert_eq!(
+                channel
+                    .current_prevhash_msg
+                    .as_ref()
+                    .expect("BUG: missing prevhash")
+                    .job_id,
+                JOB_ID
+            );
+            assert!(!channel.all_jobs[&JOB_ID].future_job);
+        }
+        assert_eq!(*client.client_stats.valid_jobs.take_snapshot(), 2);
+        let last_job = client.last_job.lock().await.clone().expect("BUG: no job");
+        assert_eq!(last_job.id, JOB_ID);
+        assert_eq!(last_job.channel_id, 2);
+
+        // Messages addressed to a channel of another client are ignored
+        simulate_incoming_message(
+            &mut event_handler,
+            NewMiningJob {
+                channel_id: 3,
+                job_id: JOB_ID + 1,
+                future_job: false,
+                version: 0x20000000,
+                merkle_root: Uint256Bytes([0x33; 32]),
+            },
+        )
+        .await;
+        assert_eq!(*client.client_stats.valid_jobs.take_snapshot(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pending_solutions() {
+        const JOB_ID: u32 = 1;
+
+        // Any hash meets the maximal target
+        let max_target =
+            ii_bitcoin::Target::from_hex(&"f".repeat(64)).expect("BUG: cannot parse target");
+        let (client, solution_sender) = test_client();
+        let mut event_handler = StratumEventHandler::new(
+            client.clone(),
+            ChannelInfo {
+                channel_id: 1,
+                group_channel_id: 0,
+                init_target: max_target,
+            },
+        );
+        simulate_incoming_message(
+            &mut event_handler,
+            NewMiningJob {
+                channel_id: 1,
+                job_id: JOB_ID,
+                future_job: true,
+                version: 0x20000000,
+                merkle_root: Uint256Bytes([0x11; 32]),
+            },
+        )
+        .await;
+        simulate_incoming_message(
+            &mut event_handler,
+            SetNewPrevHash {
+                channel_id: 1,
+                job_id: JOB_ID,
+                prev_hash: Uint256Bytes([0x22; 32]),
+                min_ntime: 0x5e000000,
+                nbits: 0x1d00ffff,
+            },
+        )
+        .await;
+        let job = client.last_job.lock().await.clone().expect("BUG: no job");
+
+        for nonce in 0..3 {
+            let midstate = work::Midstate {
+                version: job.version,
+                state: Default::default(),
+            };
+            let solution = work::Solution::new(
+                work::Assignment::new(job.clone(), vec![midstate], job.time),
+                TestSolution {
+                    nonce,
+                    target: max_target,
+                },
+                None,
+            );
+            solution_sender
+                .send(solution)
+                .expect("BUG: cannot send solution");
+        }
+        assert_eq!(client.pending_solutions().await, 3);
+
+        // Submit two solutions while the third one stays in the queue
+        let (share_sender, mut share_receiver) = mpsc::unbounded();
+        let mut solution_handler =
+            StratumSolutionHandler::new(client.clone(), Arc::new(Mutex::new(share_sender)));
+        for _ in 0..2 {
+            let solution = client
+                .solution_receiver
+                .lock()
+                .await
+                .receive()
+                .await
+                .expect("BUG: missing solution");
+            solution_handler
+                .process_solution(solution)
+                .await
+                .expect("BUG: cannot submit solution");
+            assert!(share_receiver.next().await.is_some());
+        }
+        assert_eq!(client.pending_solutions().await, 3);
+
+        // The first submitted solution is acknowledged
+        simulate_incoming_message(
+            &mut event_handler,
+            SubmitSharesSuccess {
+                channel_id: 1,
+                last_seq_num: 0,
+                new_submits_accepted_count: 1,
+                new_shares_sum: 1,
+            },
+        )
+        .await;
+        assert_eq!(
+            client.client_stats.accepted.take_snapshot().await.solutions,
+            1
+        );
+        assert_eq!(client.pending_solutions().await, 2);
+
+        // Acknowledgement of the remaining ones times out
+        discard_solutions(&client.solution_receiver, &client.solutions).await;
+        assert_eq!(
+            client
+                .client_stats
+                .discarded
+                .take_snapshot()
+                .await
+                .solutions,
+            2
+        );
+        assert_eq!(client.pending_solutions().await, 0);
+    }
+
+    #[test]
+    fn test_redirect() {
+        let (client, _) = test_client();
+        let redirect = |new_host: &str, new_port| {
+            client.redirect(&Reconnect {
+                new_host: Str0_255::from_string(new_host.to_string()),
+                new_port,
+            })
+        };
+
+        redirect("10.0.0.1", 0);
+        assert!(client.reconnect_requested.load(Ordering::Relaxed));
+        assert_eq!(
+            client.endpoint_connection_details().get_host_and_port(),
+            "10.0.0.1:3336"
+        );
+        // Empty host is resolved against the redirected endpoint
+        redirect("", 3337);
+        assert_eq!(
+            client.endpoint_connection_details().get_host_and_port(),
+            "10.0.0.1:3337"
+        );
+        // Configured connection details reported to the API stay untouched
+        assert_eq!(
+            client.connection_details().get_host_and_port(),
+            "127.0.0.1:3336"
+        );
+        assert!(client.to_string().contains("127.0.0.1"));
+
+        // New configuration takes precedence over the redirect
+        node::Client::change_connection_details(
+            client.as_ref(),
+            &ClientDescriptor {
+                protocol: ClientProtocol::StratumV2Insecure,
+                enabled: true,
+                user: "user".to_string(),
+                password: None,
+                host: "127.0.0.2".to_string(),
+                port: Some(3336),
+                fragment: None,
+                stale_policy: Default::default(),
+                allow_cross_host_reconnect: false,
+            },
+        );
+        assert_eq!(
+            client.endpoint_connection_details().get_host_and_port(),
+            "127.0.0.2:3336"
+        );
+    }
+}
@@ -24,9 +24,12 @@
 
 use ii_logging::macros::*;
 
+use crate::client::journal;
+use crate::client::stratum_v2::{discard_solutions, SolutionQueue};
 use crate::error;
 use crate::job;
 use crate::node;
+use crate::recorder;
 use crate::stats;
 use crate::sync;
 use crate::work;
@@ -35,7 +38,7 @@ use failure::ResultExt;
 
 use ii_bitcoin::HashTrait;
 
-use bosminer_config::{ClientDescriptor, ClientProtocol};
+use bosminer_config::{ClientDescriptor, ClientProtocol, StalePolicy};
 use bosminer_macros::ClientNode;
 
 use async_trait::async_trait;
@@ -47,15 +50,17 @@ use ii_async_compat::select;
 use std::collections::VecDeque;
 use std::fmt;
 use std::net::ToSocketAddrs;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex as StdMutex;
 use std::sync::{Arc, Weak};
 use std::time;
 
 use ii_stratum::v2::framing::{Framing, Header};
 use ii_stratum::v2::messages::{
-    NewMiningJob, OpenStandardMiningChannel, OpenStandardMiningChannelError,
-    OpenStandardMiningChannelSuccess, SetNewPrevHash, SetTarget, SetupConnection,
-    SetupConnectionError, SetupConnectionSuccess, SubmitSharesError, SubmitSharesStandard,
-    SubmitSharesSuccess,
+    NewExtendedMiningJob, OpenExtendedMiningChannel, OpenExtendedMiningChannelError,
+    OpenExtendedMiningChannelSuccess, Reconnect, SetExtranoncePrefix, SetNewPrevHash, SetTarget,
+    SetupConnection, SetupConnectionError, SetupConnectionSuccess, SubmitSharesError,
+    SubmitSharesExtended, SubmitSharesSuccess,
 };
 use ii_stratum::v2::types::DeviceInfo;
 use ii_stratum::v2::types::*;
@@ -69,12 +74,15 @@ use std::collections::HashMap;
 // TODO: move it to the stratum crate
 const VERSION_MASK: u32 = 0x1fffe000;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConnectionDetails {
     pub user: String,
     pub host: String,
     pub port: u16,
+    pub stale_policy: StalePolicy,
     pub fragment: Option<String>,
+    /// Follow `client.reconnect` to a different host
+    pub allow_cross_host_reconnect: bool,
 }
 
 impl ConnectionDetails {
@@ -83,7 +91,9 @@ impl ConnectionDetails {
             user: descriptor.user.clone(),
             host: descriptor.host.clone(),
             port: descriptor.port(),
+            stale_policy: descriptor.stale_policy,
             fragment: descriptor.fragment.clone(),
+            allow_cross_host_reconnect: descriptor.allow_cross_host_reconnect,
         }
     }
 
@@ -101,6 +111,15 @@ impl ConnectionDetails {
     }
 }
 
+/// Extranonce assigned to the extended channel by the upstream
+#[derive(Debug, Clone, Default)]
+struct Extranonce {
+    /// Fixed part of extranonce which is prepended to the rolled part
+    prefix: Vec<u8>,
+    /// Number of extranonce bytes which are rolled by the miner
+    size: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct StratumJob {
     client: Weak<StratumClient>,
@@ -108,19 +127,38 @@ pub struct StratumJob {
     channel_id: u32,
     version: u32,
     prev_hash: ii_bitcoin::DHash,
+    /// Merkle root for the first extranonce2 (zero)
     merkle_root: ii_bitcoin::DHash,
     time: u32,
     bits: u32,
     target: ii_bitcoin::Target,
+    coinbase: job::Coinbase,
+    /// Job generation of the client at the time the job has been received
+    generation: usize,
 }
 
 impl StratumJob {
-    pub fn new(
+    fn new(
         client: Arc<StratumClient>,
-        job_msg: &NewMiningJob,
+        job_msg: &NewExtendedMiningJob,
         prevhash_msg: &SetNewPrevHash,
         target: ii_bitcoin::Target,
+        extranonce: &Extranonce,
     ) -> Self {
+        let coinbase = job::Coinbase {
+            coinbase1: job_msg.coinbase_tx_prefix.to_vec(),
+            extranonce1: extranonce.prefix.clone(),
+            extranonce2_size: extranonce.size,
+            coinbase2: job_msg.coinbase_tx_suffix.to_vec(),
+            merkle_branch: job_msg
+                .merkle_path
+                .iter()
+                .map(|hash| {
+                    ii_bitcoin::DHash::from_slice(hash.as_ref())
+                        .expect("BUG: Stratum: incorrect size of merkle path hash")
+                })
+                .collect(),
+        };
         Self {
             client: Arc::downgrade(&client),
             id: job_msg.job_id,
@@ -128,11 +166,12 @@ impl StratumJob {
             version: job_msg.version,
             prev_hash: ii_bitcoin::DHash::from_slice(prevhash_msg.prev_hash.as_ref())
                 .expect("BUG: Stratum: incorrect size of prev hash"),
-            merkle_root: ii_bitcoin::DHash::from_slice(job_msg.merkle_root.as_ref())
-                .expect("BUG: Stratum: incorrect size of merkle root"),
+            merkle_root: coinbase.merkle_root(0),
             time: prevhash_msg.min_ntime,
             bits: prevhash_msg.nbits,
             target,
+            coinbase,
+            generation: client.job_generation.load(Ordering::Relaxed),
         }
     }
 }
@@ -170,45 +209,49 @@ impl job::Bitcoin for StratumJob {
         self.target
     }
 
+    fn coinbase(&self) -> Option<&job::Coinbase> {
+        Some(&self.coinbase)
+    }
+
     fn is_valid(&self) -> bool {
-        // TODO: currently there is no easy way to detect the job is valid -> we have to check
-        //  its presence in the registry. The inequality below was possible in the previous
-        //  iteration of the protocol
-        // self.block_height >= self.current_block_height.load(Ordering::Relaxed)
-        true
+        self.client
+            .upgrade()
+            .map(|client| job::is_generation_current(&client.job_generation, self.generation))
+            .unwrap_or(false)
     }
 }
 
-/// Queue that contains pairs of solution and its assigned sequence number. It is our responsibility
-/// to keep the sequence number monotonic so that we as a stratum V2 client can easily process bulk
-/// acknowledgements. The sequence number type has been selected as u32 to match
-/// up with the protocol.
-type SolutionQueue = Mutex<VecDeque<(work::Solution, u32)>>;
-
 /// Helper task for `StratumClient` that implements Stratum V2 visitor which processes incoming
 /// messages from remote server.
 struct StratumEventHandler {
     client: Arc<StratumClient>,
-    all_jobs: HashMap<u32, NewMiningJob>,
+    all_jobs: HashMap<u32, NewExtendedMiningJob>,
     current_prevhash_msg: Option<SetNewPrevHash>,
     /// Mining target for the next job that is to be solved
     current_target: ii_bitcoin::Target,
+    /// Extranonce for the next job that is to be solved
+    current_extranonce: Extranonce,
 }
 
 impl StratumEventHandler {
-    pub fn new(client: Arc<StratumClient>, current_target: ii_bitcoin::Target) -> Self {
+    fn new(
+        client: Arc<StratumClient>,
+        current_target: ii_bitcoin::Target,
+        current_extranonce: Extranonce,
+    ) -> Self {
         Self {
             client,
             all_jobs: Default::default(),
             current_prevhash_msg: None,
             current_target,
+            current_extranonce,
         }
     }
 
     /// Convert new mining job message into StratumJob and send it down the line for solving.
     ///
     /// * `job_msg` - job message used as a base for the StratumJob
-    async fn update_job(&mut self, job_msg: &NewMiningJob) {
+    async fn update_job(&mut self, job_msg: &NewExtendedMiningJob) {
         let job = Arc::new(StratumJob::new(
             self.client.clone(),
             job_msg,
@@ -216,6 +259,7 @@ impl StratumEventHandler {
                 .as_ref()
                 .expect("TODO: no prevhash"),
             self.current_target,
+            &self.current_extranonce,
         ));
         self.client.update_last_job(job.clone()).await;
         self.client.job_sender.lock().await.send(job);
@@ -231,9 +275,29 @@ impl StratumEventHandler {
         self.current_target = new_target;
     }
 
+    /// Record the server response to submitted share in the recording and the share journal
+    fn journal_share(
+        &self,
+        solution: &work::Solution,
+        submitted: time::Instant,
+        result: journal::ShareResult,
+    ) {
+        recorder::record_response(solution, &result);
+        let job: &StratumJob = solution.job();
+        journal::record(journal::Record::new(
+            &*self.client,
+            job.id,
+            solution,
+            submitted,
+            result,
+        ));
+    }
+
     async fn process_accepted_shares(&self, success_msg: &SubmitSharesSuccess) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submitted)) =
+            self.client.solutions.lock().await.pop_front()
+        {
             info!(
                 "Stratum: accepted solution #{} with nonce={:08x}",
                 seq_num,
@@ -244,6 +308,7 @@ impl StratumEventHandler {
                 .accepted
                 .account_solution(&solution.job_target(), now)
                 .await;
+            self.journal_share(&solution, submitted, journal::ShareResult::Accepted);
             if success_msg.last_seq_num == seq_num {
                 // all accepted solutions have been found
                 return;
@@ -257,18 +322,27 @@ impl StratumEventHandler {
 
     async fn process_rejected_shares(&self, error_msg: &SubmitSharesError) {
         let now = std::time::Instant::now();
-        while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
+        while let Some((solution, seq_num, submitted)) =
+            self.client.solutions.lock().await.pop_front()
+        {
             if error_msg.seq_num == seq_num {
                 info!(
                     "Stratum: rejected solution #{} with nonce={:08x}!",
                     seq_num,
                     solution.nonce()
                 );
-                self.client
-                    .client_stats
-                    .rejected
-                    .account_solution(&solution.job_target(), now)
-                    .await;
+                // Rejected solution of invalidated job is most likely stale
+                let meter = if solution.has_valid_job() {
+                    &self.client.client_stats.rejected
+                } else {
+                    &self.client.client_stats.stale
+                };
+                meter.account_solution(&solution.job_target(), now).await;
+                self.journal_share(
+                    &solution,
+                    submitted,
+                    journal::ShareResult::Rejected(error_msg.code.to_string()),
+                );
                 // the rejected solution has been found
                 return;
             } else {
@@ -284,6 +358,7 @@ impl StratumEventHandler {
                     .accepted
                     .account_solution(&solution.job_target(), now)
                     .await;
+                self.journal_share(&solution, submitted, journal::ShareResult::Accepted);
                 warn!(
                     "Stratum: the solution #{} precedes rejected solution #{}!",
                     seq_num, error_msg.seq_num
@@ -312,7 +387,11 @@ impl Handler for StratumEventHandler {
     //      - start mining the job it references (by job id)
     //      - flush all other jobs
 
-    async fn visit_new_mining_job(&mut self, _header: &Header, job_msg: &NewMiningJob) {
+    async fn visit_new_extended_mining_job(
+        &mut self,
+        _header: &Header,
+        job_msg: &NewExtendedMiningJob,
+    ) {
         // all jobs since last `prevmsg` have to be stored in job table
         self.all_jobs.insert(job_msg.job_id, job_msg.clone());
         // TODO: close connection when maximal capacity of `all_jobs` has been reached
@@ -331,6 +410,8 @@ impl Handler for StratumEventHandler {
 
     async fn visit_set_new_prev_hash(&mut self, _header: &Header, prevhash_msg: &SetNewPrevHash) {
         self.current_prevhash_msg.replace(prevhash_msg.clone());
+        // All jobs received so far are stale now
+        self.client.job_generation.fetch_add(1, Ordering::Relaxed);
 
         // find the future job with ID referenced in prevhash_msg
         let (_, mut future_job_msg) = self
@@ -354,6 +435,16 @@ impl Handler for StratumEventHandler {
         self.update_target(target_msg.max_target);
     }
 
+    async fn visit_set_extranonce_prefix(
+        &mut self,
+        _header: &Header,
+        prefix_msg: &SetExtranoncePrefix,
+    ) {
+        info!("Stratum: changing extranonce prefix");
+        // Only jobs received from now on use the new prefix
+        self.current_extranonce.prefix = prefix_msg.extranonce_prefix.to_vec();
+    }
+
     async fn visit_submit_shares_success(
         &mut self,
         _header: &Header,
@@ -362,6 +453,10 @@ impl Handler for StratumEventHandler {
         self.process_accepted_shares(success_msg).await;
     }
 
+    async fn visit_reconnect(&mut self, _header: &Header, reconnect_msg: &Reconnect) {
+        self.client.redirect(reconnect_msg);
+    }
+
     async fn visit_submit_shares_error(&mut self, _header: &Header, error_msg: &SubmitSharesError) {
         self.process_rejected_shares(error_msg).await;
     }
@@ -423,20 +518,24 @@ where
         let seq_num = self.seq_num;
         self.seq_num = self.seq_num.wrapping_add(1);
 
-        let share_msg = SubmitSharesStandard {
+        let share_msg = SubmitSharesExtended {
             channel_id: job.channel_id,
             seq_num,
             job_id: job.id,
             nonce: solution.nonce(),
             ntime: solution.time(),
             version: solution.version(),
+            extranonce: Bytes0_32::from_vec(
+                job.coinbase
+                    .extranonce2_bytes(solution.extranonce2().unwrap_or(0)),
+            ),
         };
         // store solution with sequence number for future server acknowledge
         self.client
             .solutions
             .lock()
             .await
-            .push_back((solution, seq_num));
+            .push_back((solution, seq_num, time::Instant::now()));
         // send solutions back to the stratum server
         StratumClient::send_msg(&mut self.connection_tx, share_msg)
             .await
@@ -449,6 +548,7 @@ where
 struct StratumConnectionHandler {
     client: Arc<StratumClient>,
     init_target: ii_bitcoin::Target,
+    init_extranonce: Extranonce,
     status: Option<error::Result<()>>,
 }
 
@@ -457,6 +557,7 @@ impl StratumConnectionHandler {
         Self {
             client,
             init_target: Default::default(),
+            init_extranonce: Default::default(),
             status: None,
         }
     }
@@ -470,13 +571,14 @@ impl StratumConnectionHandler {
         R: FrameStream,
         S: FrameSink,
     {
+        let connection_details = self.client.connection_details();
         let setup_msg = SetupConnection {
             protocol: 0,
             max_version: 2,
             min_version: 2,
             flags: 0,
-            endpoint_host: Str0_255::from_string(self.client.connection_details.host.clone()),
-            endpoint_port: self.client.connection_details.port,
+            endpoint_host: Str0_255::from_string(connection_details.host.clone()),
+            endpoint_port: connection_details.port,
             // TODO: Fill it with correct information
             device: DeviceInfo {
                 vendor: "Braiins"
@@ -518,18 +620,19 @@ impl StratumConnectionHandler {
         R: FrameStream,
         S: FrameSink,
     {
-        let channel_msg = OpenStandardMiningChannel {
+        let channel_msg = OpenExtendedMiningChannel {
             req_id: 10,
             user: self
                 .client
-                .connection_details
+                .connection_details()
                 .user
-                .clone()
                 .try_into()
-                .expect("BUG: cannot convert 'OpenStandardMiningChannel::user'"),
+                .expect("BUG: cannot convert 'OpenExtendedMiningChannel::user'"),
             nominal_hashrate: 1e9,
             // Maximum bitcoin target is 0xffff << 208 (= difficulty 1 share)
             max_target: ii_bitcoin::Target::default().into(),
+            // Any extranonce size is acceptable, the version is rolled anyway
+            min_extranonce_size: 0,
         };
 
         StratumClient::send_msg(connection_tx, channel_msg)
@@ -552,7 +655,7 @@ impl StratumConnectionHandler {
     async fn connect(self) -> error::Result<v1::Framed> {
         let socket_addr = self
             .client
-            .connection_details
+            .connection_details()
             .get_host_and_port()
             .to_socket_addrs()
             .context("Invalid server address")?
@@ -568,12 +671,13 @@ impl StratumConnectionHandler {
         Ok(connection.into_inner())
     }
 
-    /// Starts mining session and provides the initial target negotiated by the upstream endpoint
+    /// Starts mining session and provides the initial target and extranonce negotiated by the
+    /// upstream endpoint
     async fn init_mining_session<R, S>(
         mut self,
         connection_rx: &mut R,
         connection_tx: &mut S,
-    ) -> error::Result<ii_bitcoin::Target>
+    ) -> error::Result<(ii_bitcoin::Target, Extranonce)>
     where
         R: FrameStream,
         S: FrameSink,
@@ -585,7 +689,7 @@ impl StratumConnectionHandler {
             .await
             .context("Cannot open stratum channel")?;
 
-        Ok(self.init_target)
+        Ok((self.init_target, self.init_extranonce))
     }
 }
 
@@ -608,19 +712,23 @@ impl Handler for StratumConnectionHandler {
             Err(format!("Setup connection error: {}", error_msg.code.to_string()).into()).into();
     }
 
-    async fn visit_open_standard_mining_channel_success(
+    async fn visit_open_extended_mining_channel_success(
         &mut self,
         _header: &Header,
-        success_msg: &OpenStandardMiningChannelSuccess,
+        success_msg: &OpenExtendedMiningChannelSuccess,
     ) {
         self.init_target = success_msg.target.into();
+        self.init_extranonce = Extranonce {
+            prefix: success_msg.extranonce_prefix.to_vec(),
+            size: success_msg.extranonce_size as usize,
+        };
         self.status = Ok(()).into();
     }
 
-    async fn visit_open_standard_mining_channel_error(
+    async fn visit_open_extended_mining_channel_error(
         &mut self,
         _header: &Header,
-        error_msg: &OpenStandardMiningChannelError,
+        error_msg: &OpenExtendedMiningChannelError,
     ) {
         self.status =
             Err(format!("Open channel error: {}", error_msg.code.to_string()).into()).into();
@@ -629,7 +737,7 @@ impl Handler for StratumConnectionHandler {
 
 #[derive(Debug, ClientNode)]
 pub struct StratumClient {
-    connection_details: ConnectionDetails,
+    connection_details: StdMutex<ConnectionDetails>,
     #[member_status]
     status: sync::StatusMonitor,
     #[member_client_stats]
@@ -642,6 +750,11 @@ pub struct StratumClient {
     solutions: SolutionQueue,
     job_sender: Mutex<job::Sender>,
     solution_receiver: Mutex<job::SolutionReceiver>,
+    solution_queue_length: job::SolutionQueueLength,
+    /// Upstream has requested reconnection to a different endpoint (see `client.reconnect`)
+    reconnect_requested: AtomicBool,
+    /// Incremented each time all received jobs become stale
+    job_generation: AtomicUsize,
 }
 
 impl StratumClient {
@@ -652,7 +765,7 @@ impl StratumClient {
     pub fn new(connection_details: ConnectionDetails, solver: job::Solver) -> Self {
         let (stop_sender, stop_receiver) = mpsc::channel(1);
         Self {
-            connection_details,
+            connection_details: StdMutex::new(connection_details),
             status: Default::default(),
             client_stats: Default::default(),
             stop_sender: stop_sender,
@@ -660,8 +773,54 @@ impl StratumClient {
             last_job: Mutex::new(None),
             solutions: Mutex::new(VecDeque::new()),
             job_sender: Mutex::new(solver.job_sender),
+            solution_queue_length: solver.solution_receiver.queue_length(),
             solution_receiver: Mutex::new(solver.solution_receiver),
+            reconnect_requested: AtomicBool::new(false),
+            job_generation: AtomicUsize::new(0),
+        }
+    }
+
+    fn connection_details(&self) -> ConnectionDetails {
+        self.connection_details
+            .lock()
+            .expect("BUG: cannot lock connection details")
+            .clone()
+    }
+
+    /// Applies new host and port requested by upstream and schedules reconnection. The client
+    /// (including its statistics) is preserved, only the connection is reestablished.
+    /// Redirection to a different host is ignored unless it is explicitly allowed.
+    fn redirect(&self, reconnect_msg: &Reconnect) {
+        let mut connection_details = self
+            .connection_details
+            .lock()
+            .expect("BUG: cannot lock connection details");
+        let old_host_and_port = connection_details.get_host_and_port();
+
+        let new_host = reconnect_msg.new_host.to_string();
+        if !new_host.is_empty()
+            && new_host != connection_details.host
+            && !connection_details.allow_cross_host_reconnect
+        {
+            warn!(
+                "Stratum: ignoring upstream request to reconnect from {} to different host {}",
+                old_host_and_port, new_host
+            );
+            return;
+        }
+        // Empty host or zero port means that the current value is to be kept
+        if !new_host.is_empty() {
+            connection_details.host = new_host;
+        }
+        if reconnect_msg.new_port != 0 {
+            connection_details.port = reconnect_msg.new_port;
         }
+        info!(
+            "Stratum: upstream requested reconnect from {} to {}",
+            old_host_and_port,
+            connection_details.get_host_and_port()
+        );
+        self.reconnect_requested.store(true, Ordering::Relaxed);
     }
 
     async fn update_last_job(&self, job: Arc<StratumJob>) {
@@ -693,8 +852,9 @@ impl StratumClient {
         S: FrameSink,
     {
         let mut solution_receiver = self.solution_receiver.lock().await;
+        solution_receiver.set_stale_policy(self.connection_details().stale_policy);
 
-        while !self.status.is_shutting_down() {
+        while !self.status.is_shutting_down() && !self.reconnect_requested.load(Ordering::Relaxed) {
             select! {
                 frame = connection_rx.next().timeout(Self::EVENT_TIMEOUT).fuse() => {
                     match frame {
@@ -732,8 +892,9 @@ impl StratumClient {
             .timeout(Self::CONNECTION_TIMEOUT)
             .await;
         match mining_session_result {
-            Ok(Ok(init_target)) => {
-                let mut event_handler = StratumEventHandler::new(self.clone(), init_target);
+            Ok(Ok((init_target, init_extranonce))) => {
+                let mut event_handler =
+                    StratumEventHandler::new(self.clone(), init_target, init_extranonce);
                 let solution_handler = StratumSolutionHandler::new(self.clone(), connection_tx);
                 if let Err(_) = self
                     .main_loop(connection_rx, &mut event_handler, solution_handler)
@@ -755,7 +916,10 @@ impl StratumClient {
             Ok(Ok(v1_framed_connection)) => {
                 if self.status.initiate_running() {
                     let options = V2ToV1TranslationOptions {
-                        try_enable_xnsub: self.connection_details.try_enable_xnsub(),
+                        try_enable_xnsub: self.connection_details().try_enable_xnsub(),
+                        // The embedded translation is the only downstream of the V1 upstream,
+                        // the new endpoint has to be passed to this client
+                        forward_reconnect_endpoint: true,
                     };
                     let (translation_handler, v2_translation_rx, v2_translation_tx) =
                         TranslationHandler::new(v1_framed_connection, options);
@@ -773,9 +937,8 @@ impl StratumClient {
     }
 
     async fn main_task(self: Arc<Self>) {
-        // TODO: Count as a discarded solution?
         // Flush all obsolete solutions from previous run
-        self.solution_receiver.lock().await.flush();
+        discard_solutions(&self.solution_receiver, &self.solutions).await;
 
         loop {
             let mut stop_receiver = self.stop_receiver.lock().await;
@@ -783,14 +946,20 @@ impl StratumClient {
                 _ = self.clone().run().fuse() => {}
                 _ = stop_receiver.next() => {}
             }
+            let reconnect = self.reconnect_requested.swap(false, Ordering::Relaxed);
 
             // Invalidate current job to stop working on it
+            self.job_generation.fetch_add(1, Ordering::Relaxed);
             self.job_sender.lock().await.invalidate();
             // Flush all unprocessed solutions to empty buffer
-            // TODO: Count as a discarded solution?
-            self.solution_receiver.lock().await.flush();
-            self.solutions.lock().await.clear();
+            discard_solutions(&self.solution_receiver, &self.solutions).await;
 
+            if reconnect && self.status.status() == sync::Status::Running {
+                // The connection has been torn down on upstream request, connect to the new
+                // endpoint right away. The wait time of `client.reconnect` has already elapsed
+                // as the embedded translation holds the request back until then.
+                continue;
+            }
             if self.status.can_stop() {
                 // NOTE: it is not safe to add here any code!
                 // The reason is that at this point the main task can be executed in parallel again
@@ -912,6 +1081,14 @@ impl node::Client for StratumClient {
         tokio::spawn(self.clone().main_task());
     }
 
+    fn change_connection_details(&self, descriptor: &bosminer_config::ClientDescriptor) {
+        *self
+            .connection_details
+            .lock()
+            .expect("BUG: cannot lock connection details") =
+            ConnectionDetails::from_descriptor(descriptor);
+    }
+
     fn stop(&self) {
         if let Err(e) = self.stop_sender.clone().try_send(()) {
             assert!(
@@ -922,6 +1099,11 @@ impl node::Client for StratumClient {
         }
     }
 
+    async fn pending_solutions(&self) -> usize {
+        // Solutions which have not been taken from the queue yet are pending too
+        self.solutions.lock().await.len() + self.solution_queue_length.get()
+    }
+
     async fn get_last_job(&self) -> Option<Arc<dyn job::Bitcoin>> {
         self.last_job
             .lock()
@@ -933,12 +1115,254 @@ impl node::Client for StratumClient {
 
 impl fmt::Display for StratumClient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let connection_details = self.connection_details();
         write!(
             f,
             "{}://{}@{}",
             ClientProtocol::SCHEME_STRATUM_V1,
-            self.connection_details.host,
-            self.connection_details.user
+            connection_details.host,
+            connection_details.user
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hal;
+
+    use ii_async_compat::tokio;
+
+    use std::convert::TryFrom;
+
+    const MIDSTATE_COUNT: usize = 4;
+
+    #[derive(Debug)]
+    struct TestSolution {
+        nonce: u32,
+        target: ii_bitcoin::Target,
+    }
+
+    impl hal::BackendSolution for TestSolution {
+        fn nonce(&self) -> u32 {
+            self.nonce
+        }
+
+        fn midstate_idx(&self) -> usize {
+            0
+        }
+
+        fn solution_idx(&self) -> usize {
+            0
+        }
+
+        fn target(&self) -> &ii_bitcoin::Target {
+            &self.target
+        }
+    }
+
+    /// Passes `message` to `event_handler` as if it has been received from the remote server
+    async fn simulate_incoming_message<M>(event_handler: &mut StratumEventHandler, message: M)
+    where
+        M: TryInto<<Framing as ii_wire::Framing>::Tx, Error = <Framing as ii_wire::Framing>::Error>,
+    {
+        let frame = message.try_into().expect("BUG: cannot serialize message");
+        build_message_from_frame(frame)
+            .expect("BUG: cannot deserialize message")
+            .accept(event_handler)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_extranonce_rolled_share() {
+        const CHANNEL_ID: u32 = 1;
+        const JOB_ID: u32 = 1;
+
+        // Any hash meets the maximal target
+        let max_target =
+            ii_bitcoin::Target::from_hex(&"f".repeat(64)).expect("BUG: cannot parse target");
+        // Keep the work engine of the most recent job the same way as the mining backend does
+        let engine: Arc<StdMutex<Option<work::DynEngine>>> = Default::default();
+        let engine_sender = Arc::new(work::EngineSender::new(None));
+        let engine_slot = engine.clone();
+        engine_sender.replace_engine_generator(Box::new(move |job| {
+            let engine = work::engine::create(job, MIDSTATE_COUNT);
+            engine_slot
+                .lock()
+                .expect("BUG: cannot lock engine")
+                .replace(engine.clone());
+            engine
+        }));
+        let (solution_sender, solution_receiver) = job::solution_channel();
+        let client = Arc::new(StratumClient::new(
+            ConnectionDetails {
+                user: "user".to_string(),
+                host: "127.0.0.1".to_string(),
+                port: 3333,
+                stale_policy: Default::default(),
+                fragment: None,
+                allow_cross_host_reconnect: false,
+            },
+            job::Solver::new(engine_sender, solution_receiver),
+        ));
+        let mut event_handler = StratumEventHandler::new(
+            client.clone(),
+            max_target,
+            Extranonce {
+                prefix: vec![0x6c, 0x6f, 0x01, 0x00],
+                size: 4,
+            },
+        );
+
+        simulate_incoming_message(
+            &mut event_handler,
+            NewExtendedMiningJob {
+                channel_id: CHANNEL_ID,
+                job_id: JOB_ID,
+                future_job: true,
+                version: 0x20000000,
+                version_rolling_allowed: true,
+                merkle_path: Seq0_255::from_vec(vec![Uint256Bytes([0x11; 32])]),
+                coinbase_tx_prefix: Bytes0_64k::from_slice(&[0x01, 0x02, 0x03]),
+                coinbase_tx_suffix: Bytes0_64k::from_slice(&[0x04, 0x05]),
+            },
+        )
+        .await;
+        simulate_incoming_message(
+            &mut event_handler,
+            SetNewPrevHash {
+                channel_id: CHANNEL_ID,
+                job_id: JOB_ID,
+                prev_hash: Uint256Bytes([0x22; 32]),
+                min_ntime: 0x5e000000,
+                nbits: 0x1d00ffff,
+            },
+        )
+        .await;
+        let job = client
+            .last_job
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|job| job.upgrade())
+            .expect("BUG: no job");
+        let coinbase = job.coinbase.clone();
+        assert_eq!(coinbase.extranonce1, vec![0x6c, 0x6f, 0x01, 0x00]);
+        assert_eq!(job.merkle_root, coinbase.merkle_root(0));
+
+        // Roll the work until the whole version space of the first extranonce2 is exhausted
+        let engine = engine
+            .lock()
+            .expect("BUG: cannot lock engine")
+            .clone()
+            .expect("BUG: no work engine");
+        let solution = loop {
+            let solution = work::Solution::new(
+                engine.next_work().unwrap(),
+                TestSolution {
+                    nonce: 0x12345678,
+                    target: max_target,
+                },
+                None,
+            );
+            if solution.extranonce2() == Some(1) {
+                break solution;
+            }
+        };
+        assert_eq!(
+            solution.get_block_header().merkle_root,
+            coinbase.merkle_root(1).into_inner()
+        );
+        solution_sender
+            .send(solution)
+            .expect("BUG: cannot send solution");
+
+        // The share is submitted with the rolled extranonce2
+        let (share_sender, mut share_receiver) = mpsc::unbounded();
+        let mut solution_handler = StratumSolutionHandler::new(client.clone(), share_sender);
+        let solution = client
+            .solution_receiver
+            .lock()
+            .await
+            .receive()
+            .await
+            .expect("BUG: missing solution");
+        solution_handler
+            .process_solution(solution)
+            .await
+            .expect("BUG: cannot submit solution");
+        let share_msg = SubmitSharesExtended::try_from(
+            share_receiver
+                .next()
+                .await
+                .expect("BUG: missing submitted share"),
+        )
+        .expect("BUG: cannot deserialize share");
+        assert_eq!(share_msg.job_id, JOB_ID);
+        assert_eq!(share_msg.nonce, 0x12345678);
+        assert_eq!(share_msg.extranonce.to_vec(), coinbase.extranonce2_bytes(1));
+
+        simulate_incoming_message(
+            &mut event_handler,
+            SubmitSharesSuccess {
+                channel_id: CHANNEL_ID,
+                last_seq_num: share_msg.seq_num,
+                new_submits_accepted_count: 1,
+                new_shares_sum: 1,
+            },
+        )
+        .await;
+        assert_eq!(
+            client.client_stats.accepted.take_snapshot().await.solutions,
+            1
+        );
+        assert_eq!(client.pending_solutions().await, 0);
+    }
+
+    #[test]
+    fn test_redirect_cross_host() {
+        let build_client = |allow_cross_host_reconnect| {
+            let (_, solution_receiver) = job::solution_channel();
+            StratumClient::new(
+                ConnectionDetails {
+                    user: "user".to_string(),
+                    host: "127.0.0.1".to_string(),
+                    port: 3333,
+                    stale_policy: Default::default(),
+                    fragment: None,
+                    allow_cross_host_reconnect,
+                },
+                job::Solver::new(Arc::new(work::EngineSender::new(None)), solution_receiver),
+            )
+        };
+        let reconnect = |new_host: &str, new_port| Reconnect {
+            new_host: Str0_255::from_string(new_host.to_string()),
+            new_port,
+        };
+
+        // Different host is rejected by default and the connection is kept
+        let client = build_client(false);
+        client.redirect(&reconnect("10.0.0.1", 3334));
+        assert_eq!(
+            client.connection_details().get_host_and_port(),
+            "127.0.0.1:3333"
+        );
+        assert!(!client.reconnect_requested.load(Ordering::Relaxed));
+
+        // Port of the current host can be changed
+        client.redirect(&reconnect("", 3334));
+        assert_eq!(
+            client.connection_details().get_host_and_port(),
+            "127.0.0.1:3334"
+        );
+        assert!(client.reconnect_requested.load(Ordering::Relaxed));
+
+        let client = build_client(true);
+        client.redirect(&reconnect("10.0.0.1", 3334));
+        assert_eq!(
+            client.connection_details().get_host_and_port(),
+            "10.0.0.1:3334"
+        );
+        assert!(client.reconnect_requested.load(Ordering::Relaxed));
+    }
+}
@@ -23,10 +23,14 @@
 //! This module provides top level functionality to build the BOSminer core and use it to connect
 //! the frontend and hardware specific backend.
 
+use ii_logging::macros::*;
+
 use crate::api;
 use crate::backend;
+use crate::client;
 use crate::hal::{self, BackendConfig as _};
 use crate::hub;
+use crate::recorder;
 use crate::stats;
 
 use ii_async_compat::tokio;
@@ -37,6 +41,21 @@ pub async fn main<T: hal::Backend>(backend_config: T::Config, signature: String)
     let backend_registry = Arc::new(backend::Registry::new());
     // Get frontend specific settings from backend config
     let backend_info = backend_config.info();
+    let metrics_listen_addr = backend_config.metrics_listen_addr();
+    if let Some(journal_config) = backend_config.share_journal() {
+        match client::journal::Journal::from_config(&journal_config) {
+            Ok(journal) => {
+                client::journal::install(journal).expect("BUG: share journal already installed")
+            }
+            Err(e) => error!("Cannot open share journal '{}': {}", journal_config.path, e),
+        }
+    }
+    if let Some(recording_config) = backend_config.recording() {
+        match recorder::Recorder::from_config(&recording_config) {
+            Ok(recorder) => recorder::install(recorder).expect("BUG: recorder already installed"),
+            Err(e) => error!("Cannot create recording '{}': {}", recording_config.path, e),
+        }
+    }
 
     // Initialize hub core which manages all resources
     let core = Arc::new(hub::Core::new(
@@ -59,5 +78,5 @@ pub async fn main<T: hal::Backend>(backend_config: T::Config, signature: String)
     ));
 
     // the bosminer is controlled with API which also controls when the miner will end
-    api::run(core, frontend_config, signature).await;
+    api::run(core, frontend_config, signature, metrics_listen_addr).await;
 }
@@ -32,4 +32,6 @@ pub enum ErrorKind {
     OnlyFixedShareRatio,
     #[fail(display = "total fixed share ratio is greater than or equal to 1.0")]
     FixedShareRatioOverflow,
+    #[fail(display = "no client group has been specified")]
+    NoGroup,
 }
@@ -30,6 +30,7 @@ use ii_stratum::v2::types::DeviceInfo;
 
 use std::convert::TryInto;
 use std::fmt::Debug;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -110,6 +111,18 @@ pub trait BackendConfig: Debug + Send + Sync {
     fn info(&self) -> Option<BackendInfo> {
         None
     }
+    /// Optional address of HTTP server exporting statistics in Prometheus format
+    fn metrics_listen_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+    /// Optional journal recording all shares submitted to remote servers
+    fn share_journal(&self) -> Option<bosminer_config::ShareJournalConfig> {
+        None
+    }
+    /// Optional recording of all jobs, work and solutions for later replay
+    fn recording(&self) -> Option<bosminer_config::RecordingConfig> {
+        None
+    }
 }
 
 pub struct FrontendConfig {
@@ -72,7 +72,7 @@ impl SolutionRouter {
             // NOTE: all solutions targeting to removed clients are discarded
             if let Some(solution_sender) = self.job_executor.get_solution_sender(&solution).await {
                 solution_sender
-                    .unbounded_send(solution)
+                    .send(solution)
                     .expect("solution queue send failed");
             } else {
                 warn!("Hub: solution has been discarded because client does not exist anymore");
@@ -106,7 +106,7 @@ impl Core {
         let (engine_sender, engine_receiver) = work::engine_channel(EventHandler);
         let (solution_sender, solution_receiver) = mpsc::unbounded();
 
-        let client_manager = client::Manager::new(midstate_count);
+        let client_manager = client::Manager::new(midstate_count, Arc::downgrade(backend_registry));
         let job_executor = Arc::new(client::JobExecutor::new(
             frontend.clone(),
             engine_sender,
@@ -197,6 +197,60 @@ impl Core {
         }
     }
 
+    /// Find registered work solver addressed by node `path`. The deepest node in the path which
+    /// is a registered work solver is chosen so that a solution path can be used directly.
+    pub async fn find_work_solver(&self, path: &node::Path) -> Option<Arc<dyn node::WorkSolver>> {
+        let work_solvers = self.get_work_solvers().await;
+        path.iter().rev().find_map(|node| {
+            let node_ptr = node.clone().get_unique_ptr();
+            work_solvers
+                .iter()
+                .find(|work_solver| Arc::ptr_eq(&work_solver.clone().get_unique_ptr(), &node_ptr))
+                .cloned()
+        })
+    }
+
+    async fn get_addressed_work_solver(
+        &self,
+        path: &node::Path,
+    ) -> error::Result<Arc<dyn node::WorkSolver>> {
+        self.find_work_solver(path).await.ok_or_else(|| {
+            let path: Vec<_> = path.iter().map(|node| node.to_string()).collect();
+            error::ErrorKind::General(format!("no work solver at path '{}'", path.join("/"))).into()
+        })
+    }
+
+    pub async fn get_work_solver_state(
+        &self,
+        path: &node::Path,
+    ) -> error::Result<node::WorkSolverState> {
+        Ok(self
+            .get_addressed_work_solver(path)
+            .await?
+            .get_state()
+            .await)
+    }
+
+    /// Take work solver at `path` online again after it has been disabled
+    pub async fn enable_work_solver(&self, path: &node::Path) -> error::Result<()> {
+        let work_solver = self.get_addressed_work_solver(path).await?;
+        info!("Enabling work solver '{}'", work_solver);
+        work_solver.enable().await
+    }
+
+    /// Take work solver at `path` offline without affecting the rest of the miner
+    pub async fn disable_work_solver(&self, path: &node::Path) -> error::Result<()> {
+        let work_solver = self.get_addressed_work_solver(path).await?;
+        info!("Disabling work solver '{}'", work_solver);
+        work_solver.disable().await
+    }
+
+    pub async fn restart_work_solver(&self, path: &node::Path) -> error::Result<()> {
+        let work_solver = self.get_addressed_work_solver(path).await?;
+        info!("Restarting work solver '{}'", work_solver);
+        work_solver.restart().await
+    }
+
     pub fn get_client_manager(&self) -> &client::Manager {
         &self.client_manager
     }
@@ -229,11 +283,13 @@ pub mod test {
         let (engine_sender, engine_receiver) = work::engine_channel(EventHandler);
         let (solution_sender, solution_receiver) = mpsc::unbounded();
         let frontend = Arc::new(crate::Frontend::new());
-        let _ = engine_sender.replace_engine_generator(Box::new(move |job| {
-            Arc::new(work::engine::VersionRolling::new(job, 1))
-        }));
+        let _ = engine_sender
+            .replace_engine_generator(Box::new(move |job| work::engine::create(job, 1)));
         (
-            job::Solver::new(Arc::new(engine_sender), solution_receiver),
+            job::Solver::new(
+                Arc::new(engine_sender),
+                job::SolutionReceiver::new(solution_receiver),
+            ),
             work::SolverBuilder::new(
                 frontend,
                 Arc::new(backend::Registry::new()),
@@ -290,4 +346,56 @@ pub mod test {
         drop(job_solver);
         assert!(work_generator.generate().await.is_some());
     }
+
+    #[tokio::test]
+    async fn test_work_solver_control() {
+        use crate::backend::HierarchyBuilder as _;
+
+        let backend_registry = Arc::new(backend::Registry::new());
+        let core = Core::new(1, &backend_registry, None);
+
+        let work_solver = test_utils::create_test_work_solver();
+        backend_registry.add_work_solver(work_solver.clone()).await;
+
+        // work solver is addressed by the deepest registered node in the path
+        let path: node::Path = vec![
+            Arc::new(test_utils::TestNode::new()) as node::DynInfo,
+            work_solver.clone() as node::DynInfo,
+            Arc::new(test_utils::TestNode::new()) as node::DynInfo,
+        ];
+        let found = core
+            .find_work_solver(&path)
+            .await
+            .expect("BUG: work solver not found");
+        let expected: Arc<dyn node::WorkSolver> = work_solver.clone();
+        assert!(Arc::ptr_eq(
+            &found.get_unique_ptr(),
+            &expected.get_unique_ptr()
+        ));
+
+        assert_eq!(
+            core.get_work_solver_state(&path).await.unwrap(),
+            node::WorkSolverState::Running
+        );
+        core.disable_work_solver(&path).await.unwrap();
+        assert_eq!(
+            core.get_work_solver_state(&path).await.unwrap(),
+            node::WorkSolverState::Disabled
+        );
+        core.enable_work_solver(&path).await.unwrap();
+        assert_eq!(
+            core.get_work_solver_state(&path).await.unwrap(),
+            node::WorkSolverState::Running
+        );
+        core.restart_work_solver(&path).await.unwrap();
+        assert_eq!(
+            core.get_work_solver_state(&path).await.unwrap(),
+            node::WorkSolverState::Running
+        );
+
+        // unknown node cannot be controlled
+        let path: node::Path = vec![Arc::new(test_utils::TestNode::new()) as node::DynInfo];
+        assert!(core.find_work_solver(&path).await.is_none());
+        assert!(core.disable_work_solver(&path).await.is_err());
+    }
 }
@@ -26,17 +26,23 @@ use ii_bitcoin::{HashTrait as _, MeetsTarget};
 
 use crate::job;
 use crate::node;
+use crate::recorder;
 use crate::stats::{self, DiffTargetType};
 use crate::work;
 
+use bosminer_config::StalePolicy;
+
 use futures::channel::mpsc;
 use futures::stream::StreamExt;
 use ii_async_compat::futures;
 
+use std::collections::{HashSet, VecDeque};
 use std::convert::TryInto;
 use std::fmt::Debug;
 use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Weak};
+use std::time;
 
 use downcast_rs::{impl_downcast, Downcast};
 
@@ -67,6 +73,10 @@ pub trait Bitcoin: Debug + Downcast + Send + Sync {
     fn target(&self) -> ii_bitcoin::Target;
     /// Checks if job is still valid for mining
     fn is_valid(&self) -> bool;
+    /// Parts of coinbase transaction for jobs which allow local rolling of extranonce2
+    fn coinbase(&self) -> Option<&Coinbase> {
+        None
+    }
 
     /// Extract least-significant word of merkle root that goes to chunk2 of SHA256
     /// The word is interpreted as a little endian number.
@@ -82,6 +92,57 @@ pub trait Bitcoin: Debug + Downcast + Send + Sync {
 }
 impl_downcast!(Bitcoin);
 
+/// Coinbase transaction split around extranonce as specified by Stratum V1 `mining.notify`
+/// together with merkle branch required for computation of merkle root for any extranonce2
+#[derive(Debug, Clone)]
+pub struct Coinbase {
+    /// Initial part of coinbase transaction preceding extranonce1
+    pub coinbase1: Vec<u8>,
+    /// Extranonce assigned by the remote server
+    pub extranonce1: Vec<u8>,
+    /// Number of extranonce2 bytes which can be freely rolled
+    pub extranonce2_size: usize,
+    /// Final part of coinbase transaction following extranonce2
+    pub coinbase2: Vec<u8>,
+    /// Hashes of merkle tree on the path from coinbase transaction to merkle root
+    pub merkle_branch: Vec<ii_bitcoin::DHash>,
+}
+
+impl Coinbase {
+    /// Number of distinct extranonce2 values which fit into `extranonce2_size`
+    pub fn extranonce2_count(&self) -> u64 {
+        if self.extranonce2_size >= mem::size_of::<u64>() {
+            std::u64::MAX
+        } else {
+            1 << (self.extranonce2_size * 8)
+        }
+    }
+
+    /// Serialize extranonce2 as a little endian number with `extranonce2_size` bytes
+    pub fn extranonce2_bytes(&self, extranonce2: u64) -> Vec<u8> {
+        let mut bytes = extranonce2.to_le_bytes().to_vec();
+        bytes.resize(self.extranonce2_size, 0);
+        bytes
+    }
+
+    /// Compute merkle root of a block with coinbase transaction containing given extranonce2
+    pub fn merkle_root(&self, extranonce2: u64) -> ii_bitcoin::DHash {
+        let coinbase = [
+            &self.coinbase1[..],
+            &self.extranonce1[..],
+            &self.extranonce2_bytes(extranonce2)[..],
+            &self.coinbase2[..],
+        ]
+        .concat();
+
+        self.merkle_branch
+            .iter()
+            .fold(ii_bitcoin::DHash::hash(&coinbase), |merkle_root, branch| {
+                ii_bitcoin::DHash::hash(&[&merkle_root[..], &branch[..]].concat())
+            })
+    }
+}
+
 /// Compound object for job submission and solution reception intended to be passed to
 /// protocol handler
 pub struct Solver {
@@ -92,11 +153,11 @@ pub struct Solver {
 impl Solver {
     pub fn new(
         engine_sender: Arc<work::EngineSender>,
-        solution_receiver: mpsc::UnboundedReceiver<work::Solution>,
+        solution_receiver: SolutionReceiver,
     ) -> Self {
         Self {
             job_sender: Sender::new(engine_sender),
-            solution_receiver: SolutionReceiver::new(solution_receiver),
+            solution_receiver,
         }
     }
 }
@@ -142,6 +203,7 @@ impl Sender {
         // send only jobs with correct data
         if let Some(origin) = origin {
             origin.client_stats().valid_jobs().inc();
+            recorder::record_job(&job);
             info!("--- broadcasting new job ---");
             self.engine_sender.broadcast_job(job);
         } else {
@@ -156,16 +218,134 @@ impl Sender {
     }
 }
 
+/// Number of solutions which have been sent to a client but which haven't been taken from its
+/// queue yet
+#[derive(Debug, Clone, Default)]
+pub struct SolutionQueueLength(Arc<AtomicUsize>);
+
+impl SolutionQueueLength {
+    #[inline]
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Sends `work::Solution` to a client and keeps track of the length of its queue
+#[derive(Debug, Clone)]
+pub struct SolutionSender {
+    solution_channel: mpsc::UnboundedSender<work::Solution>,
+    queue_length: SolutionQueueLength,
+}
+
+impl SolutionSender {
+    pub fn send(&self, solution: work::Solution) -> Result<(), mpsc::TrySendError<work::Solution>> {
+        // The length is increased in advance so that it never underflows in the receiver
+        self.queue_length.0.fetch_add(1, Ordering::Relaxed);
+        self.solution_channel.unbounded_send(solution).map_err(|e| {
+            self.queue_length.0.fetch_sub(1, Ordering::Relaxed);
+            e
+        })
+    }
+}
+
+/// Checks whether a job created in `generation` is still valid. The client increments its
+/// generation each time all received jobs become stale (new prevhash or new block in the network)
+/// and when the mining session ends.
+pub fn is_generation_current(client_generation: &AtomicUsize, generation: usize) -> bool {
+    client_generation.load(Ordering::Relaxed) == generation
+}
+
+/// Create solution queue of a client with tracking of its length
+pub fn solution_channel() -> (SolutionSender, SolutionReceiver) {
+    let (solution_sender, solution_receiver) = mpsc::unbounded();
+    let queue_length = SolutionQueueLength::default();
+    (
+        SolutionSender {
+            solution_channel: solution_sender,
+            queue_length: queue_length.clone(),
+        },
+        SolutionReceiver {
+            queue_length: Some(queue_length),
+            ..SolutionReceiver::new(solution_receiver)
+        },
+    )
+}
+
 /// Receives `work::Solution` via a channel and filters only solutions that meet the client/pool
 /// specified target
 #[derive(Debug)]
 pub struct SolutionReceiver {
     solution_channel: mpsc::UnboundedReceiver<work::Solution>,
+    /// Length of the queue is tracked only when it has been created with `solution_channel`
+    queue_length: Option<SolutionQueueLength>,
+    stale_policy: StalePolicy,
+    /// Hashes of recently submitted solutions used for detection of duplicates
+    recent_hashes: HashSet<ii_bitcoin::DHash>,
+    /// Order in which hashes have been inserted to limit the size of `recent_hashes`
+    recent_order: VecDeque<ii_bitcoin::DHash>,
+}
+
+/// Classification of a solution before it is submitted to the remote server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolutionClass {
+    Valid,
+    /// The job of the solution has been invalidated (e.g. by a new block)
+    Stale,
+    /// The same solution has already been submitted
+    Duplicate,
 }
 
 impl SolutionReceiver {
+    /// Maximal number of recently submitted solutions remembered for duplicate detection
+    const MAX_RECENT_SOLUTIONS: usize = 1024;
+
     pub fn new(solution_channel: mpsc::UnboundedReceiver<work::Solution>) -> Self {
-        Self { solution_channel }
+        Self {
+            solution_channel,
+            queue_length: None,
+            stale_policy: Default::default(),
+            recent_hashes: HashSet::with_capacity(Self::MAX_RECENT_SOLUTIONS),
+            recent_order: VecDeque::with_capacity(Self::MAX_RECENT_SOLUTIONS),
+        }
+    }
+
+    pub fn set_stale_policy(&mut self, stale_policy: StalePolicy) {
+        self.stale_policy = stale_policy;
+    }
+
+    /// Return shared length of the queue which can be read without locking the receiver
+    pub fn queue_length(&self) -> SolutionQueueLength {
+        self.queue_length.clone().unwrap_or_default()
+    }
+
+    /// Account solution which has been taken from the queue
+    #[inline]
+    fn dequeued(&self) {
+        if let Some(queue_length) = self.queue_length.as_ref() {
+            queue_length.0.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn classify(&self, solution: &work::Solution) -> SolutionClass {
+        if self.recent_hashes.contains(solution.hash()) {
+            SolutionClass::Duplicate
+        } else if !solution.has_valid_job() {
+            SolutionClass::Stale
+        } else {
+            SolutionClass::Valid
+        }
+    }
+
+    fn remember(&mut self, solution: &work::Solution) {
+        if self.recent_order.len() >= Self::MAX_RECENT_SOLUTIONS {
+            if let Some(hash) = self.recent_order.pop_front() {
+                self.recent_hashes.remove(&hash);
+            }
+        }
+        let hash = *solution.hash();
+        if self.recent_hashes.insert(hash) {
+            self.recent_order.push_back(hash);
+        }
     }
 
     fn trace_share(solution: &work::Solution, target: &ii_bitcoin::Target) {
@@ -188,6 +368,7 @@ impl SolutionReceiver {
 
     pub async fn receive(&mut self) -> Option<work::Solution> {
         while let Some(solution) = self.solution_channel.next().await {
+            self.dequeued();
             let path = solution.path();
             let time = solution.timestamp();
             let hash = solution.hash();
@@ -212,20 +393,122 @@ impl SolutionReceiver {
                 continue;
             }
 
-            if solution.has_valid_job() {
-                // TODO: Account solution to Discard meter
-                Self::trace_share(&solution, &job_target);
-                return Some(solution);
+            match self.classify(&solution) {
+                SolutionClass::Valid => {}
+                SolutionClass::Stale => {
+                    if self.stale_policy == StalePolicy::Drop {
+                        stats::account_stale_solution(&solution, time).await;
+                        continue;
+                    }
+                    // Let the remote server decide about the solution
+                    info!(
+                        "Submitting stale solution with nonce={:08x}",
+                        solution.nonce()
+                    );
+                }
+                SolutionClass::Duplicate => {
+                    warn!(
+                        "Dropping duplicate solution with nonce={:08x}",
+                        solution.nonce()
+                    );
+                    stats::account_duplicate_solution(&solution, time).await;
+                    continue;
+                }
             }
+            self.remember(&solution);
+            Self::trace_share(&solution, &job_target);
+            return Some(solution);
         }
         None
     }
 
     /// Empty all buffered solutions without blocking. This is to prevent the client from submitting
-    /// already stale solutions
+    /// already stale solutions. Solutions which would otherwise be submitted are accounted as
+    /// discarded and their number is returned.
     /// TODO: We should review this regularly as there may be extensions in the mining protocol that
     /// may allow resume a mining session
-    pub fn flush(&mut self) {
-        while let Ok(Some(_)) = self.solution_channel.try_next() {}
+    pub async fn flush(&mut self) -> usize {
+        let now = time::Instant::now();
+        let mut discarded = 0;
+        while let Ok(Some(solution)) = self.solution_channel.try_next() {
+            self.dequeued();
+            if solution.hash().meets(solution.job_target()) {
+                stats::account_discarded_solution(&solution, now).await;
+                discarded += 1;
+            }
+        }
+        discarded
+    }
+
+    /// Flush all queued solutions together with `submitted` solutions which wait for
+    /// acknowledgement from the remote server and account them as discarded. Returns number of
+    /// discarded solutions.
+    pub async fn discard<I>(&mut self, submitted: I) -> usize
+    where
+        I: IntoIterator<Item = work::Solution>,
+    {
+        let now = time::Instant::now();
+        let mut discarded = self.flush().await;
+        for solution in submitted {
+            stats::account_discarded_solution(&solution, now).await;
+            discarded += 1;
+        }
+        discarded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils;
+
+    #[test]
+    fn test_solution_classification() {
+        let (_solution_sender, solution_receiver) = mpsc::unbounded();
+        let mut solution_receiver = SolutionReceiver::new(solution_receiver);
+
+        let solution: work::Solution = test_utils::TEST_BLOCKS[0].into();
+        let other_solution: work::Solution = test_utils::TEST_BLOCKS[1].into();
+        assert_eq!(solution_receiver.classify(&solution), SolutionClass::Valid);
+
+        solution_receiver.remember(&solution);
+        assert_eq!(
+            solution_receiver.classify(&solution),
+            SolutionClass::Duplicate
+        );
+        assert_eq!(
+            solution_receiver.classify(&other_solution),
+            SolutionClass::Valid
+        );
+    }
+
+    #[test]
+    fn test_coinbase_merkle_root() {
+        let hashes: Vec<_> = (0u8..3).map(|i| ii_bitcoin::DHash::hash(&[i])).collect();
+        let coinbase = Coinbase {
+            coinbase1: vec![0x01, 0x02],
+            extranonce1: vec![0x03],
+            extranonce2_size: 2,
+            coinbase2: vec![0x04],
+            merkle_branch: vec![hashes[0], ii_bitcoin::merkle_root(&hashes[1..])],
+        };
+        assert_eq!(coinbase.extranonce2_count(), 0x10000);
+        assert_eq!(coinbase.extranonce2_bytes(0x1234), vec![0x34, 0x12]);
+
+        let coinbase_txid = ii_bitcoin::DHash::hash(&[0x01, 0x02, 0x03, 0x34, 0x12, 0x04]);
+        assert_eq!(
+            coinbase.merkle_root(0x1234),
+            ii_bitcoin::merkle_root(&[coinbase_txid, hashes[0], hashes[1], hashes[2]])
+        );
+    }
+
+    #[test]
+    fn test_generation_current() {
+        let client_generation = AtomicUsize::new(0);
+        assert!(is_generation_current(&client_generation, 0));
+
+        client_generation.fetch_add(1, Ordering::Relaxed);
+        assert!(!is_generation_current(&client_generation, 0));
+        assert!(is_generation_current(&client_generation, 1));
     }
 }
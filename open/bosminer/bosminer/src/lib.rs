@@ -34,6 +34,7 @@ pub mod hal;
 pub mod hub;
 pub mod job;
 pub mod node;
+pub mod recorder;
 pub mod stats;
 pub mod sync;
 pub mod version;
@@ -20,6 +20,7 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use crate::error;
 use crate::job;
 use crate::stats;
 use crate::sync;
@@ -60,6 +61,13 @@ pub trait Client: Info + ClientStats {
     async fn get_last_job(&self) -> Option<Arc<dyn job::Bitcoin>>;
     /// FIXME: Do not allow dynamic descriptor changes
     fn change_connection_details(&self, _descriptor: &bosminer_config::ClientDescriptor) {}
+    /// Nominal hashrate of the backend has changed (e.g. a hash chain has been started or
+    /// stopped) and the client may announce it to the remote server
+    fn update_nominal_hashrate(&self, _nominal_hashrate: ii_bitcoin::HashesUnit) {}
+    /// Return number of solutions submitted to the remote server which wait for its response
+    async fn pending_solutions(&self) -> usize {
+        0
+    }
 }
 
 pub trait ClientStats: Stats {
@@ -91,6 +99,18 @@ where
     }
 }
 
+/// Operational state of a work solver reported to the operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkSolverState {
+    /// Work solver is solving work
+    Running,
+    /// Work solver has been taken offline on request
+    Disabled,
+    /// Work solver is not solving work although it has not been disabled (e.g. it failed to
+    /// start or it is being started)
+    Stopped,
+}
+
 /// Common interface for nodes with ability to solve generated work and providing common interface
 /// for mining control
 #[async_trait]
@@ -101,6 +121,26 @@ pub trait WorkSolver: Info + WorkSolverStats {
     }
     /// Return nominal/expected hashrate in hashes per second
     async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit>;
+    /// Return current operational state. Work solvers without run-time control are always
+    /// running.
+    async fn get_state(&self) -> WorkSolverState {
+        WorkSolverState::Running
+    }
+    /// Start solving work again after the work solver has been disabled or it has stopped.
+    /// Enabling running work solver has no effect.
+    async fn enable(self: Arc<Self>) -> error::Result<()> {
+        Err(error::ErrorKind::General(format!("work solver '{}' cannot be enabled", self)).into())
+    }
+    /// Stop solving work and keep the work solver offline until it is enabled again.
+    /// Disabling disabled work solver has no effect.
+    async fn disable(self: Arc<Self>) -> error::Result<()> {
+        Err(error::ErrorKind::General(format!("work solver '{}' cannot be disabled", self)).into())
+    }
+    /// Disable the work solver and enable it again
+    async fn restart(self: Arc<Self>) -> error::Result<()> {
+        self.clone().disable().await?;
+        self.enable().await
+    }
 }
 
 pub trait WorkSolverStats: Stats {
@@ -138,6 +178,22 @@ impl<T: ?Sized + WorkSolver> WorkSolver for Arc<T> {
     async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
         self.as_ref().get_nominal_hashrate().await
     }
+
+    async fn get_state(&self) -> WorkSolverState {
+        self.as_ref().get_state().await
+    }
+
+    async fn enable(self: Arc<Self>) -> error::Result<()> {
+        self.as_ref().clone().enable().await
+    }
+
+    async fn disable(self: Arc<Self>) -> error::Result<()> {
+        self.as_ref().clone().disable().await
+    }
+
+    async fn restart(self: Arc<Self>) -> error::Result<()> {
+        self.as_ref().clone().restart().await
+    }
 }
 
 impl<T: ?Sized + WorkSolverStats> WorkSolverStats for Arc<T> {
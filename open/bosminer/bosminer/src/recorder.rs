@@ -0,0 +1,753 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Recorder of jobs received by clients, work assigned to backends, solutions returned by them and
+//! responses of remote servers to submitted solutions. Every event is stored as one compact JSON
+//! line in the order in which it occurred. Problems seen in the field (e.g. pool specific rejects
+//! or wrong ordering of jobs and solutions) can then be reproduced by feeding the recording back
+//! through the client and job layers with `replay::Replay` without any network connection or
+//! mining hardware.
+//! Events are only queued by the miner and the file is written by a separate task. The recording
+//! is stopped when it reaches the configured size.
+
+pub mod replay;
+
+use ii_logging::macros::*;
+
+use crate::client::journal::ShareResult;
+use crate::error;
+use crate::job;
+use crate::work;
+
+use bosminer_config::RecordingConfig;
+
+use ii_bitcoin::FromHex as _;
+
+use futures::FutureExt as _;
+use ii_async_compat::{futures, tokio};
+use tokio::sync::mpsc;
+use tokio::task;
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard, Weak};
+use std::time;
+
+/// Default size of the recording after which no more events are recorded
+pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+/// Maximal number of recent jobs remembered for pairing of work and solutions with their jobs
+const MAX_RECENT_JOBS: usize = 64;
+/// Maximum number of events waiting for the writer task. Events are dropped when the writer
+/// cannot keep up so that mining is never blocked by the recorder.
+const EVENT_QUEUE_SIZE: usize = 4096;
+
+/// Recorder installed for the whole process together with the queue of its writer task
+struct Installed {
+    recorder: Arc<Recorder>,
+    event_sender: mpsc::Sender<QueuedEvent>,
+    /// Number of events dropped since the last write due to full queue
+    dropped_events: AtomicUsize,
+}
+
+/// Recorder installed for the whole process (see `install`)
+static RECORDER: OnceCell<Installed> = OnceCell::new();
+
+/// Parts of coinbase transaction of a job which allows rolling of extranonce2 (see `job::Coinbase`)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CoinbaseRecord {
+    pub coinbase1: String,
+    pub extranonce1: String,
+    pub extranonce2_size: usize,
+    pub coinbase2: String,
+    pub merkle_branch: Vec<String>,
+}
+
+impl CoinbaseRecord {
+    pub fn new(coinbase: &job::Coinbase) -> Self {
+        Self {
+            coinbase1: hex::encode(&coinbase.coinbase1),
+            extranonce1: hex::encode(&coinbase.extranonce1),
+            extranonce2_size: coinbase.extranonce2_size,
+            coinbase2: hex::encode(&coinbase.coinbase2),
+            merkle_branch: coinbase
+                .merkle_branch
+                .iter()
+                .map(|hash| format!("{:x}", hash))
+                .collect(),
+        }
+    }
+
+    pub fn parse(&self) -> error::Result<job::Coinbase> {
+        let decode = |name: &str, value: &str| -> error::Result<Vec<u8>> {
+            hex::decode(value).map_err(|_| {
+                error::ErrorKind::General(format!("invalid coinbase {} '{}'", name, value)).into()
+            })
+        };
+        Ok(job::Coinbase {
+            coinbase1: decode("part 1", &self.coinbase1)?,
+            extranonce1: decode("extranonce1", &self.extranonce1)?,
+            extranonce2_size: self.extranonce2_size,
+            coinbase2: decode("part 2", &self.coinbase2)?,
+            merkle_branch: self
+                .merkle_branch
+                .iter()
+                .map(|hash| parse_hash(hash))
+                .collect::<error::Result<_>>()?,
+        })
+    }
+}
+
+/// Job received by a client and sent to the work engine
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JobRecord {
+    /// Identifier of the job unique within one recording
+    pub id: u64,
+    /// Client which received the job
+    pub client: String,
+    pub version: u32,
+    pub version_mask: u32,
+    pub previous_hash: String,
+    pub merkle_root: String,
+    pub time: u32,
+    pub max_time: u32,
+    pub bits: u32,
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coinbase: Option<CoinbaseRecord>,
+}
+
+impl JobRecord {
+    pub fn new(id: u64, job: &dyn job::Bitcoin) -> Self {
+        Self {
+            id,
+            client: job
+                .origin()
+                .upgrade()
+                .map(|client| client.to_string())
+                .unwrap_or_default(),
+            version: job.version(),
+            version_mask: job.version_mask(),
+            previous_hash: format!("{:x}", job.previous_hash()),
+            merkle_root: format!("{:x}", job.merkle_root()),
+            time: job.time(),
+            max_time: job.max_time(),
+            bits: job.bits(),
+            target: format!("{:x}", job.target()),
+            coinbase: job.coinbase().map(CoinbaseRecord::new),
+        }
+    }
+}
+
+/// Work handed to a backend. Midstates are not stored because they can be computed from the job
+/// and the versions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AssignmentRecord {
+    /// Identifier of the job from which the work has been generated
+    pub job: u64,
+    pub ntime: u32,
+    /// Block versions of all midstates
+    pub versions: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extranonce2: Option<u64>,
+}
+
+/// Solution returned by a backend
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SolutionRecord {
+    /// Identifier of the job of the solved work
+    pub job: u64,
+    pub ntime: u32,
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extranonce2: Option<u64>,
+    pub nonce: u32,
+    /// Target used by the backend for finding this nonce
+    pub backend_target: String,
+    /// The job was still valid when the solution has been returned in the original run. It is
+    /// informative only because the replay derives the validity from the sequence of jobs.
+    pub valid_job: bool,
+}
+
+/// Response of the remote server to a submitted solution
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ResponseRecord {
+    /// Identifier of the job of the submitted solution
+    pub job: u64,
+    pub ntime: u32,
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extranonce2: Option<u64>,
+    pub nonce: u32,
+    pub result: ShareResult,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Job(JobRecord),
+    Assignment(AssignmentRecord),
+    Solution(SolutionRecord),
+    Response(ResponseRecord),
+}
+
+/// One line of the recording
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Time since the start of the recording in milliseconds
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Parse double hash stored in the same representation as it is printed
+fn parse_hash(value: &str) -> error::Result<ii_bitcoin::DHash> {
+    ii_bitcoin::DHash::from_hex(value)
+        .map_err(|_| error::ErrorKind::General(format!("invalid hash '{}'", value)).into())
+}
+
+/// Parse target stored in the same representation as it is printed
+fn parse_target(value: &str) -> error::Result<ii_bitcoin::Target> {
+    ii_bitcoin::Target::from_hex(value)
+        .map_err(|_| error::ErrorKind::General(format!("invalid target '{}'", value)).into())
+}
+
+/// Event waiting for the writer task. The jobs are kept referenced until the event is written
+/// because records of the jobs are created only by the writer.
+#[derive(Debug)]
+enum PendingEvent {
+    Job(Arc<dyn job::Bitcoin>),
+    Assignment {
+        job: Arc<dyn job::Bitcoin>,
+        ntime: u32,
+        versions: Vec<u32>,
+        extranonce2: Option<u64>,
+    },
+    Solution {
+        solution: work::Solution,
+        valid_job: bool,
+    },
+    Response {
+        solution: work::Solution,
+        result: ShareResult,
+    },
+}
+
+impl PendingEvent {
+    fn assignment(work: &work::Assignment) -> Self {
+        PendingEvent::Assignment {
+            job: work.job().clone(),
+            ntime: work.ntime,
+            versions: work.midstates.iter().map(|mid| mid.version).collect(),
+            extranonce2: work.extranonce2,
+        }
+    }
+
+    fn solution(solution: &work::Solution) -> Self {
+        PendingEvent::Solution {
+            solution: solution.clone(),
+            valid_job: solution.has_valid_job(),
+        }
+    }
+
+    fn response(solution: &work::Solution, result: &ShareResult) -> Self {
+        PendingEvent::Response {
+            solution: solution.clone(),
+            result: result.clone(),
+        }
+    }
+}
+
+/// Event together with the time when it occurred
+#[derive(Debug)]
+struct QueuedEvent {
+    timestamp: time::Instant,
+    event: PendingEvent,
+}
+
+impl QueuedEvent {
+    fn new(event: PendingEvent) -> Self {
+        Self {
+            timestamp: time::Instant::now(),
+            event,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    file: io::BufWriter<fs::File>,
+    /// Number of bytes written to the recording
+    size: u64,
+    /// The recording has reached its maximal size and no more events are written
+    full: bool,
+    next_job_id: u64,
+    /// Recently recorded jobs with their identifiers
+    recent_jobs: VecDeque<(u64, Weak<dyn job::Bitcoin>)>,
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    started: time::Instant,
+    max_size: u64,
+    inner: StdMutex<Inner>,
+}
+
+impl Recorder {
+    /// Create a new recording at `path`. Existing file is truncated.
+    pub fn create<P: AsRef<Path>>(path: P, max_size: u64) -> io::Result<Self> {
+        let file = fs::File::create(path)?;
+
+        Ok(Self {
+            started: time::Instant::now(),
+            max_size,
+            inner: StdMutex::new(Inner {
+                file: io::BufWriter::new(file),
+                size: 0,
+                full: false,
+                next_job_id: 0,
+                recent_jobs: VecDeque::with_capacity(MAX_RECENT_JOBS),
+            }),
+        })
+    }
+
+    pub fn from_config(config: &RecordingConfig) -> io::Result<Self> {
+        Self::create(&config.path, config.max_size.unwrap_or(DEFAULT_MAX_SIZE))
+    }
+
+    fn lock_inner(&self) -> StdMutexGuard<Inner> {
+        self.inner.lock().expect("BUG: cannot lock recorder")
+    }
+
+    /// The recording is stopped by the first event which does not fit into its maximal size
+    fn write(&self, inner: &mut Inner, timestamp: time::Instant, event: Event) -> io::Result<()> {
+        if inner.full {
+            return Ok(());
+        }
+        let record = Record {
+            timestamp: timestamp
+                .saturating_duration_since(self.started)
+                .as_millis() as u64,
+            event,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        if inner.size + line.len() as u64 > self.max_size {
+            warn!(
+                "Recorder: recording has reached its maximal size {} bytes and has been stopped",
+                self.max_size
+            );
+            inner.full = true;
+            return Ok(());
+        }
+        inner.file.write_all(&line)?;
+        inner.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Return identifier of the `job` within this recording. The job is recorded when it has not
+    /// been seen yet.
+    fn job_id(
+        &self,
+        inner: &mut Inner,
+        timestamp: time::Instant,
+        job: &Arc<dyn job::Bitcoin>,
+    ) -> io::Result<u64> {
+        let recent_job = inner.recent_jobs.iter().find(|(_, recent_job)| {
+            recent_job
+                .upgrade()
+                .map(|recent_job| Arc::ptr_eq(&recent_job, job))
+                .unwrap_or(false)
+        });
+        if let Some((id, _)) = recent_job {
+            return Ok(*id);
+        }
+
+        let id = inner.next_job_id;
+        inner.next_job_id += 1;
+        self.write(inner, timestamp, Event::Job(JobRecord::new(id, &**job)))?;
+
+        inner
+            .recent_jobs
+            .retain(|(_, recent_job)| recent_job.upgrade().is_some());
+        if inner.recent_jobs.len() >= MAX_RECENT_JOBS {
+            inner.recent_jobs.pop_front();
+        }
+        inner.recent_jobs.push_back((id, Arc::downgrade(job)));
+        Ok(id)
+    }
+
+    fn write_event(&self, inner: &mut Inner, queued: QueuedEvent) -> io::Result<()> {
+        let timestamp = queued.timestamp;
+        let event = match queued.event {
+            PendingEvent::Job(job) => return self.job_id(inner, timestamp, &job).map(|_| ()),
+            PendingEvent::Assignment {
+                job,
+                ntime,
+                versions,
+                extranonce2,
+            } => Event::Assignment(AssignmentRecord {
+                job: self.job_id(inner, timestamp, &job)?,
+                ntime,
+                versions,
+                extranonce2,
+            }),
+            PendingEvent::Solution {
+                solution,
+                valid_job,
+            } => Event::Solution(SolutionRecord {
+                job: self.job_id(inner, timestamp, solution.work().job())?,
+                ntime: solution.time(),
+                version: solution.version(),
+                extranonce2: solution.extranonce2(),
+                nonce: solution.nonce(),
+                backend_target: format!("{:x}", solution.backend_target()),
+                valid_job,
+            }),
+            PendingEvent::Response { solution, result } => Event::Response(ResponseRecord {
+                job: self.job_id(inner, timestamp, solution.work().job())?,
+                ntime: solution.time(),
+                version: solution.version(),
+                extranonce2: solution.extranonce2(),
+                nonce: solution.nonce(),
+                result,
+            }),
+        };
+        self.write(inner, timestamp, event)
+    }
+
+    /// Write all `events` at once and flush the recording
+    fn write_events<I>(&self, events: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = QueuedEvent>,
+    {
+        let mut inner = self.lock_inner();
+        for queued in events {
+            self.write_event(&mut inner, queued)?;
+        }
+        inner.file.flush()
+    }
+
+    pub fn record_job(&self, job: &Arc<dyn job::Bitcoin>) -> io::Result<()> {
+        self.write_events(Some(QueuedEvent::new(PendingEvent::Job(job.clone()))))
+    }
+
+    pub fn record_assignment(&self, work: &work::Assignment) -> io::Result<()> {
+        self.write_events(Some(QueuedEvent::new(PendingEvent::assignment(work))))
+    }
+
+    pub fn record_solution(&self, solution: &work::Solution) -> io::Result<()> {
+        self.write_events(Some(QueuedEvent::new(PendingEvent::solution(solution))))
+    }
+
+    pub fn record_response(
+        &self,
+        solution: &work::Solution,
+        result: &ShareResult,
+    ) -> io::Result<()> {
+        self.write_events(Some(QueuedEvent::new(PendingEvent::response(
+            solution, result,
+        ))))
+    }
+
+    /// Number of bytes written to the recording
+    pub fn size(&self) -> u64 {
+        self.lock_inner().size
+    }
+}
+
+/// Reader of records stored in a recording
+pub struct Reader {
+    lines: io::Lines<io::BufReader<fs::File>>,
+}
+
+impl Reader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+
+        Ok(Self {
+            lines: io::BufReader::new(file).lines(),
+        })
+    }
+}
+
+impl Iterator for Reader {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.lines.next()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => return Some(serde_json::from_str(&line).map_err(|e| e.into())),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Task writing queued events to the recording. All events queued so far are written at once on
+/// the blocking thread pool to not block the executor.
+async fn run_writer(recorder: Arc<Recorder>, mut event_receiver: mpsc::Receiver<QueuedEvent>) {
+    while let Some(queued) = event_receiver.recv().await {
+        let mut events = vec![queued];
+        while let Some(Some(queued)) = event_receiver.recv().now_or_never() {
+            events.push(queued);
+        }
+        let dropped_events = RECORDER
+            .get()
+            .map(|installed| installed.dropped_events.swap(0, Ordering::Relaxed))
+            .unwrap_or_default();
+        if dropped_events > 0 {
+            warn!(
+                "Recorder: {} events dropped due to full queue",
+                dropped_events
+            );
+        }
+
+        let recorder = recorder.clone();
+        match task::spawn_blocking(move || recorder.write_events(events)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Recorder: cannot write events: {}", e),
+            Err(e) => error!("Recorder: writer failed: {}", e),
+        }
+    }
+}
+
+/// Install recorder used by all clients and backends and start its writer task
+pub fn install(recorder: Recorder) -> Result<(), Recorder> {
+    let recorder = Arc::new(recorder);
+    let (event_sender, event_receiver) = mpsc::channel(EVENT_QUEUE_SIZE);
+    if let Err(installed) = RECORDER.set(Installed {
+        recorder: recorder.clone(),
+        event_sender,
+        dropped_events: AtomicUsize::new(0),
+    }) {
+        drop(installed);
+        return Err(Arc::try_unwrap(recorder).expect("BUG: recorder is still shared"));
+    }
+    tokio::spawn(run_writer(recorder, event_receiver));
+    Ok(())
+}
+
+/// Return recorder installed for this process
+pub fn get() -> Option<&'static Recorder> {
+    RECORDER.get().map(|installed| installed.recorder.as_ref())
+}
+
+/// Queue event for the writer task of the installed recorder (if any). The event is built only
+/// when there is a recorder.
+fn record<F>(build_event: F)
+where
+    F: FnOnce() -> PendingEvent,
+{
+    if let Some(installed) = RECORDER.get() {
+        if installed
+            .event_sender
+            .clone()
+            .try_send(QueuedEvent::new(build_event()))
+            .is_err()
+        {
+            installed.dropped_events.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Record job received by a client (if any recorder is installed)
+pub fn record_job(job: &Arc<dyn job::Bitcoin>) {
+    record(|| PendingEvent::Job(job.clone()));
+}
+
+/// Record work handed to a backend (if any recorder is installed)
+pub fn record_assignment(work: &work::Assignment) {
+    record(|| PendingEvent::assignment(work));
+}
+
+/// Record solution returned by a backend (if any recorder is installed)
+pub fn record_solution(solution: &work::Solution) {
+    record(|| PendingEvent::solution(solution));
+}
+
+/// Record response of the remote server to a submitted solution (if any recorder is installed)
+pub fn record_response(solution: &work::Solution, result: &ShareResult) {
+    record(|| PendingEvent::response(solution, result));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils;
+
+    use ii_bitcoin::HashTrait as _;
+
+    #[test]
+    fn test_record_and_read() {
+        let dir = test_utils::TempDir::new("recorder");
+        let path = dir.join("test.rec");
+        let recorder = Recorder::create(&path, DEFAULT_MAX_SIZE).unwrap();
+
+        let solution: work::Solution = test_utils::TEST_BLOCKS[0].into();
+        let job = solution.work().job().clone();
+        let other_solution: work::Solution = test_utils::TEST_BLOCKS[1].into();
+
+        recorder.record_job(&job).unwrap();
+        // The same job is recorded only once
+        recorder.record_job(&job).unwrap();
+        recorder.record_assignment(solution.work()).unwrap();
+        recorder.record_solution(&solution).unwrap();
+        // Job of a solution is recorded automatically when it has not been seen yet
+        recorder.record_solution(&other_solution).unwrap();
+        recorder
+            .record_response(&solution, &ShareResult::Accepted)
+            .unwrap();
+
+        let records: Vec<_> = Reader::open(&path)
+            .unwrap()
+            .map(|record| record.unwrap().event)
+            .collect();
+        assert_eq!(records.len(), 6);
+
+        match &records[0] {
+            Event::Job(record) => {
+                assert_eq!(record.id, 0);
+                assert_eq!(record.client, test_utils::TEST_CLIENT.to_string());
+                assert_eq!(
+                    &parse_hash(&record.previous_hash).unwrap(),
+                    job.previous_hash()
+                );
+                assert_eq!(&parse_hash(&record.merkle_root).unwrap(), job.merkle_root());
+                assert_eq!(parse_target(&record.target).unwrap(), job.target());
+                assert_eq!(record.coinbase, None);
+            }
+            _ => panic!("job record expected"),
+        }
+        match &records[1] {
+            Event::Assignment(record) => {
+                assert_eq!(record.job, 0);
+                assert_eq!(record.versions, vec![job.version()]);
+            }
+            _ => panic!("assignment record expected"),
+        }
+        match &records[2] {
+            Event::Solution(record) => {
+                assert_eq!(record.job, 0);
+                assert_eq!(record.nonce, solution.nonce());
+                assert!(record.valid_job);
+            }
+            _ => panic!("solution record expected"),
+        }
+        match (&records[3], &records[4]) {
+            (Event::Job(job_record), Event::Solution(solution_record)) => {
+                assert_eq!(job_record.id, 1);
+                assert_eq!(solution_record.job, 1);
+            }
+            _ => panic!("job and solution records expected"),
+        }
+        match &records[5] {
+            Event::Response(record) => {
+                assert_eq!(record.job, 0);
+                assert_eq!(record.nonce, solution.nonce());
+                assert_eq!(record.result, ShareResult::Accepted);
+            }
+            _ => panic!("response record expected"),
+        }
+    }
+
+    #[test]
+    fn test_max_size() {
+        let dir = test_utils::TempDir::new("recorder-max-size");
+        let path = dir.join("test.rec");
+        let solution: work::Solution = test_utils::TEST_BLOCKS[0].into();
+
+        // Measure size of the job and the first solution and leave room for longer timestamps
+        let recorder = Recorder::create(&path, DEFAULT_MAX_SIZE).unwrap();
+        recorder.record_solution(&solution).unwrap();
+        let max_size = recorder.size() + 10;
+
+        // The recording is stopped by the first event which does not fit
+        let recorder = Recorder::create(&path, max_size).unwrap();
+        recorder.record_solution(&solution).unwrap();
+        recorder.record_solution(&solution).unwrap();
+        recorder.record_job(solution.work().job()).unwrap();
+        assert!(recorder.size() <= max_size);
+        assert_eq!(fs::metadata(&path).unwrap().len(), recorder.size());
+        assert_eq!(Reader::open(&path).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_writer() {
+        let dir = test_utils::TempDir::new("recorder-writer");
+        let path = dir.join("test.rec");
+        let recorder = Arc::new(Recorder::create(&path, DEFAULT_MAX_SIZE).unwrap());
+        let (mut event_sender, event_receiver) = mpsc::channel(EVENT_QUEUE_SIZE);
+        let writer = tokio::spawn(run_writer(recorder, event_receiver));
+
+        let solutions: Vec<work::Solution> = test_utils::TEST_BLOCKS[..3]
+            .iter()
+            .map(|block| block.into())
+            .collect();
+        for solution in solutions.iter() {
+            event_sender
+                .send(QueuedEvent::new(PendingEvent::solution(solution)))
+                .await
+                .unwrap();
+        }
+        // The writer task terminates when all events have been written
+        drop(event_sender);
+        writer.await.unwrap();
+
+        let nonces: Vec<_> = Reader::open(&path)
+            .unwrap()
+            .filter_map(|record| match record.unwrap().event {
+                Event::Solution(record) => Some(record.nonce),
+                _ => None,
+            })
+            .collect();
+        let expected_nonces: Vec<_> = solutions.iter().map(|solution| solution.nonce()).collect();
+        assert_eq!(nonces, expected_nonces);
+    }
+
+    #[test]
+    fn test_coinbase_record() {
+        let coinbase = job::Coinbase {
+            coinbase1: vec![0x01, 0x02],
+            extranonce1: vec![0x03],
+            extranonce2_size: 4,
+            coinbase2: vec![0x04],
+            merkle_branch: vec![ii_bitcoin::DHash::hash(&[0x05])],
+        };
+        let record = CoinbaseRecord::new(&coinbase);
+        assert_eq!(record.coinbase1, "0102");
+
+        let parsed = record.parse().unwrap();
+        assert_eq!(parsed.coinbase1, coinbase.coinbase1);
+        assert_eq!(parsed.extranonce1, coinbase.extranonce1);
+        assert_eq!(parsed.coinbase2, coinbase.coinbase2);
+        assert_eq!(parsed.merkle_branch, coinbase.merkle_branch);
+        assert_eq!(parsed.merkle_root(7), coinbase.merkle_root(7));
+    }
+}
@@ -0,0 +1,680 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Deterministic replay of recordings. Recorded jobs are sent through `job::Sender` of a replay
+//! client with the same name as the original one, work assignments are reconstructed from their
+//! jobs and solutions are passed to `job::SolutionReceiver` which decides whether they would be
+//! submitted to the remote server. Recorded responses of the remote server are paired with the
+//! replayed solutions. The replay is synchronous and does not depend on timing of the original
+//! run.
+
+use super::{
+    parse_hash, parse_target, AssignmentRecord, Event, JobRecord, Record, ResponseRecord,
+    SolutionRecord,
+};
+
+use crate::client::journal::ShareResult;
+use crate::error;
+use crate::hal;
+use crate::job;
+use crate::node;
+use crate::stats;
+use crate::sync;
+use crate::work;
+
+use bosminer_config::StalePolicy;
+use bosminer_macros::ClientNode;
+
+use ii_bitcoin::HashTrait as _;
+
+use futures::FutureExt as _;
+use ii_async_compat::futures;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+
+use async_trait::async_trait;
+
+/// Client standing in for the original client which received the recorded jobs
+#[derive(Debug, ClientNode)]
+pub struct Client {
+    name: String,
+    #[member_status]
+    status: sync::StatusMonitor,
+    #[member_client_stats]
+    client_stats: stats::BasicClient,
+    /// Incremented each time all replayed jobs become stale
+    job_generation: AtomicUsize,
+}
+
+impl Client {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            status: Default::default(),
+            client_stats: Default::default(),
+            job_generation: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl node::Client for Client {
+    fn start(self: Arc<Self>) {}
+
+    fn stop(&self) {}
+
+    async fn get_last_job(&self) -> Option<Arc<dyn job::Bitcoin>> {
+        None
+    }
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Job reconstructed from its record. It is invalidated by a replayed job of the same client with
+/// another previous hash the same way as the original client invalidates its jobs with a new block.
+#[derive(Debug)]
+pub struct Job {
+    client: Weak<Client>,
+    version: u32,
+    version_mask: u32,
+    previous_hash: ii_bitcoin::DHash,
+    merkle_root: ii_bitcoin::DHash,
+    time: u32,
+    max_time: u32,
+    bits: u32,
+    target: ii_bitcoin::Target,
+    coinbase: Option<job::Coinbase>,
+    /// Job generation of the client at the time the job has been replayed
+    generation: usize,
+}
+
+impl Job {
+    pub fn from_record(record: &JobRecord, client: &Arc<Client>) -> error::Result<Self> {
+        Ok(Self {
+            client: Arc::downgrade(client),
+            version: record.version,
+            version_mask: record.version_mask,
+            previous_hash: parse_hash(&record.previous_hash)?,
+            merkle_root: parse_hash(&record.merkle_root)?,
+            time: record.time,
+            max_time: record.max_time,
+            bits: record.bits,
+            target: parse_target(&record.target)?,
+            coinbase: match &record.coinbase {
+                Some(coinbase) => Some(coinbase.parse()?),
+                None => None,
+            },
+            generation: client.job_generation.load(Ordering::Relaxed),
+        })
+    }
+}
+
+impl job::Bitcoin for Job {
+    fn origin(&self) -> Weak<dyn node::Client> {
+        self.client.clone()
+    }
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn version_mask(&self) -> u32 {
+        self.version_mask
+    }
+
+    fn previous_hash(&self) -> &ii_bitcoin::DHash {
+        &self.previous_hash
+    }
+
+    fn merkle_root(&self) -> &ii_bitcoin::DHash {
+        &self.merkle_root
+    }
+
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn max_time(&self) -> u32 {
+        self.max_time
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn target(&self) -> ii_bitcoin::Target {
+        self.target
+    }
+
+    fn is_valid(&self) -> bool {
+        self.client
+            .upgrade()
+            .map(|client| client.job_generation.load(Ordering::Relaxed) == self.generation)
+            .unwrap_or(false)
+    }
+
+    fn coinbase(&self) -> Option<&job::Coinbase> {
+        self.coinbase.as_ref()
+    }
+}
+
+/// Backend solution reconstructed from its record
+#[derive(Debug)]
+struct BackendSolution {
+    nonce: u32,
+    target: ii_bitcoin::Target,
+}
+
+impl hal::BackendSolution for BackendSolution {
+    fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    /// Replayed work always contains only the midstate of the solution
+    fn midstate_idx(&self) -> usize {
+        0
+    }
+
+    fn solution_idx(&self) -> usize {
+        0
+    }
+
+    fn target(&self) -> &ii_bitcoin::Target {
+        &self.target
+    }
+}
+
+/// Decision of the job layer about a replayed solution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The solution would be submitted to the remote server
+    Submitted,
+    /// The solution has been dropped (e.g. it does not meet the job target, it is stale or
+    /// duplicate)
+    Dropped,
+}
+
+#[derive(Debug)]
+pub struct ReplayedSolution {
+    /// Identifier of the job of the solution within the recording
+    pub job: u64,
+    pub solution: work::Solution,
+    pub outcome: Outcome,
+    /// Recorded response of the remote server to the solution
+    pub response: Option<ShareResult>,
+}
+
+impl ReplayedSolution {
+    fn is_response_to(&self, record: &ResponseRecord) -> bool {
+        self.response.is_none()
+            && self.job == record.job
+            && self.solution.nonce() == record.nonce
+            && self.solution.version() == record.version
+            && self.solution.time() == record.ntime
+            && self.solution.extranonce2() == record.extranonce2
+    }
+}
+
+/// Replay client together with its job solver
+struct ClientHandle {
+    client: Arc<Client>,
+    job_sender: job::Sender,
+    solution_sender: job::SolutionSender,
+    solution_receiver: job::SolutionReceiver,
+    /// Previous hash of the most recently replayed job
+    previous_hash: Option<ii_bitcoin::DHash>,
+}
+
+impl ClientHandle {
+    fn new(name: String, midstate_count: usize, stale_policy: StalePolicy) -> Self {
+        let engine_sender = Arc::new(work::EngineSender::new(None));
+        engine_sender.replace_engine_generator(Box::new(move |job| {
+            work::engine::create(job, midstate_count)
+        }));
+        let (solution_sender, solution_receiver) = job::solution_channel();
+        let mut solver = job::Solver::new(engine_sender, solution_receiver);
+        solver.solution_receiver.set_stale_policy(stale_policy);
+
+        Self {
+            client: Arc::new(Client::new(name)),
+            job_sender: solver.job_sender,
+            solution_sender,
+            solution_receiver: solver.solution_receiver,
+            previous_hash: None,
+        }
+    }
+}
+
+pub struct Replay {
+    midstate_count: usize,
+    stale_policy: StalePolicy,
+    clients: HashMap<String, ClientHandle>,
+    jobs: HashMap<u64, Arc<Job>>,
+    /// Results of solutions replayed so far
+    solutions: Vec<ReplayedSolution>,
+}
+
+impl Replay {
+    pub fn new(midstate_count: usize, stale_policy: StalePolicy) -> Self {
+        Self {
+            midstate_count,
+            stale_policy,
+            clients: HashMap::new(),
+            jobs: HashMap::new(),
+            solutions: Vec::new(),
+        }
+    }
+
+    /// Return replay client standing in for the original client with `name`
+    pub fn client(&self, name: &str) -> Option<Arc<Client>> {
+        self.clients.get(name).map(|handle| handle.client.clone())
+    }
+
+    fn get_job(&self, id: u64) -> error::Result<Arc<Job>> {
+        self.jobs.get(&id).cloned().ok_or_else(|| {
+            error::ErrorKind::General(format!("unknown job {} in recording", id)).into()
+        })
+    }
+
+    fn client_handle(&mut self, job: &Job) -> error::Result<&mut ClientHandle> {
+        let client = job
+            .client
+            .upgrade()
+            .expect("BUG: missing replay client")
+            .to_string();
+        self.clients.get_mut(&client).ok_or_else(|| {
+            error::ErrorKind::General(format!("unknown client '{}' in recording", client)).into()
+        })
+    }
+
+    /// Build work with given versions of midstates
+    fn build_assignment(
+        job: Arc<Job>,
+        ntime: u32,
+        versions: &[u32],
+        extranonce2: Option<u64>,
+    ) -> error::Result<work::Assignment> {
+        let merkle_root = match extranonce2 {
+            Some(extranonce2) => job
+                .coinbase
+                .as_ref()
+                .ok_or("work with extranonce2 for job without coinbase")?
+                .merkle_root(extranonce2),
+            None => job.merkle_root,
+        };
+        let mut block_chunk1 = ii_bitcoin::BlockHeader {
+            previous_hash: job.previous_hash.into_inner(),
+            merkle_root: merkle_root.into_inner(),
+            ..Default::default()
+        };
+        let midstates = versions
+            .iter()
+            .map(|&version| {
+                block_chunk1.version = version;
+                work::Midstate {
+                    version,
+                    state: block_chunk1.midstate(),
+                }
+            })
+            .collect();
+
+        Ok(match extranonce2 {
+            Some(extranonce2) => {
+                work::Assignment::with_extranonce2(job, midstates, ntime, extranonce2, merkle_root)
+            }
+            None => work::Assignment::new(job, midstates, ntime),
+        })
+    }
+
+    fn replay_job(&mut self, record: &JobRecord) -> error::Result<()> {
+        let midstate_count = self.midstate_count;
+        let stale_policy = self.stale_policy;
+        let handle = self
+            .clients
+            .entry(record.client.clone())
+            .or_insert_with(|| {
+                ClientHandle::new(record.client.clone(), midstate_count, stale_policy)
+            });
+
+        // All jobs of the client become stale when a job for a new block is received
+        let previous_hash = parse_hash(&record.previous_hash)?;
+        if handle
+            .previous_hash
+            .replace(previous_hash)
+            .map_or(false, |last_previous_hash| {
+                last_previous_hash != previous_hash
+            })
+        {
+            handle.client.job_generation.fetch_add(1, Ordering::Relaxed);
+        }
+        let job = Arc::new(Job::from_record(record, &handle.client)?);
+        handle.job_sender.send(job.clone());
+        self.jobs.insert(record.id, job);
+        Ok(())
+    }
+
+    fn replay_assignment(&mut self, record: &AssignmentRecord) -> error::Result<()> {
+        let job = self.get_job(record.job)?;
+        let work = Self::build_assignment(
+            job.clone(),
+            record.ntime,
+            &record.versions,
+            record.extranonce2,
+        )?;
+        // account generated work the same way as the work generator does
+        self.client_handle(&job)?
+            .client
+            .client_stats
+            .generated_work
+            .add(work.generated_work_amount() as u64);
+        Ok(())
+    }
+
+    fn replay_solution(&mut self, record: &SolutionRecord) -> error::Result<ReplayedSolution> {
+        let job = self.get_job(record.job)?;
+
+        let work = Self::build_assignment(
+            job.clone(),
+            record.ntime,
+            &[record.version],
+            record.extranonce2,
+        )?;
+        let solution = work::Solution::new(
+            work,
+            BackendSolution {
+                nonce: record.nonce,
+                target: parse_target(&record.backend_target)?,
+            },
+            None,
+        );
+
+        let handle = self.client_handle(&job)?;
+        handle
+            .solution_sender
+            .send(solution.clone())
+            .expect("BUG: replay solution queue closed");
+        // The receiver does not wait for anything else than the solution queue so when it is not
+        // ready immediately the solution has been dropped
+        let outcome = match handle.solution_receiver.receive().now_or_never() {
+            Some(Some(_)) => Outcome::Submitted,
+            _ => Outcome::Dropped,
+        };
+        Ok(ReplayedSolution {
+            job: record.job,
+            solution,
+            outcome,
+            response: None,
+        })
+    }
+
+    /// Pair the response with the most recent matching solution
+    fn replay_response(&mut self, record: &ResponseRecord) -> error::Result<()> {
+        let replayed = self
+            .solutions
+            .iter_mut()
+            .rev()
+            .find(|replayed| replayed.is_response_to(record))
+            .ok_or_else(|| {
+                error::ErrorKind::General(format!(
+                    "response to unknown solution of job {} in recording",
+                    record.job
+                ))
+            })?;
+        replayed.response = Some(record.result.clone());
+        Ok(())
+    }
+
+    /// Replay one record. Results of replayed solutions are available in `solutions`.
+    pub fn feed(&mut self, record: &Record) -> error::Result<()> {
+        match &record.event {
+            Event::Job(record) => self.replay_job(record),
+            Event::Assignment(record) => self.replay_assignment(record),
+            Event::Solution(record) => {
+                let replayed = self.replay_solution(record)?;
+                self.solutions.push(replayed);
+                Ok(())
+            }
+            Event::Response(record) => self.replay_response(record),
+        }
+    }
+
+    /// Return results of all solutions replayed so far
+    pub fn solutions(&self) -> &[ReplayedSolution] {
+        &self.solutions
+    }
+
+    /// Replay all records (e.g. from `recorder::Reader`) and return results of all solutions
+    /// replayed so far
+    pub fn run<I>(&mut self, records: I) -> error::Result<Vec<ReplayedSolution>>
+    where
+        I: IntoIterator<Item = io::Result<Record>>,
+    {
+        for record in records {
+            self.feed(&record?)?;
+        }
+        Ok(mem::replace(&mut self.solutions, Vec::new()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::job::Bitcoin as _;
+    use crate::recorder::{Reader, Recorder, DEFAULT_MAX_SIZE};
+    use crate::test_utils;
+
+    #[test]
+    fn test_replay() {
+        let dir = test_utils::TempDir::new("replay");
+        let path = dir.join("test.rec");
+        let recorder = Recorder::create(&path, DEFAULT_MAX_SIZE).unwrap();
+
+        let solutions: Vec<work::Solution> = test_utils::TEST_BLOCKS[..2]
+            .iter()
+            .map(|block| block.into())
+            .collect();
+        for solution in solutions.iter() {
+            recorder.record_assignment(solution.work()).unwrap();
+            recorder.record_solution(solution).unwrap();
+        }
+        // Duplicate solution
+        recorder.record_solution(&solutions[0]).unwrap();
+        recorder
+            .record_response(&solutions[1], &ShareResult::Accepted)
+            .unwrap();
+        recorder
+            .record_response(
+                &solutions[0],
+                &ShareResult::Rejected("low-diff".to_string()),
+            )
+            .unwrap();
+
+        let mut replay = Replay::new(1, StalePolicy::Drop);
+        let replayed = replay.run(Reader::open(&path).unwrap()).unwrap();
+        let outcomes: Vec<_> = replayed.iter().map(|replayed| replayed.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![Outcome::Submitted, Outcome::Submitted, Outcome::Dropped]
+        );
+        // Response is paired with the most recent solution which has not been answered yet
+        let responses: Vec<_> = replayed
+            .iter()
+            .map(|replayed| replayed.response.clone())
+            .collect();
+        assert_eq!(
+            responses,
+            vec![
+                None,
+                Some(ShareResult::Accepted),
+                Some(ShareResult::Rejected("low-diff".to_string()))
+            ]
+        );
+        for (replayed, solution) in replayed.iter().zip(solutions.iter()) {
+            assert_eq!(replayed.solution.hash(), solution.hash());
+            assert_eq!(
+                replayed.solution.work().midstates[0].state,
+                solution.work().midstates[0].state
+            );
+        }
+
+        let client = replay
+            .client(&test_utils::TEST_CLIENT.to_string())
+            .expect("missing replay client");
+        assert_eq!(*client.client_stats.valid_jobs.take_snapshot(), 2);
+        assert_eq!(*client.client_stats.generated_work.take_snapshot(), 2);
+    }
+
+    #[test]
+    fn test_replay_stale_solution() {
+        let block = &test_utils::TEST_BLOCKS[0];
+        let job = JobRecord {
+            id: 0,
+            client: "stratum2+tcp://localhost@user".to_string(),
+            version: block.version,
+            version_mask: 0,
+            previous_hash: format!("{:x}", block.previous_hash),
+            merkle_root: format!("{:x}", block.merkle_root),
+            time: block.time,
+            max_time: block.time,
+            bits: block.bits,
+            target: format!("{:x}", block.target),
+            coinbase: None,
+        };
+        let solution = SolutionRecord {
+            job: 0,
+            ntime: block.time,
+            version: block.version,
+            extranonce2: None,
+            nonce: block.nonce,
+            backend_target: format!("{:x}", ii_bitcoin::Target::default()),
+            // The validity is derived from the replayed jobs
+            valid_job: true,
+        };
+        let records = vec![
+            Event::Job(job.clone()),
+            // Job with the same previous hash does not invalidate the previous one
+            Event::Job(JobRecord {
+                id: 1,
+                ..job.clone()
+            }),
+            // Job for a new block makes all the previous jobs stale
+            Event::Job(JobRecord {
+                id: 2,
+                previous_hash: format!("{:x}", test_utils::TEST_BLOCKS[1].previous_hash),
+                ..job
+            }),
+            Event::Solution(solution.clone()),
+            Event::Solution(SolutionRecord {
+                nonce: block.nonce.wrapping_add(1),
+                ..solution
+            }),
+        ];
+
+        for &(stale_policy, outcome) in &[
+            (StalePolicy::Drop, Outcome::Dropped),
+            (StalePolicy::Submit, Outcome::Submitted),
+        ] {
+            let mut replay = Replay::new(1, stale_policy);
+            let mut records = records.iter().map(|event| Record {
+                timestamp: 0,
+                event: event.clone(),
+            });
+            for record in records.by_ref().take(2) {
+                replay.feed(&record).unwrap();
+            }
+            assert!(replay.get_job(0).unwrap().is_valid());
+            assert!(replay.get_job(1).unwrap().is_valid());
+
+            let replayed = replay.run(records.map(Ok)).unwrap();
+            assert!(!replay.get_job(0).unwrap().is_valid());
+            assert!(!replay.get_job(1).unwrap().is_valid());
+            assert!(replay.get_job(2).unwrap().is_valid());
+            assert_eq!(replayed[0].solution.hash(), &block.hash);
+            assert_eq!(replayed[0].outcome, outcome);
+            // Solution with wrong nonce does not meet backend target
+            assert_eq!(replayed[1].outcome, Outcome::Dropped);
+        }
+    }
+
+    #[test]
+    fn test_replay_extranonce_rolling() {
+        let coinbase = job::Coinbase {
+            coinbase1: vec![0x01, 0x02],
+            extranonce1: vec![0x03],
+            extranonce2_size: 2,
+            coinbase2: vec![0x04],
+            merkle_branch: vec![test_utils::TEST_BLOCKS[1].merkle_root],
+        };
+        let block = &test_utils::TEST_BLOCKS[0];
+        let job = JobRecord {
+            id: 7,
+            client: "test".to_string(),
+            version: block.version,
+            version_mask: 0,
+            previous_hash: format!("{:x}", block.previous_hash),
+            merkle_root: format!("{:x}", block.merkle_root),
+            time: block.time,
+            max_time: block.time,
+            bits: block.bits,
+            target: format!("{:x}", block.target),
+            coinbase: Some(crate::recorder::CoinbaseRecord::new(&coinbase)),
+        };
+        let assignment = AssignmentRecord {
+            job: 7,
+            ntime: block.time,
+            versions: vec![block.version],
+            extranonce2: Some(0x1234),
+        };
+
+        let mut replay = Replay::new(1, StalePolicy::Drop);
+        replay.replay_job(&job).unwrap();
+        replay.replay_assignment(&assignment).unwrap();
+        let work = Replay::build_assignment(
+            replay.get_job(7).unwrap(),
+            assignment.ntime,
+            &assignment.versions,
+            assignment.extranonce2,
+        )
+        .unwrap();
+        assert_eq!(
+            work.get_block_header(0, 0).merkle_root,
+            coinbase.merkle_root(0x1234).into_inner()
+        );
+
+        // Work cannot be rolled for unknown jobs
+        assert!(replay
+            .replay_assignment(&AssignmentRecord {
+                job: 8,
+                ..assignment
+            })
+            .is_err());
+    }
+}
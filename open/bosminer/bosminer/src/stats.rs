@@ -434,6 +434,11 @@ pub trait Client: Mining {
     fn rejected(&self) -> &Meter;
     /// Valid shares rejected by remote server or discarded due to some error
     fn stale(&self) -> &Meter;
+    /// Shares discarded because the same solution has already been submitted
+    fn duplicate(&self) -> &Meter;
+    /// Shares which have never been acknowledged by remote server because they were dropped
+    /// before submission or before receiving the response (e.g. on disconnection or shutdown)
+    fn discarded(&self) -> &Meter;
 }
 
 pub trait WorkSolver: Mining {
@@ -501,6 +506,10 @@ pub struct BasicClient {
     pub rejected: stats::Meter,
     #[member_stale]
     pub stale: stats::Meter,
+    #[member_duplicate]
+    pub duplicate: stats::Meter,
+    #[member_discarded]
+    pub discarded: stats::Meter,
     #[member_valid_network_diff]
     pub valid_network_diff: Meter,
     #[member_valid_job_diff]
@@ -522,7 +531,9 @@ impl BasicClient {
             best_share: Default::default(),
             accepted: Meter::new(&intervals),
             rejected: Meter::new(&intervals),
-            stale: Default::default(),
+            stale: Meter::new(&intervals),
+            duplicate: Meter::new(&intervals),
+            discarded: Meter::new(&intervals),
             valid_network_diff: Meter::new(&intervals),
             valid_job_diff: Meter::new(&intervals),
             valid_backend_diff: Meter::new(&intervals),
@@ -615,6 +626,39 @@ pub enum DiffTargetType {
     Backend,
 }
 
+/// Accounts a solution for an invalidated job to its origin client
+pub async fn account_stale_solution(solution: &work::Solution, time: time::Instant) {
+    if let Some(client) = solution.origin().upgrade() {
+        client
+            .client_stats()
+            .stale()
+            .account_solution(solution.job_target(), time)
+            .await;
+    }
+}
+
+/// Accounts a solution which has already been submitted to its origin client
+pub async fn account_duplicate_solution(solution: &work::Solution, time: time::Instant) {
+    if let Some(client) = solution.origin().upgrade() {
+        client
+            .client_stats()
+            .duplicate()
+            .account_solution(solution.job_target(), time)
+            .await;
+    }
+}
+
+/// Accounts a solution which has been dropped without knowing its result to its origin client
+pub async fn account_discarded_solution(solution: &work::Solution, time: time::Instant) {
+    if let Some(client) = solution.origin().upgrade() {
+        client
+            .client_stats()
+            .discarded()
+            .account_solution(solution.job_target(), time)
+            .await;
+    }
+}
+
 /// Accounts a valid `solution` to all relevant share accounting statistics based on
 /// `met_diff_target_type`. Higher level DiffTargetType also belongs to all lower level types e.g.:
 /// - solution that meets DiffTargetType::Network also belongs to DiffTargetType::{Job, Backend}
@@ -22,6 +22,7 @@
 
 pub mod block_mining;
 
+use crate::error;
 use crate::hal;
 use crate::job::{self, Bitcoin as _};
 use crate::node;
@@ -37,6 +38,9 @@ use futures::lock::Mutex;
 use ii_async_compat::futures;
 
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard, Weak};
 
 use async_trait::async_trait;
@@ -103,12 +107,14 @@ impl fmt::Display for TestClient {
 pub struct TestWorkSolver {
     #[member_work_solver_stats]
     work_solver_stats: stats::BasicWorkSolver,
+    disabled: AtomicBool,
 }
 
 impl TestWorkSolver {
     pub fn new() -> Self {
         Self {
             work_solver_stats: Default::default(),
+            disabled: AtomicBool::new(false),
         }
     }
 }
@@ -118,6 +124,24 @@ impl node::WorkSolver for TestWorkSolver {
     async fn get_nominal_hashrate(&self) -> Option<ii_bitcoin::HashesUnit> {
         None
     }
+
+    async fn get_state(&self) -> node::WorkSolverState {
+        if self.disabled.load(Ordering::Relaxed) {
+            node::WorkSolverState::Disabled
+        } else {
+            node::WorkSolverState::Running
+        }
+    }
+
+    async fn enable(self: Arc<Self>) -> error::Result<()> {
+        self.disabled.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn disable(self: Arc<Self>) -> error::Result<()> {
+        self.disabled.store(true, Ordering::Relaxed);
+        Ok(())
+    }
 }
 
 impl fmt::Display for TestWorkSolver {
@@ -395,6 +419,37 @@ pub fn create_test_work_generator(work_solver: Arc<dyn node::WorkSolver>) -> wor
     )
 }
 
+/// Temporary directory which is removed together with its content when it is dropped
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create empty directory unique for the test `name` and this process
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("bosminer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("BUG: cannot create temporary directory");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return path of file `name` in this directory
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
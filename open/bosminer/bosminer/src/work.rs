@@ -39,6 +39,7 @@ use tokio::sync::watch;
 
 use once_cell::sync::OnceCell;
 
+use std::convert::TryInto;
 use std::fmt::{self, Debug};
 use std::iter;
 use std::mem;
@@ -95,15 +96,39 @@ pub struct Assignment {
     pub midstates: Vec<Midstate>,
     /// nTime value for current work
     pub ntime: u32,
+    /// Rolled extranonce2 used for construction of coinbase transaction (if any)
+    pub extranonce2: Option<u64>,
+    /// Merkle root of this work which differs from the job one when extranonce2 is rolled
+    merkle_root: ii_bitcoin::DHash,
 }
 
 impl Assignment {
     pub fn new(job: Arc<dyn job::Bitcoin>, midstates: Vec<Midstate>, ntime: u32) -> Self {
         Self {
             path: vec![],
+            merkle_root: *job.merkle_root(),
             job,
             midstates,
             ntime,
+            extranonce2: None,
+        }
+    }
+
+    /// Construct work with merkle root computed for coinbase transaction with rolled extranonce2
+    pub fn with_extranonce2(
+        job: Arc<dyn job::Bitcoin>,
+        midstates: Vec<Midstate>,
+        ntime: u32,
+        extranonce2: u64,
+        merkle_root: ii_bitcoin::DHash,
+    ) -> Self {
+        Self {
+            path: vec![],
+            job,
+            midstates,
+            ntime,
+            extranonce2: Some(extranonce2),
+            merkle_root,
         }
     }
 
@@ -113,10 +138,21 @@ impl Assignment {
         self.job.origin()
     }
 
+    /// Return job from which the work has been generated
+    #[inline]
+    pub fn job(&self) -> &Arc<dyn job::Bitcoin> {
+        &self.job
+    }
+
     /// Return merkle root tail
     #[inline]
     pub fn merkle_root_tail(&self) -> u32 {
-        self.job.merkle_root_tail()
+        let merkle_root = self.merkle_root.into_inner();
+        u32::from_le_bytes(
+            merkle_root[merkle_root.len() - mem::size_of::<u32>()..]
+                .try_into()
+                .expect("slice with incorrect length"),
+        )
     }
 
     /// Return current target (nBits)
@@ -125,11 +161,36 @@ impl Assignment {
         self.job.bits()
     }
 
+    /// Return pool/protocol target of the job which has to be met by submitted solutions
+    #[inline]
+    pub fn job_target(&self) -> ii_bitcoin::Target {
+        self.job.target()
+    }
+
     /// Return number of generated work associated within this work assignment
     #[inline]
     pub fn generated_work_amount(&self) -> usize {
         self.midstates.len()
     }
+
+    /// Check if the job of this work assignment hasn't been invalidated (e.g. by a new block)
+    #[inline]
+    pub fn has_valid_job(&self) -> bool {
+        self.job.is_valid()
+    }
+
+    /// Converts mining work for the specified midstate and nonce to Bitcoin block header
+    /// structure which is packable
+    pub fn get_block_header(&self, midstate_idx: usize, nonce: u32) -> ii_bitcoin::BlockHeader {
+        ii_bitcoin::BlockHeader {
+            version: self.midstates[midstate_idx].version,
+            previous_hash: self.job.previous_hash().into_inner(),
+            merkle_root: self.merkle_root.into_inner(),
+            time: self.ntime,
+            bits: self.job.bits(),
+            nonce,
+        }
+    }
 }
 
 /// Container with mining work and a corresponding solution received at a particular time
@@ -177,6 +238,12 @@ impl Solution {
         self.timestamp
     }
 
+    /// Return mining work associated with this solution
+    #[inline]
+    pub fn work(&self) -> &Assignment {
+        &self.work
+    }
+
     pub fn job<T: job::Bitcoin>(&self) -> &T {
         self.work
             .job
@@ -200,6 +267,13 @@ impl Solution {
         self.work.midstates[i].version
     }
 
+    /// Extranonce2 which has to be submitted together with this solution when it has been rolled
+    /// by the work engine
+    #[inline]
+    pub fn extranonce2(&self) -> Option<u64> {
+        self.work.extranonce2
+    }
+
     #[inline]
     pub fn network_target(&self) -> ii_bitcoin::Target {
         // NOTE: it is expected that job has been checked in client and is correct
@@ -230,16 +304,8 @@ impl Solution {
 
     /// Converts mining work solution to Bitcoin block header structure which is packable
     pub fn get_block_header(&self) -> ii_bitcoin::BlockHeader {
-        let job = &self.work.job;
-
-        ii_bitcoin::BlockHeader {
-            version: self.version(),
-            previous_hash: job.previous_hash().into_inner(),
-            merkle_root: job.merkle_root().into_inner(),
-            time: self.time(),
-            bits: job.bits(),
-            nonce: self.nonce(),
-        }
+        let midstate_idx = self.midstate_idx();
+        self.work.get_block_header(midstate_idx, self.nonce())
     }
 
     #[inline]
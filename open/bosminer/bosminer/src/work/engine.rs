@@ -26,7 +26,7 @@ use super::*;
 use crate::job;
 
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 
 #[derive(Debug)]
 pub struct ExhaustedWork;
@@ -50,6 +50,56 @@ const BIP320_UPPER_BOUND_EXCLUSIVE_INDEX: u32 = ii_bitcoin::BIP320_VERSION_MAX +
 /// The current limit gives us support for miners with speed up to 2.4 PH/s
 /// hash_space * roll_ntime_seconds / new_stratum_job_every_sec = 2**(32 + 16) * 256 / 30 = 2.4e15
 const ROLL_NTIME_SECONDS: u32 = 256;
+/// Maximal number of extranonce2 values rolled for one job. Together with the version space it
+/// gives 2**(32 + 16 + 16) hashes per job which is enough for any hardware between two jobs.
+const EXTRANONCE2_MAX_COUNT: u32 = std::u16::MAX as u32;
+
+/// Create the most suitable engine for given job. Extranonce2 is rolled when the job provides
+/// parts of coinbase transaction otherwise only version and ntime is rolled.
+pub fn create(job: Arc<dyn job::Bitcoin>, midstate_count: usize) -> DynEngine {
+    if job.coinbase().is_some() {
+        Arc::new(ExtranonceRolling::new(job, midstate_count))
+    } else {
+        Arc::new(VersionRolling::new(job, midstate_count))
+    }
+}
+
+/// Convert the allocated index to a block version as per BIP320
+#[inline]
+fn rolled_block_version(base_version: u32, index: u32) -> u32 {
+    let version = index % BIP320_UPPER_BOUND_EXCLUSIVE_INDEX;
+    assert!(version <= ii_bitcoin::BIP320_VERSION_MAX);
+    base_version | (version << ii_bitcoin::BIP320_VERSION_SHIFT)
+}
+
+/// Generate midstates for all versions from given range of indexes
+fn rolled_midstates(
+    job: &Arc<dyn job::Bitcoin>,
+    merkle_root: &ii_bitcoin::DHash,
+    base_version: u32,
+    current: u32,
+    next: u32,
+) -> Vec<Midstate> {
+    // prepare block chunk1 with all invariants
+    let mut block_chunk1 = ii_bitcoin::BlockHeader {
+        previous_hash: job.previous_hash().into_inner(),
+        merkle_root: merkle_root.into_inner(),
+        ..Default::default()
+    };
+
+    // generate all midstates from given range of indexes
+    (current..next)
+        .map(|index| {
+            // use index for generation compatible header version
+            let version = rolled_block_version(base_version, index);
+            block_chunk1.version = version;
+            Midstate {
+                version,
+                state: block_chunk1.midstate(),
+            }
+        })
+        .collect()
+}
 
 /// Primitive for atomic range counter
 /// This structure can be freely shared among parallel processes and each range is returned only to
@@ -169,14 +219,6 @@ impl VersionRolling {
         }
     }
 
-    /// Convert the allocated index to a block version as per BIP320
-    #[inline]
-    fn get_block_version(&self, index: u32) -> u32 {
-        let version = index % BIP320_UPPER_BOUND_EXCLUSIVE_INDEX;
-        assert!(version <= ii_bitcoin::BIP320_VERSION_MAX);
-        self.base_version | (version << ii_bitcoin::BIP320_VERSION_SHIFT)
-    }
-
     /// Convert the allocated index to a ntime offset
     #[inline]
     fn get_ntime_offset(&self, index: u32) -> u32 {
@@ -206,25 +248,13 @@ impl Engine for VersionRolling {
 
         // check if given range is the same as number of midstates
         assert_eq!(self.midstate_count, (next - current) as usize);
-        let mut midstates = Vec::with_capacity(self.midstate_count);
-
-        // prepare block chunk1 with all invariants
-        let mut block_chunk1 = ii_bitcoin::BlockHeader {
-            previous_hash: self.job.previous_hash().into_inner(),
-            merkle_root: self.job.merkle_root().into_inner(),
-            ..Default::default()
-        };
-
-        // generate all midstates from given range of indexes
-        for index in current..next {
-            // use index for generation compatible header version
-            let version = self.get_block_version(index);
-            block_chunk1.version = version;
-            midstates.push(Midstate {
-                version,
-                state: block_chunk1.midstate(),
-            })
-        }
+        let midstates = rolled_midstates(
+            &self.job,
+            self.job.merkle_root(),
+            self.base_version,
+            current,
+            next,
+        );
 
         // Once we exhaust version-rolling-space, we start rolling ntime.
         // We can be sure ntime offset is common for all blocks, because `midstate_count`
@@ -244,6 +274,124 @@ impl Engine for VersionRolling {
     }
 }
 
+/// Extranonce rolling implements WorkEngine trait for jobs which provide parts of coinbase
+/// transaction. The whole BIP320 version space is rolled for each extranonce2 and then the merkle
+/// root is recomputed for the next extranonce2. The job is therefore practically never exhausted
+/// and ntime does not have to be rolled at all.
+#[derive(Debug)]
+pub struct ExtranonceRolling {
+    job: Arc<dyn job::Bitcoin>,
+    /// Parts of coinbase transaction used for merkle root computation
+    coinbase: job::Coinbase,
+    /// Number of midstates that each generated work covers
+    midstate_count: usize,
+    /// Current range of the compound index with rolled part of the version in lower 16 bits and
+    /// extranonce2 in upper bits
+    curr_range: AtomicRange,
+    /// Base Bitcoin block header version with BIP320 bits cleared
+    base_version: u32,
+    /// Merkle root of the most recently used extranonce2
+    last_merkle_root: StdMutex<Option<(u64, ii_bitcoin::DHash)>>,
+}
+
+impl ExtranonceRolling {
+    pub fn new(job: Arc<dyn job::Bitcoin>, midstate_count: usize) -> Self {
+        let coinbase = job
+            .coinbase()
+            .expect("BUG: job does not support extranonce rolling")
+            .clone();
+        let extranonce2_count = coinbase
+            .extranonce2_count()
+            .min(EXTRANONCE2_MAX_COUNT as u64) as u32;
+        let base_version = job.version() & !ii_bitcoin::BIP320_VERSION_MASK;
+        // we have to be sure we have no "leftover" midstates when we roll
+        assert_eq!(
+            BIP320_UPPER_BOUND_EXCLUSIVE_INDEX % (midstate_count as u32),
+            0
+        );
+        Self {
+            job,
+            coinbase,
+            midstate_count,
+            curr_range: AtomicRange::new(
+                0,
+                BIP320_UPPER_BOUND_EXCLUSIVE_INDEX * extranonce2_count,
+                midstate_count as u32,
+            ),
+            base_version,
+            last_merkle_root: StdMutex::new(None),
+        }
+    }
+
+    /// Convert the allocated index to extranonce2
+    #[inline]
+    fn get_extranonce2(&self, index: u32) -> u64 {
+        (index / BIP320_UPPER_BOUND_EXCLUSIVE_INDEX) as u64
+    }
+
+    /// Return merkle root for given extranonce2 which is computed only when extranonce2 changes
+    fn get_merkle_root(&self, extranonce2: u64) -> ii_bitcoin::DHash {
+        let mut last_merkle_root = self
+            .last_merkle_root
+            .lock()
+            .expect("BUG: cannot lock merkle root");
+        match *last_merkle_root {
+            Some((last_extranonce2, merkle_root)) if last_extranonce2 == extranonce2 => merkle_root,
+            _ => {
+                let merkle_root = self.coinbase.merkle_root(extranonce2);
+                last_merkle_root.replace((extranonce2, merkle_root));
+                merkle_root
+            }
+        }
+    }
+}
+
+impl Engine for ExtranonceRolling {
+    fn terminate(&self) {
+        self.curr_range.terminate();
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.curr_range.is_exhausted(None)
+    }
+
+    fn next_work(&self) -> LoopState<Assignment> {
+        // determine next range of indexes from version and extranonce2 space
+        let (current, next) = match self.curr_range.next() {
+            // return immediately when the space is exhausted
+            None => return LoopState::Exhausted,
+            // use range of indexes for generation of midstates
+            Some(range) => range,
+        };
+
+        // check if given range is the same as number of midstates
+        assert_eq!(self.midstate_count, (next - current) as usize);
+
+        // extranonce2 is common for all midstates, because `midstate_count` divides the size of
+        // version space
+        let extranonce2 = self.get_extranonce2(current);
+        assert_eq!(extranonce2, self.get_extranonce2(next - 1));
+
+        let merkle_root = self.get_merkle_root(extranonce2);
+        let midstates = rolled_midstates(&self.job, &merkle_root, self.base_version, current, next);
+
+        let work = Assignment::with_extranonce2(
+            self.job.clone(),
+            midstates,
+            self.job.time(),
+            extranonce2,
+            merkle_root,
+        );
+        if self.curr_range.is_exhausted(next) {
+            // when the whole space has been exhausted then mark the generated work as a last one
+            // (the next call of this method will return 'Exhausted')
+            LoopState::Break(work)
+        } else {
+            LoopState::Continue(work)
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -306,7 +454,7 @@ pub mod test {
         }
     }
 
-    fn get_block_version(job: &Arc<test_utils::TestBlock>, version_index: u32) -> u32 {
+    fn get_block_version(job: &test_utils::TestBlock, version_index: u32) -> u32 {
         job.version() | (version_index << ii_bitcoin::BIP320_VERSION_SHIFT)
     }
 
@@ -403,4 +551,144 @@ pub mod test {
         }
         assert!(engine.is_exhausted());
     }
+
+    /// Test job which allows rolling of extranonce2
+    #[derive(Debug)]
+    struct TestCoinbaseJob {
+        block: test_utils::TestBlock,
+        coinbase: job::Coinbase,
+    }
+
+    impl TestCoinbaseJob {
+        fn new(extranonce2_size: usize) -> Self {
+            Self {
+                block: test_utils::TEST_BLOCKS[0],
+                coinbase: job::Coinbase {
+                    coinbase1: vec![0x01, 0x02, 0x03],
+                    extranonce1: vec![0x04, 0x05],
+                    extranonce2_size,
+                    coinbase2: vec![0x06],
+                    merkle_branch: vec![test_utils::TEST_BLOCKS[1].hash],
+                },
+            }
+        }
+    }
+
+    impl job::Bitcoin for TestCoinbaseJob {
+        fn origin(&self) -> Weak<dyn node::Client> {
+            self.block.origin()
+        }
+
+        fn version(&self) -> u32 {
+            self.block.version()
+        }
+
+        fn version_mask(&self) -> u32 {
+            self.block.version_mask()
+        }
+
+        fn previous_hash(&self) -> &ii_bitcoin::DHash {
+            self.block.previous_hash()
+        }
+
+        fn merkle_root(&self) -> &ii_bitcoin::DHash {
+            self.block.merkle_root()
+        }
+
+        fn time(&self) -> u32 {
+            self.block.time()
+        }
+
+        fn bits(&self) -> u32 {
+            self.block.bits()
+        }
+
+        fn target(&self) -> ii_bitcoin::Target {
+            self.block.target()
+        }
+
+        fn is_valid(&self) -> bool {
+            true
+        }
+
+        fn coinbase(&self) -> Option<&job::Coinbase> {
+            Some(&self.coinbase)
+        }
+    }
+
+    fn check_extranonce_work(job: &Arc<TestCoinbaseJob>, work: &Assignment, extranonce2: u64) {
+        let merkle_root = job.coinbase.merkle_root(extranonce2);
+        assert_eq!(work.extranonce2, Some(extranonce2));
+        assert_eq!(work.ntime, job.time());
+
+        for (i, midstate) in work.midstates.iter().enumerate() {
+            let block_header = work.get_block_header(i, 0);
+            assert_eq!(block_header.merkle_root, merkle_root.into_inner());
+            assert_eq!(block_header.version, midstate.version);
+            assert_eq!(block_header.midstate(), midstate.state);
+        }
+        assert_eq!(
+            work.merkle_root_tail(),
+            u32::from_le_bytes([
+                merkle_root[28],
+                merkle_root[29],
+                merkle_root[30],
+                merkle_root[31]
+            ])
+        );
+    }
+
+    #[test]
+    fn test_extranonce_rolling() {
+        const MIDSTATE_COUNT: usize = 4;
+
+        let job = Arc::new(TestCoinbaseJob::new(4));
+        let engine = ExtranonceRolling::new(job.clone(), MIDSTATE_COUNT);
+
+        let work = engine.next_work().unwrap();
+        check_extranonce_work(&job, &work, 0);
+        assert_eq!(work.midstates[0].version, get_block_version(&job.block, 0));
+
+        // position ourselves to end of first version range
+        engine.curr_range.curr_index.store(
+            BIP320_UPPER_BOUND_EXCLUSIVE_INDEX - MIDSTATE_COUNT as u32,
+            Ordering::Relaxed,
+        );
+        let work = engine.next_work().unwrap();
+        check_extranonce_work(&job, &work, 0);
+        assert_eq!(
+            work.midstates[MIDSTATE_COUNT - 1].version,
+            get_block_version(&job.block, ii_bitcoin::BIP320_VERSION_MAX)
+        );
+
+        // the version is rolled again with next extranonce2 and the same ntime
+        let work = engine.next_work().unwrap();
+        check_extranonce_work(&job, &work, 1);
+        assert_eq!(work.midstates[0].version, get_block_version(&job.block, 0));
+        assert!(!engine.is_exhausted());
+    }
+
+    #[test]
+    fn test_exhausted_extranonce() {
+        // use one byte extranonce2 to decrease the search space
+        let job = Arc::new(TestCoinbaseJob::new(1));
+        let engine = ExtranonceRolling::new(job.clone(), 1);
+
+        engine.curr_range.curr_index.store(
+            BIP320_UPPER_BOUND_EXCLUSIVE_INDEX * 256 - 1,
+            Ordering::Relaxed,
+        );
+        assert!(!engine.is_exhausted());
+
+        match engine.next_work() {
+            LoopState::Break(work) => check_extranonce_work(&job, &work, 255),
+            _ => panic!("expected 'LoopState::Break'"),
+        }
+        assert!(engine.is_exhausted());
+
+        match engine.next_work() {
+            LoopState::Exhausted => {}
+            _ => panic!("expected 'LoopState::Exhausted'"),
+        }
+    }
 }
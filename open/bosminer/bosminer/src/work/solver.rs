@@ -23,6 +23,7 @@
 use super::*;
 use crate::backend;
 use crate::node;
+use crate::recorder;
 
 use futures::channel::mpsc;
 use futures::lock::Mutex;
@@ -244,6 +245,7 @@ impl Generator {
                 work_solver_stats.generated_work().add(work_amount);
                 work_solver_stats.last_work_time().touch(now).await;
             }
+            recorder::record_assignment(&work);
             return Some(work);
         }
     }
@@ -256,6 +258,7 @@ pub struct SolutionSender(mpsc::UnboundedSender<Solution>);
 
 impl SolutionSender {
     pub fn send(&self, solution: Solution) {
+        recorder::record_solution(&solution);
         self.0
             .unbounded_send(solution)
             .expect("solution queue send failed");
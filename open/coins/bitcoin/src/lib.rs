@@ -101,6 +101,26 @@ impl BlockHeader {
     }
 }
 
+/// Compute merkle root from transaction hashes where the first one belongs to the coinbase
+/// transaction. The last hash of a tree level with an odd number of hashes is paired with itself.
+pub fn merkle_root(hashes: &[DHash]) -> DHash {
+    assert!(!hashes.is_empty(), "BUG: missing coinbase transaction");
+
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut engine = DHash::engine();
+                engine.input(&pair[0][..]);
+                engine.input(&pair.get(1).unwrap_or(&pair[0])[..]);
+                DHash::from_engine(engine)
+            })
+            .collect();
+    }
+    level[0]
+}
+
 /// Array containing SHA256 digest
 type Sha256Array = [u8; SHA256_DIGEST_SIZE];
 
@@ -836,4 +856,30 @@ pub mod test {
         assert!(Shares::default() < shares);
         assert!(shares > Shares::default());
     }
+
+    #[test]
+    fn test_merkle_root() {
+        // Transactions of block 100000
+        let hashes: Vec<_> = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .iter()
+        .map(|hash| DHash::from_hex(hash).expect("parse hex"))
+        .collect();
+
+        assert_eq!(
+            merkle_root(&hashes),
+            DHash::from_hex("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766")
+                .expect("parse hex")
+        );
+        // Block with coinbase transaction only
+        assert_eq!(merkle_root(&hashes[..1]), hashes[0]);
+        // Odd number of transactions
+        let mut odd_hashes = hashes[..3].to_vec();
+        odd_hashes.push(hashes[2]);
+        assert_eq!(merkle_root(&hashes[..3]), merkle_root(&odd_hashes));
+    }
 }
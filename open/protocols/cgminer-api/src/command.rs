@@ -53,6 +53,8 @@ const CHECK: &str = "check";
 const COIN: &str = "coin";
 const ASC_COUNT: &str = "asccount";
 const ASC: &str = "asc";
+const ASC_ENABLE: &str = "ascenable";
+const ASC_DISABLE: &str = "ascdisable";
 const LCD: &str = "lcd";
 
 // List of all standard commands which can be optionally implemented.
@@ -62,6 +64,7 @@ pub const DEVDETAILS: &str = "devdetails";
 pub const TEMPCTRL: &str = "tempctrl";
 pub const TEMPS: &str = "temps";
 pub const FANS: &str = "fans";
+pub const RELOAD_CONFIG: &str = "reloadconfig";
 
 pub type Result<T> = std::result::Result<T, response::Error>;
 /// Type describing command table
@@ -98,6 +101,14 @@ pub trait Handler: Send + Sync {
     async fn handle_coin(&self) -> Result<response::Coin>;
     async fn handle_asc_count(&self) -> Result<response::AscCount>;
     async fn handle_asc(&self, parameter: Option<&json::Value>) -> Result<response::Asc>;
+    async fn handle_asc_enable(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> Result<response::AscEnable>;
+    async fn handle_asc_disable(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> Result<response::AscDisable>;
     async fn handle_lcd(&self) -> Result<response::Lcd>;
 }
 
@@ -253,6 +264,10 @@ where
             Box::new(|command, parameter| Self::check_pool_id(command, parameter));
         let check_asc: ParameterCheckHandler =
             Box::new(|command, parameter| Self::check_asc(command, parameter));
+        let check_asc_enable: ParameterCheckHandler =
+            Box::new(|command, parameter| Self::check_asc(command, parameter));
+        let check_asc_disable: ParameterCheckHandler =
+            Box::new(|command, parameter| Self::check_asc(command, parameter));
 
         let mut commands = commands![
             // generic commands
@@ -271,6 +286,8 @@ where
             (COIN: ParameterLess -> handler.handle_coin),
             (ASC_COUNT: ParameterLess -> handler.handle_asc_count),
             (ASC: Parameter(check_asc) -> handler.handle_asc),
+            (ASC_ENABLE: Parameter(check_asc_enable) -> handler.handle_asc_enable),
+            (ASC_DISABLE: Parameter(check_asc_disable) -> handler.handle_asc_disable),
             (LCD: ParameterLess -> handler.handle_lcd),
             // special built-in commands
             (VERSION: BuiltIn(Version)),
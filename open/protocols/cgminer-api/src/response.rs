@@ -123,16 +123,21 @@ pub enum StatusCode {
     Coin = 78,
     AscCount = 104,
     Asc = 106,
+    AscEnable = 110,
+    AscDisable = 111,
     Lcd = 125,
 
     // extended command status codes
     TempCtrl = 200,
     Temps = 201,
     Fans = 202,
+    ReloadConfig = 203,
 
     // info status codes
     PoolAlreadyEnabled = 49,
     PoolAlreadyDisabled = 50,
+    AscAlreadyEnabled = 108,
+    AscAlreadyDisabled = 109,
 
     // error status codes
     InvalidCommand = 14,
@@ -146,6 +151,7 @@ pub enum StatusCode {
     InvalidAddPoolDetails = 53,
     MissingCheckCmd = 71,
     InvalidAscId = 107,
+    AscSetError = 119,
 
     // special value which is added to the custom status codes
     CustomBase = 300,
@@ -181,6 +187,8 @@ impl Serialize for StatusCodeType {
 pub enum InfoCode {
     PoolAlreadyEnabled(i32, String),
     PoolAlreadyDisabled(i32, String),
+    AscAlreadyEnabled(i32),
+    AscAlreadyDisabled(i32),
 }
 
 impl From<InfoCode> for Dispatch {
@@ -201,6 +209,7 @@ pub enum ErrorCode {
     InvalidAddPoolDetails(String),
     MissingCheckCmd,
     InvalidAscId(i32, i32),
+    AscSetError(i32, String),
 }
 
 impl From<ErrorCode> for Dispatch {
@@ -245,6 +254,14 @@ impl From<InfoCode> for Error {
                 StatusCode::PoolAlreadyDisabled,
                 format!("Pool {}:'{}' already disabled", idx, url),
             ),
+            InfoCode::AscAlreadyEnabled(idx) => (
+                StatusCode::AscAlreadyEnabled,
+                format!("ASC {} already enabled", idx),
+            ),
+            InfoCode::AscAlreadyDisabled(idx) => (
+                StatusCode::AscAlreadyDisabled,
+                format!("ASC {} already disabled", idx),
+            ),
         };
 
         Self {
@@ -303,6 +320,10 @@ impl From<ErrorCode> for Error {
                     idx_requested, idx_last
                 ),
             ),
+            ErrorCode::AscSetError(idx, reason) => (
+                StatusCode::AscSetError,
+                format!("ASC {} set failed: {}", idx, reason),
+            ),
         };
 
         Self {
@@ -509,6 +530,34 @@ impl From<Asc> for Dispatch {
     }
 }
 
+pub struct AscEnable {
+    pub idx: i32,
+}
+
+impl From<AscEnable> for Dispatch {
+    fn from(asc_enable: AscEnable) -> Self {
+        Dispatch::from_success::<()>(
+            StatusCode::AscEnable.into(),
+            format!("ASC {} sent enable message", asc_enable.idx),
+            None,
+        )
+    }
+}
+
+pub struct AscDisable {
+    pub idx: i32,
+}
+
+impl From<AscDisable> for Dispatch {
+    fn from(asc_disable: AscDisable) -> Self {
+        Dispatch::from_success::<()>(
+            StatusCode::AscDisable.into(),
+            format!("ASC {} set disable flag", asc_disable.idx),
+            None,
+        )
+    }
+}
+
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct Devs {
     pub list: Vec<Asc>,
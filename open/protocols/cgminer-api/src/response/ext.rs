@@ -125,3 +125,16 @@ impl From<Fans> for Dispatch {
         )
     }
 }
+
+/// Configuration has been re-read and applied to the running miner
+pub struct ReloadConfig;
+
+impl From<ReloadConfig> for Dispatch {
+    fn from(_: ReloadConfig) -> Self {
+        Dispatch::from_success::<()>(
+            StatusCode::ReloadConfig.into(),
+            "Configuration reloaded".to_string(),
+            None,
+        )
+    }
+}
@@ -338,3 +338,42 @@ async fn test_multiple_custom_commands() {
 
     assert_json_eq(&response, &expected);
 }
+
+#[tokio::test]
+async fn test_asc_enable() {
+    let command: json::Value = json::json!({
+        "command": "ascenable",
+        "parameter": 0
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 110,
+            "Msg": "ASC 0 sent enable message",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+
+    assert_json_eq(&response, &expected);
+}
+
+#[tokio::test]
+async fn test_asc_disable_missing_parameter() {
+    let command: json::Value = json::json!({ "command": "ascdisable" });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 15,
+            "Msg": "Missing device id parameter",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+
+    assert_json_eq(&response, &expected);
+}
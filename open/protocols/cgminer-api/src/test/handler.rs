@@ -322,6 +322,20 @@ impl command::Handler for BasicTest {
         })
     }
 
+    async fn handle_asc_enable(
+        &self,
+        _parameter: Option<&json::Value>,
+    ) -> command::Result<response::AscEnable> {
+        Ok(response::AscEnable { idx: 0 })
+    }
+
+    async fn handle_asc_disable(
+        &self,
+        _parameter: Option<&json::Value>,
+    ) -> command::Result<response::AscDisable> {
+        Ok(response::AscDisable { idx: 0 })
+    }
+
     async fn handle_lcd(&self) -> command::Result<response::Lcd> {
         Ok(response::Lcd {
             elapsed: 0,
@@ -55,6 +55,11 @@ pub enum ErrorKind {
     #[fail(display = "Noise handshake error: {}", _0)]
     Noise(String),
 
+    /// Authenticity of the remote static key cannot be proven (invalid signature, certificate
+    /// not yet valid or expired)
+    #[fail(display = "Noise certificate verification error: {}", _0)]
+    NoiseCertificate(String),
+
     /// Stratum version 1 error
     #[fail(display = "V1 error: {}", _0)]
     V1(super::v1::error::ErrorKind),
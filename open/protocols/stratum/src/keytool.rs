@@ -23,7 +23,8 @@
 //! Keytool that allows:
 //! - generating public/secret keypair for ED25519 curve
 //! - generating and signing a stratum server certificate with a specified master secret key
-//! - validating a specified certificate
+//! - inspecting and validating a specified certificate
+//! - renewing a certificate with a new validity period
 
 use anyhow::{anyhow, Context, Result};
 use ii_stratum::v2::noise;
@@ -32,7 +33,7 @@ use std::convert::{TryFrom, TryInto};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use structopt::StructOpt;
 
 /// All commands recognized by the keytool
@@ -48,6 +49,12 @@ enum Command {
     GenNoiseKey(GenNoiseKeyCommand),
     /// Sign a specified key
     SignKey(SignKeyCommand),
+    /// Print validity period, public key and authority of a certificate
+    Inspect(InspectCommand),
+    /// Verify a certificate against a CA public key and the current time
+    Verify(VerifyCommand),
+    /// Re-sign the key from an existing certificate with a new validity period
+    Renew(RenewCommand),
 }
 
 /// Generates keypair suitable for certification authority and stores secret and public key into
@@ -152,83 +159,232 @@ struct SignKeyCommand {
 }
 
 impl SignKeyCommand {
-    fn open_file(file: &PathBuf, descr: &str) -> Result<File> {
-        OpenOptions::new().read(true).open(file).context(format!(
-            "cannot open {} ({:?})",
-            descr,
-            file.clone().into_os_string()
-        ))
-    }
-
-    fn read_from_file<T: TryFrom<String>>(
-        file_path_buf: &PathBuf,
-        error_context_descr: &str,
-    ) -> Result<T>
-    where
-        T: TryFrom<String>,
-        <T as std::convert::TryFrom<std::string::String>>::Error: std::fmt::Display,
-    {
-        let mut file = Self::open_file(file_path_buf, error_context_descr)?;
-        let mut file_content = String::new();
-        file.read_to_string(&mut file_content).context(format!(
-            "Cannot read {} ({:?})",
-            error_context_descr, file_path_buf
-        ))?;
-
-        let parsed_file_content = T::try_from(file_content).map_err(|e| {
-            anyhow!(
-                "Cannot parse {} ({:?}) {}",
-                error_context_descr,
-                file_path_buf,
-                e
-            )
-        })?;
-
-        Ok(parsed_file_content)
-    }
-
     fn execute(self) -> Result<()> {
-        let public_key = Self::read_from_file::<noise::auth::StaticPublicKeyFormat>(
+        let public_key = read_from_file::<noise::auth::StaticPublicKeyFormat>(
             &self.public_key_to_sign,
             "static public key to sign",
         )?;
+        let authority_keypair = read_signing_keypair(&self.signing_key)?;
+        let certificate = sign_public_key(
+            public_key.into_inner(),
+            &authority_keypair,
+            self.valid_for_days,
+        )?;
 
-        let authority_secret_key = Self::read_from_file::<noise::auth::Ed25519SecretKeyFormat>(
-            &self.signing_key,
-            "signing key",
-        )?
-        .into_inner();
+        // Derive the certificate file name from the public key filename
+        let mut cert_file = self.public_key_to_sign.clone();
+        cert_file.set_extension("cert");
 
-        // Dalek crate requires the full Keypair for signing
-        let authority_keypair = ed25519_dalek::Keypair {
-            // Derive the public key from the secret key
-            public: (&authority_secret_key).into(),
-            secret: authority_secret_key,
-        };
+        write_to_file(&cert_file, certificate, "certificate")
+    }
+}
 
-        let header = noise::auth::SignedPartHeader::with_duration(Duration::from_secs(
-            (self.valid_for_days * 24 * 60 * 60) as u64,
-        ))
-        .map_err(|e| anyhow!("{}", e))?;
+/// Prints details of a specified certificate
+#[derive(Debug, StructOpt)]
+struct InspectCommand {
+    /// Certificate to be inspected
+    #[structopt(short, long, parse(from_os_str))]
+    certificate: PathBuf,
+}
 
-        let signed_part =
-            noise::auth::SignedPart::new(header, public_key.into_inner(), authority_keypair.public);
+impl InspectCommand {
+    fn execute(self) -> Result<()> {
+        let certificate =
+            read_from_file::<noise::auth::Certificate>(&self.certificate, "certificate")?;
+        let header = certificate.header();
+
+        println!("Certificate: {:?}", self.certificate);
+        println!("Valid from: {} (unix time)", unix_time(header.valid_from()));
+        println!(
+            "Not valid after: {} (unix time)",
+            unix_time(header.not_valid_after())
+        );
+        match header.not_valid_after().duration_since(SystemTime::now()) {
+            Ok(remaining) => println!("Expires in: {} days", remaining.as_secs() / SECS_PER_DAY),
+            Err(_) => println!("Expired"),
+        }
+        println!(
+            "Public key: {}",
+            noise::auth::EncodedStaticPublicKey::new(certificate.public_key.clone().into_inner())
+        );
+        println!(
+            "Authority public key: {}",
+            noise::auth::EncodedEd25519PublicKey::new(certificate.authority_public_key())
+        );
 
-        let signature = signed_part
-            .sign_with(&authority_keypair)
+        Ok(())
+    }
+}
+
+/// Verifies that a certificate has been signed by a specified certification authority and that it
+/// is valid at the current time
+#[derive(Debug, StructOpt)]
+struct VerifyCommand {
+    /// Certificate to be verified
+    #[structopt(short, long, parse(from_os_str))]
+    certificate: PathBuf,
+    /// Public key of the certification authority
+    #[structopt(short = "p", long, parse(from_os_str))]
+    authority_public_key: PathBuf,
+    /// Fail when the certificate expires in less than the specified number of days
+    #[structopt(short, long, default_value = "0")]
+    min_valid_days: u64,
+}
+
+impl VerifyCommand {
+    fn execute(self) -> Result<()> {
+        let certificate =
+            read_from_file::<noise::auth::Certificate>(&self.certificate, "certificate")?;
+        let authority_public_key = read_from_file::<noise::auth::Ed25519PublicKeyFormat>(
+            &self.authority_public_key,
+            "authority public key",
+        )?
+        .into_inner();
+
+        let now = SystemTime::now();
+        certificate
+            .verify_authority(&authority_public_key, now)
+            .map_err(|e| anyhow!("{}", e))
+            .context(format!("Verifying certificate ({:?})", self.certificate))?;
+        // The certificate has to remain valid for the requested period, too
+        certificate
+            .validate_at(now + Duration::from_secs(self.min_valid_days * SECS_PER_DAY))
             .map_err(|e| anyhow!("{}", e))
-            .context("Signing certificate")?;
+            .context(format!(
+                "Certificate ({:?}) expires in less than {} days",
+                self.certificate, self.min_valid_days
+            ))?;
+        println!("Certificate {:?} is valid", self.certificate);
 
-        // Final step is to compose the certificate from all components and serialize it into a file
-        let certificate = noise::auth::Certificate::new(signed_part, signature);
-        // Derive the certificate file name from the public key filename
-        let mut cert_file = self.public_key_to_sign.clone();
-        cert_file.set_extension("cert");
+        Ok(())
+    }
+}
 
-        write_to_file(&cert_file, certificate, "certificate")
+/// Creates a new certificate for the public key of an existing certificate, signing it with
+/// `signing_key`. The signing key has to belong to the authority of the original certificate.
+#[derive(Debug, StructOpt)]
+struct RenewCommand {
+    /// Certificate to be renewed
+    #[structopt(short, long, parse(from_os_str))]
+    certificate: PathBuf,
+    /// Actual signing key
+    #[structopt(short, long, parse(from_os_str))]
+    signing_key: PathBuf,
+    /// How many days the renewed certificate should be valid for
+    #[structopt(short, long, default_value = "90")]
+    valid_for_days: usize,
+    /// File for the renewed certificate, it must not exist
+    #[structopt(short, long, parse(from_os_str))]
+    output_certificate: PathBuf,
+}
+
+impl RenewCommand {
+    fn execute(self) -> Result<()> {
+        let certificate =
+            read_from_file::<noise::auth::Certificate>(&self.certificate, "certificate")?;
+        let authority_keypair = read_signing_keypair(&self.signing_key)?;
+
+        if certificate.authority_public_key() != authority_keypair.public {
+            return Err(anyhow!(
+                "Signing key doesn't belong to the authority of the certificate ({})",
+                noise::auth::EncodedEd25519PublicKey::new(certificate.authority_public_key())
+            ));
+        }
+        let renewed_certificate = sign_public_key(
+            certificate.public_key.into_inner(),
+            &authority_keypair,
+            self.valid_for_days,
+        )?;
+
+        write_to_file(
+            &self.output_certificate,
+            renewed_certificate,
+            "renewed certificate",
+        )
     }
 }
 
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Helper that converts system time into unix timestamp for displaying purposes
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Helper that reads secret key of the certification authority and builds the full keypair
+fn read_signing_keypair(signing_key: &PathBuf) -> Result<ed25519_dalek::Keypair> {
+    let authority_secret_key =
+        read_from_file::<noise::auth::Ed25519SecretKeyFormat>(signing_key, "signing key")?
+            .into_inner();
+
+    // Dalek crate requires the full Keypair for signing
+    Ok(ed25519_dalek::Keypair {
+        // Derive the public key from the secret key
+        public: (&authority_secret_key).into(),
+        secret: authority_secret_key,
+    })
+}
+
+/// Helper that builds a certificate for `public_key` signed by `authority_keypair`
+fn sign_public_key(
+    public_key: noise::StaticPublicKey,
+    authority_keypair: &ed25519_dalek::Keypair,
+    valid_for_days: usize,
+) -> Result<noise::auth::Certificate> {
+    let header = noise::auth::SignedPartHeader::with_duration(Duration::from_secs(
+        valid_for_days as u64 * SECS_PER_DAY,
+    ))
+    .map_err(|e| anyhow!("{}", e))?;
+
+    let signed_part = noise::auth::SignedPart::new(header, public_key, authority_keypair.public);
+
+    let signature = signed_part
+        .sign_with(authority_keypair)
+        .map_err(|e| anyhow!("{}", e))
+        .context("Signing certificate")?;
+
+    // Final step is to compose the certificate from all components
+    Ok(noise::auth::Certificate::new(signed_part, signature))
+}
+
+fn open_file(file: &PathBuf, descr: &str) -> Result<File> {
+    OpenOptions::new().read(true).open(file).context(format!(
+        "cannot open {} ({:?})",
+        descr,
+        file.clone().into_os_string()
+    ))
+}
+
+/// Helper that reads any String deserializable type from a specified path
+fn read_from_file<T: TryFrom<String>>(
+    file_path_buf: &PathBuf,
+    error_context_descr: &str,
+) -> Result<T>
+where
+    T: TryFrom<String>,
+    <T as std::convert::TryFrom<std::string::String>>::Error: std::fmt::Display,
+{
+    let mut file = open_file(file_path_buf, error_context_descr)?;
+    let mut file_content = String::new();
+    file.read_to_string(&mut file_content).context(format!(
+        "Cannot read {} ({:?})",
+        error_context_descr, file_path_buf
+    ))?;
+
+    let parsed_file_content = T::try_from(file_content).map_err(|e| {
+        anyhow!(
+            "Cannot parse {} ({:?}) {}",
+            error_context_descr,
+            file_path_buf,
+            e
+        )
+    })?;
+
+    Ok(parsed_file_content)
+}
+
 /// Helper that opens a new file for writing or emits an error with specified context description
 /// if the file already exists. This is important to prevent overwriting already generated files.
 fn open_new_file(file: &PathBuf, descr: &str) -> Result<File> {
@@ -277,5 +433,8 @@ fn main() -> Result<()> {
         Command::GenCAKey(gen_key_cmd) => gen_key_cmd.execute(),
         Command::GenNoiseKey(gen_key_cmd) => gen_key_cmd.execute(),
         Command::SignKey(sign_key_cmd) => sign_key_cmd.execute(),
+        Command::Inspect(inspect_cmd) => inspect_cmd.execute(),
+        Command::Verify(verify_cmd) => verify_cmd.execute(),
+        Command::Renew(renew_cmd) => renew_cmd.execute(),
     }
 }
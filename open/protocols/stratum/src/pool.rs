@@ -0,0 +1,94 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Reference Stratum V2 pool that serves standard channels with synthetic jobs. It is intended
+//! as a local stand-in for a real pool when testing mining software.
+
+use std::time::Duration;
+use structopt::StructOpt;
+
+use ii_async_compat::tokio;
+use ii_logging::macros::*;
+use ii_stratum::v2::server::{self, Config, JobTemplate, Server, VardiffConfig};
+use ii_wire::Address;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "ii-stratum-pool",
+    about = "Reference Stratum V2 pool server (insecure, standard channels only)"
+)]
+struct Args {
+    /// Address to listen on for incoming Stratum V2 connections
+    #[structopt(short = "l", long = "listen", default_value = "localhost:3336")]
+    listen_address: Address,
+    /// Difficulty of newly opened channels
+    #[structopt(short = "d", long, default_value = "64")]
+    difficulty: usize,
+    /// Interval in seconds between new blocks
+    #[structopt(short = "j", long, default_value = "30")]
+    job_interval: u64,
+    /// Enable vardiff aiming at the specified number of shares per minute and channel
+    #[structopt(long)]
+    shares_per_minute: Option<f64>,
+    /// Minimum difficulty vardiff may assign
+    #[structopt(long, default_value = "1")]
+    min_difficulty: usize,
+    /// Maximum difficulty vardiff may assign
+    #[structopt(long)]
+    max_difficulty: Option<usize>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    ii_async_compat::setup_panic_handling();
+    let _log_guard =
+        ii_logging::setup_for_app(ii_logging::LoggingConfig::ASYNC_LOGGER_DRAIN_CHANNEL_SIZE);
+
+    let args = Args::from_args();
+    anyhow::ensure!(args.difficulty > 0, "difficulty must be positive");
+
+    let vardiff = args
+        .shares_per_minute
+        .map(|shares_per_minute| VardiffConfig {
+            shares_per_minute,
+            min_difficulty: args.min_difficulty,
+            max_difficulty: args
+                .max_difficulty
+                .unwrap_or(VardiffConfig::default().max_difficulty),
+            ..Default::default()
+        });
+    let config = Config {
+        initial_difficulty: args.difficulty,
+        job_template: JobTemplate::Synthetic,
+        job_interval: Duration::from_secs(args.job_interval),
+        vardiff,
+    };
+
+    let listener = ii_wire::Server::bind(&args.listen_address)?;
+    info!(
+        "Pool: listening on {} for Stratum V2 protocol version {}",
+        listener.local_addr()?,
+        server::PROTOCOL_VERSION
+    );
+    Server::new(config).run(listener).await;
+    Ok(())
+}
@@ -259,6 +259,53 @@ pub fn build_authorize() -> Authorize {
     Authorize(USER_CREDENTIALS.to_string(), "".to_string())
 }
 
+pub const MINING_SUGGEST_DIFFICULTY_JSON: &str =
+    r#"{"id":4,"method":"mining.suggest_difficulty","params":[512.0]}"#;
+
+pub fn build_suggest_difficulty() -> SuggestDifficulty {
+    SuggestDifficulty([512f32])
+}
+
+pub const CLIENT_RECONNECT_JSON: &str =
+    r#"{"id":null,"method":"client.reconnect","params":["stratum.slushpool.com",3333,10]}"#;
+
+pub fn build_client_reconnect_request_message() -> Rpc {
+    build_request_message(None, build_client_reconnect())
+}
+
+/// Same as `build_client_reconnect_request_message` but with custom `wait_time`
+pub fn build_client_reconnect_request_message_with_wait_time(wait_time: Option<u64>) -> Rpc {
+    build_request_message(
+        None,
+        ClientReconnect::new(
+            Some(POOL_URL.to_string()),
+            Some(POOL_PORT as u16),
+            wait_time,
+        ),
+    )
+}
+
+pub fn build_client_reconnect() -> ClientReconnect {
+    ClientReconnect::new(Some(POOL_URL.to_string()), Some(POOL_PORT as u16), Some(10))
+}
+
+pub const CLIENT_SHOW_MESSAGE_JSON: &str =
+    r#"{"id":null,"method":"client.show_message","params":["Scheduled maintenance"]}"#;
+
+pub fn build_client_show_message() -> ClientShowMessage {
+    ClientShowMessage(["Scheduled maintenance".to_string()])
+}
+
+pub const CLIENT_GET_VERSION_JSON: &str = r#"{"id":5,"method":"client.get_version","params":[]}"#;
+
+pub fn build_client_get_version_request_message() -> Rpc {
+    build_request_message(Some(5), build_client_get_version())
+}
+
+pub fn build_client_get_version() -> ClientGetVersion {
+    ClientGetVersion()
+}
+
 pub const MINING_AUTHORIZE_OK: &str = r#"{"id": 1,"error":null,"result":true}"#;
 
 /// Message payload visitor that compares the payload of the visited message (e.g. after
@@ -370,6 +417,37 @@ impl Handler for TestIdentityHandler {
     async fn visit_submit(&mut self, id: &MessageId, payload: &Submit) {
         self.visit_and_check_request(id, payload, build_mining_submit, MINING_SUBMIT_JSON);
     }
+
+    async fn visit_suggest_difficulty(&mut self, id: &MessageId, payload: &SuggestDifficulty) {
+        self.visit_and_check_request(
+            id,
+            payload,
+            build_suggest_difficulty,
+            MINING_SUGGEST_DIFFICULTY_JSON,
+        );
+    }
+
+    async fn visit_client_reconnect(&mut self, id: &MessageId, payload: &ClientReconnect) {
+        self.visit_and_check_request(id, payload, build_client_reconnect, CLIENT_RECONNECT_JSON);
+    }
+
+    async fn visit_client_show_message(&mut self, id: &MessageId, payload: &ClientShowMessage) {
+        self.visit_and_check_request(
+            id,
+            payload,
+            build_client_show_message,
+            CLIENT_SHOW_MESSAGE_JSON,
+        );
+    }
+
+    async fn visit_client_get_version(&mut self, id: &MessageId, payload: &ClientGetVersion) {
+        self.visit_and_check_request(
+            id,
+            payload,
+            build_client_get_version,
+            CLIENT_GET_VERSION_JSON,
+        );
+    }
 }
 
 /// A complete list of all requests in this module for massive testing
@@ -381,4 +459,8 @@ pub const V1_TEST_REQUESTS: &[&str] = &[
     MINING_SUBSCRIBE_REQ_JSON,
     MINING_SET_DIFFICULTY_JSON,
     MINING_SUBMIT_JSON,
+    MINING_SUGGEST_DIFFICULTY_JSON,
+    CLIENT_RECONNECT_JSON,
+    CLIENT_SHOW_MESSAGE_JSON,
+    CLIENT_GET_VERSION_JSON,
 ];
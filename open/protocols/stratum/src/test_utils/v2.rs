@@ -107,6 +107,38 @@ impl Handler for TestIdentityHandler {
     ) {
         self.visit_and_check(header, payload, build_submit_shares);
     }
+
+    async fn visit_open_extended_mining_channel(
+        &mut self,
+        header: &framing::Header,
+        payload: &OpenExtendedMiningChannel,
+    ) {
+        self.visit_and_check(header, payload, build_open_extended_channel);
+    }
+
+    async fn visit_open_extended_mining_channel_success(
+        &mut self,
+        header: &framing::Header,
+        payload: &OpenExtendedMiningChannelSuccess,
+    ) {
+        self.visit_and_check(header, payload, build_open_extended_channel_success);
+    }
+
+    async fn visit_new_extended_mining_job(
+        &mut self,
+        header: &framing::Header,
+        payload: &NewExtendedMiningJob,
+    ) {
+        self.visit_and_check(header, payload, build_new_extended_mining_job);
+    }
+
+    async fn visit_submit_shares_extended(
+        &mut self,
+        header: &framing::Header,
+        payload: &SubmitSharesExtended,
+    ) {
+        self.visit_and_check(header, payload, build_submit_shares_extended);
+    }
 }
 
 #[cfg(not(feature = "v2json"))]
@@ -213,3 +245,101 @@ pub fn build_submit_shares() -> SubmitSharesStandard {
         version: MINING_WORK_VERSION,
     }
 }
+
+pub fn build_open_extended_channel() -> OpenExtendedMiningChannel {
+    OpenExtendedMiningChannel {
+        req_id: 11,
+        user: USER_CREDENTIALS.try_into().unwrap(),
+        nominal_hashrate: 1e12,
+        max_target: ii_bitcoin::Target::default().into(),
+        min_extranonce_size: 4,
+    }
+}
+
+pub fn build_open_extended_channel_success() -> OpenExtendedMiningChannelSuccess {
+    let open_channel_success = build_open_channel_success();
+
+    OpenExtendedMiningChannelSuccess {
+        req_id: 11,
+        channel_id: 1,
+        target: open_channel_success.target,
+        extranonce_size: 4,
+        extranonce_prefix: Bytes0_32::from_slice(&[0xde, 0xad, 0xbe, 0xef]),
+    }
+}
+
+/// Extended job is split from the coinbase of the V1 mining job so that the extended channel
+/// mines the same block as the standard channel
+pub fn build_new_extended_mining_job() -> NewExtendedMiningJob {
+    let v1_req = v1::build_mining_notify();
+    let merkle_path: Vec<Uint256Bytes> = v1_req
+        .merkle_branch()
+        .iter()
+        .map(|branch| {
+            let mut hash = Uint256Bytes([0; 32]);
+            hash.as_mut().copy_from_slice(branch.as_ref());
+            hash
+        })
+        .collect();
+
+    NewExtendedMiningJob {
+        channel_id: 1,
+        job_id: 0,
+        future_job: true,
+        version: MINING_WORK_VERSION,
+        version_rolling_allowed: true,
+        merkle_path: Seq0_255::from_vec(merkle_path),
+        coinbase_tx_prefix: Bytes0_64k::from_slice(v1_req.coin_base_1()),
+        coinbase_tx_suffix: Bytes0_64k::from_slice(v1_req.coin_base_2()),
+    }
+}
+
+pub fn build_submit_shares_extended() -> SubmitSharesExtended {
+    let mining_job = build_new_extended_mining_job();
+
+    SubmitSharesExtended {
+        channel_id: mining_job.channel_id,
+        seq_num: 0,
+        job_id: mining_job.job_id,
+        nonce: MINING_WORK_NONCE,
+        ntime: MINING_WORK_NTIME,
+        version: MINING_WORK_VERSION,
+        extranonce: Bytes0_32::from_slice(&[0, 0, 0, 0]),
+    }
+}
+
+pub fn build_set_custom_mining_job() -> SetCustomMiningJob {
+    let prev_hash_msg = build_set_new_prev_hash();
+    let extended_job = build_new_extended_mining_job();
+
+    SetCustomMiningJob {
+        channel_id: extended_job.channel_id,
+        req_id: 12,
+        mining_job_token: Bytes0_255::from_slice(&[0xaa; 8]),
+        version: MINING_WORK_VERSION,
+        prev_hash: prev_hash_msg.prev_hash,
+        min_ntime: prev_hash_msg.min_ntime,
+        nbits: prev_hash_msg.nbits,
+        coinbase_tx_version: 1,
+        coinbase_prefix: Bytes0_255::from_slice(&[0x03, 0x40, 0x0d, 0x03]),
+        coinbase_tx_input_sequence: 0xffffffff,
+        coinbase_tx_value_remaining: 0,
+        coinbase_tx_outputs: Bytes0_64k::from_slice(&[0u8; 43]),
+        coinbase_tx_locktime: 0,
+        merkle_path: extended_job.merkle_path,
+        extranonce_size: 8,
+        future_job: false,
+    }
+}
+
+pub fn build_set_custom_mining_job_success() -> SetCustomMiningJobSuccess {
+    let extended_job = build_new_extended_mining_job();
+
+    SetCustomMiningJobSuccess {
+        channel_id: extended_job.channel_id,
+        req_id: 12,
+        job_id: 1,
+        coinbase_tx_prefix: extended_job.coinbase_tx_prefix,
+        coinbase_tx_suffix: extended_job.coinbase_tx_suffix,
+    }
+}
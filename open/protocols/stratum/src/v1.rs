@@ -94,6 +94,34 @@ pub trait Handler: 'static + Send {
     }
 
     async fn visit_submit(&mut self, _id: &MessageId, _payload: &messages::Submit) {}
+
+    async fn visit_suggest_difficulty(
+        &mut self,
+        _id: &MessageId,
+        _payload: &messages::SuggestDifficulty,
+    ) {
+    }
+
+    async fn visit_client_reconnect(
+        &mut self,
+        _id: &MessageId,
+        _payload: &messages::ClientReconnect,
+    ) {
+    }
+
+    async fn visit_client_show_message(
+        &mut self,
+        _id: &MessageId,
+        _payload: &messages::ClientShowMessage,
+    ) {
+    }
+
+    async fn visit_client_get_version(
+        &mut self,
+        _id: &MessageId,
+        _payload: &messages::ClientGetVersion,
+    ) {
+    }
 }
 
 pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protocol>> {
@@ -126,6 +154,20 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
                 }
                 Method::SetVersionMask => Box::new(messages::SetVersionMask::try_from(request)?)
                     as Box<dyn AnyPayload<Protocol>>,
+                Method::SuggestDifficulty => {
+                    Box::new(messages::SuggestDifficulty::try_from(request)?)
+                        as Box<dyn AnyPayload<Protocol>>
+                }
+                Method::ClientReconnect => Box::new(messages::ClientReconnect::try_from(request)?)
+                    as Box<dyn AnyPayload<Protocol>>,
+                Method::ClientShowMessage => {
+                    Box::new(messages::ClientShowMessage::try_from(request)?)
+                        as Box<dyn AnyPayload<Protocol>>
+                }
+                Method::ClientGetVersion => {
+                    Box::new(messages::ClientGetVersion::try_from(request)?)
+                        as Box<dyn AnyPayload<Protocol>>
+                }
                 _ => {
                     return Err(ErrorKind::Rpc(format!("Unsupported request {:?}", request)).into())
                 }
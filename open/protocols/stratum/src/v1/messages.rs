@@ -508,3 +508,109 @@ impl Submit {
 }
 
 impl_conversion_request!(Submit, Method::Submit, visit_submit);
+
+/// Difficulty preferred by the miner, the server may take it into account for subsequent
+/// `mining.set_difficulty`
+/// Note, that we explicitly enforce 1 one element array so that serde doesn't flatten the
+/// 'params' JSON array to a single value, eliminating the array completely.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct SuggestDifficulty(pub [f32; 1]);
+
+impl SuggestDifficulty {
+    pub fn value(&self) -> f32 {
+        self.0[0]
+    }
+}
+
+impl_conversion_request!(
+    SuggestDifficulty,
+    Method::SuggestDifficulty,
+    visit_suggest_difficulty
+);
+
+/// Server asks the client to reconnect, optionally to a different host/port after waiting for
+/// the specified number of seconds. All parameters (`[host, port, wait_time]`) are optional and
+/// servers send the numbers both as JSON numbers and strings, hence the generic representation.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ClientReconnect(pub Vec<serde_json::Value>);
+
+impl ClientReconnect {
+    pub fn new(host: Option<String>, port: Option<u16>, wait_time: Option<u64>) -> Self {
+        let mut params = vec![
+            host.map_or(serde_json::Value::Null, Into::into),
+            port.map_or(serde_json::Value::Null, Into::into),
+            wait_time.map_or(serde_json::Value::Null, Into::into),
+        ];
+        // Omit trailing parameters that haven't been specified
+        while params.last() == Some(&serde_json::Value::Null) {
+            params.pop();
+        }
+        Self(params)
+    }
+
+    fn param_as_u64(&self, index: usize) -> Option<u64> {
+        match self.0.get(index)? {
+            serde_json::Value::Number(value) => value.as_u64(),
+            serde_json::Value::String(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// New host, `None` means that the current host is to be kept
+    pub fn host(&self) -> Option<&str> {
+        self.0
+            .get(0)
+            .and_then(|host| host.as_str())
+            .filter(|host| !host.is_empty())
+    }
+
+    /// New port, `None` means that the current port is to be kept
+    pub fn port(&self) -> Option<u16> {
+        self.param_as_u64(1)
+            .filter(|port| *port != 0)
+            .and_then(|port| u16::try_from(port).ok())
+    }
+
+    /// Number of seconds to wait before reconnecting
+    pub fn wait_time(&self) -> Option<u64> {
+        self.param_as_u64(2)
+    }
+}
+
+impl_conversion_request!(
+    ClientReconnect,
+    Method::ClientReconnect,
+    visit_client_reconnect
+);
+
+/// Human readable message from the server that should be presented to the user
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ClientShowMessage(pub [String; 1]);
+
+impl ClientShowMessage {
+    pub fn message(&self) -> &String {
+        &self.0[0]
+    }
+}
+
+impl_conversion_request!(
+    ClientShowMessage,
+    Method::ClientShowMessage,
+    visit_client_show_message
+);
+
+/// Server asks for the version of the mining software, see `ClientGetVersionResult`
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ClientGetVersion();
+
+impl_conversion_request!(
+    ClientGetVersion,
+    Method::ClientGetVersion,
+    visit_client_get_version
+);
+
+/// Version of the mining software (e.g. "bosminer/1.0.0")
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ClientGetVersionResult(pub String);
+
+impl_conversion_response!(ClientGetVersionResult);
@@ -23,6 +23,7 @@
 use std::str::FromStr;
 
 use super::*;
+use crate::test_utils::common::*;
 use crate::test_utils::v1::*;
 use crate::v1::rpc::Rpc;
 
@@ -85,3 +86,26 @@ fn test_subscribe_malformed_result_json() {
         Rpc::Request(_) => (),
     }
 }
+
+#[test]
+fn test_client_reconnect_params() {
+    let reconnect = build_client_reconnect();
+    assert_eq!(reconnect.host(), Some(POOL_URL));
+    assert_eq!(reconnect.port(), Some(POOL_PORT as u16));
+    assert_eq!(reconnect.wait_time(), Some(10));
+
+    // Some pools send numbers as strings
+    let reconnect: ClientReconnect =
+        serde_json::from_str(r#"["stratum.slushpool.com","3334","5"]"#).expect("parse params");
+    assert_eq!(reconnect.port(), Some(3334));
+    assert_eq!(reconnect.wait_time(), Some(5));
+
+    // Missing or empty parameters mean that the current endpoint is to be kept
+    for params in &[r#"[]"#, r#"["",0]"#] {
+        let reconnect: ClientReconnect = serde_json::from_str(params).expect("parse params");
+        assert_eq!(reconnect.host(), None);
+        assert_eq!(reconnect.port(), None);
+        assert_eq!(reconnect.wait_time(), None);
+    }
+    assert_eq!(ClientReconnect::new(None, None, None).0, vec![]);
+}
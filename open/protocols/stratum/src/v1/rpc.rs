@@ -38,7 +38,8 @@ use super::{framing, Handler, Protocol};
 use crate::error::{Error, Result, ResultExt};
 use crate::AnyPayload;
 
-/// All recognized methods of the V1 protocol have the 'mining.' prefix in json.
+/// All recognized methods of the V1 protocol have the 'mining.' prefix in json except for the
+/// 'client.' methods that control the connection itself.
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub enum Method {
     #[serde(rename = "mining.subscribe")]
@@ -59,6 +60,14 @@ pub enum Method {
     Notify,
     #[serde(rename = "mining.set_version_mask")]
     SetVersionMask,
+    #[serde(rename = "mining.suggest_difficulty")]
+    SuggestDifficulty,
+    #[serde(rename = "client.reconnect")]
+    ClientReconnect,
+    #[serde(rename = "client.show_message")]
+    ClientShowMessage,
+    #[serde(rename = "client.get_version")]
+    ClientGetVersion,
     /// Catch all variant
     #[serde(other)]
     Unknown,
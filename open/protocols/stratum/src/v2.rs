@@ -21,15 +21,19 @@
 // contact us at opensource@braiins.com.
 
 //! Stratum version 2 top level module
+pub mod channel_group;
 pub mod error;
 pub mod framing;
 #[macro_use]
 pub mod macros;
 pub mod extensions;
+pub mod job_negotiation;
 pub mod messages;
 pub mod noise;
 pub mod serialization;
+pub mod server;
 pub mod telemetry;
+pub mod template_distribution;
 pub mod types;
 
 use self::messages::MessageType;
@@ -121,6 +125,13 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_channel_endpoint_changed(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::ChannelEndpointChanged,
+    ) {
+    }
+
     async fn visit_open_standard_mining_channel(
         &mut self,
         _header: &framing::Header,
@@ -143,6 +154,27 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_open_extended_mining_channel(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::OpenExtendedMiningChannel,
+    ) {
+    }
+
+    async fn visit_open_extended_mining_channel_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::OpenExtendedMiningChannelSuccess,
+    ) {
+    }
+
+    async fn visit_open_extended_mining_channel_error(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::OpenExtendedMiningChannelError,
+    ) {
+    }
+
     async fn visit_update_channel(
         &mut self,
         _header: &framing::Header,
@@ -157,6 +189,13 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_close_channel(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::CloseChannel,
+    ) {
+    }
+
     async fn visit_submit_shares_standard(
         &mut self,
         _header: &framing::Header,
@@ -164,6 +203,13 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_submit_shares_extended(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SubmitSharesExtended,
+    ) {
+    }
+
     async fn visit_submit_shares_success(
         &mut self,
         _header: &framing::Header,
@@ -185,6 +231,13 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_new_extended_mining_job(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::NewExtendedMiningJob,
+    ) {
+    }
+
     async fn visit_set_new_prev_hash(
         &mut self,
         _header: &framing::Header,
@@ -199,6 +252,44 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_set_extranonce_prefix(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetExtranoncePrefix,
+    ) {
+    }
+
+    async fn visit_set_custom_mining_job(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetCustomMiningJob,
+    ) {
+    }
+
+    async fn visit_set_custom_mining_job_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetCustomMiningJobSuccess,
+    ) {
+    }
+
+    async fn visit_set_custom_mining_job_error(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetCustomMiningJobError,
+    ) {
+    }
+
+    async fn visit_reconnect(&mut self, _header: &framing::Header, _payload: &messages::Reconnect) {
+    }
+
+    async fn visit_set_group_channel(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetGroupChannel,
+    ) {
+    }
+
     // TODO the methods below will be removed once we will split off a separate handler
     //  type for the telemetry extension and refactor message handling completely
     async fn visit_open_telemetry_channel(
@@ -278,6 +369,9 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
         MessageType::SetupConnectionError => {
             Box::new(messages::SetupConnectionError::try_from(frame)?)
         }
+        MessageType::ChannelEndpointChanged => {
+            Box::new(messages::ChannelEndpointChanged::try_from(frame)?)
+        }
         MessageType::OpenStandardMiningChannel => {
             Box::new(messages::OpenStandardMiningChannel::try_from(frame)?)
         }
@@ -287,12 +381,42 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
         MessageType::OpenStandardMiningChannelError => {
             Box::new(messages::OpenStandardMiningChannelError::try_from(frame)?)
         }
+        MessageType::OpenExtendedMiningChannel => {
+            Box::new(messages::OpenExtendedMiningChannel::try_from(frame)?)
+        }
+        MessageType::OpenExtendedMiningChannelSuccess => {
+            Box::new(messages::OpenExtendedMiningChannelSuccess::try_from(frame)?)
+        }
+        MessageType::OpenExtendedMiningChannelError => {
+            Box::new(messages::OpenExtendedMiningChannelError::try_from(frame)?)
+        }
+        MessageType::UpdateChannel => Box::new(messages::UpdateChannel::try_from(frame)?),
+        MessageType::UpdateChannelError => Box::new(messages::UpdateChannelError::try_from(frame)?),
+        MessageType::CloseChannel => Box::new(messages::CloseChannel::try_from(frame)?),
+        MessageType::SetExtranoncePrefix => {
+            Box::new(messages::SetExtranoncePrefix::try_from(frame)?)
+        }
         MessageType::NewMiningJob => Box::new(messages::NewMiningJob::try_from(frame)?),
+        MessageType::NewExtendedMiningJob => {
+            Box::new(messages::NewExtendedMiningJob::try_from(frame)?)
+        }
         MessageType::SetNewPrevHash => Box::new(messages::SetNewPrevHash::try_from(frame)?),
         MessageType::SetTarget => Box::new(messages::SetTarget::try_from(frame)?),
+        MessageType::Reconnect => Box::new(messages::Reconnect::try_from(frame)?),
+        MessageType::SetGroupChannel => Box::new(messages::SetGroupChannel::try_from(frame)?),
+        MessageType::SetCustomMiningJob => Box::new(messages::SetCustomMiningJob::try_from(frame)?),
+        MessageType::SetCustomMiningJobSuccess => {
+            Box::new(messages::SetCustomMiningJobSuccess::try_from(frame)?)
+        }
+        MessageType::SetCustomMiningJobError => {
+            Box::new(messages::SetCustomMiningJobError::try_from(frame)?)
+        }
         MessageType::SubmitSharesStandard => {
             Box::new(messages::SubmitSharesStandard::try_from(frame)?)
         }
+        MessageType::SubmitSharesExtended => {
+            Box::new(messages::SubmitSharesExtended::try_from(frame)?)
+        }
         MessageType::SubmitSharesSuccess => {
             Box::new(messages::SubmitSharesSuccess::try_from(frame)?)
         }
@@ -330,4 +454,25 @@ pub mod test {
             build_message_from_frame(frame).expect("Message payload deserialization failed");
         message.accept(&mut TestIdentityHandler).await;
     }
+
+    #[tokio::test]
+    async fn test_build_extended_message_from_frame() {
+        let frame: framing::Frame = build_new_extended_mining_job()
+            .try_into()
+            .expect("Cannot create test frame");
+        // Serialize the frame payload so that the message is built via full deserialization
+        let (header, payload) = frame.split();
+        let frame = framing::Frame::from_serialized_payload(
+            header.is_channel_message,
+            header.extension_type,
+            header.msg_type,
+            payload
+                .into_bytes_mut()
+                .expect("Cannot serialize test payload"),
+        );
+
+        let message =
+            build_message_from_frame(frame).expect("Message payload deserialization failed");
+        message.accept(&mut TestIdentityHandler).await;
+    }
 }
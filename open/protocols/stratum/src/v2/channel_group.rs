@@ -0,0 +1,159 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Group channels allow sending a single message (typically `NewMiningJob` or `SetNewPrevHash`)
+//! to many standard channels that share a connection. This module keeps track of group
+//! membership on both sides of the connection.
+
+use super::messages::SetGroupChannel;
+use super::types::*;
+
+use std::collections::{BTreeSet, HashMap};
+
+/// Membership of standard channels in group channels on a single connection. Each standard
+/// channel can be a member of at most one group channel.
+#[derive(Debug, Default, Clone)]
+pub struct ChannelGroups {
+    /// Member channel IDs for each group channel ID
+    groups: HashMap<u32, BTreeSet<u32>>,
+    /// Reverse mapping - group channel ID for each member channel ID
+    membership: HashMap<u32, u32>,
+}
+
+impl ChannelGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `channel_id` into the group channel `group_channel_id`. The channel is removed from
+    /// its previous group, if any.
+    pub fn add_channel(&mut self, group_channel_id: u32, channel_id: u32) {
+        self.remove_channel(channel_id);
+        self.groups
+            .entry(group_channel_id)
+            .or_default()
+            .insert(channel_id);
+        self.membership.insert(channel_id, group_channel_id);
+    }
+
+    /// Removes `channel_id` from its group and provides the group channel ID it was member of.
+    /// Empty groups are dropped.
+    pub fn remove_channel(&mut self, channel_id: u32) -> Option<u32> {
+        let group_channel_id = self.membership.remove(&channel_id)?;
+        if let Some(members) = self.groups.get_mut(&group_channel_id) {
+            members.remove(&channel_id);
+            if members.is_empty() {
+                self.groups.remove(&group_channel_id);
+            }
+        }
+        Some(group_channel_id)
+    }
+
+    /// Group channel ID that `channel_id` is a member of
+    pub fn group_of(&self, channel_id: u32) -> Option<u32> {
+        self.membership.get(&channel_id).copied()
+    }
+
+    #[inline]
+    pub fn is_group(&self, channel_id: u32) -> bool {
+        self.groups.contains_key(&channel_id)
+    }
+
+    /// Member channels of `group_channel_id` in ascending order
+    pub fn members(&self, group_channel_id: u32) -> Vec<u32> {
+        self.groups
+            .get(&group_channel_id)
+            .map(|members| members.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Applies `SetGroupChannel` message received from upstream
+    pub fn set_group_channel(&mut self, msg: &SetGroupChannel) {
+        for channel_id in msg.channel_ids.iter() {
+            self.add_channel(msg.group_channel_id, *channel_id);
+        }
+    }
+
+    /// Builds `SetGroupChannel` message that describes the current members of
+    /// `group_channel_id`. The members are split into more messages if they don't fit into a
+    /// single one.
+    pub fn build_set_group_channel(&self, group_channel_id: u32) -> Vec<SetGroupChannel> {
+        self.members(group_channel_id)
+            .chunks(u16::MAX as usize)
+            .map(|channel_ids| SetGroupChannel {
+                group_channel_id,
+                channel_ids: Seq0_64k::from_slice(channel_ids),
+            })
+            .collect()
+    }
+
+    /// Resolves all standard channels that a message addressed to `channel_id` applies to. For
+    /// a group channel, all its members are provided, otherwise it is the channel itself.
+    pub fn resolve(&self, channel_id: u32) -> Vec<u32> {
+        match self.groups.get(&channel_id) {
+            Some(members) => members.iter().copied().collect(),
+            None => vec![channel_id],
+        }
+    }
+
+    /// Tests whether a message addressed to `target_channel_id` applies to `channel_id`
+    pub fn is_addressed_to(&self, target_channel_id: u32, channel_id: u32) -> bool {
+        target_channel_id == channel_id || self.group_of(channel_id) == Some(target_channel_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_channel_groups() {
+        let mut groups = ChannelGroups::new();
+        groups.set_group_channel(&SetGroupChannel {
+            group_channel_id: 100,
+            channel_ids: Seq0_64k::from_vec(vec![1, 2, 3]),
+        });
+        groups.add_channel(200, 4);
+
+        assert!(groups.is_group(100));
+        assert_eq!(groups.resolve(100), vec![1, 2, 3]);
+        assert_eq!(groups.resolve(2), vec![2]);
+        assert!(groups.is_addressed_to(100, 2));
+        assert!(!groups.is_addressed_to(200, 2));
+
+        // Moving a channel into another group removes it from the original one
+        groups.add_channel(200, 2);
+        assert_eq!(groups.resolve(100), vec![1, 3]);
+        assert_eq!(groups.resolve(200), vec![2, 4]);
+        assert_eq!(groups.group_of(2), Some(200));
+
+        // Empty groups are dropped
+        assert_eq!(groups.remove_channel(1), Some(100));
+        assert_eq!(groups.remove_channel(3), Some(100));
+        assert!(!groups.is_group(100));
+        assert_eq!(groups.remove_channel(3), None);
+
+        let messages = groups.build_set_group_channel(200);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(&*messages[0].channel_ids, &[2, 4]);
+    }
+}
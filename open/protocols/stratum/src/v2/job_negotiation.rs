@@ -0,0 +1,105 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Job Negotiation Protocol - allows a miner to negotiate its own block template (custom mining
+//! job) with the pool. The negotiated job is then activated on a mining channel via
+//! `v2::messages::SetCustomMiningJob`
+
+pub mod messages;
+
+use crate::v2::framing;
+
+use async_trait::async_trait;
+
+/// Protocol associates a custom handler with it
+pub struct Protocol;
+impl crate::Protocol for Protocol {
+    type Handler = dyn Handler;
+    type Header = framing::Header;
+}
+
+/// Specifies all job negotiation messages to be visited
+#[async_trait]
+pub trait Handler: 'static + Send {
+    async fn visit_allocate_mining_job_token(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::AllocateMiningJobToken,
+    ) {
+    }
+
+    async fn visit_allocate_mining_job_token_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::AllocateMiningJobTokenSuccess,
+    ) {
+    }
+
+    async fn visit_commit_mining_job(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::CommitMiningJob,
+    ) {
+    }
+
+    async fn visit_commit_mining_job_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::CommitMiningJobSuccess,
+    ) {
+    }
+
+    async fn visit_commit_mining_job_error(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::CommitMiningJobError,
+    ) {
+    }
+
+    async fn visit_identify_transactions(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::IdentifyTransactions,
+    ) {
+    }
+
+    async fn visit_identify_transactions_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::IdentifyTransactionsSuccess,
+    ) {
+    }
+
+    async fn visit_provide_missing_transactions(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::ProvideMissingTransactions,
+    ) {
+    }
+
+    async fn visit_provide_missing_transactions_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::ProvideMissingTransactionsSuccess,
+    ) {
+    }
+}
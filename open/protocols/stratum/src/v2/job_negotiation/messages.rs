@@ -0,0 +1,223 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+#[cfg(not(feature = "v2json"))]
+use crate::v2::serialization;
+use crate::{
+    error::{Error, Result},
+    v2::{error, extensions, framing, types::*},
+    AnyPayload, Message,
+};
+use async_trait::async_trait;
+use packed_struct::prelude::*;
+use packed_struct_codegen::PrimitiveEnum_u8;
+use serde;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "v2json")]
+use serde_json as serialization;
+use std::convert::TryFrom;
+
+use ii_logging::macros::*;
+
+use super::Protocol;
+
+#[cfg(test)]
+mod test;
+
+/// Generates conversion for job negotiation protocol messages. The messages are part of the base
+/// protocol, they are distinguished by their message type only.
+macro_rules! impl_job_negotiation_message_conversion {
+    ($message:tt, $handler_fn:tt) => {
+        impl_message_conversion!(extensions::BASE, $message, false, $handler_fn);
+    };
+}
+
+/// All messages recognized by the job negotiation protocol
+#[derive(PrimitiveEnum_u8, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MessageType {
+    AllocateMiningJobToken = 0x50,
+    AllocateMiningJobTokenSuccess = 0x51,
+    CommitMiningJob = 0x52,
+    CommitMiningJobSuccess = 0x53,
+    CommitMiningJobError = 0x54,
+    IdentifyTransactions = 0x55,
+    IdentifyTransactionsSuccess = 0x56,
+    ProvideMissingTransactions = 0x57,
+    ProvideMissingTransactionsSuccess = 0x58,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AllocateMiningJobToken {
+    /// Unconstrained identification of the user (for logging/accounting purposes on the
+    /// pool side)
+    pub user_identifier: Str0_255,
+    pub req_id: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AllocateMiningJobTokenSuccess {
+    pub req_id: u32,
+    /// Token that is to be used for committing a mining job
+    pub mining_job_token: Bytes0_255,
+    /// Space (in bytes) that the pool requires for its own coinbase outputs
+    pub coinbase_output_max_additional_size: u32,
+    /// When true, the miner is allowed to start mining the job before it has been confirmed
+    /// by `CommitMiningJobSuccess`
+    pub async_mining_allowed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CommitMiningJob {
+    pub req_id: u32,
+    pub mining_job_token: Bytes0_255,
+    pub version: u32,
+    pub coinbase_tx_version: u32,
+    pub coinbase_prefix: Bytes0_255,
+    pub coinbase_tx_input_sequence: u32,
+    pub coinbase_tx_value_remaining: u64,
+    pub coinbase_tx_outputs: Bytes0_64k,
+    pub coinbase_tx_locktime: u32,
+    pub min_extranonce_size: u16,
+    /// Nonce used for calculating short transaction IDs
+    pub tx_short_hash_nonce: u64,
+    /// Short IDs of all transactions in the block (excluding the coinbase) in the block order
+    pub tx_short_hash_list: Seq0_64k<ShortTxId>,
+    /// Hash of the full transaction hash list that allows detecting short ID collisions
+    pub tx_hash_list_hash: Uint256Bytes,
+    pub excess_data: Bytes0_64k,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CommitMiningJobSuccess {
+    pub req_id: u32,
+    /// Token that is to be used for `SetCustomMiningJob` on the mining connection
+    pub new_mining_job_token: Bytes0_255,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CommitMiningJobError {
+    pub req_id: u32,
+    pub code: Str0_255,
+    pub error_details: Bytes0_64k,
+}
+
+/// The pool asks the miner to provide full transaction hashes of the committed job
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IdentifyTransactions {
+    pub req_id: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IdentifyTransactionsSuccess {
+    pub req_id: u32,
+    pub tx_data_hashes: Seq0_64k<Uint256Bytes>,
+}
+
+/// The pool asks the miner for full transactions it doesn't know about
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProvideMissingTransactions {
+    pub req_id: u32,
+    /// Positions of the transactions in `CommitMiningJob::tx_short_hash_list`
+    pub unknown_tx_position_list: Seq0_64k<u16>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProvideMissingTransactionsSuccess {
+    pub req_id: u32,
+    /// Full serialized transactions in the order of the requested positions
+    pub transaction_list: Seq0_64k<Bytes0_16M>,
+}
+
+impl_job_negotiation_message_conversion!(AllocateMiningJobToken, visit_allocate_mining_job_token);
+impl_job_negotiation_message_conversion!(
+    AllocateMiningJobTokenSuccess,
+    visit_allocate_mining_job_token_success
+);
+impl_job_negotiation_message_conversion!(CommitMiningJob, visit_commit_mining_job);
+impl_job_negotiation_message_conversion!(CommitMiningJobSuccess, visit_commit_mining_job_success);
+impl_job_negotiation_message_conversion!(CommitMiningJobError, visit_commit_mining_job_error);
+impl_job_negotiation_message_conversion!(IdentifyTransactions, visit_identify_transactions);
+impl_job_negotiation_message_conversion!(
+    IdentifyTransactionsSuccess,
+    visit_identify_transactions_success
+);
+impl_job_negotiation_message_conversion!(
+    ProvideMissingTransactions,
+    visit_provide_missing_transactions
+);
+impl_job_negotiation_message_conversion!(
+    ProvideMissingTransactionsSuccess,
+    visit_provide_missing_transactions_success
+);
+
+/// Consumes `frame` and produces a Message object based on the payload type
+pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protocol>> {
+    trace!(
+        "V2: building job negotiation message from frame {:x?}",
+        frame
+    );
+
+    // Payload that already contains deserialized message can be returned directly
+    // TODO this is duplicate chunk from v2::build_message_from_frame()
+    if frame.payload.is_serializable() {
+        let (header, payload) = frame.split();
+        let serializable_payload = payload
+            .into_serializable()
+            .expect("BUG: cannot convert payload into serializable");
+
+        return Ok(Message {
+            header,
+            payload: serializable_payload,
+        });
+    }
+    // Header will be consumed by the subsequent transformation of the frame into the actual
+    // payload for further handling. Therefore we create a copy for constructing a
+    // Message<Protocol>
+    let header = frame.header.clone();
+    // Deserialize the payload based on its type specified in the header
+    let payload: Box<dyn AnyPayload<Protocol>> = match MessageType::from_primitive(
+        frame.header.msg_type,
+    )
+    .ok_or(error::ErrorKind::UnknownMessage(
+        format!("Unexpected payload type, full header: {:x?}", frame.header).into(),
+    ))? {
+        MessageType::AllocateMiningJobToken => Box::new(AllocateMiningJobToken::try_from(frame)?),
+        MessageType::AllocateMiningJobTokenSuccess => {
+            Box::new(AllocateMiningJobTokenSuccess::try_from(frame)?)
+        }
+        MessageType::CommitMiningJob => Box::new(CommitMiningJob::try_from(frame)?),
+        MessageType::CommitMiningJobSuccess => Box::new(CommitMiningJobSuccess::try_from(frame)?),
+        MessageType::CommitMiningJobError => Box::new(CommitMiningJobError::try_from(frame)?),
+        MessageType::IdentifyTransactions => Box::new(IdentifyTransactions::try_from(frame)?),
+        MessageType::IdentifyTransactionsSuccess => {
+            Box::new(IdentifyTransactionsSuccess::try_from(frame)?)
+        }
+        MessageType::ProvideMissingTransactions => {
+            Box::new(ProvideMissingTransactions::try_from(frame)?)
+        }
+        MessageType::ProvideMissingTransactionsSuccess => {
+            Box::new(ProvideMissingTransactionsSuccess::try_from(frame)?)
+        }
+    };
+
+    Ok(Message { header, payload })
+}
@@ -0,0 +1,153 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use bytes::buf::BufMutExt;
+use std::fmt::Debug;
+
+use ii_async_compat::{bytes, tokio};
+
+use super::*;
+use crate::v2::job_negotiation::Handler;
+
+fn build_commit_mining_job() -> CommitMiningJob {
+    CommitMiningJob {
+        req_id: 1,
+        mining_job_token: Bytes0_255::from_slice(&[0xaa; 8]),
+        version: 0x20000000,
+        coinbase_tx_version: 2,
+        coinbase_prefix: Bytes0_255::from_slice(&[0x03, 0x40, 0x0d, 0x03]),
+        coinbase_tx_input_sequence: 0xffffffff,
+        coinbase_tx_value_remaining: 625_000_000,
+        coinbase_tx_outputs: Bytes0_64k::from_slice(&[0u8; 43]),
+        coinbase_tx_locktime: 0,
+        min_extranonce_size: 8,
+        tx_short_hash_nonce: 0x0102030405060708,
+        tx_short_hash_list: Seq0_64k::from_vec(vec![
+            ShortTxId([1, 2, 3, 4, 5, 6]),
+            ShortTxId([6, 5, 4, 3, 2, 1]),
+        ]),
+        tx_hash_list_hash: Uint256Bytes([0x33; 32]),
+        excess_data: Bytes0_64k::new(),
+    }
+}
+
+fn build_provide_missing_transactions() -> ProvideMissingTransactions {
+    ProvideMissingTransactions {
+        req_id: 2,
+        unknown_tx_position_list: Seq0_64k::from_vec(vec![0, 5, 1024]),
+    }
+}
+
+/// Serializes `message` and deserializes it back so that both results can be compared
+fn serialization_roundtrip<M>(message: M) -> M
+where
+    M: AnyPayload<Protocol> + for<'a> TryFrom<&'a [u8], Error = Error> + Debug + PartialEq,
+{
+    let mut writer = bytes::BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("Cannot serialize message");
+    let serialized_message = writer.into_inner();
+
+    M::try_from(&serialized_message[..]).expect("Deserialization failed")
+}
+
+#[test]
+fn test_job_negotiation_roundtrip() {
+    let message = AllocateMiningJobToken {
+        user_identifier: Str0_255::from_str("braiins.worker0"),
+        req_id: 0,
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = AllocateMiningJobTokenSuccess {
+        req_id: 0,
+        mining_job_token: Bytes0_255::from_slice(&[0xaa; 8]),
+        coinbase_output_max_additional_size: 100,
+        async_mining_allowed: true,
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = build_commit_mining_job();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = CommitMiningJobError {
+        req_id: 1,
+        code: Str0_255::from_str("invalid-mining-job-token"),
+        error_details: Bytes0_64k::new(),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = IdentifyTransactionsSuccess {
+        req_id: 2,
+        tx_data_hashes: Seq0_64k::from_vec(vec![Uint256Bytes([0x44; 32])]),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = build_provide_missing_transactions();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = ProvideMissingTransactionsSuccess {
+        req_id: 2,
+        transaction_list: Seq0_64k::from_vec(vec![Bytes0_16M::from_slice(&[0x01; 100])]),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+}
+
+/// Handler that records the visited messages
+#[derive(Default)]
+struct TestHandler {
+    commit_mining_job: Option<CommitMiningJob>,
+}
+
+#[async_trait]
+impl Handler for TestHandler {
+    async fn visit_commit_mining_job(
+        &mut self,
+        _header: &framing::Header,
+        payload: &CommitMiningJob,
+    ) {
+        self.commit_mining_job.replace(payload.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_build_message_from_frame() {
+    let frame: framing::Frame = build_commit_mining_job()
+        .try_into()
+        .expect("Cannot create test frame");
+    let (header, payload) = frame.split();
+    let frame = framing::Frame::from_serialized_payload(
+        header.is_channel_message,
+        header.extension_type,
+        header.msg_type,
+        payload
+            .into_bytes_mut()
+            .expect("Cannot serialize test payload"),
+    );
+
+    let mut handler = TestHandler::default();
+    let message = build_message_from_frame(frame).expect("Message deserialization failed");
+    message.accept(&mut handler).await;
+
+    assert_eq!(handler.commit_mining_job, Some(build_commit_mining_job()));
+}
@@ -78,7 +78,7 @@ pub enum MessageType {
     SetTarget = 0x21,
     SetCustomMiningJob = 0x22,
     SetCustomMiningJobSuccess = 0x23,
-    SetCustomMiningError = 0x24,
+    SetCustomMiningJobError = 0x24,
     Reconnect = 0x25,
     SetGroupChannel = 0x26,
 }
@@ -108,6 +108,13 @@ pub struct SetupConnectionError {
     pub code: Str0_255,
 }
 
+/// Notifies the downstream node that the upstream endpoint of the channel has changed. All
+/// share submissions that haven't been acknowledged yet should be considered lost.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChannelEndpointChanged {
+    pub channel_id: u32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct OpenStandardMiningChannel {
     pub req_id: u32,
@@ -134,12 +141,58 @@ pub struct OpenStandardMiningChannelError {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct UpdateChannel;
+pub struct OpenExtendedMiningChannel {
+    pub req_id: u32,
+    pub user: Str1_255,
+    pub nominal_hashrate: f32,
+    pub max_target: Uint256Bytes,
+    /// Minimum size of extranonce space required by the downstream node
+    pub min_extranonce_size: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenExtendedMiningChannelSuccess {
+    pub req_id: u32,
+    pub channel_id: u32,
+    /// Initial target for mining
+    pub target: Uint256Bytes,
+    /// Extranonce size (in bytes) that the downstream node is allowed to roll
+    pub extranonce_size: u16,
+    pub extranonce_prefix: Bytes0_32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenExtendedMiningChannelError {
+    pub req_id: u32,
+    pub code: Str0_32,
+}
+
+/// Downstream node announces a change of its nominal hashrate or of the maximum target it accepts
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UpdateChannel {
+    pub channel_id: u32,
+    pub nominal_hashrate: f32,
+    pub max_target: Uint256Bytes,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UpdateChannelError {
+    pub channel_id: u32,
+    pub code: Str0_32,
+}
 
+/// Either side of the connection indicates that the channel is no longer used
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct UpdateChannelError;
+pub struct CloseChannel {
+    pub channel_id: u32,
+    pub reason_code: Str0_32,
+}
 
-pub struct CloseChannel;
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetExtranoncePrefix {
+    pub channel_id: u32,
+    pub extranonce_prefix: Bytes0_32,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SubmitSharesStandard {
@@ -152,6 +205,20 @@ pub struct SubmitSharesStandard {
     pub version: u32,
 }
 
+/// Extended variant of share submission, the downstream node provides the part of extranonce it
+/// has rolled (the extranonce prefix of the channel is not included)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SubmitSharesExtended {
+    pub channel_id: u32,
+    pub seq_num: u32,
+    pub job_id: u32,
+
+    pub nonce: u32,
+    pub ntime: u32,
+    pub version: u32,
+    pub extranonce: Bytes0_32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SubmitSharesSuccess {
     pub channel_id: u32,
@@ -176,7 +243,21 @@ pub struct NewMiningJob {
     pub merkle_root: Uint256Bytes,
 }
 
-pub struct NewExtendedMiningJob;
+/// Extended job provides all information for the downstream node to build the coinbase
+/// transaction and the merkle root on its own. The coinbase transaction is built as:
+/// `coinbase_tx_prefix + extranonce_prefix + extranonce + coinbase_tx_suffix`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NewExtendedMiningJob {
+    pub channel_id: u32,
+    pub job_id: u32,
+    pub future_job: bool,
+    pub version: u32,
+    /// If false, the version field has to be used as is (no version rolling)
+    pub version_rolling_allowed: bool,
+    pub merkle_path: Seq0_255<Uint256Bytes>,
+    pub coinbase_tx_prefix: Bytes0_64k,
+    pub coinbase_tx_suffix: Bytes0_64k,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SetNewPrevHash {
@@ -189,9 +270,52 @@ pub struct SetNewPrevHash {
     //pub signature: ??,
 }
 
-pub struct SetCustomMiningJob;
-pub struct SetCustomMiningJobSuccess;
-pub struct Reconnect;
+/// Custom job negotiated via Job Negotiation Protocol. The `mining_job_token` has been obtained
+/// from the job negotiator (see `v2::job_negotiation`)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetCustomMiningJob {
+    pub channel_id: u32,
+    pub req_id: u32,
+    pub mining_job_token: Bytes0_255,
+    pub version: u32,
+    pub prev_hash: Uint256Bytes,
+    pub min_ntime: u32,
+    pub nbits: u32,
+    pub coinbase_tx_version: u32,
+    pub coinbase_prefix: Bytes0_255,
+    pub coinbase_tx_input_sequence: u32,
+    pub coinbase_tx_value_remaining: u64,
+    pub coinbase_tx_outputs: Bytes0_64k,
+    pub coinbase_tx_locktime: u32,
+    pub merkle_path: Seq0_255<Uint256Bytes>,
+    pub extranonce_size: u16,
+    pub future_job: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetCustomMiningJobSuccess {
+    pub channel_id: u32,
+    pub req_id: u32,
+    /// Job identifier assigned by the upstream node, it is to be used for share submission
+    pub job_id: u32,
+    pub coinbase_tx_prefix: Bytes0_64k,
+    pub coinbase_tx_suffix: Bytes0_64k,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetCustomMiningJobError {
+    pub channel_id: u32,
+    pub req_id: u32,
+    pub code: Str0_255,
+}
+
+/// Instructs the downstream node to reconnect to a different host/port. Empty `new_host` or zero
+/// `new_port` means that the current host or port is to be kept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Reconnect {
+    pub new_host: Str0_255,
+    pub new_port: u16,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SetTarget {
@@ -199,7 +323,13 @@ pub struct SetTarget {
     pub max_target: Uint256Bytes,
 }
 
-pub struct SetGroupChannel;
+/// Adds channels into a group channel, each subsequent message (e.g. `NewMiningJob`,
+/// `SetNewPrevHash`) addressed to `group_channel_id` applies to all member channels
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetGroupChannel {
+    pub group_channel_id: u32,
+    pub channel_ids: Seq0_64k<u32>,
+}
 
 impl_base_message_conversion!(SetupConnection, false, visit_setup_connection);
 impl_base_message_conversion!(
@@ -208,6 +338,7 @@ impl_base_message_conversion!(
     visit_setup_connection_success
 );
 impl_base_message_conversion!(SetupConnectionError, false, visit_setup_connection_error);
+impl_base_message_conversion!(ChannelEndpointChanged, true, visit_channel_endpoint_changed);
 impl_base_message_conversion!(
     OpenStandardMiningChannel,
     false,
@@ -223,11 +354,43 @@ impl_base_message_conversion!(
     false,
     visit_open_standard_mining_channel_error
 );
+impl_base_message_conversion!(
+    OpenExtendedMiningChannel,
+    false,
+    visit_open_extended_mining_channel
+);
+impl_base_message_conversion!(
+    OpenExtendedMiningChannelSuccess,
+    false,
+    visit_open_extended_mining_channel_success
+);
+impl_base_message_conversion!(
+    OpenExtendedMiningChannelError,
+    false,
+    visit_open_extended_mining_channel_error
+);
 impl_base_message_conversion!(UpdateChannel, true, visit_update_channel);
 impl_base_message_conversion!(UpdateChannelError, true, visit_update_channel_error);
+impl_base_message_conversion!(CloseChannel, true, visit_close_channel);
 impl_base_message_conversion!(SubmitSharesStandard, true, visit_submit_shares_standard);
+impl_base_message_conversion!(SubmitSharesExtended, true, visit_submit_shares_extended);
 impl_base_message_conversion!(SubmitSharesSuccess, true, visit_submit_shares_success);
 impl_base_message_conversion!(SubmitSharesError, true, visit_submit_shares_error);
 impl_base_message_conversion!(NewMiningJob, true, visit_new_mining_job);
+impl_base_message_conversion!(NewExtendedMiningJob, true, visit_new_extended_mining_job);
 impl_base_message_conversion!(SetNewPrevHash, true, visit_set_new_prev_hash);
 impl_base_message_conversion!(SetTarget, true, visit_set_target);
+impl_base_message_conversion!(SetExtranoncePrefix, true, visit_set_extranonce_prefix);
+impl_base_message_conversion!(Reconnect, false, visit_reconnect);
+impl_base_message_conversion!(SetGroupChannel, false, visit_set_group_channel);
+impl_base_message_conversion!(SetCustomMiningJob, true, visit_set_custom_mining_job);
+impl_base_message_conversion!(
+    SetCustomMiningJobSuccess,
+    true,
+    visit_set_custom_mining_job_success
+);
+impl_base_message_conversion!(
+    SetCustomMiningJobError,
+    true,
+    visit_set_custom_mining_job_error
+);
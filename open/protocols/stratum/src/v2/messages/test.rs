@@ -21,6 +21,7 @@
 // contact us at opensource@braiins.com.
 
 use bytes::{buf::BufMutExt, BytesMut};
+use std::fmt::Debug;
 
 use ii_async_compat::bytes;
 
@@ -55,3 +56,92 @@ fn test_serialize_setup_connection() {
         serialized_message
     );
 }
+
+/// Serializes `message` via its `AnyPayload` implementation and deserializes it back so that
+/// both results can be compared
+fn serialization_roundtrip<M>(message: M) -> M
+where
+    M: AnyPayload<Protocol> + for<'a> TryFrom<&'a [u8], Error = Error> + Debug + PartialEq,
+{
+    let mut writer = bytes::BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("Cannot serialize message");
+    let serialized_message = writer.into_inner();
+
+    M::try_from(&serialized_message[..]).expect("Deserialization failed")
+}
+
+#[test]
+fn test_extended_channel_roundtrip() {
+    let message = build_open_extended_channel();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = build_open_extended_channel_success();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = build_new_extended_mining_job();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = build_submit_shares_extended();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+}
+
+#[test]
+fn test_custom_mining_job_roundtrip() {
+    let message = build_set_custom_mining_job();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = build_set_custom_mining_job_success();
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = SetCustomMiningJobError {
+        channel_id: 1,
+        req_id: 12,
+        code: Str0_255::from_str("invalid-job-param-value-prev-hash"),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+}
+
+#[test]
+fn test_reconnect_roundtrip() {
+    let message = Reconnect {
+        new_host: Str0_255::from_str("stratum2.slushpool.com"),
+        new_port: 3336,
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = ChannelEndpointChanged { channel_id: 1 };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+}
+
+#[test]
+fn test_set_group_channel_roundtrip() {
+    let message = SetGroupChannel {
+        group_channel_id: 100,
+        channel_ids: Seq0_64k::from_vec(vec![1, 2, 3]),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+}
+
+#[test]
+fn test_update_and_close_channel_roundtrip() {
+    let message = UpdateChannel {
+        channel_id: 1,
+        nominal_hashrate: 14e12,
+        max_target: ii_bitcoin::Target::default().into(),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = UpdateChannelError {
+        channel_id: 1,
+        code: Str0_32::from_str("max-target-out-of-range"),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+
+    let message = CloseChannel {
+        channel_id: 1,
+        reason_code: Str0_32::from_str("shutdown"),
+    };
+    assert_eq!(serialization_roundtrip(message.clone()), message);
+}
@@ -33,7 +33,7 @@ use tokio_util::codec::{Framed, FramedParts};
 use ii_async_compat::prelude::*;
 use ii_wire;
 
-use crate::error::{Error, ErrorKind, Result, ResultExt};
+use crate::error::{Error, ErrorKind, Result};
 use crate::v2;
 
 pub mod codec;
@@ -114,10 +114,13 @@ impl Initiator {
         Ok(transport_mode.into_stratum_framed_stream(noise_framed_stream))
     }
 
-    /// Verify the signature of the remote static key
-    /// TODO: verify the signature of the remote static public key based on:
-    ///  - remote central authority public key (must be provided to Initiator instance upon
-    ///    creation)
+    /// Verify the signature of the remote static key. The certificate is reconstructed from the
+    /// `signature_noise_message`, remote static key and the authority public key that has been
+    /// provided upon creation of the Initiator. Both the signature and the validity period of the
+    /// certificate are checked.
+    ///
+    /// Any failure is reported as `ErrorKind::NoiseCertificate` so that the user can distinguish
+    /// an untrusted upstream from other handshake errors.
     fn verify_remote_static_key_signature(
         &mut self,
         signature_noise_message: BytesMut,
@@ -128,18 +131,17 @@ impl Initiator {
             .expect("BUG: remote static has not been provided yet");
         let remote_static_key = StaticPublicKey::from(remote_static_key);
         let signature_noise_message =
-            auth::SignatureNoiseMessage::try_from(&signature_noise_message[..])?;
+            auth::SignatureNoiseMessage::try_from(&signature_noise_message[..]).map_err(|e| {
+                ErrorKind::NoiseCertificate(format!("Malformed signature noise message: {}", e))
+            })?;
 
         let certificate = auth::Certificate::from_noise_message(
             signature_noise_message,
             remote_static_key,
             self.authority_public_key,
         );
-        certificate
-            .validate()
-            .context("Validation of certificate")?;
-
-        Ok(())
+        // NOTE: no context is attached intentionally as it would replace the error kind
+        certificate.validate()
     }
 }
 
@@ -165,8 +167,7 @@ impl handshake::Step for Initiator {
                 // <- e, ee, s, es
                 let in_msg = in_msg.ok_or(ErrorKind::Noise("No message arrived".to_string()))?;
                 let signature_len = self.handshake_state.read_message(&in_msg.inner, &mut buf)?;
-                self.verify_remote_static_key_signature(BytesMut::from(&buf[..signature_len]))
-                    .context("Certificate signature verification")?;
+                self.verify_remote_static_key_signature(BytesMut::from(&buf[..signature_len]))?;
                 handshake::StepResult::Done
             }
             _ => {
@@ -187,7 +188,8 @@ pub struct Responder {
 }
 
 impl Responder {
-    /// TODO add static keypair signature and store it inside the instance
+    /// `signature_noise_message` - serialized `auth::SignatureNoiseMessage` built from the
+    /// certificate of the `static_keypair` (see `auth::Certificate::build_noise_message()`)
     pub fn new(static_keypair: &StaticKeypair, signature_noise_message: Bytes) -> Self {
         let params: NoiseParams = PARAMS.parse().expect("BUG: cannot parse noise parameters");
 
@@ -234,8 +236,7 @@ impl handshake::Step for Responder {
                 // <- e
                 let in_msg = in_msg.ok_or(ErrorKind::Noise("No message arrived".to_string()))?;
                 self.handshake_state.read_message(&in_msg.inner, &mut buf)?;
-                // Send the signature along this message
-                // TODO: use actual signature stored inside the responder instance
+                // Send the signature of our static key along this message
                 // -> e, ee, s, es [encrypted signature]
                 let len_written = self
                     .handshake_state
@@ -433,6 +434,45 @@ pub(crate) mod test {
         assert_eq!(&message[..], &decrypted_msg, "Messages don't match");
     }
 
+    /// Verifies that the initiator refuses a responder whose static key hasn't been signed by
+    /// the expected authority
+    #[test]
+    fn test_handshake_untrusted_authority() {
+        let (signature_noise_message, _authority_keypair, static_keypair) =
+            build_serialized_signature_noise_message_and_keypairs();
+        let (_, untrusted_authority_keypair, _) =
+            build_serialized_signature_noise_message_and_keypairs();
+
+        let mut initiator = Initiator::new(untrusted_authority_keypair.public);
+        let mut responder = Responder::new(&static_keypair, signature_noise_message);
+
+        responder
+            .step(None, BytesMut::new())
+            .expect("BUG: responder failed in the first step");
+        let initiator_out_msg = match initiator
+            .step(None, BytesMut::new())
+            .expect("BUG: initiator failed in the first step")
+        {
+            handshake::StepResult::ExpectReply(msg) => msg,
+            result => panic!("BUG: unexpected initiator step result: {:?}", result),
+        };
+        let responder_out_msg = match responder
+            .step(Some(initiator_out_msg), BytesMut::new())
+            .expect("BUG: responder failed")
+        {
+            handshake::StepResult::NoMoreReply(msg) => msg,
+            result => panic!("BUG: unexpected responder step result: {:?}", result),
+        };
+
+        let error = initiator
+            .step(Some(responder_out_msg), BytesMut::new())
+            .expect_err("BUG: initiator accepted untrusted static key");
+        match error.kind() {
+            ErrorKind::NoiseCertificate(_) => {}
+            kind => panic!("BUG: unexpected error kind: {:?}", kind),
+        }
+    }
+
     fn bind_test_server() -> Option<(ii_wire::Server, ii_wire::Address)> {
         const ADDR: &'static str = "127.0.0.1";
         const MIN_PORT: u16 = 9999;
@@ -71,16 +71,16 @@ impl SignedPartHeader {
     pub fn verify_expiration(&self, now: SystemTime) -> Result<()> {
         let now_timestamp = Self::system_time_to_unix_time_u32(&now)?;
         if now_timestamp < self.valid_from {
-            return Err(ErrorKind::Noise(format!(
+            return Err(ErrorKind::NoiseCertificate(format!(
                 "Certificate not yet valid, valid from: {:?}, now: {:?}",
                 self.valid_from, now
             ))
             .into());
         }
         if now_timestamp > self.not_valid_after {
-            return Err(ErrorKind::Noise(format!(
+            return Err(ErrorKind::NoiseCertificate(format!(
                 "Certificate expired, not valid after: {:?}, now: {:?}",
-                self.valid_from, now
+                self.not_valid_after, now
             ))
             .into());
         }
@@ -161,7 +161,8 @@ impl SignedPart {
     fn verify(&self, signature: &ed25519_dalek::Signature) -> Result<()> {
         let signed_part_buf = self.serialize_to_buf()?;
         self.authority_public_key
-            .verify_strict(&signed_part_buf[..], signature)?;
+            .verify_strict(&signed_part_buf[..], signature)
+            .map_err(|e| ErrorKind::NoiseCertificate(format!("Invalid signature: {}", e)))?;
         Ok(())
     }
 
@@ -28,7 +28,7 @@ use std::fmt;
 use std::time::SystemTime;
 
 use super::{SignatureNoiseMessage, SignedPart, SignedPartHeader};
-use crate::error::{Error, Result};
+use crate::error::{Error, ErrorKind, Result};
 use crate::v2::noise::{StaticPublicKey, StaticSecretKey};
 
 /// Generates implementation for the encoded type, Display trait and the file format and
@@ -249,16 +249,47 @@ impl Certificate {
     //        }
     //    }
 
+    pub fn header(&self) -> &SignedPartHeader {
+        &self.signed_part_header
+    }
+
+    pub fn authority_public_key(&self) -> ed25519_dalek::PublicKey {
+        self.authority_public_key.clone().into_inner()
+    }
+
     /// See  https://docs.rs/ed25519-dalek/1.0.0-pre.3/ed25519_dalek/struct.PublicKey.html on
     /// details for the strict verification
     pub fn validate(&self) -> Result<()> {
+        self.validate_at(SystemTime::now())
+    }
+
+    /// Same as `validate()` but the validity period is checked against the specified `now` time
+    pub fn validate_at(&self, now: SystemTime) -> Result<()> {
         let signed_part = SignedPart::new(
             self.signed_part_header.clone(),
             self.public_key.clone().into_inner(),
-            self.authority_public_key.clone().into_inner(),
+            self.authority_public_key(),
         );
         signed_part.verify(&self.signature.clone().into_inner())?;
-        signed_part.verify_expiration(SystemTime::now())
+        signed_part.verify_expiration(now)
+    }
+
+    /// Validates the certificate and ensures that it has been issued by the specified
+    /// `authority_public_key`
+    pub fn verify_authority(
+        &self,
+        authority_public_key: &ed25519_dalek::PublicKey,
+        now: SystemTime,
+    ) -> Result<()> {
+        if self.authority_public_key() != *authority_public_key {
+            return Err(ErrorKind::NoiseCertificate(format!(
+                "Certificate issued by authority {} instead of {}",
+                EncodedEd25519PublicKey::new(self.authority_public_key()),
+                EncodedEd25519PublicKey::new(*authority_public_key)
+            ))
+            .into());
+        }
+        self.validate_at(now)
     }
 
     pub fn from_noise_message(
@@ -309,6 +340,55 @@ pub mod test {
         certificate.validate().expect("BUG: Certificate not valid!");
     }
 
+    #[test]
+    fn certificate_validate_expired() {
+        let (mut signed_part, authority_keypair, _static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        signed_part.header.valid_from -= 7200;
+        signed_part.header.not_valid_after -= 7200;
+        let signature = signed_part
+            .sign_with(&authority_keypair)
+            .expect("BUG: cannot sign");
+        let certificate = Certificate::new(signed_part, signature);
+
+        match certificate.validate().map_err(|e| e.kind()) {
+            Err(ErrorKind::NoiseCertificate(_)) => {}
+            result => panic!("BUG: expired certificate not refused: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn certificate_validate_tampered() {
+        let (mut signed_part, _authority_keypair, _static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        signed_part.header.not_valid_after += 1;
+        let certificate = Certificate::new(signed_part, signature);
+
+        match certificate.validate().map_err(|e| e.kind()) {
+            Err(ErrorKind::NoiseCertificate(_)) => {}
+            result => panic!("BUG: tampered certificate not refused: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn certificate_verify_authority() {
+        let (signed_part, authority_keypair, _static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let (_, other_authority_keypair, _, _) = build_test_signed_part_and_auth();
+        let certificate = Certificate::new(signed_part, signature);
+
+        certificate
+            .verify_authority(&authority_keypair.public, SystemTime::now())
+            .expect("BUG: Certificate not valid!");
+        match certificate
+            .verify_authority(&other_authority_keypair.public, SystemTime::now())
+            .map_err(|e| e.kind())
+        {
+            Err(ErrorKind::NoiseCertificate(_)) => {}
+            result => panic!("BUG: foreign authority not refused: {:?}", result),
+        }
+    }
+
     #[test]
     fn certificate_serialization() {
         let (signed_part, _authority_keypair, _static_keypair, signature) =
@@ -186,6 +186,7 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
             "Bytes1_255" => value.serialize(SizedSeqEmitter::<W, u8>::new(self)),
             "Bytes0_64k" => value.serialize(SizedSeqEmitter::<W, u16>::new(self)),
             "Bytes1_64k" => value.serialize(SizedSeqEmitter::<W, u16>::new(self)),
+            "Bytes0_16M" => value.serialize(SizedSeqEmitter::<W, U24>::new(self)),
 
             "Seq0_255" => value.serialize(SizedSeqEmitter::<W, u8>::new(self)),
             "Seq0_64k" => value.serialize(SizedSeqEmitter::<W, u16>::new(self)),
@@ -339,6 +340,33 @@ impl<'a, W: io::Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
     }
 }
 
+/// Helper for 24-bit length prefixes (e.g. `B0_16M` type), the value is serialized as 3 bytes in
+/// little endian
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct U24(u32);
+
+impl U24 {
+    const MAX: u32 = 0xffffff;
+}
+
+impl TryFrom<usize> for U24 {
+    type Error = ();
+
+    fn try_from(value: usize) -> StdResult<Self, ()> {
+        match u32::try_from(value) {
+            Ok(value) if value <= Self::MAX => Ok(Self(value)),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Serialize for U24 {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> StdResult<S::Ok, S::Error> {
+        let bytes = self.0.to_le_bytes();
+        (bytes[0], bytes[1], bytes[2]).serialize(serializer)
+    }
+}
+
 struct SizedSeqEmitter<'a, W, I> {
     serializer: &'a mut Serializer<W>,
     _marker: PhantomData<*const I>,
@@ -612,6 +640,12 @@ impl<'de> Deserializer<'de> {
         Ok(u32::from_le_bytes(bytes))
     }
 
+    #[inline]
+    fn read_u24(&mut self) -> Result<u32> {
+        let bytes = self.read_bytes(3)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
+
     #[inline]
     fn read_u64(&mut self) -> Result<u64> {
         let bytes = self.read_bytes(8)?;
@@ -804,6 +838,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             "Bytes1_255" => self.deserialize_sized_seq(1, 255, Deserializer::read_u8, visitor),
             "Bytes0_64k" => self.deserialize_sized_seq(0, 65535, Deserializer::read_u16, visitor),
             "Bytes1_64k" => self.deserialize_sized_seq(1, 65535, Deserializer::read_u16, visitor),
+            "Bytes0_16M" => {
+                self.deserialize_sized_seq(0, U24::MAX as usize, Deserializer::read_u24, visitor)
+            }
 
             "Seq0_255" => self.deserialize_sized_seq(0, 255, Deserializer::read_u8, visitor),
             "Seq0_64k" => self.deserialize_sized_seq(0, 65535, Deserializer::read_u16, visitor),
@@ -1249,6 +1286,12 @@ mod test {
         let bytes: Bytes0_64k = bytes.try_into().expect("Bytes1_64k constructor failure");
         let bytes = to_vec(&bytes).expect("Serialization failure");
         assert_eq!(&bytes[..2], &[0xff, 0xff]);
+
+        // Buffer with 24-bit length prefix
+        let bytes: Vec<u8> = iter::repeat(1).take(64 * 1024).collect();
+        let bytes: Bytes0_16M = bytes.try_into().expect("Bytes0_16M constructor failure");
+        let bytes = to_vec(&bytes).expect("Serialization failure");
+        assert_eq!(&bytes[..3], &[0x00, 0x00, 0x01]);
     }
 
     #[test]
@@ -1269,6 +1312,10 @@ mod test {
         let bytes: Bytes1_64k = from_slice(&bytes).expect("Deserialization failure");
         assert_eq!(&*bytes, &[1, 2, 3]);
 
+        let bytes = [3, 0, 0, 1, 2, 3];
+        let bytes: Bytes0_16M = from_slice(&bytes).expect("Deserialization failure");
+        assert_eq!(&*bytes, &[1, 2, 3]);
+
         // Zero-sized buffer
         let bytes = [0];
         let s: Bytes0_255 = from_slice(&bytes).expect("Deserialization failure");
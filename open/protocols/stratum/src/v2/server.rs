@@ -0,0 +1,678 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Reference Stratum V2 pool server
+//!
+//! The server speaks the mining protocol over standard channels only. Jobs are generated from a
+//! `JobTemplate` (either a fixed block header or a synthetic sequence of headers), submitted shares
+//! are validated against the channel target and each channel may be retargeted by a simple
+//! variable difficulty algorithm. It serves as a local stand-in for a real pool when testing
+//! mining clients and proxies.
+
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use bitcoin_hashes::{sha256d, Hash};
+
+use ii_async_compat::prelude::*;
+use ii_async_compat::{select, tokio};
+use ii_bitcoin::MeetsTarget;
+use ii_logging::macros::*;
+use ii_wire::Connection;
+
+use super::framing::{Frame, Header};
+use super::messages::*;
+use super::types::*;
+use super::{build_message_from_frame, Framing, Handler};
+use crate::error::{Error, Result};
+use crate::BIP320_N_VERSION_MASK;
+
+#[cfg(test)]
+mod test;
+
+/// Identifier of the mining protocol in `SetupConnection`
+pub const MINING_PROTOCOL: u8 = 0;
+/// The only protocol version the server speaks
+pub const PROTOCOL_VERSION: u16 = 2;
+/// Difficulty assigned to newly opened channels when vardiff doesn't say otherwise
+pub const DEFAULT_DIFFICULTY: usize = 64;
+/// How often a new block (new previous hash) is announced to all channels
+pub const DEFAULT_JOB_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Header fields used by synthetic templates (compact form of difficulty 1 target)
+const SYNTHETIC_VERSION: u32 = 0x20000000;
+const SYNTHETIC_NBITS: u32 = 0x1d00ffff;
+
+/// Expected number of hashes needed to find a share at difficulty 1
+const HASHES_PER_DIFFICULTY_1: f64 = 4294967296.0;
+/// Single retarget never changes the difficulty by more than this factor
+const MAX_RETARGET_FACTOR: f64 = 4.0;
+
+/// Checks that a downstream supplied `max_target` can be used as a channel target. Targets below
+/// the one of maximum pool difficulty (including zero) don't have a representable difficulty.
+fn is_valid_max_target(max_target: ii_bitcoin::Target) -> bool {
+    max_target >= ii_bitcoin::Target::from_pool_difficulty(usize::max_value())
+}
+
+/// Block header fields shared by all jobs of a single block
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockTemplate {
+    pub version: u32,
+    pub prev_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub ntime: u32,
+    pub nbits: u32,
+}
+
+impl BlockTemplate {
+    /// Build template from an existing block header (e.g. one of `ii_bitcoin::TEST_BLOCKS`)
+    pub fn from_block_header(header: &ii_bitcoin::BlockHeader) -> Self {
+        Self {
+            version: header.version,
+            prev_hash: header.previous_hash,
+            merkle_root: header.merkle_root,
+            ntime: header.time,
+            nbits: header.bits,
+        }
+    }
+
+    fn block_header(&self, version: u32, ntime: u32, nonce: u32) -> ii_bitcoin::BlockHeader {
+        ii_bitcoin::BlockHeader {
+            version,
+            previous_hash: self.prev_hash,
+            merkle_root: self.merkle_root,
+            time: ntime,
+            bits: self.nbits,
+            nonce,
+        }
+    }
+}
+
+/// Source of block templates for mining jobs
+#[derive(Clone, Debug)]
+pub enum JobTemplate {
+    /// Every block uses the same header fields and all channels get the same merkle root. This is
+    /// handy for tests that submit known solutions.
+    Static(BlockTemplate),
+    /// Every block gets a fresh previous hash and each channel gets its own merkle root derived
+    /// from its extranonce prefix, so that no two channels search the same space
+    Synthetic,
+}
+
+impl JobTemplate {
+    fn build(&self, sequence: u64) -> BlockTemplate {
+        match self {
+            Self::Static(template) => *template,
+            Self::Synthetic => {
+                let ntime = unix_time();
+                let mut seed = ntime.to_le_bytes().to_vec();
+                seed.extend_from_slice(&sequence.to_le_bytes());
+                let prev_hash = sha256d::Hash::hash(&seed).into_inner();
+                BlockTemplate {
+                    version: SYNTHETIC_VERSION,
+                    prev_hash,
+                    merkle_root: sha256d::Hash::hash(&prev_hash).into_inner(),
+                    ntime,
+                    nbits: SYNTHETIC_NBITS,
+                }
+            }
+        }
+    }
+
+    fn channel_merkle_root(&self, template: &BlockTemplate, extranonce_prefix: &[u8]) -> [u8; 32] {
+        match self {
+            Self::Static(_) => template.merkle_root,
+            Self::Synthetic => {
+                let mut coinbase = template.merkle_root.to_vec();
+                coinbase.extend_from_slice(extranonce_prefix);
+                sha256d::Hash::hash(&coinbase).into_inner()
+            }
+        }
+    }
+}
+
+/// Variable difficulty settings, the difficulty of each channel is adjusted so that it submits
+/// approximately `shares_per_minute` shares
+#[derive(Clone, Debug)]
+pub struct VardiffConfig {
+    pub shares_per_minute: f64,
+    /// Minimum time between two adjustments of a single channel
+    pub retarget_interval: Duration,
+    pub min_difficulty: usize,
+    pub max_difficulty: usize,
+}
+
+impl VardiffConfig {
+    fn clamp(&self, difficulty: f64) -> usize {
+        let difficulty = difficulty
+            .max(self.min_difficulty as f64)
+            .min(self.max_difficulty as f64);
+        cmp::max(difficulty as usize, 1)
+    }
+
+    /// Difficulty at which a device with `hashrate` (in hashes per second) submits the desired
+    /// number of shares
+    fn difficulty_for_hashrate(&self, hashrate: f64) -> usize {
+        self.clamp(hashrate * 60.0 / (self.shares_per_minute * HASHES_PER_DIFFICULTY_1))
+    }
+
+    /// New difficulty based on the share rate observed at `difficulty`
+    fn retarget(&self, difficulty: usize, accepted: u32, elapsed: Duration) -> usize {
+        let shares_per_minute = accepted as f64 * 60.0 / elapsed.as_secs_f64();
+        let factor = (shares_per_minute / self.shares_per_minute)
+            .max(1.0 / MAX_RETARGET_FACTOR)
+            .min(MAX_RETARGET_FACTOR);
+        self.clamp(difficulty as f64 * factor)
+    }
+}
+
+impl Default for VardiffConfig {
+    fn default() -> Self {
+        Self {
+            shares_per_minute: 20.0,
+            retarget_interval: Duration::from_secs(60),
+            min_difficulty: 1,
+            max_difficulty: usize::max_value(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Difficulty of newly opened channels
+    pub initial_difficulty: usize,
+    pub job_template: JobTemplate,
+    pub job_interval: Duration,
+    /// Fixed difficulty is used when vardiff is not configured
+    pub vardiff: Option<VardiffConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            initial_difficulty: DEFAULT_DIFFICULTY,
+            job_template: JobTemplate::Synthetic,
+            job_interval: DEFAULT_JOB_INTERVAL,
+            vardiff: None,
+        }
+    }
+}
+
+/// Accepts downstream connections and serves each of them by an independent session
+#[derive(Debug)]
+pub struct Server {
+    config: Arc<Config>,
+    /// Source of unique extranonce prefixes among all channels of all sessions
+    extranonce_counter: Arc<AtomicU32>,
+}
+
+impl Server {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config: Arc::new(config),
+            extranonce_counter: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Serve all connections accepted by `listener` until it is exhausted
+    pub async fn run(&self, mut listener: ii_wire::Server) {
+        while let Some(stream) = listener.next().await {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Pool: cannot accept connection: {}", e);
+                    continue;
+                }
+            };
+            let peer_addr = match stream.peer_addr() {
+                Ok(peer_addr) => peer_addr,
+                Err(e) => {
+                    warn!("Pool: cannot get peer address: {}", e);
+                    continue;
+                }
+            };
+            let session = Session::new(self.config.clone(), self.extranonce_counter.clone());
+            tokio::spawn(async move {
+                info!("Pool: accepted connection from {}", peer_addr);
+                match session.run(stream).await {
+                    Ok(()) => info!("Pool: connection from {} closed", peer_addr),
+                    Err(e) => info!("Pool: connection from {} terminated: {}", peer_addr, e),
+                }
+            });
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Job {
+    template: BlockTemplate,
+    /// Channel target at the time the job was sent out
+    target: ii_bitcoin::Target,
+    /// The job belongs to a previous block
+    stale: bool,
+}
+
+#[derive(Debug)]
+struct Channel {
+    user: String,
+    target: ii_bitcoin::Target,
+    max_target: ii_bitcoin::Target,
+    extranonce_prefix: Vec<u8>,
+    jobs: HashMap<u32, Job>,
+    /// (job_id, nonce, ntime, version) of all shares accepted for the current block
+    submits: HashSet<(u32, u32, u32, u32)>,
+    accepted_since_retarget: u32,
+    last_retarget: Instant,
+}
+
+/// State of a single downstream connection. Responses are collected in `outgoing` by the message
+/// handlers and sent out by the session loop.
+struct Session {
+    config: Arc<Config>,
+    extranonce_counter: Arc<AtomicU32>,
+    is_setup: bool,
+    finished: bool,
+    channels: HashMap<u32, Channel>,
+    next_channel_id: u32,
+    next_job_id: u32,
+    block_sequence: u64,
+    template: BlockTemplate,
+    outgoing: Vec<Frame>,
+}
+
+impl Session {
+    fn new(config: Arc<Config>, extranonce_counter: Arc<AtomicU32>) -> Self {
+        let template = config.job_template.build(0);
+        Self {
+            config,
+            extranonce_counter,
+            is_setup: false,
+            finished: false,
+            channels: HashMap::new(),
+            next_channel_id: 0,
+            next_job_id: 0,
+            block_sequence: 0,
+            template,
+            outgoing: Vec::new(),
+        }
+    }
+
+    async fn run(mut self, stream: tokio::net::TcpStream) -> Result<()> {
+        let (mut sink, mut stream) = Connection::<Framing>::new(stream).into_inner().split();
+        let job_interval = self.config.job_interval;
+        let mut job_timer =
+            tokio::time::interval_at(tokio::time::Instant::now() + job_interval, job_interval);
+
+        while !self.finished {
+            select! {
+                frame = stream.next().fuse() => match frame {
+                    Some(frame) => {
+                        let message = build_message_from_frame(frame?)?;
+                        message.accept(&mut self).await;
+                    }
+                    None => break,
+                },
+                _ = job_timer.tick().fuse() => self.new_block(),
+            }
+            for frame in self.outgoing.drain(..) {
+                sink.send(frame).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn send<M>(&mut self, message: M)
+    where
+        M: TryInto<Frame, Error = Error>,
+    {
+        let frame = message
+            .try_into()
+            .expect("BUG: cannot convert message to frame");
+        self.outgoing.push(frame);
+    }
+
+    fn next_job_id(&mut self) -> u32 {
+        let job_id = self.next_job_id;
+        self.next_job_id = self.next_job_id.wrapping_add(1);
+        job_id
+    }
+
+    /// Moves all channels to a new block, jobs of the previous block are kept only to tell stale
+    /// shares from invalid ones
+    fn new_block(&mut self) {
+        self.block_sequence += 1;
+        self.template = self.config.job_template.build(self.block_sequence);
+
+        let mut channel_ids: Vec<_> = self.channels.keys().cloned().collect();
+        channel_ids.sort();
+        for channel_id in channel_ids {
+            let channel = self
+                .channels
+                .get_mut(&channel_id)
+                .expect("BUG: missing channel");
+            channel.jobs.retain(|_, job| !job.stale);
+            channel.jobs.values_mut().for_each(|job| job.stale = true);
+            channel.submits.clear();
+
+            // Channels that have stopped submitting shares are retargeted here
+            self.retarget(channel_id);
+            self.send_job(channel_id);
+        }
+    }
+
+    /// Sends a job for the current block as a future job immediately activated by
+    /// `SetNewPrevHash`
+    fn send_job(&mut self, channel_id: u32) {
+        let job_id = self.next_job_id();
+        let channel = self
+            .channels
+            .get_mut(&channel_id)
+            .expect("BUG: missing channel");
+        let template = BlockTemplate {
+            merkle_root: self
+                .config
+                .job_template
+                .channel_merkle_root(&self.template, &channel.extranonce_prefix),
+            ..self.template
+        };
+        channel.jobs.insert(
+            job_id,
+            Job {
+                template,
+                target: channel.target,
+                stale: false,
+            },
+        );
+
+        self.send(NewMiningJob {
+            channel_id,
+            job_id,
+            future_job: true,
+            version: template.version,
+            merkle_root: Uint256Bytes(template.merkle_root),
+        });
+        self.send(SetNewPrevHash {
+            channel_id,
+            job_id,
+            prev_hash: Uint256Bytes(template.prev_hash),
+            min_ntime: template.ntime,
+            nbits: template.nbits,
+        });
+    }
+
+    /// Adjusts channel difficulty when vardiff is enabled and the retarget interval has elapsed
+    fn retarget(&mut self, channel_id: u32) {
+        let config = self.config.clone();
+        let vardiff = match &config.vardiff {
+            Some(vardiff) => vardiff,
+            None => return,
+        };
+        let channel = self
+            .channels
+            .get_mut(&channel_id)
+            .expect("BUG: missing channel");
+        let elapsed = channel.last_retarget.elapsed();
+        if elapsed < vardiff.retarget_interval {
+            return;
+        }
+
+        let difficulty = vardiff.retarget(
+            cmp::max(channel.target.get_difficulty(), 1),
+            channel.accepted_since_retarget,
+            elapsed,
+        );
+        channel.accepted_since_retarget = 0;
+        channel.last_retarget = Instant::now();
+
+        let target = cmp::min(
+            ii_bitcoin::Target::from_pool_difficulty(difficulty),
+            channel.max_target,
+        );
+        if target == channel.target {
+            return;
+        }
+        debug!(
+            "Pool: channel {} ({}) retargeted to difficulty {}",
+            channel_id,
+            channel.user,
+            target.get_difficulty()
+        );
+        channel.target = target;
+        self.send(SetTarget {
+            channel_id,
+            max_target: target.into(),
+        });
+    }
+
+    /// Validates the share and returns its difficulty or error code for `SubmitSharesError`
+    fn validate_share(
+        &mut self,
+        share: &SubmitSharesStandard,
+    ) -> std::result::Result<u32, &'static str> {
+        let channel = self
+            .channels
+            .get_mut(&share.channel_id)
+            .ok_or("invalid-channel-id")?;
+        let job = *channel.jobs.get(&share.job_id).ok_or("invalid-job-id")?;
+        if job.stale {
+            return Err("stale-share");
+        }
+        if (share.version ^ job.template.version) & !BIP320_N_VERSION_MASK != 0 {
+            return Err("invalid-version");
+        }
+        if share.ntime < job.template.ntime {
+            return Err("invalid-timestamp");
+        }
+        let submit = (share.job_id, share.nonce, share.ntime, share.version);
+        if channel.submits.contains(&submit) {
+            return Err("duplicate-share");
+        }
+
+        // Shares computed before the latest `SetTarget` reached the miner are accepted at the
+        // original target
+        let target = cmp::max(job.target, channel.target);
+        let hash = job
+            .template
+            .block_header(share.version, share.ntime, share.nonce)
+            .hash();
+        if !hash.meets(&target) {
+            return Err("difficulty-too-low");
+        }
+
+        channel.submits.insert(submit);
+        channel.accepted_since_retarget += 1;
+        Ok(cmp::min(target.get_difficulty(), u32::max_value() as usize) as u32)
+    }
+}
+
+#[async_trait]
+impl Handler for Session {
+    async fn visit_setup_connection(&mut self, _header: &Header, payload: &SetupConnection) {
+        let code = if payload.protocol != MINING_PROTOCOL {
+            "unsupported-protocol"
+        } else if payload.min_version > PROTOCOL_VERSION || payload.max_version < PROTOCOL_VERSION {
+            "protocol-version-mismatch"
+        } else {
+            self.is_setup = true;
+            self.send(SetupConnectionSuccess {
+                used_version: PROTOCOL_VERSION,
+                flags: 0,
+            });
+            return;
+        };
+        self.send(SetupConnectionError {
+            flags: 0,
+            code: Str0_255::from_str(code),
+        });
+        self.finished = true;
+    }
+
+    async fn visit_open_standard_mining_channel(
+        &mut self,
+        _header: &Header,
+        payload: &OpenStandardMiningChannel,
+    ) {
+        if !self.is_setup {
+            self.send(OpenStandardMiningChannelError {
+                req_id: payload.req_id,
+                code: Str0_32::from_str("connection-not-set-up"),
+            });
+            return;
+        }
+        let max_target: ii_bitcoin::Target = payload.max_target.into();
+        if !is_valid_max_target(max_target) {
+            self.send(OpenStandardMiningChannelError {
+                req_id: payload.req_id,
+                code: Str0_32::from_str("invalid-max-target"),
+            });
+            return;
+        }
+
+        let difficulty = match &self.config.vardiff {
+            Some(vardiff) if payload.nominal_hashrate > 0.0 => {
+                vardiff.difficulty_for_hashrate(payload.nominal_hashrate as f64)
+            }
+            _ => self.config.initial_difficulty,
+        };
+        let target = cmp::min(
+            ii_bitcoin::Target::from_pool_difficulty(difficulty),
+            max_target,
+        );
+
+        let channel_id = self.next_channel_id;
+        self.next_channel_id = self.next_channel_id.wrapping_add(1);
+        let extranonce_prefix = self
+            .extranonce_counter
+            .fetch_add(1, Ordering::Relaxed)
+            .to_be_bytes()
+            .to_vec();
+
+        info!(
+            "Pool: opened channel {} for '{}' with difficulty {}",
+            channel_id,
+            payload.user.to_string(),
+            target.get_difficulty()
+        );
+        self.channels.insert(
+            channel_id,
+            Channel {
+                user: payload.user.to_string(),
+                target,
+                max_target,
+                extranonce_prefix: extranonce_prefix.clone(),
+                jobs: HashMap::new(),
+                submits: HashSet::new(),
+                accepted_since_retarget: 0,
+                last_retarget: Instant::now(),
+            },
+        );
+        self.send(OpenStandardMiningChannelSuccess {
+            req_id: payload.req_id,
+            channel_id,
+            target: target.into(),
+            extranonce_prefix: Bytes0_32::from_vec(extranonce_prefix),
+            group_channel_id: 0,
+        });
+        self.send_job(channel_id);
+    }
+
+    async fn visit_update_channel(&mut self, _header: &Header, payload: &UpdateChannel) {
+        let max_target: ii_bitcoin::Target = payload.max_target.into();
+        if !is_valid_max_target(max_target) {
+            self.send(UpdateChannelError {
+                channel_id: payload.channel_id,
+                code: Str0_32::from_str("invalid-max-target"),
+            });
+            return;
+        }
+        let channel = match self.channels.get_mut(&payload.channel_id) {
+            Some(channel) => channel,
+            None => {
+                self.send(UpdateChannelError {
+                    channel_id: payload.channel_id,
+                    code: Str0_32::from_str("invalid-channel-id"),
+                });
+                return;
+            }
+        };
+        channel.max_target = max_target;
+        if channel.target > channel.max_target {
+            channel.target = channel.max_target;
+            let max_target = channel.target.into();
+            self.send(SetTarget {
+                channel_id: payload.channel_id,
+                max_target,
+            });
+        }
+    }
+
+    async fn visit_close_channel(&mut self, _header: &Header, payload: &CloseChannel) {
+        if self.channels.remove(&payload.channel_id).is_some() {
+            info!(
+                "Pool: channel {} closed by downstream: {}",
+                payload.channel_id,
+                payload.reason_code.to_string()
+            );
+        }
+    }
+
+    async fn visit_submit_shares_standard(
+        &mut self,
+        _header: &Header,
+        payload: &SubmitSharesStandard,
+    ) {
+        match self.validate_share(payload) {
+            Ok(difficulty) => {
+                self.send(SubmitSharesSuccess {
+                    channel_id: payload.channel_id,
+                    last_seq_num: payload.seq_num,
+                    new_submits_accepted_count: 1,
+                    new_shares_sum: difficulty,
+                });
+                self.retarget(payload.channel_id);
+            }
+            Err(code) => {
+                debug!(
+                    "Pool: rejected share {} on channel {}: {}",
+                    payload.seq_num, payload.channel_id, code
+                );
+                self.send(SubmitSharesError {
+                    channel_id: payload.channel_id,
+                    seq_num: payload.seq_num,
+                    code: Str0_32::from_str(code),
+                });
+            }
+        }
+    }
+}
+
+fn unix_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("BUG: system time before UNIX epoch")
+        .as_secs() as u32
+}
@@ -0,0 +1,266 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use std::convert::TryFrom;
+
+use super::*;
+use crate::test_utils::v2::*;
+use crate::v2::framing;
+use crate::v2::messages::MessageType;
+
+/// Starts a pool serving a single static block template with difficulty 1 and returns address of
+/// its listener
+fn start_pool(block: &ii_bitcoin::TestBlock) -> std::net::SocketAddr {
+    let listener = ii_wire::Server::bind(("127.0.0.1", 0)).expect("cannot bind pool listener");
+    let addr = listener.local_addr().expect("cannot get pool address");
+    let server = Server::new(Config {
+        initial_difficulty: 1,
+        job_template: JobTemplate::Static(BlockTemplate {
+            version: block.version,
+            prev_hash: block.previous_hash.into_inner(),
+            merkle_root: block.merkle_root.into_inner(),
+            ntime: block.time,
+            nbits: block.bits,
+        }),
+        // Make sure no new block interferes with the test
+        job_interval: Duration::from_secs(3600),
+        vardiff: None,
+    });
+    tokio::spawn(async move { server.run(listener).await });
+    addr
+}
+
+async fn send<M>(connection: &mut Connection<Framing>, message: M)
+where
+    M: TryInto<Frame, Error = Error>,
+{
+    let frame = message.try_into().expect("cannot build frame");
+    connection.send(frame).await.expect("cannot send frame");
+}
+
+async fn receive<M>(connection: &mut Connection<Framing>, msg_type: MessageType) -> M
+where
+    M: TryFrom<Frame, Error = Error>,
+{
+    let frame = connection
+        .next()
+        .await
+        .expect("connection closed")
+        .expect("cannot receive frame");
+    assert_eq!(frame.header.msg_type, msg_type as framing::MsgType);
+    M::try_from(frame).expect("cannot deserialize message")
+}
+
+fn build_share(
+    job: &NewMiningJob,
+    block: &ii_bitcoin::TestBlock,
+    nonce: u32,
+) -> SubmitSharesStandard {
+    SubmitSharesStandard {
+        channel_id: job.channel_id,
+        seq_num: 0,
+        job_id: job.job_id,
+        nonce,
+        ntime: block.time,
+        version: block.version,
+    }
+}
+
+#[tokio::test]
+async fn test_pool_share_validation() {
+    let block = ii_bitcoin::TEST_BLOCKS[0];
+    let addr = start_pool(&block);
+    let mut connection = Connection::<Framing>::connect(addr)
+        .await
+        .expect("cannot connect to pool");
+
+    send(&mut connection, build_setup_connection()).await;
+    let success: SetupConnectionSuccess =
+        receive(&mut connection, MessageType::SetupConnectionSuccess).await;
+    assert_eq!(success.used_version, PROTOCOL_VERSION);
+
+    send(&mut connection, build_open_channel()).await;
+    let channel: OpenStandardMiningChannelSuccess = receive(
+        &mut connection,
+        MessageType::OpenStandardMiningChannelSuccess,
+    )
+    .await;
+    assert_eq!(ii_bitcoin::Target::from(channel.target), Default::default());
+
+    let job: NewMiningJob = receive(&mut connection, MessageType::NewMiningJob).await;
+    assert_eq!(job.channel_id, channel.channel_id);
+    assert!(job.future_job);
+    assert_eq!(
+        job.merkle_root,
+        Uint256Bytes(block.merkle_root.into_inner())
+    );
+    let prev_hash: SetNewPrevHash = receive(&mut connection, MessageType::SetNewPrevHash).await;
+    assert_eq!(prev_hash.job_id, job.job_id);
+    assert_eq!(prev_hash.nbits, block.bits);
+
+    // Real block solution must meet difficulty 1
+    send(&mut connection, build_share(&job, &block, block.nonce)).await;
+    let accepted: SubmitSharesSuccess =
+        receive(&mut connection, MessageType::SubmitSharesSuccess).await;
+    assert_eq!(accepted.new_submits_accepted_count, 1);
+    assert_eq!(accepted.new_shares_sum, 1);
+
+    let rejected_shares = vec![
+        (build_share(&job, &block, block.nonce), "duplicate-share"),
+        (
+            build_share(&job, &block, block.nonce.wrapping_add(1)),
+            "difficulty-too-low",
+        ),
+        (
+            SubmitSharesStandard {
+                job_id: job.job_id + 1,
+                ..build_share(&job, &block, block.nonce)
+            },
+            "invalid-job-id",
+        ),
+        (
+            SubmitSharesStandard {
+                channel_id: job.channel_id + 1,
+                ..build_share(&job, &block, block.nonce)
+            },
+            "invalid-channel-id",
+        ),
+    ];
+    for (seq_num, (share, expected_code)) in rejected_shares.into_iter().enumerate() {
+        let seq_num = seq_num as u32 + 1;
+        send(&mut connection, SubmitSharesStandard { seq_num, ..share }).await;
+        let error: SubmitSharesError =
+            receive(&mut connection, MessageType::SubmitSharesError).await;
+        assert_eq!(error.seq_num, seq_num);
+        assert_eq!(error.code.to_string(), expected_code);
+    }
+}
+
+#[tokio::test]
+async fn test_pool_unsupported_protocol() {
+    let addr = start_pool(&ii_bitcoin::TEST_BLOCKS[0]);
+    let mut connection = Connection::<Framing>::connect(addr)
+        .await
+        .expect("cannot connect to pool");
+
+    send(
+        &mut connection,
+        SetupConnection {
+            protocol: MINING_PROTOCOL + 1,
+            ..build_setup_connection()
+        },
+    )
+    .await;
+    let error: SetupConnectionError =
+        receive(&mut connection, MessageType::SetupConnectionError).await;
+    assert_eq!(error.code.to_string(), "unsupported-protocol");
+    assert!(
+        connection.next().await.is_none(),
+        "pool must close the connection"
+    );
+}
+
+/// Connects to the pool and sets up the mining protocol connection
+async fn setup_connection(addr: std::net::SocketAddr) -> Connection<Framing> {
+    let mut connection = Connection::<Framing>::connect(addr)
+        .await
+        .expect("cannot connect to pool");
+    send(&mut connection, build_setup_connection()).await;
+    let _: SetupConnectionSuccess =
+        receive(&mut connection, MessageType::SetupConnectionSuccess).await;
+    connection
+}
+
+#[tokio::test]
+async fn test_pool_open_channel_invalid_max_target() {
+    let addr = start_pool(&ii_bitcoin::TEST_BLOCKS[0]);
+    let mut connection = setup_connection(addr).await;
+
+    send(
+        &mut connection,
+        OpenStandardMiningChannel {
+            max_target: Uint256Bytes([0; 32]),
+            ..build_open_channel()
+        },
+    )
+    .await;
+    let error: OpenStandardMiningChannelError =
+        receive(&mut connection, MessageType::OpenStandardMiningChannelError).await;
+    assert_eq!(error.req_id, build_open_channel().req_id);
+    assert_eq!(error.code.to_string(), "invalid-max-target");
+}
+
+#[tokio::test]
+async fn test_pool_update_channel_invalid_max_target() {
+    let block = ii_bitcoin::TEST_BLOCKS[0];
+    let addr = start_pool(&block);
+    let mut connection = setup_connection(addr).await;
+
+    send(&mut connection, build_open_channel()).await;
+    let channel: OpenStandardMiningChannelSuccess = receive(
+        &mut connection,
+        MessageType::OpenStandardMiningChannelSuccess,
+    )
+    .await;
+    let job: NewMiningJob = receive(&mut connection, MessageType::NewMiningJob).await;
+    let _: SetNewPrevHash = receive(&mut connection, MessageType::SetNewPrevHash).await;
+
+    send(
+        &mut connection,
+        UpdateChannel {
+            channel_id: channel.channel_id,
+            nominal_hashrate: 1e9,
+            max_target: Uint256Bytes([0; 32]),
+        },
+    )
+    .await;
+    let error: UpdateChannelError = receive(&mut connection, MessageType::UpdateChannelError).await;
+    assert_eq!(error.channel_id, channel.channel_id);
+    assert_eq!(error.code.to_string(), "invalid-max-target");
+
+    // The channel keeps its original target, a difficulty 1 share is still accepted
+    send(&mut connection, build_share(&job, &block, block.nonce)).await;
+    let _: SubmitSharesSuccess = receive(&mut connection, MessageType::SubmitSharesSuccess).await;
+}
+
+#[test]
+fn test_vardiff_retarget() {
+    let vardiff = VardiffConfig {
+        shares_per_minute: 10.0,
+        retarget_interval: Duration::from_secs(60),
+        min_difficulty: 1,
+        max_difficulty: 1024,
+    };
+    let minute = Duration::from_secs(60);
+
+    assert_eq!(vardiff.retarget(64, 10, minute), 64);
+    assert_eq!(vardiff.retarget(64, 20, minute), 128);
+    assert_eq!(vardiff.retarget(64, 5, minute), 32);
+    // Adjustment is limited by maximum retarget factor and by difficulty bounds
+    assert_eq!(vardiff.retarget(64, 1000, minute), 256);
+    assert_eq!(vardiff.retarget(64, 0, minute), 16);
+    assert_eq!(vardiff.retarget(512, 1000, minute), 1024);
+    assert_eq!(vardiff.retarget(2, 0, minute), 1);
+
+    // 10 shares per minute at difficulty 1 requires ~716 MH/s
+    assert_eq!(vardiff.difficulty_for_hashrate(716e6 * 64.0), 64);
+}
@@ -0,0 +1,90 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Template Distribution Protocol - allows a template provider (typically running next to a
+//! bitcoin node) to distribute block templates to pools or job declarators
+
+pub mod messages;
+
+use crate::v2::framing;
+
+use async_trait::async_trait;
+
+/// Protocol associates a custom handler with it
+pub struct Protocol;
+impl crate::Protocol for Protocol {
+    type Handler = dyn Handler;
+    type Header = framing::Header;
+}
+
+/// Specifies all template distribution messages to be visited
+#[async_trait]
+pub trait Handler: 'static + Send {
+    async fn visit_coinbase_output_data_size(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::CoinbaseOutputDataSize,
+    ) {
+    }
+
+    async fn visit_new_template(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::NewTemplate,
+    ) {
+    }
+
+    async fn visit_set_new_prev_hash(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetNewPrevHash,
+    ) {
+    }
+
+    async fn visit_request_transaction_data(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::RequestTransactionData,
+    ) {
+    }
+
+    async fn visit_request_transaction_data_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::RequestTransactionDataSuccess,
+    ) {
+    }
+
+    async fn visit_request_transaction_data_error(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::RequestTransactionDataError,
+    ) {
+    }
+
+    async fn visit_submit_solution(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SubmitSolution,
+    ) {
+    }
+}
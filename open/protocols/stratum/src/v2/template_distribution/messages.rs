@@ -0,0 +1,202 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+#[cfg(not(feature = "v2json"))]
+use crate::v2::serialization;
+use crate::{
+    error::{Error, Result},
+    v2::{error, extensions, framing, types::*},
+    AnyPayload, Message,
+};
+use async_trait::async_trait;
+use packed_struct::prelude::*;
+use packed_struct_codegen::PrimitiveEnum_u8;
+use serde;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "v2json")]
+use serde_json as serialization;
+use std::convert::TryFrom;
+
+use ii_logging::macros::*;
+
+use super::Protocol;
+
+#[cfg(test)]
+mod test;
+
+/// Generates conversion for template distribution protocol messages. The messages are part of
+/// the base protocol, they are distinguished by their message type only.
+macro_rules! impl_template_distribution_message_conversion {
+    ($message:tt, $handler_fn:tt) => {
+        impl_message_conversion!(extensions::BASE, $message, false, $handler_fn);
+    };
+}
+
+/// All messages recognized by the template distribution protocol
+#[derive(PrimitiveEnum_u8, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MessageType {
+    CoinbaseOutputDataSize = 0x70,
+    NewTemplate = 0x71,
+    SetNewPrevHash = 0x72,
+    RequestTransactionData = 0x73,
+    RequestTransactionDataSuccess = 0x74,
+    RequestTransactionDataError = 0x75,
+    SubmitSolution = 0x76,
+}
+
+/// Tells the template provider how much space (in bytes) has to be reserved in the coinbase
+/// transaction for additional outputs added by the client
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CoinbaseOutputDataSize {
+    pub coinbase_output_max_additional_size: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NewTemplate {
+    /// Unique identifier of the template on the template provider side
+    pub template_id: u64,
+    /// The template is to be mined once a matching `SetNewPrevHash` arrives
+    pub future_template: bool,
+    pub version: u32,
+    pub coinbase_tx_version: u32,
+    /// Up to 8 bytes (not including the length byte) of block height are expected to be here
+    pub coinbase_prefix: Bytes0_255,
+    pub coinbase_tx_input_sequence: u32,
+    /// Value (in satoshis) available for spending in coinbase outputs added by the client
+    pub coinbase_tx_value_remaining: u64,
+    pub coinbase_tx_outputs_count: u32,
+    /// Serialized outputs that have to be included in the coinbase transaction
+    pub coinbase_tx_outputs: Bytes0_64k,
+    pub coinbase_tx_locktime: u32,
+    /// Merkle path hashes ordered from deepest
+    pub merkle_path: Seq0_255<Uint256Bytes>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetNewPrevHash {
+    /// Template that is to be mined on top of the new previous hash
+    pub template_id: u64,
+    pub prev_hash: Uint256Bytes,
+    pub header_timestamp: u32,
+    pub nbits: u32,
+    /// Network target for convenience (derived from `nbits`)
+    pub target: Uint256Bytes,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RequestTransactionData {
+    pub template_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RequestTransactionDataSuccess {
+    pub template_id: u64,
+    /// Extra data that the template provider passes along (reserved for future use)
+    pub excess_data: Bytes0_64k,
+    /// Full serialized transactions (excluding the coinbase) in the block order
+    pub transaction_list: Seq0_64k<Bytes0_16M>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RequestTransactionDataError {
+    pub template_id: u64,
+    pub error_code: Str0_255,
+}
+
+/// Solution of a template found by the client, the template provider is expected to assemble
+/// and broadcast the block
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SubmitSolution {
+    pub template_id: u64,
+    pub version: u32,
+    pub header_timestamp: u32,
+    pub header_nonce: u32,
+    /// Full serialized coinbase transaction
+    pub coinbase_tx: Bytes0_64k,
+}
+
+impl_template_distribution_message_conversion!(
+    CoinbaseOutputDataSize,
+    visit_coinbase_output_data_size
+);
+impl_template_distribution_message_conversion!(NewTemplate, visit_new_template);
+impl_template_distribution_message_conversion!(SetNewPrevHash, visit_set_new_prev_hash);
+impl_template_distribution_message_conversion!(
+    RequestTransactionData,
+    visit_request_transaction_data
+);
+impl_template_distribution_message_conversion!(
+    RequestTransactionDataSuccess,
+    visit_request_transaction_data_success
+);
+impl_template_distribution_message_conversion!(
+    RequestTransactionDataError,
+    visit_request_transaction_data_error
+);
+impl_template_distribution_message_conversion!(SubmitSolution, visit_submit_solution);
+
+/// Consumes `frame` and produces a Message object based on the payload type
+pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protocol>> {
+    trace!(
+        "V2: building template distribution message from frame {:x?}",
+        frame
+    );
+
+    // Payload that already contains deserialized message can be returned directly
+    // TODO this is duplicate chunk from v2::build_message_from_frame()
+    if frame.payload.is_serializable() {
+        let (header, payload) = frame.split();
+        let serializable_payload = payload
+            .into_serializable()
+            .expect("BUG: cannot convert payload into serializable");
+
+        return Ok(Message {
+            header,
+            payload: serializable_payload,
+        });
+    }
+    // Header will be consumed by the subsequent transformation of the frame into the actual
+    // payload for further handling. Therefore we create a copy for constructing a
+    // Message<Protocol>
+    let header = frame.header.clone();
+    // Deserialize the payload based on its type specified in the header
+    let payload: Box<dyn AnyPayload<Protocol>> = match MessageType::from_primitive(
+        frame.header.msg_type,
+    )
+    .ok_or(error::ErrorKind::UnknownMessage(
+        format!("Unexpected payload type, full header: {:x?}", frame.header).into(),
+    ))? {
+        MessageType::CoinbaseOutputDataSize => Box::new(CoinbaseOutputDataSize::try_from(frame)?),
+        MessageType::NewTemplate => Box::new(NewTemplate::try_from(frame)?),
+        MessageType::SetNewPrevHash => Box::new(SetNewPrevHash::try_from(frame)?),
+        MessageType::RequestTransactionData => Box::new(RequestTransactionData::try_from(frame)?),
+        MessageType::RequestTransactionDataSuccess => {
+            Box::new(RequestTransactionDataSuccess::try_from(frame)?)
+        }
+        MessageType::RequestTransactionDataError => {
+            Box::new(RequestTransactionDataError::try_from(frame)?)
+        }
+        MessageType::SubmitSolution => Box::new(SubmitSolution::try_from(frame)?),
+    };
+
+    Ok(Message { header, payload })
+}
@@ -0,0 +1,124 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use ii_async_compat::tokio;
+
+use super::*;
+use crate::v2::template_distribution::Handler;
+
+fn build_new_template() -> NewTemplate {
+    NewTemplate {
+        template_id: 0x1122334455667788,
+        future_template: true,
+        version: 0x20000000,
+        coinbase_tx_version: 2,
+        coinbase_prefix: Bytes0_255::from_slice(&[0x03, 0x40, 0x0d, 0x03]),
+        coinbase_tx_input_sequence: 0xffffffff,
+        coinbase_tx_value_remaining: 625_000_000,
+        coinbase_tx_outputs_count: 1,
+        coinbase_tx_outputs: Bytes0_64k::from_slice(&[0u8; 43]),
+        coinbase_tx_locktime: 0,
+        merkle_path: Seq0_255::from_vec(vec![Uint256Bytes([0x11; 32]), Uint256Bytes([0x22; 32])]),
+    }
+}
+
+fn build_request_transaction_data_success() -> RequestTransactionDataSuccess {
+    RequestTransactionDataSuccess {
+        template_id: 0x1122334455667788,
+        excess_data: Bytes0_64k::new(),
+        transaction_list: Seq0_64k::from_vec(vec![
+            Bytes0_16M::from_slice(&[0x01; 100]),
+            // Transaction that doesn't fit into 16-bit length prefix
+            Bytes0_16M::from_vec(vec![0x02; 70_000]),
+        ]),
+    }
+}
+
+/// Builds a frame that contains serialized payload of the `message` so that the full
+/// deserialization path is exercised
+fn build_serialized_frame<M>(message: M) -> framing::Frame
+where
+    M: TryInto<framing::Frame, Error = Error>,
+{
+    let frame: framing::Frame = message.try_into().expect("Cannot create test frame");
+    let (header, payload) = frame.split();
+
+    framing::Frame::from_serialized_payload(
+        header.is_channel_message,
+        header.extension_type,
+        header.msg_type,
+        payload
+            .into_bytes_mut()
+            .expect("Cannot serialize test payload"),
+    )
+}
+
+/// Handler that records the visited messages
+#[derive(Default)]
+struct TestHandler {
+    new_template: Option<NewTemplate>,
+    transaction_data: Option<RequestTransactionDataSuccess>,
+}
+
+#[async_trait]
+impl Handler for TestHandler {
+    async fn visit_new_template(&mut self, _header: &framing::Header, payload: &NewTemplate) {
+        self.new_template.replace(payload.clone());
+    }
+
+    async fn visit_request_transaction_data_success(
+        &mut self,
+        _header: &framing::Header,
+        payload: &RequestTransactionDataSuccess,
+    ) {
+        self.transaction_data.replace(payload.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_new_template_roundtrip() {
+    let frame = build_serialized_frame(build_new_template());
+    assert_eq!(
+        frame.header.msg_type,
+        MessageType::NewTemplate as framing::MsgType
+    );
+
+    let mut handler = TestHandler::default();
+    let message = build_message_from_frame(frame).expect("Message deserialization failed");
+    message.accept(&mut handler).await;
+
+    assert_eq!(handler.new_template, Some(build_new_template()));
+}
+
+#[tokio::test]
+async fn test_request_transaction_data_success_roundtrip() {
+    let frame = build_serialized_frame(build_request_transaction_data_success());
+
+    let mut handler = TestHandler::default();
+    let message = build_message_from_frame(frame).expect("Message deserialization failed");
+    message.accept(&mut handler).await;
+
+    assert_eq!(
+        handler.transaction_data,
+        Some(build_request_transaction_data_success())
+    );
+}
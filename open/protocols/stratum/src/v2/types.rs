@@ -384,6 +384,15 @@ macro_rules! sized_seq_type {
 
         impl<T> Eq for $name<T> where T: Serialize + for<'dx> Deserialize<'dx> + PartialEq {}
 
+        impl<T> Clone for $name<T>
+        where
+            T: Serialize + for<'dx> Deserialize<'dx> + Clone,
+        {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
         impl<T> Debug for $name<T>
         where
             T: Serialize + for<'dx> Deserialize<'dx> + Debug,
@@ -406,6 +415,7 @@ sized_bytes_type!(Bytes0_255, 0, 255);
 sized_bytes_type!(Bytes1_255, 1, 255);
 sized_bytes_type!(Bytes0_64k, 0, 65535);
 sized_bytes_type!(Bytes1_64k, 1, 65535);
+sized_bytes_type!(Bytes0_16M, 0, 16777215);
 
 sized_seq_type!(Seq0_255, 0, 255);
 sized_seq_type!(Seq0_64k, 0, 65535);
@@ -420,6 +430,11 @@ pub struct DeviceInfo {
     pub dev_id: Str0_255,
 }
 
+/// Short transaction identifier (SipHash-2-4 based) used by Job Negotiation Protocol for
+/// referencing transactions without sending full transaction hashes
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShortTxId(pub [u8; 6]);
+
 /// PubKey for authenticating some protocol messages
 /// TODO: Preliminary as exact signing algorithm has not been chosen, we may even have this as
 /// dynamic field Bytes0_255
@@ -20,6 +20,7 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use std::net::SocketAddr;
 use std::net::TcpListener as StdTcpListener;
 use std::net::ToSocketAddrs as StdToSocketAddrs;
 use std::pin::Pin;
@@ -43,6 +44,11 @@ impl Server {
 
         Ok(Server { tcp })
     }
+
+    /// Address the server is actually bound to (useful when binding to port 0)
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.tcp.local_addr()
+    }
 }
 
 impl Stream for Server {
@@ -211,6 +211,11 @@ impl ConnTranslation {
                     match v2_frame? {
                         Some(v2_frame) => {
                             Self::v2_handle_frame(&mut translation, v2_frame?).await?;
+                            // Dropping the translation closes the upstream connection
+                            if translation.is_closed() {
+                                info!("V2 client closed its channel ({:?})", self.v2_peer_addr);
+                                return Ok(());
+                            }
                         }
                         None => {
                             Err(format!("V2 client disconnected ({:?})", self.v2_peer_addr))?;
@@ -251,6 +256,8 @@ impl SecurityContext {
         certificate: v2::noise::auth::Certificate,
         secret_key: v2::noise::auth::StaticSecretKeyFormat,
     ) -> Result<Self> {
+        // Refuse to serve an invalid or expired certificate as no initiator would accept it
+        certificate.validate()?;
         let signature_noise_message = certificate
             .build_noise_message()
             .serialize_to_bytes_mut()?
@@ -26,8 +26,9 @@ use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::fmt;
 use std::mem::size_of;
+use std::time::Duration;
 
-use ii_async_compat::{bytes, futures};
+use ii_async_compat::{bytes, futures, tokio};
 
 use async_trait::async_trait;
 use bytes::BytesMut;
@@ -39,7 +40,8 @@ use serde_json;
 use ii_stratum::v1;
 use ii_stratum::v2::{
     self,
-    types::{Bytes0_32, Uint256Bytes},
+    channel_group::ChannelGroups,
+    types::{Bytes0_32, Bytes0_64k, Seq0_255, Str0_255, Uint256Bytes},
 };
 
 use ii_logging::macros::*;
@@ -70,12 +72,18 @@ impl SeqId {
 pub struct V2ToV1TranslationOptions {
     /// Try to send `extranonce.subscribe` during handshake
     pub try_enable_xnsub: bool,
+    /// Pass the endpoint from upstream `client.reconnect` in the downstream `Reconnect` message.
+    /// This only makes sense when the downstream node connects to the V1 upstream itself (the
+    /// translation is embedded in the miner). Otherwise, the downstream node is asked to reconnect
+    /// to its current endpoint.
+    pub forward_reconnect_endpoint: bool,
 }
 
 impl Default for V2ToV1TranslationOptions {
     fn default() -> Self {
         Self {
             try_enable_xnsub: false,
+            forward_reconnect_endpoint: false,
         }
     }
 }
@@ -96,6 +104,8 @@ enum V2ToV1TranslationState {
     V1SubscribeOrAuthorizeFail,
     /// Channel is operational
     Operational,
+    /// Downstream node has closed the channel and the upstream V1 session is being terminated
+    Closed,
 }
 
 /// Represents a handler method that can process a particular ii_stratum result.
@@ -129,8 +139,8 @@ type JobMap = HashMap<u32, V1SubmitTemplate>;
 
 //type V2ReqMap = HashMap<u32, FnMut(&mut V2ToV1Translation, &ii_stratum::Message<Protocol>, &v1::rpc::StratumResult)>;
 
-/// Object capable of translating stratm V2 header-only mining protocol that uses standard mining
-/// channels into stratum V1 including extranonce 1 subscription
+/// Object capable of translating stratm V2 mining protocol that uses standard (header-only) or
+/// extended mining channels into stratum V1 including extranonce 1 subscription
 pub struct V2ToV1Translation {
     /// Statemachine tracking the translation setup
     state: V2ToV1TranslationState,
@@ -163,9 +173,14 @@ pub struct V2ToV1Translation {
     v2_conn_details: Option<v2::messages::SetupConnection>,
     /// Additional information about the pending channel being open
     v2_channel_details: Option<v2::messages::OpenStandardMiningChannel>,
+    /// Minimum extranonce size requested by the downstream node which opens an extended channel
+    /// and rolls extra nonce 2 on its own (`None` for standard channel)
+    v2_min_extranonce_size: Option<usize>,
     /// Target difficulty derived from mining.set_difficulty message
     /// The channel opening is not complete until the target is determined
     v2_target: Option<uint::U256>,
+    /// Standard channels that receive jobs and targets derived from the V1 session
+    v2_channel_groups: ChannelGroups,
     /// Unique job ID generator
     v2_job_id: SeqId,
     /// Translates V2 job ID to V1 job ID
@@ -176,9 +191,6 @@ pub struct V2ToV1Translation {
 
 impl V2ToV1Translation {
     const PROTOCOL_VERSION: usize = 0;
-    /// No support for the extended protocol yet, therefore, no extranonce advertised
-    #[allow(dead_code)]
-    const MAX_EXTRANONCE_SIZE: usize = 0;
     /// Currently, no support for multiple channels in the proxy
     const CHANNEL_ID: u32 = 0;
     /// Default group channel
@@ -189,6 +201,9 @@ impl V2ToV1Translation {
     /// TODO: DIFF1 const target is broken, the last U64 word gets actually initialized to 0xffffffff, not sure why
     const DIFF1_TARGET: uint::U256 = uint::U256([0, 0, 0, 0xffff0000u64]);
 
+    /// Reported to upstream when the downstream device doesn't provide its firmware version
+    const VERSION: &'static str = concat!("ii-stratum-proxy/", env!("CARGO_PKG_VERSION"));
+
     pub fn new(
         v1_tx: mpsc::Sender<v1::Frame>,
         v2_tx: mpsc::Sender<v2::Frame>,
@@ -197,7 +212,9 @@ impl V2ToV1Translation {
         Self {
             v2_conn_details: None,
             v2_channel_details: None,
+            v2_min_extranonce_size: None,
             v2_target: None,
+            v2_channel_groups: ChannelGroups::new(),
             state: V2ToV1TranslationState::Init,
             v1_tx,
             v1_req_id: SeqId::new(),
@@ -216,6 +233,11 @@ impl V2ToV1Translation {
         }
     }
 
+    /// The downstream node has closed its channel and the whole translation is to be torn down
+    pub fn is_closed(&self) -> bool {
+        self.state == V2ToV1TranslationState::Closed
+    }
+
     /// Builds a V1 request from V1 method and assigns a unique identifier to it
     fn v1_method_into_message<M, E>(
         &mut self,
@@ -261,15 +283,39 @@ impl V2ToV1Translation {
 
         // when V1 authorization has already taken place, report channel opening success
         if let Some(v2_channel_details) = self.v2_channel_details.as_ref() {
-            self.state = V2ToV1TranslationState::Operational;
-            let msg = v2::messages::OpenStandardMiningChannelSuccess {
-                req_id: v2_channel_details.req_id,
-                channel_id: Self::CHANNEL_ID,
-                target: init_target.clone(),
-                extranonce_prefix: Bytes0_32::new(),
-                group_channel_id: Self::DEFAULT_GROUP_CHANNEL_ID,
-            };
-            util::submit_message(&mut self.v2_tx, msg)?;
+            let req_id = v2_channel_details.req_id;
+            match self.v2_min_extranonce_size {
+                // The whole extra nonce 2 is rolled by the downstream node and the extra nonce 1
+                // assigned by upstream becomes the extranonce prefix
+                Some(min_extranonce_size) => {
+                    if self.v1_extra_nonce2_size < min_extranonce_size {
+                        Err(super::error::ErrorKind::General(format!(
+                            "Extra nonce 2 size {} is less than requested {}",
+                            self.v1_extra_nonce2_size, min_extranonce_size
+                        )))?;
+                    }
+                    let msg = v2::messages::OpenExtendedMiningChannelSuccess {
+                        req_id,
+                        channel_id: Self::CHANNEL_ID,
+                        target: init_target.clone(),
+                        extranonce_size: self.v1_extra_nonce2_size as u16,
+                        extranonce_prefix: self.v2_extranonce_prefix()?,
+                    };
+                    self.mark_channel_operational();
+                    util::submit_message(&mut self.v2_tx, msg)?;
+                }
+                None => {
+                    let msg = v2::messages::OpenStandardMiningChannelSuccess {
+                        req_id,
+                        channel_id: Self::CHANNEL_ID,
+                        target: init_target.clone(),
+                        extranonce_prefix: Bytes0_32::new(),
+                        group_channel_id: Self::DEFAULT_GROUP_CHANNEL_ID,
+                    };
+                    self.mark_channel_operational();
+                    util::submit_message(&mut self.v2_tx, msg)?;
+                }
+            }
 
             // If mining.notify is pending, process it now as part of open channel finalization
             if let Some(notify_payload) = self.v1_deferred_notify.take() {
@@ -286,6 +332,183 @@ impl V2ToV1Translation {
         }
     }
 
+    fn mark_channel_operational(&mut self) {
+        self.state = V2ToV1TranslationState::Operational;
+        self.v2_channel_groups
+            .add_channel(Self::DEFAULT_GROUP_CHANNEL_ID, Self::CHANNEL_ID);
+    }
+
+    /// Extra nonce 1 of the V1 session is used as the extranonce prefix of an extended channel
+    fn v2_extranonce_prefix(&self) -> Result<Bytes0_32> {
+        let v1_extra_nonce1 =
+            self.v1_extra_nonce1
+                .as_ref()
+                .ok_or(super::error::ErrorKind::General(
+                    "Extra nonce 1 missing, cannot build extranonce prefix".into(),
+                ))?;
+        Bytes0_32::try_from(v1_extra_nonce1.0.as_ref().as_slice()).map_err(|_| {
+            super::error::ErrorKind::General(format!(
+                "Extra nonce 1 too long: {} bytes",
+                v1_extra_nonce1.0.len()
+            ))
+            .into()
+        })
+    }
+
+    /// Reports failure to open a standard or an extended channel with given `code`
+    fn send_open_channel_error(&mut self, req_id: u32, code: &str) -> Result<()> {
+        let code = code.try_into().expect("BUG: incorrect error message");
+        if self.v2_min_extranonce_size.is_some() {
+            let msg = v2::messages::OpenExtendedMiningChannelError { req_id, code };
+            util::submit_message(&mut self.v2_tx, msg)
+        } else {
+            let msg = v2::messages::OpenStandardMiningChannelError { req_id, code };
+            util::submit_message(&mut self.v2_tx, msg)
+        }
+    }
+
+    /// Opening a channel is a 2 stage process when translating to  V1 ii_stratum, where
+    /// both stages can be executed in arbitrary order:
+    /// - perform subscribe (and start queuing incoming V1 jobs)
+    /// - perform authorize
+    ///
+    /// Upon successful authorization:
+    /// - communicate OpenStandardMiningChannelSuccess (or its extended variant)
+    /// - start sending Jobs downstream to V2 client
+    fn open_channel(&mut self, payload: &v2::messages::OpenStandardMiningChannel) {
+        if self.state != V2ToV1TranslationState::ConnectionSetup
+            && self.state != V2ToV1TranslationState::V1SubscribeOrAuthorizeFail
+        {
+            trace!(
+                "Out of sequence OpenStandardMiningChannel message, received: {:?}",
+                payload
+            );
+            if let Err(submit_err) =
+                self.send_open_channel_error(payload.req_id, "Out of sequence open channel msg")
+            {
+                info!("Cannot send open channel error message: {:?}", submit_err);
+                return;
+            }
+        }
+        // Connection details are present by now
+        if let Some(conn_details) = self.v2_conn_details.as_ref() {
+            self.v2_channel_details = Some(payload.clone());
+            self.state = V2ToV1TranslationState::OpenStandardMiningChannelPending;
+
+            let hostname: String = conn_details
+                .endpoint_host
+                .clone()
+                .try_into()
+                .expect("BUG: Cannot convert to string from connection details");
+
+            let hostname_port = format!("{}:{}", hostname, conn_details.endpoint_port);
+            let subscribe = v1::messages::Subscribe(
+                Some(conn_details.device.fw_ver.to_string()),
+                None,
+                Some(hostname_port),
+                None,
+            );
+
+            let v1_subscribe_message = self.v1_method_into_message(
+                subscribe,
+                Self::handle_subscribe_result,
+                Self::handle_authorize_or_subscribe_error,
+            );
+
+            if let Err(submit_err) = util::submit_message(&mut self.v1_tx, v1_subscribe_message) {
+                info!("Cannot send V1 mining.subscribe: {:?}", submit_err);
+                return;
+            }
+
+            if self.options.try_enable_xnsub {
+                let extranonce_subscribe = v1::messages::ExtranonceSubscribe();
+                let v1_extranonce_subscribe = self.v1_method_into_message(
+                    extranonce_subscribe,
+                    Self::handle_extranonce_subscribe_result,
+                    Self::handle_extranonce_subscribe_error,
+                );
+                if let Err(submit_err) =
+                    util::submit_message(&mut self.v1_tx, v1_extranonce_subscribe)
+                {
+                    info!(
+                        "Cannot send V1 mining.extranonce_subscribe: {:?}",
+                        submit_err
+                    );
+                    return;
+                }
+            }
+
+            let authorize = v1::messages::Authorize(payload.user.to_string(), "".to_string());
+            let v1_authorize_message = self.v1_method_into_message(
+                authorize,
+                Self::handle_authorize_result,
+                Self::handle_authorize_or_subscribe_error,
+            );
+            if let Err(submit_err) = util::submit_message(&mut self.v1_tx, v1_authorize_message) {
+                info!("Cannot send V1 mining.authorized: {:?}", submit_err);
+                return;
+            }
+        }
+    }
+
+    /// Emits V1 mining.submit for share of standard or extended channel with given `extra_nonce2`
+    fn submit_shares(&mut self, payload: &v2::messages::SubmitSharesStandard, extra_nonce2: &[u8]) {
+        // Report invalid channel ID
+        if payload.channel_id != Self::CHANNEL_ID {
+            self.reject_shares(
+                payload,
+                format!("Unrecognized channel ID {}", payload.channel_id),
+            );
+            return;
+        }
+
+        // Channel details must be filled by now, anything else is a bug, unfortunately, due to
+        // the 'expect' we have to clone them. TODO review this code
+        let v2_channel_details = &self
+            .v2_channel_details
+            .clone()
+            .expect("Missing channel details");
+
+        // Check job ID validity
+        let v1_submit_template = self
+            .v2_to_v1_job_map
+            .get(&payload.job_id)
+            // convert missing job ID (None) into an error
+            .ok_or(crate::error::ErrorKind::General(format!(
+                "V2 Job ID not present {} in registry",
+                payload.job_id
+            )))
+            .map(|tmpl| tmpl.clone());
+        // TODO validate the job (recalculate the hash and compare the target)
+        // Submit upstream V1 job based on the found job ID in the map
+        match v1_submit_template {
+            Ok(v1_submit_template) => {
+                let submit = v1::messages::Submit::new(
+                    v2_channel_details.user.to_string(),
+                    v1_submit_template.job_id.clone(),
+                    extra_nonce2,
+                    payload.ntime,
+                    payload.nonce,
+                    // ensure the version bits in the template follow BIP320
+                    payload.version & ii_stratum::BIP320_N_VERSION_MASK,
+                );
+                // Convert the method into a message + provide handling methods
+                let v1_submit_message = self.v1_method_into_message(
+                    submit,
+                    Self::handle_submit_result,
+                    Self::handle_submit_error,
+                );
+                if let Err(submit_err) = util::submit_message(&mut self.v1_tx, v1_submit_message) {
+                    info!(
+                        "SubmitShares: cannot send translated V1 message: {:?}",
+                        submit_err
+                    );
+                }
+            }
+            Err(e) => self.reject_shares(payload, format!("{}", e)),
+        }
+    }
+
     /// Send new target
     /// TODO extend the translation unit test accordingly
     fn send_set_target(&mut self) -> Result<()> {
@@ -295,12 +518,17 @@ impl V2ToV1Translation {
              OpenStandardMiningChannel",
         ));
 
-        let msg = v2::messages::SetTarget {
-            channel_id: Self::CHANNEL_ID,
-            max_target,
-        };
-
-        util::submit_message(&mut self.v2_tx, msg)
+        for channel_id in self
+            .v2_channel_groups
+            .resolve(Self::DEFAULT_GROUP_CHANNEL_ID)
+        {
+            let msg = v2::messages::SetTarget {
+                channel_id,
+                max_target,
+            };
+            util::submit_message(&mut self.v2_tx, msg)?;
+        }
+        Ok(())
     }
 
     /// Reports failure to open the channel and changes the translation state
@@ -314,18 +542,16 @@ impl V2ToV1Translation {
         self.state = V2ToV1TranslationState::V1SubscribeOrAuthorizeFail;
 
         // Cleanup all parts associated with opening the channel
+        self.v2_channel_groups.remove_channel(Self::CHANNEL_ID);
         self.v1_authorized = false;
         self.v1_extra_nonce1 = None;
         self.v1_extra_nonce2_size = 0;
 
-        if let Some(v2_channel_details) = self.v2_channel_details.as_ref() {
-            let msg = v2::messages::OpenStandardMiningChannelError {
-                req_id: v2_channel_details.req_id,
-                code: err_msg.try_into().expect("BUG: incorrect error message"),
-            };
-            self.v2_channel_details = None;
+        if let Some(v2_channel_details) = self.v2_channel_details.take() {
+            let result = self.send_open_channel_error(v2_channel_details.req_id, err_msg);
+            self.v2_min_extranonce_size = None;
 
-            if let Err(submit_err) = util::submit_message(&mut self.v2_tx, msg) {
+            if let Err(submit_err) = result {
                 info!(
                     "abort_open_channel() failed: {:?}, abort message: {}",
                     submit_err, err_msg
@@ -628,11 +854,12 @@ impl V2ToV1Translation {
     }
 
     /// Iterates the merkle branches and calculates block merkle root using the extra nonce 1.
-    /// Extra nonce 2 encodes the channel ID.
+    /// Extra nonce 2 encodes the `channel_id`.
     /// TODO review, whether a Result has to be returned as missing enonce1 would be considered a bug
     fn calculate_merkle_root(
         &mut self,
         payload: &v1::messages::Notify,
+        channel_id: u32,
     ) -> crate::error::Result<sha256d::Hash> {
         // TODO get rid of extra nonce 1 cloning
         if let Some(v1_extra_nonce1) = self.v1_extra_nonce1.clone() {
@@ -646,8 +873,7 @@ impl V2ToV1Translation {
             coin_base.extend_from_slice(payload.coin_base_1());
             coin_base.extend_from_slice(v1_extra_nonce1.0.as_ref());
             coin_base.extend_from_slice(
-                Self::channel_to_extra_nonce2_bytes(Self::CHANNEL_ID, self.v1_extra_nonce2_size)
-                    .as_ref(),
+                Self::channel_to_extra_nonce2_bytes(channel_id, self.v1_extra_nonce2_size).as_ref(),
             );
             coin_base.extend_from_slice(payload.coin_base_2());
 
@@ -677,7 +903,7 @@ impl V2ToV1Translation {
         }
     }
 
-    /// Builds SetNewPrevHash for the specified v1 Notify `payload`
+    /// Builds SetNewPrevHash of `channel_id` for the specified v1 Notify `payload`
     ///
     /// The SetNewPrevHash has to reference the future job that the V2 downstream has
     /// previously received from us.
@@ -692,6 +918,7 @@ impl V2ToV1Translation {
     /// interval
     fn build_set_new_prev_hash(
         &self,
+        channel_id: u32,
         job_id: u32,
         payload: &v1::messages::Notify,
     ) -> crate::error::Result<v2::messages::SetNewPrevHash> {
@@ -702,7 +929,7 @@ impl V2ToV1Translation {
         let prev_hash = Uint256Bytes(prev_hash.into_inner());
 
         Ok(v2::messages::SetNewPrevHash {
-            channel_id: Self::CHANNEL_ID,
+            channel_id,
             prev_hash,
             min_ntime: payload.time(),
             nbits: payload.bits(),
@@ -754,33 +981,96 @@ impl V2ToV1Translation {
         }
     }
 
-    fn perform_notify(&mut self, payload: &v1::messages::Notify) -> Result<()> {
-        let merkle_root = self.calculate_merkle_root(payload)?;
+    /// Splits coinbase of mining.notify around extra nonce so that the downstream node of an
+    /// extended channel can build the merkle root for any extra nonce 2 on its own
+    fn build_new_extended_mining_job(
+        payload: &v1::messages::Notify,
+        channel_id: u32,
+        job_id: u32,
+        future_job: bool,
+    ) -> Result<v2::messages::NewExtendedMiningJob> {
+        let merkle_path = payload
+            .merkle_branch()
+            .iter()
+            .map(|tx_hash| {
+                let mut hash = Uint256Bytes([0; 32]);
+                if tx_hash.len() != hash.0.len() {
+                    Err(super::error::ErrorKind::General(format!(
+                        "Invalid merkle branch length: {}",
+                        tx_hash.len()
+                    )))?;
+                }
+                hash.as_mut().copy_from_slice(tx_hash.as_ref());
+                Ok(hash)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let too_long = |part: &str| -> Error {
+            super::error::ErrorKind::General(format!("Coinbase {} too long", part)).into()
+        };
 
-        let v2_job = v2::messages::NewMiningJob {
-            channel_id: Self::CHANNEL_ID,
-            job_id: self.v2_job_id.next(),
-            future_job: self.v2_to_v1_job_map.is_empty()
-                || payload.clean_jobs()
-                || self.v1_force_future_jobs,
-            merkle_root: Uint256Bytes(merkle_root.into_inner()),
+        Ok(v2::messages::NewExtendedMiningJob {
+            channel_id,
+            job_id,
+            future_job,
             version: payload.version(),
-        };
+            version_rolling_allowed: true,
+            merkle_path: Seq0_255::try_from(merkle_path).map_err(|_| too_long("merkle path"))?,
+            coinbase_tx_prefix: Bytes0_64k::try_from(payload.coin_base_1())
+                .map_err(|_| too_long("prefix"))?,
+            coinbase_tx_suffix: Bytes0_64k::try_from(payload.coin_base_2())
+                .map_err(|_| too_long("suffix"))?,
+        })
+    }
+
+    /// Translates mining.notify into a job for all channels of the group channel. Each standard
+    /// channel gets its own job message with a distinct merkle root because the channel ID is
+    /// encoded in extra nonce 2. Extended channel gets the coinbase split around extra nonce.
+    fn perform_notify(&mut self, payload: &v1::messages::Notify) -> Result<()> {
+        let job_id = self.v2_job_id.next();
+        let future_job =
+            self.v2_to_v1_job_map.is_empty() || payload.clean_jobs() || self.v1_force_future_jobs;
+
+        let mut v2_jobs: Vec<(u32, v2::Frame)> = Vec::new();
+        for channel_id in self
+            .v2_channel_groups
+            .resolve(Self::DEFAULT_GROUP_CHANNEL_ID)
+        {
+            let v2_job: v2::Frame = if self.v2_min_extranonce_size.is_some() {
+                Self::build_new_extended_mining_job(payload, channel_id, job_id, future_job)?
+                    .try_into()?
+            } else {
+                let merkle_root = self.calculate_merkle_root(payload, channel_id)?;
+                v2::messages::NewMiningJob {
+                    channel_id,
+                    job_id,
+                    future_job,
+                    merkle_root: Uint256Bytes(merkle_root.into_inner()),
+                    version: payload.version(),
+                }
+                .try_into()?
+            };
+            v2_jobs.push((channel_id, v2_job));
+        }
 
         // Make sure we generate new prev hash. Empty JobMap means this is the first mining.notify
         // message and we also have to issue NewPrevHash. In addition to that, we also check the
         // clean jobs flag that indicates a must for new prev hash, too.
-        let maybe_set_new_prev_hash = if v2_job.future_job {
+        let mut set_new_prev_hashes = Vec::new();
+        if future_job {
             self.v2_to_v1_job_map.clear();
-            // Any error means immediate termination
-            // TODO write a unit test for such scenario, too
-            Some(self.build_set_new_prev_hash(v2_job.job_id, payload)?)
-        } else {
-            None
-        };
+            for (channel_id, _) in v2_jobs.iter() {
+                // Any error means immediate termination
+                // TODO write a unit test for such scenario, too
+                set_new_prev_hashes.push(self.build_set_new_prev_hash(
+                    *channel_id,
+                    job_id,
+                    payload,
+                )?);
+            }
+        }
         trace!(
             "Registering V2 job ID {:x?} -> V1 job ID {:x?}",
-            v2_job.job_id,
+            job_id,
             payload.job_id(),
         );
         // TODO extract this duplicate code, turn the map into a new type with this
@@ -788,7 +1078,7 @@ impl V2ToV1Translation {
         if self
             .v2_to_v1_job_map
             .insert(
-                v2_job.job_id,
+                job_id,
                 V1SubmitTemplate {
                     job_id: v1::messages::JobId::from_str(payload.job_id()),
                     time: payload.time(),
@@ -797,15 +1087,18 @@ impl V2ToV1Translation {
             )
             .is_some()
         {
-            error!("BUG: V2 id {} already exists...", v2_job.job_id);
+            error!("BUG: V2 id {} already exists...", job_id);
             // TODO add graceful handling of this bug (shutdown?)
             panic!("V2 id already exists");
         }
 
-        util::submit_message(&mut self.v2_tx, v2_job)?;
+        let mut set_new_prev_hashes = set_new_prev_hashes.into_iter();
+        for (_, v2_job) in v2_jobs {
+            util::submit_message(&mut self.v2_tx, v2_job)?;
 
-        if let Some(set_new_prev_hash) = maybe_set_new_prev_hash {
-            util::submit_message(&mut self.v2_tx, set_new_prev_hash)?
+            if let Some(set_new_prev_hash) = set_new_prev_hashes.next() {
+                util::submit_message(&mut self.v2_tx, set_new_prev_hash)?
+            }
         }
         Ok(())
     }
@@ -840,6 +1133,21 @@ impl V2ToV1Translation {
         .ok();
     }
 
+    /// Responds to `client.get_version` with firmware version of the downstream device
+    fn send_client_version(&mut self, id: u32) -> Result<()> {
+        let version = self
+            .v2_conn_details
+            .as_ref()
+            .map(|conn_details| conn_details.device.fw_ver.to_string())
+            .filter(|fw_ver| !fw_ver.is_empty())
+            .unwrap_or_else(|| Self::VERSION.to_string());
+        let response = v1::rpc::Response {
+            id,
+            payload: v1::messages::ClientGetVersionResult(version).try_into()?,
+        };
+        util::submit_message(&mut self.v1_tx, v1::rpc::Rpc::from(response))
+    }
+
     fn log_session_details(&self, msg: &str) {
         let v2_channel_details = self
             .v2_channel_details
@@ -908,7 +1216,10 @@ impl v1::Handler for V2ToV1Translation {
             // Initial set difficulty finalizes open channel if all preconditions are met
             if self.state == V2ToV1TranslationState::OpenStandardMiningChannelPending {
                 self.finalize_open_channel()
-                    .map_err(|e| trace!("visit_set_difficulty: {}", e))
+                    .map_err(|e| {
+                        trace!("visit_set_difficulty: {}", e);
+                        self.abort_open_channel("Cannot finalize channel");
+                    })
                     // Consume the error as there is no way to return anything from the visitor for now.
                     .ok();
             }
@@ -939,6 +1250,24 @@ impl v1::Handler for V2ToV1Translation {
         //   https://en.bitcoin.it/wiki/Stratum_mining_protocol#mining.set_extranonce
         self.v1_extra_nonce1 = Some(payload.extra_nonce_1().clone());
         self.v1_extra_nonce2_size = payload.extra_nonce_2_size().clone();
+
+        // Downstream node of an extended channel builds the coinbase on its own, the size of
+        // extra nonce 2 cannot be changed, though
+        if self.v2_min_extranonce_size.is_some()
+            && self.state == V2ToV1TranslationState::Operational
+        {
+            self.v2_extranonce_prefix()
+                .and_then(|extranonce_prefix| {
+                    let msg = v2::messages::SetExtranoncePrefix {
+                        channel_id: Self::CHANNEL_ID,
+                        extranonce_prefix,
+                    };
+                    util::submit_message(&mut self.v2_tx, msg)
+                })
+                .map_err(|e| info!("Cannot send SetExtranoncePrefix: {}", e))
+                // Consume the error as there is no way this can be communicated further
+                .ok();
+        }
     }
 
     /// Composes a new mining job and sends it downstream
@@ -969,6 +1298,86 @@ impl v1::Handler for V2ToV1Translation {
             .ok();
     }
 
+    /// Upstream asks for reconnection, the downstream node is asked to do the same via V2
+    /// `Reconnect` (see `V2ToV1TranslationOptions::forward_reconnect_endpoint`)
+    async fn visit_client_reconnect(
+        &mut self,
+        id: &v1::MessageId,
+        payload: &v1::messages::ClientReconnect,
+    ) {
+        trace!(
+            "visit_client_reconnect() id={:?} state={:?} payload:{:?}",
+            id,
+            self.state,
+            payload,
+        );
+        info!(
+            "Upstream requested reconnect to {}:{} (wait time: {}s)",
+            payload.host().unwrap_or("<current host>"),
+            payload
+                .port()
+                .map_or("<current port>".to_string(), |port| port.to_string()),
+            payload.wait_time().unwrap_or(0),
+        );
+        // Empty host and zero port instruct the downstream node to keep its current endpoint
+        let (new_host, new_port) = if self.options.forward_reconnect_endpoint {
+            (
+                payload.host().unwrap_or_default(),
+                payload.port().unwrap_or(0),
+            )
+        } else {
+            ("", 0)
+        };
+        let reconnect = v2::messages::Reconnect {
+            new_host: Str0_255::try_from(new_host).unwrap_or_default(),
+            new_port,
+        };
+        // V2 Reconnect has no notion of wait time, the downstream node reconnects immediately.
+        // Therefore, the message is held back until the wait time requested by upstream elapses.
+        let wait_time = Duration::from_secs(payload.wait_time().unwrap_or(0));
+        let mut v2_tx = self.v2_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::delay_for(wait_time).await;
+            util::submit_message(&mut v2_tx, reconnect)
+                .map_err(|e| info!("Cannot send Reconnect: {}", e))
+                // Consume the error as there is no way this can be communicated further
+                .ok();
+        });
+    }
+
+    async fn visit_client_show_message(
+        &mut self,
+        id: &v1::MessageId,
+        payload: &v1::messages::ClientShowMessage,
+    ) {
+        trace!(
+            "visit_client_show_message() id={:?} state={:?} payload:{:?}",
+            id,
+            self.state,
+            payload,
+        );
+        info!("Message from upstream: {}", payload.message());
+    }
+
+    async fn visit_client_get_version(
+        &mut self,
+        id: &v1::MessageId,
+        payload: &v1::messages::ClientGetVersion,
+    ) {
+        trace!(
+            "visit_client_get_version() id={:?} state={:?} payload:{:?}",
+            id,
+            self.state,
+            payload,
+        );
+        // Notification cannot be responded to
+        if let Some(id) = id {
+            self.send_client_version(*id)
+                .map_err(|e| info!("Cannot send client version: {}", e))
+                .ok();
+        }
+    }
+
     /// TODO currently unimplemented, the proxy should refuse changing the version mask from the server
     /// Since this is a notification only, the only action that the translation can do is log +
     /// report an error
@@ -1037,14 +1446,7 @@ impl v2::Handler for V2ToV1Translation {
         self.state = V2ToV1TranslationState::V1Configure;
     }
 
-    /// Opening a channel is a 2 stage process when translating to  V1 ii_stratum, where
-    /// both stages can be executed in arbitrary order:
-    /// - perform subscribe (and start queuing incoming V1 jobs)
-    /// - perform authorize
-    ///
-    /// Upon successful authorization:
-    /// - communicate OpenStandardMiningChannelSuccess
-    /// - start sending Jobs downstream to V2 client
+    /// See `open_channel()` for details
     async fn visit_open_standard_mining_channel(
         &mut self,
         header: &v2::framing::Header,
@@ -1056,87 +1458,30 @@ impl v2::Handler for V2ToV1Translation {
             self.state,
             payload,
         );
-        if self.state != V2ToV1TranslationState::ConnectionSetup
-            && self.state != V2ToV1TranslationState::V1SubscribeOrAuthorizeFail
-        {
-            trace!(
-                "Out of sequence OpenStandardMiningChannel message, received: {:?}",
-                payload
-            );
-            let err_msg = v2::messages::OpenStandardMiningChannelError {
-                req_id: payload.req_id,
-                code: "Out of sequence OpenStandardMiningChannel msg"
-                    .try_into()
-                    .expect("BUG: incorrect error message"),
-            };
-
-            if let Err(submit_err) = util::submit_message(&mut self.v2_tx, err_msg) {
-                info!(
-                    "Cannot send OpenStandardMiningChannelError message: {:?}",
-                    submit_err
-                );
-                return;
-            }
-        }
-        // Connection details are present by now
-        if let Some(conn_details) = self.v2_conn_details.as_ref() {
-            self.v2_channel_details = Some(payload.clone());
-            self.state = V2ToV1TranslationState::OpenStandardMiningChannelPending;
-
-            let hostname: String = conn_details
-                .endpoint_host
-                .clone()
-                .try_into()
-                .expect("BUG: Cannot convert to string from connection details");
-
-            let hostname_port = format!("{}:{}", hostname, conn_details.endpoint_port);
-            let subscribe = v1::messages::Subscribe(
-                Some(conn_details.device.fw_ver.to_string()),
-                None,
-                Some(hostname_port),
-                None,
-            );
-
-            let v1_subscribe_message = self.v1_method_into_message(
-                subscribe,
-                Self::handle_subscribe_result,
-                Self::handle_authorize_or_subscribe_error,
-            );
-
-            if let Err(submit_err) = util::submit_message(&mut self.v1_tx, v1_subscribe_message) {
-                info!("Cannot send V1 mining.subscribe: {:?}", submit_err);
-                return;
-            }
-
-            if self.options.try_enable_xnsub {
-                let extranonce_subscribe = v1::messages::ExtranonceSubscribe();
-                let v1_extranonce_subscribe = self.v1_method_into_message(
-                    extranonce_subscribe,
-                    Self::handle_extranonce_subscribe_result,
-                    Self::handle_extranonce_subscribe_error,
-                );
-                if let Err(submit_err) =
-                    util::submit_message(&mut self.v1_tx, v1_extranonce_subscribe)
-                {
-                    info!(
-                        "Cannot send V1 mining.extranonce_subscribe: {:?}",
-                        submit_err
-                    );
-                    return;
-                }
-            }
+        self.v2_min_extranonce_size = None;
+        self.open_channel(payload);
+    }
 
-            let authorize = v1::messages::Authorize(payload.user.to_string(), "".to_string());
-            let v1_authorize_message = self.v1_method_into_message(
-                authorize,
-                Self::handle_authorize_result,
-                Self::handle_authorize_or_subscribe_error,
-            );
-            if let Err(submit_err) = util::submit_message(&mut self.v1_tx, v1_authorize_message) {
-                info!("Cannot send V1 mining.authorized: {:?}", submit_err);
-                return;
-            }
-        }
+    /// Extended channel is opened the same way as the standard one, the only difference is that
+    /// the downstream node is responsible for rolling of extra nonce 2
+    async fn visit_open_extended_mining_channel(
+        &mut self,
+        header: &v2::framing::Header,
+        payload: &v2::messages::OpenExtendedMiningChannel,
+    ) {
+        trace!(
+            "visit_open_extended_mining_channel() header={:x?} state={:?} payload:{:?}",
+            header,
+            self.state,
+            payload,
+        );
+        self.v2_min_extranonce_size = Some(payload.min_extranonce_size as usize);
+        self.open_channel(&v2::messages::OpenStandardMiningChannel {
+            req_id: payload.req_id,
+            user: payload.user.clone(),
+            nominal_hashrate: payload.nominal_hashrate,
+            max_target: payload.max_target,
+        });
     }
 
     /// The flow of share processing is as follows:
@@ -1157,63 +1502,104 @@ impl v2::Handler for V2ToV1Translation {
             self.state,
             payload,
         );
-        // Report invalid channel ID
-        if payload.channel_id != Self::CHANNEL_ID {
+        let extra_nonce2 =
+            Self::channel_to_extra_nonce2_bytes(Self::CHANNEL_ID, self.v1_extra_nonce2_size);
+        self.submit_shares(payload, extra_nonce2.as_ref());
+    }
+
+    /// Extended shares carry extra nonce 2 rolled by the downstream node
+    async fn visit_submit_shares_extended(
+        &mut self,
+        header: &v2::framing::Header,
+        payload: &v2::messages::SubmitSharesExtended,
+    ) {
+        trace!(
+            "visit_submit_shares_extended() header={:x?} state={:?} payload:{:?}",
+            header,
+            self.state,
+            payload,
+        );
+        let standard_payload = v2::messages::SubmitSharesStandard {
+            channel_id: payload.channel_id,
+            seq_num: payload.seq_num,
+            job_id: payload.job_id,
+            nonce: payload.nonce,
+            ntime: payload.ntime,
+            version: payload.version,
+        };
+        if self.v2_min_extranonce_size.is_none()
+            || payload.extranonce.len() != self.v1_extra_nonce2_size
+        {
             self.reject_shares(
-                payload,
-                format!("Unrecognized channel ID {}", payload.channel_id),
+                &standard_payload,
+                format!("Invalid extranonce size {}", payload.extranonce.len()),
             );
             return;
         }
+        self.submit_shares(&standard_payload, payload.extranonce.as_ref());
+    }
 
-        // Channel details must be filled by now, anything else is a bug, unfortunately, due to
-        // the 'expect' we have to clone them. TODO review this code
-        let v2_channel_details = &self
-            .v2_channel_details
-            .clone()
-            .expect("Missing channel details");
-        // TODO this is only here as we want to prevent locking up 'self' into multiple closures
-        // and causing borrow checker complains
-        let v1_extra_nonce2_size = self.v1_extra_nonce2_size;
-
-        // Check job ID validity
-        let v1_submit_template = self
-            .v2_to_v1_job_map
-            .get(&payload.job_id)
-            // convert missing job ID (None) into an error
-            .ok_or(crate::error::ErrorKind::General(format!(
-                "V2 Job ID not present {} in registry",
-                payload.job_id
-            )))
-            .map(|tmpl| tmpl.clone());
-        // TODO validate the job (recalculate the hash and compare the target)
-        // Submit upstream V1 job based on the found job ID in the map
-        match v1_submit_template {
-            Ok(v1_submit_template) => {
-                let submit = v1::messages::Submit::new(
-                    v2_channel_details.user.to_string(),
-                    v1_submit_template.job_id.clone(),
-                    Self::channel_to_extra_nonce2_bytes(Self::CHANNEL_ID, v1_extra_nonce2_size)
-                        .as_ref(),
-                    payload.ntime,
-                    payload.nonce,
-                    // ensure the version bits in the template follow BIP320
-                    payload.version & ii_stratum::BIP320_N_VERSION_MASK,
-                );
-                // Convert the method into a message + provide handling methods
-                let v1_submit_message = self.v1_method_into_message(
-                    submit,
-                    Self::handle_submit_result,
-                    Self::handle_submit_error,
-                );
-                if let Err(submit_err) = util::submit_message(&mut self.v1_tx, v1_submit_message) {
-                    info!(
-                        "SubmitSharesStandard: cannot send translated V1 message: {:?}",
-                        submit_err
-                    );
+    /// V1 has no means of announcing hashrate upstream, therefore the new nominal hashrate is
+    /// only recorded in the channel details
+    async fn visit_update_channel(
+        &mut self,
+        header: &v2::framing::Header,
+        payload: &v2::messages::UpdateChannel,
+    ) {
+        trace!(
+            "visit_update_channel() header={:x?} state={:?} payload:{:?}",
+            header,
+            self.state,
+            payload,
+        );
+        match self.v2_channel_details.as_mut() {
+            Some(v2_channel_details) if payload.channel_id == Self::CHANNEL_ID => {
+                v2_channel_details.nominal_hashrate = payload.nominal_hashrate;
+                v2_channel_details.max_target = payload.max_target;
+            }
+            _ => {
+                let err_msg = v2::messages::UpdateChannelError {
+                    channel_id: payload.channel_id,
+                    code: "invalid-channel-id"
+                        .try_into()
+                        .expect("BUG: incorrect error message"),
+                };
+                if let Err(submit_err) = util::submit_message(&mut self.v2_tx, err_msg) {
+                    info!("Cannot send UpdateChannelError message: {:?}", submit_err);
                 }
             }
-            Err(e) => self.reject_shares(payload, format!("{}", e)),
         }
     }
+
+    /// V1 has no means of closing a channel, therefore the upstream session is terminated. The V1
+    /// connection is closed as soon as all pending V1 frames are sent out.
+    async fn visit_close_channel(
+        &mut self,
+        header: &v2::framing::Header,
+        payload: &v2::messages::CloseChannel,
+    ) {
+        trace!(
+            "visit_close_channel() header={:x?} state={:?} payload:{:?}",
+            header,
+            self.state,
+            payload,
+        );
+        if self.v2_channel_details.is_none()
+            || self.is_closed()
+            || payload.channel_id != Self::CHANNEL_ID
+        {
+            info!(
+                "Ignoring CloseChannel for unknown channel {}",
+                payload.channel_id
+            );
+            return;
+        }
+        info!(
+            "Downstream closed channel {} ({}), terminating V1 session",
+            payload.channel_id,
+            payload.reason_code.to_string()
+        );
+        self.state = V2ToV1TranslationState::Closed;
+        self.v1_tx.close_channel();
+    }
 }
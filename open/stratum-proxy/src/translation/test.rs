@@ -165,6 +165,243 @@ async fn test_setup_connection_translate() {
     // });
 }
 
+/// Extended channel receives coinbase split around extra nonce and its shares are submitted with
+/// extra nonce 2 rolled by the downstream node
+#[tokio::test]
+async fn test_extended_channel_translate() {
+    let (v1_tx, mut v1_rx) = mpsc::channel(1);
+    let (v2_tx, mut v2_rx) = mpsc::channel(1);
+    let mut translation = V2ToV1Translation::new(v1_tx, v2_tx, Default::default());
+
+    v2_simulate_incoming_message(&mut translation, test_utils::v2::build_setup_connection()).await;
+    v1_verify_generated_response_message(&mut v1_rx).await;
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_configure_ok_response_message(),
+    )
+    .await;
+    v2_verify_generated_response_message(&mut v2_rx).await;
+
+    v2_simulate_incoming_message(
+        &mut translation,
+        test_utils::v2::build_open_extended_channel(),
+    )
+    .await;
+    v1_verify_generated_response_message(&mut v1_rx).await;
+    v1_verify_generated_response_message(&mut v1_rx).await;
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_subscribe_ok_response_message(),
+    )
+    .await;
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_authorize_ok_response_message(),
+    )
+    .await;
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_set_difficulty_request_message(),
+    )
+    .await;
+
+    // The whole extra nonce 2 is left to the downstream node
+    let subscribe_result = test_utils::v1::build_subscribe_ok_result();
+    let frame = v2_rx
+        .next()
+        .await
+        .expect("Open channel response was expected");
+    let success = v2::messages::OpenExtendedMiningChannelSuccess::try_from(frame)
+        .expect("Deserialization failed");
+    assert_eq!(
+        success.extranonce_size as usize,
+        subscribe_result.extra_nonce_2_size()
+    );
+    assert_eq!(
+        success.extranonce_prefix.as_ref(),
+        subscribe_result.extra_nonce_1().0.as_ref().as_slice()
+    );
+
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_mining_notify_request_message(),
+    )
+    .await;
+    let notify = test_utils::v1::build_mining_notify();
+    let frame = v2_rx
+        .next()
+        .await
+        .expect("NewExtendedMiningJob was expected");
+    let job = v2::messages::NewExtendedMiningJob::try_from(frame).expect("Deserialization failed");
+    assert_eq!(job.channel_id, success.channel_id);
+    assert_eq!(job.coinbase_tx_prefix.as_ref(), notify.coin_base_1());
+    assert_eq!(job.coinbase_tx_suffix.as_ref(), notify.coin_base_2());
+    assert_eq!(job.merkle_path.as_ref().len(), notify.merkle_branch().len());
+    let frame = v2_rx.next().await.expect("SetNewPrevHash was expected");
+    v2::messages::SetNewPrevHash::try_from(frame).expect("Deserialization failed");
+
+    // Share with extra nonce 2 of invalid size is rejected right away
+    let share = v2::messages::SubmitSharesExtended {
+        channel_id: job.channel_id,
+        seq_num: 0,
+        job_id: job.job_id,
+        nonce: test_utils::common::MINING_WORK_NONCE,
+        ntime: test_utils::common::MINING_WORK_NTIME,
+        version: test_utils::common::MINING_WORK_VERSION,
+        extranonce: Bytes0_32::from_slice(&[0x01]),
+    };
+    v2_simulate_incoming_message(&mut translation, share.clone()).await;
+    let frame = v2_rx.next().await.expect("SubmitSharesError was expected");
+    v2::messages::SubmitSharesError::try_from(frame).expect("Deserialization failed");
+
+    let extranonce = [0x01, 0x02, 0x03, 0x04];
+    v2_simulate_incoming_message(
+        &mut translation,
+        v2::messages::SubmitSharesExtended {
+            extranonce: Bytes0_32::from_slice(&extranonce),
+            ..share
+        },
+    )
+    .await;
+    let frame = v1_rx.next().await.expect("mining.submit was expected");
+    match v1::rpc::Rpc::try_from(frame).expect("Deserialization failed") {
+        v1::rpc::Rpc::Request(request) => {
+            let submit =
+                v1::messages::Submit::try_from(request).expect("Cannot parse mining.submit");
+            assert_eq!(submit.extra_nonce_2(), &extranonce[..]);
+            assert_eq!(submit.nonce(), test_utils::common::MINING_WORK_NONCE);
+        }
+        v1::rpc::Rpc::Response(response) => panic!("Unexpected response: {:?}", response),
+    }
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_mining_submit_ok_response_message(),
+    )
+    .await;
+    let frame = v2_rx
+        .next()
+        .await
+        .expect("SubmitSharesSuccess was expected");
+    v2::messages::SubmitSharesSuccess::try_from(frame).expect("Deserialization failed");
+}
+
+/// Closing the downstream channel terminates the upstream V1 session
+#[tokio::test]
+async fn test_close_channel_translate() {
+    let (v1_tx, mut v1_rx) = mpsc::channel(1);
+    let (v2_tx, mut v2_rx) = mpsc::channel(1);
+    let mut translation = V2ToV1Translation::new(v1_tx, v2_tx, Default::default());
+
+    v2_simulate_incoming_message(&mut translation, test_utils::v2::build_setup_connection()).await;
+    v1_verify_generated_response_message(&mut v1_rx).await;
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_configure_ok_response_message(),
+    )
+    .await;
+    v2_verify_generated_response_message(&mut v2_rx).await;
+    v2_simulate_incoming_message(&mut translation, test_utils::v2::build_open_channel()).await;
+    v1_verify_generated_response_message(&mut v1_rx).await;
+    v1_verify_generated_response_message(&mut v1_rx).await;
+
+    let close_channel = v2::messages::CloseChannel {
+        channel_id: V2ToV1Translation::CHANNEL_ID + 1,
+        reason_code: "shutdown".try_into().expect("BUG: incorrect reason code"),
+    };
+    // Unknown channel is ignored and the V1 session stays open
+    v2_simulate_incoming_message(&mut translation, close_channel.clone()).await;
+    assert!(!translation.is_closed());
+
+    v2_simulate_incoming_message(
+        &mut translation,
+        v2::messages::CloseChannel {
+            channel_id: V2ToV1Translation::CHANNEL_ID,
+            ..close_channel
+        },
+    )
+    .await;
+    assert!(translation.is_closed());
+    assert!(
+        v1_rx.next().await.is_none(),
+        "V1 session must be terminated"
+    );
+}
+
+/// Upstream reconnect is passed downstream either with the new endpoint or with an empty one
+/// (keep the current endpoint) depending on translation options
+#[tokio::test]
+async fn test_client_reconnect_translate() {
+    for &forward_reconnect_endpoint in &[false, true] {
+        let (v1_tx, _v1_rx) = mpsc::channel(1);
+        let (v2_tx, mut v2_rx) = mpsc::channel(1);
+        let options = V2ToV1TranslationOptions {
+            forward_reconnect_endpoint,
+            ..Default::default()
+        };
+        let mut translation = V2ToV1Translation::new(v1_tx, v2_tx, options);
+
+        v1_simulate_incoming_message(
+            &mut translation,
+            test_utils::v1::build_client_reconnect_request_message_with_wait_time(None),
+        )
+        .await;
+        let frame = v2_rx.next().await.expect("Reconnect was expected");
+        let reconnect = v2::messages::Reconnect::try_from(frame).expect("Deserialization failed");
+        if forward_reconnect_endpoint {
+            assert_eq!(reconnect.new_host.to_string(), test_utils::common::POOL_URL);
+            assert_eq!(reconnect.new_port, test_utils::common::POOL_PORT as u16);
+        } else {
+            assert!(reconnect.new_host.to_string().is_empty());
+            assert_eq!(reconnect.new_port, 0);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_client_reconnect_wait_time() {
+    let (v1_tx, _v1_rx) = mpsc::channel(1);
+    let (v2_tx, mut v2_rx) = mpsc::channel(1);
+    let mut translation = V2ToV1Translation::new(v1_tx, v2_tx, Default::default());
+
+    let start = std::time::Instant::now();
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_client_reconnect_request_message_with_wait_time(Some(1)),
+    )
+    .await;
+    // Downstream is asked to reconnect only after the wait time requested by upstream
+    assert!(v2_rx.try_next().is_err());
+    let frame = tokio::time::timeout(Duration::from_secs(5), v2_rx.next())
+        .await
+        .expect("Reconnect timeout")
+        .expect("Reconnect was expected");
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    v2::messages::Reconnect::try_from(frame).expect("Deserialization failed");
+}
+
+#[tokio::test]
+async fn test_client_get_version_translate() {
+    let (v1_tx, mut v1_rx) = mpsc::channel(1);
+    let (v2_tx, _v2_rx) = mpsc::channel(1);
+    let mut translation = V2ToV1Translation::new(v1_tx, v2_tx, Default::default());
+
+    v1_simulate_incoming_message(
+        &mut translation,
+        test_utils::v1::build_client_get_version_request_message(),
+    )
+    .await;
+    let frame = v1_rx.next().await.expect("Version response was expected");
+    match v1::rpc::Rpc::try_from(frame).expect("Deserialization failed") {
+        v1::rpc::Rpc::Response(response) => {
+            assert_eq!(response.id, 5);
+            let version = v1::messages::ClientGetVersionResult::try_from(response)
+                .expect("Cannot parse version");
+            assert_eq!(version.0, V2ToV1Translation::VERSION);
+        }
+        v1::rpc::Rpc::Request(request) => panic!("Unexpected request: {:?}", request),
+    }
+}
+
 #[test]
 fn test_diff_1_bitcoin_target() {
     // Difficulty 1 target in big-endian format